@@ -1,6 +1,8 @@
-use std::{fmt::Display, fs::File, io::Write};
+use std::fmt::Display;
 
-use serde::{de::value::Error, Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
+
+use persistencia::{ErrorPersistencia, Persistencia};
 
 #[derive(Debug, PartialEq)]
 pub struct ErrorMax(u32);
@@ -11,6 +13,33 @@ impl Display for ErrorMax {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum ErrorConcesionario {
+    Capacidad(ErrorMax),
+    Persistencia(ErrorPersistencia)
+}
+
+impl Display for ErrorConcesionario {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorConcesionario::Capacidad(e) => write!(f, "{e}"),
+            ErrorConcesionario::Persistencia(e) => write!(f, "No se pudo guardar autos.json: {e}")
+        }
+    }
+}
+
+impl From<ErrorMax> for ErrorConcesionario {
+    fn from(error: ErrorMax) -> Self {
+        ErrorConcesionario::Capacidad(error)
+    }
+}
+
+impl From<ErrorPersistencia> for ErrorConcesionario {
+    fn from(error: ErrorPersistencia) -> Self {
+        ErrorConcesionario::Persistencia(error)
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum Color {
     Rojo,
@@ -31,65 +60,55 @@ pub struct Auto {
 }
 
 pub struct ConcesionarioAuto {
-    nombre: String,
-    direccion: String,
+    pub nombre: String,
+    pub direccion: String,
     capacidad: u32,
-    pub autos: Vec<Auto>
+    pub autos: Vec<Auto>,
+    persistencia: Persistencia
 }
 
 impl ConcesionarioAuto {
-    pub fn new(nombre: String, direccion: String, capacidad: u32) -> ConcesionarioAuto {
+    pub fn new(nombre: String, direccion: String, capacidad: u32, persistencia: Persistencia) -> ConcesionarioAuto {
         ConcesionarioAuto {
             nombre,
             direccion,
             capacidad,
-            autos: Vec::new()
+            autos: Vec::new(),
+            persistencia
         }
     }
 
-    fn escribir_autos(&self) {
-        match File::create("./autos.json") {
-            Ok(mut file) => {
-                let b_s = serde_json::to_string_pretty(&self.autos).unwrap();
-                file.write_all(&b_s.as_bytes()).expect("Error al escribir el archivo autos.json");
-            },
-            Err(error) => {
-                println!("Error al crear archivo: {error}");
-            }
-        };
+    fn escribir_autos(&self) -> Result<(), ErrorPersistencia> {
+        self.persistencia.guardar("autos.json", &self.autos)
     }
 
-    pub fn agregar_auto(&mut self, auto: Auto) -> Result<(), ErrorMax> {
-        if self.capacidad > self.autos.len() as u32 + 1 {
+    pub fn agregar_auto(&mut self, auto: Auto) -> Result<(), ErrorConcesionario> {
+        if (self.autos.len() as u32) < self.capacidad {
             self.autos.push(auto);
-            self.escribir_autos();
+            self.escribir_autos()?;
             Ok(())
         } else {
             Err(ErrorMax(self.capacidad))?
         }
     }
 
-    pub fn eliminar_auto(&mut self, auto: &Auto) {
+    pub fn eliminar_auto(&mut self, auto: &Auto) -> Result<(), ErrorPersistencia> {
         let mut indice = None;
         for (i, a) in self.autos.iter().enumerate() {
             if auto == a {
                 indice = Some(i);
             }
         }
-        if indice.is_some() {
-            self.autos.swap_remove(indice.unwrap());
-            self.escribir_autos();
+        if let Some(i) = indice {
+            self.autos.swap_remove(i);
+            self.escribir_autos()?;
         }
+        Ok(())
     }
 
     // Retorna una referencia que va a ser valida mientras self.autos exista
     pub fn buscar_auto(&self, auto: &Auto) -> Option<&Auto> {
-        for a in self.autos.iter() {
-            if a == auto {
-                return Some(a);
-            } 
-        }
-        None
+        self.autos.iter().find(|a| *a == auto)
     }
 }
 
@@ -116,7 +135,7 @@ impl Auto {
             }
         }
 
-        if self.marca == String::from("BMW") {
+        if self.marca == "BMW" {
             precio_total *= 1.5;
         }
         if self.anio < 2000 {
@@ -129,7 +148,7 @@ impl Auto {
 
 #[test]
 fn test_concesionario_new() {
-    let mut c = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 50);
+    let c = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 50, Persistencia::memoria());
  
     assert_eq!(String::from("test1"), c.nombre);
     assert_eq!(String::from("test calle"), c.direccion);
@@ -138,42 +157,54 @@ fn test_concesionario_new() {
 
 #[test]
 fn test_agregar_auto() {
-    let mut c = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 50);
+    let mut c = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 50, Persistencia::memoria());
    
-    c.agregar_auto(Auto::new(String::from("Test1"), String::from("Test2"), 2003, 4500.0, Color::Rojo));
-    c.agregar_auto(Auto::new(String::from("Test3"), String::from("Test4"), 2007, 3500.0, Color::Amarillo));
-    c.agregar_auto(Auto::new(String::from("Test5"), String::from("Test6"), 1998, 6500.0, Color::Blanco));
+    c.agregar_auto(Auto::new(String::from("Test1"), String::from("Test2"), 2003, 4500.0, Color::Rojo)).unwrap();
+    c.agregar_auto(Auto::new(String::from("Test3"), String::from("Test4"), 2007, 3500.0, Color::Amarillo)).unwrap();
+    c.agregar_auto(Auto::new(String::from("Test5"), String::from("Test6"), 1998, 6500.0, Color::Blanco)).unwrap();
 
     assert_eq!(3, c.autos.len());
 
-    let mut c2 = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 2);
-    c2.agregar_auto(Auto::new(String::from("Test1"), String::from("Test2"), 2000, 2500.0, Color::Verde));
-    c2.agregar_auto(Auto::new(String::from("Test3"), String::from("Test4"), 2020, 5250.0, Color::Azul));
+    let mut c2 = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 2, Persistencia::memoria());
+    c2.agregar_auto(Auto::new(String::from("Test1"), String::from("Test2"), 2000, 2500.0, Color::Verde)).unwrap();
+    c2.agregar_auto(Auto::new(String::from("Test3"), String::from("Test4"), 2020, 5250.0, Color::Azul)).unwrap();
     
     let res = c2.agregar_auto(Auto::new(String::from("Test5"), String::from("Test6"), 2021, 10000.0, Color::Rojo));
 
-    assert_eq!(ErrorMax(2), res.err().unwrap());
+    assert_eq!(ErrorConcesionario::Capacidad(ErrorMax(2)), res.err().unwrap());
+}
+
+#[test]
+fn test_agregar_auto_hasta_la_capacidad() {
+    // Un concesionario de capacidad n guarda n autos, ni uno menos
+    let mut c = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 1, Persistencia::memoria());
+    c.agregar_auto(Auto::new(String::from("Test1"), String::from("Test2"), 2000, 2500.0, Color::Verde)).unwrap();
+    assert_eq!(1, c.autos.len());
+    assert!(c.agregar_auto(Auto::new(String::from("Test3"), String::from("Test4"), 2020, 5250.0, Color::Azul)).is_err());
+
+    let mut vacio = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 0, Persistencia::memoria());
+    assert_eq!(ErrorConcesionario::Capacidad(ErrorMax(0)), vacio.agregar_auto(Auto::new(String::from("Test1"), String::from("Test2"), 2000, 2500.0, Color::Verde)).unwrap_err());
 }
 
 #[test]
 fn test_eliminar_auto() {
-    let mut c = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 5);
-    c.agregar_auto(Auto::new(String::from("Test1"), String::from("Test2"), 2000, 2500.0, Color::Verde));
-    c.agregar_auto(Auto::new(String::from("Test3"), String::from("Test4"), 2020, 5250.0, Color::Azul));
-    c.agregar_auto(Auto::new(String::from("Test5"), String::from("Test6"), 2021, 10000.0, Color::Rojo));
+    let mut c = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 5, Persistencia::memoria());
+    c.agregar_auto(Auto::new(String::from("Test1"), String::from("Test2"), 2000, 2500.0, Color::Verde)).unwrap();
+    c.agregar_auto(Auto::new(String::from("Test3"), String::from("Test4"), 2020, 5250.0, Color::Azul)).unwrap();
+    c.agregar_auto(Auto::new(String::from("Test5"), String::from("Test6"), 2021, 10000.0, Color::Rojo)).unwrap();
 
     let a = Auto::new(String::from("Test1"), String::from("Test2"), 2000, 2500.0, Color::Verde);
 
-    c.eliminar_auto(&a);
+    c.eliminar_auto(&a).unwrap();
     assert_eq!(2, c.autos.len());
 }
 
 #[test]
 fn test_buscar_auto() {
-    let mut c = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 5);
-    c.agregar_auto(Auto::new(String::from("Test1"), String::from("Test2"), 2000, 2500.0, Color::Verde));
-    c.agregar_auto(Auto::new(String::from("Test3"), String::from("Test4"), 2020, 5250.0, Color::Azul));
-    c.agregar_auto(Auto::new(String::from("Test5"), String::from("Test6"), 2021, 10000.0, Color::Rojo));
+    let mut c = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 5, Persistencia::memoria());
+    c.agregar_auto(Auto::new(String::from("Test1"), String::from("Test2"), 2000, 2500.0, Color::Verde)).unwrap();
+    c.agregar_auto(Auto::new(String::from("Test3"), String::from("Test4"), 2020, 5250.0, Color::Azul)).unwrap();
+    c.agregar_auto(Auto::new(String::from("Test5"), String::from("Test6"), 2021, 10000.0, Color::Rojo)).unwrap();
     
     let a = Auto::new(String::from("Test1"), String::from("Test2"), 2000, 2500.0, Color::Verde);
     let n = Auto::new(String::from("Test23"), String::from("Test2"), 2000, 2500.0, Color::Verde);
@@ -202,3 +233,13 @@ fn test_auto_calcular_precio() {
     assert_eq!(3375.0, a.calcular_precio());
     assert_eq!(4037.5, a2.calcular_precio());
  }
+
+#[test]
+fn test_concesionario_guarda_autos() {
+    let persistencia = Persistencia::memoria();
+    let mut c = ConcesionarioAuto::new(String::from("test1"), String::from("test calle"), 5, persistencia.clone());
+    c.agregar_auto(Auto::new(String::from("Test1"), String::from("Test2"), 2000, 2500.0, Color::Verde)).unwrap();
+
+    let guardados: Vec<Auto> = persistencia.cargar("autos.json").unwrap().unwrap();
+    assert_eq!(c.autos, guardados);
+}
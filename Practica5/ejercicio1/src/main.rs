@@ -1,6 +1,2 @@
-use autos::{Auto, ErrorMax};
-
-mod autos;
-
 fn main() {
 }
@@ -1,4 +1,2 @@
-mod playlist;
-
 fn main() {
 }
@@ -1,6 +1,7 @@
-use std::{fs::File, io::Write};
 use serde::{Deserialize, Serialize};
 
+use persistencia::{ErrorPersistencia, Persistencia};
+
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub enum Genero {
     Rock,
@@ -18,7 +19,8 @@ pub struct Cancion {
 
 pub struct Playlist {
     nombre: String,
-    canciones: Vec<Cancion>
+    canciones: Vec<Cancion>,
+    persistencia: Persistencia
 }
 
 impl Cancion {
@@ -32,46 +34,40 @@ impl Cancion {
 }
 
 impl Playlist {
-    pub fn new(nombre: String) -> Playlist {
+    pub fn new(nombre: String, persistencia: Persistencia) -> Playlist {
         Playlist {
             nombre,
-            canciones: Vec::new()
+            canciones: Vec::new(),
+            persistencia
         }
     }
 
-    fn escribir_canciones(&self) {
-        match File::create("./canciones.json") {
-            Ok(mut file) => {
-                let t_s = serde_json::to_string_pretty(&self.canciones).unwrap();
-                file.write_all(&t_s.as_bytes()).expect("Error al escribir el archivo canciones.json");
-            },
-            Err(error) => {
-                println!("Error al crear archivo: {error}");
-            }
-        };
+    fn escribir_canciones(&self) -> Result<(), ErrorPersistencia> {
+        self.persistencia.guardar("canciones.json", &self.canciones)
     }
 
-    pub fn agregar_cancion(&mut self, cancion: Cancion) {
+    pub fn agregar_cancion(&mut self, cancion: Cancion) -> Result<(), ErrorPersistencia> {
         self.canciones.push(cancion);
-        self.escribir_canciones();
+        self.escribir_canciones()
     }
 
-    pub fn eliminar_cancion(&mut self, cancion: &Cancion) {
+    pub fn eliminar_cancion(&mut self, cancion: &Cancion) -> Result<(), ErrorPersistencia> {
         let mut index = None;
         for (i, c) in self.canciones.iter().enumerate() {
             if c == cancion {
                 index = Some(i);
             }
         }
-        if index.is_some() {
-            self.canciones.remove(index.unwrap());
-            self.escribir_canciones();
+        if let Some(i) = index {
+            self.canciones.remove(i);
+            self.escribir_canciones()?;
         }
+        Ok(())
     }
 
-    pub fn mover_cancion(&mut self, cancion: &Cancion, pos: usize) {
+    pub fn mover_cancion(&mut self, cancion: &Cancion, pos: usize) -> Result<(), ErrorPersistencia> {
         if pos - 1 > self.canciones.len() {
-            return;
+            return Ok(());
         }
         let mut index: Option<usize> = None;
         for (i, c) in self.canciones.iter().enumerate() {
@@ -80,10 +76,11 @@ impl Playlist {
             }
         }
 
-        if index.is_some() {
-            self.canciones.swap(index.unwrap(), pos - 1);
-            self.escribir_canciones();
+        if let Some(i) = index {
+            self.canciones.swap(i, pos - 1);
+            self.escribir_canciones()?;
         }
+        Ok(())
     }
 
     pub fn buscar_cancion_nombre(&self, nombre: String) -> Option<Cancion> {
@@ -124,8 +121,9 @@ impl Playlist {
         self.nombre = titulo;
     }
 
-    pub fn eliminar_canciones(&mut self) {
+    pub fn eliminar_canciones(&mut self) -> Result<(), ErrorPersistencia> {
         self.canciones = Vec::new();
+        self.escribir_canciones()
     }
 }
 
@@ -140,19 +138,19 @@ fn test_cancion_new() {
 
 #[test]
 fn test_playlist_new() {
-    let p = Playlist::new(String::from("Test1"));
+    let p = Playlist::new(String::from("Test1"), Persistencia::memoria());
 
     assert_eq!(String::from("Test1"), p.nombre);
-    assert_eq!(true, p.canciones.is_empty());    
+    assert!(p.canciones.is_empty());    
 }
 
 #[test]
 fn test_playlist_agregar_cancion() {
-    let mut p = Playlist::new(String::from("Test1"));
+    let mut p = Playlist::new(String::from("Test1"), Persistencia::memoria());
     let c = Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Otros);
     
-    assert_eq!(true, p.canciones.is_empty());    
-    p.agregar_cancion(c);
+    assert!(p.canciones.is_empty());    
+    p.agregar_cancion(c).unwrap();
     
     assert_eq!(1, p.canciones.len());
     assert_eq!(String::from("Test2"), p.canciones[0].titulo);
@@ -160,34 +158,34 @@ fn test_playlist_agregar_cancion() {
 
 #[test]
 fn test_playlist_eliminar_cancion() {
-    let mut p = Playlist::new(String::from("Test1"));
+    let mut p = Playlist::new(String::from("Test1"), Persistencia::memoria());
     let c = Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Otros);
-    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Otros));
+    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Otros)).unwrap();
 
-    p.eliminar_cancion(&c);
+    p.eliminar_cancion(&c).unwrap();
 
-    assert_eq!(true, p.canciones.is_empty());
+    assert!(p.canciones.is_empty());
 }
 
 #[test]
 fn test_playlist_mover_cancion() {
-    let mut p = Playlist::new(String::from("Test1"));
+    let mut p = Playlist::new(String::from("Test1"), Persistencia::memoria());
     let c = Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Otros);
-    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Otros));
-    p.agregar_cancion(Cancion::new(String::from("Test4"), String::from("Test5"), Genero::Pop));
+    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Otros)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test4"), String::from("Test5"), Genero::Pop)).unwrap();
     
-    p.mover_cancion(&c, 10);
-    p.mover_cancion(&c, 2);
+    p.mover_cancion(&c, 10).unwrap();
+    p.mover_cancion(&c, 2).unwrap();
     assert_eq!(c, p.canciones[1]);
     assert_ne!(c, p.canciones[0]);
 }
 
 #[test]
 fn test_playlist_buscar_nombre() {
-    let mut p = Playlist::new(String::from("Test1"));
-    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Otros));
-    p.agregar_cancion(Cancion::new(String::from("Test4"), String::from("Test5"), Genero::Rock));
-    p.agregar_cancion(Cancion::new(String::from("Test6"), String::from("Test7"), Genero::Jazz));
+    let mut p = Playlist::new(String::from("Test1"), Persistencia::memoria());
+    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Otros)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test4"), String::from("Test5"), Genero::Rock)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test6"), String::from("Test7"), Genero::Jazz)).unwrap();
     
     let c = p.buscar_cancion_nombre(String::from("Test6"));
     let c2 = p.buscar_cancion_nombre(String::from("No existe"));
@@ -197,29 +195,29 @@ fn test_playlist_buscar_nombre() {
 
 #[test]
 fn test_playlist_obtener_genero() {
-    let mut p = Playlist::new(String::from("Test1"));
-    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Jazz));
-    p.agregar_cancion(Cancion::new(String::from("Test4"), String::from("Test5"), Genero::Rock));
-    p.agregar_cancion(Cancion::new(String::from("Test6"), String::from("Test7"), Genero::Otros));
-    p.agregar_cancion(Cancion::new(String::from("Test8"), String::from("Test9"), Genero::Rock));
-    p.agregar_cancion(Cancion::new(String::from("Test10"), String::from("Test11"), Genero::Jazz));
-    p.agregar_cancion(Cancion::new(String::from("Test12"), String::from("Test13"), Genero::Rock));
+    let mut p = Playlist::new(String::from("Test1"), Persistencia::memoria());
+    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Jazz)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test4"), String::from("Test5"), Genero::Rock)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test6"), String::from("Test7"), Genero::Otros)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test8"), String::from("Test9"), Genero::Rock)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test10"), String::from("Test11"), Genero::Jazz)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test12"), String::from("Test13"), Genero::Rock)).unwrap();
     
     let rock = p.obtener_canciones_genero(Genero::Rock);
     let pop = p.obtener_canciones_genero(Genero::Pop);
     assert_eq!(3, rock.len());
-    assert_eq!(true, pop.is_empty());
+    assert!(pop.is_empty());
 }
 
 #[test]
 fn test_playlist_obtener_canciones_artista() {
-    let mut p = Playlist::new(String::from("Test1"));
-    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Jazz));
-    p.agregar_cancion(Cancion::new(String::from("Test4"), String::from("Test3"), Genero::Rock));
-    p.agregar_cancion(Cancion::new(String::from("Test6"), String::from("Test5"), Genero::Otros));
-    p.agregar_cancion(Cancion::new(String::from("Test8"), String::from("Test3"), Genero::Rock));
-    p.agregar_cancion(Cancion::new(String::from("Test10"), String::from("Test11"), Genero::Jazz));
-    p.agregar_cancion(Cancion::new(String::from("Test12"), String::from("Test13"), Genero::Rock));
+    let mut p = Playlist::new(String::from("Test1"), Persistencia::memoria());
+    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Jazz)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test4"), String::from("Test3"), Genero::Rock)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test6"), String::from("Test5"), Genero::Otros)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test8"), String::from("Test3"), Genero::Rock)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test10"), String::from("Test11"), Genero::Jazz)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test12"), String::from("Test13"), Genero::Rock)).unwrap();
     
     let test3 = p.obtener_canciones_artista("Test3".to_string());
     let test5 = p.obtener_canciones_artista("Test5".to_string());
@@ -233,7 +231,7 @@ fn test_playlist_obtener_canciones_artista() {
 
 #[test]
 fn test_playlist_modificar_titulo() {
-    let mut p = Playlist::new(String::from("Test1"));
+    let mut p = Playlist::new(String::from("Test1"), Persistencia::memoria());
 
     p.modificar_titulo(String::from("Playlist musica"));
 
@@ -242,12 +240,23 @@ fn test_playlist_modificar_titulo() {
 
 #[test]
 fn test_playlist_eliminar_canciones() {
-    let mut p = Playlist::new(String::from("Test1"));
-    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Jazz));
-    p.agregar_cancion(Cancion::new(String::from("Test4"), String::from("Test5"), Genero::Rock));
-    p.agregar_cancion(Cancion::new(String::from("Test6"), String::from("Test7"), Genero::Otros));
-    p.agregar_cancion(Cancion::new(String::from("Test8"), String::from("Test9"), Genero::Rock));
-
-    p.eliminar_canciones();
-    assert_eq!(true, p.canciones.is_empty());
-}
\ No newline at end of file
+    let mut p = Playlist::new(String::from("Test1"), Persistencia::memoria());
+    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Jazz)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test4"), String::from("Test5"), Genero::Rock)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test6"), String::from("Test7"), Genero::Otros)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test8"), String::from("Test9"), Genero::Rock)).unwrap();
+
+    p.eliminar_canciones().unwrap();
+    assert!(p.canciones.is_empty());
+}
+#[test]
+fn test_playlist_guarda_canciones() {
+    let persistencia = Persistencia::memoria();
+    let mut p = Playlist::new(String::from("Test1"), persistencia.clone());
+    p.agregar_cancion(Cancion::new(String::from("Test2"), String::from("Test3"), Genero::Jazz)).unwrap();
+    p.agregar_cancion(Cancion::new(String::from("Test4"), String::from("Test5"), Genero::Rock)).unwrap();
+
+    assert_eq!(Ok(Some(p.canciones.clone())), persistencia.cargar::<Vec<Cancion>>("canciones.json"));
+    p.eliminar_canciones().unwrap();
+    assert_eq!(Ok(Some(Vec::new())), persistencia.cargar::<Vec<Cancion>>("canciones.json"));
+}
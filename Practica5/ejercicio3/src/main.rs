@@ -1,5 +1,2 @@
-mod veterinaria;
-mod fecha;
-
 fn main() {
 }
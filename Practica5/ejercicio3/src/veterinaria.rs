@@ -1,7 +1,8 @@
-use std::{collections::VecDeque, fs::File, io::Write};
+use std::collections::VecDeque;
 use serde::{Deserialize, Serialize};
 
-use crate::fecha::Fecha;
+use fecha::Fecha;
+use persistencia::{ErrorPersistencia, Persistencia};
 
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub struct Duenio {
@@ -37,15 +38,17 @@ pub struct Atencion {
 
 
 pub struct Veterinaria {
-    nombre: String,
-    direccion: String,
-    id: u32,
+    pub nombre: String,
+    pub direccion: String,
+    pub id: u32,
     cola: VecDeque<Mascota>,
-    atenciones_realizadas: Vec<Atencion>
+    atenciones_realizadas: Vec<Atencion>,
+    persistencia: Persistencia
 }
 
+#[derive(Default)]
 pub struct Cadena {
-    veterinarias: Vec<Veterinaria>
+    pub veterinarias: Vec<Veterinaria>
 }
 
 impl Duenio {
@@ -81,29 +84,21 @@ impl Atencion {
 }
 
 impl Veterinaria {
-    pub fn new(nombre: String, direccion: String, id: u32) -> Veterinaria {
+    pub fn new(nombre: String, direccion: String, id: u32, persistencia: Persistencia) -> Veterinaria {
         Veterinaria {
             nombre,
             direccion,
             id,
             cola: VecDeque::new(),
-            atenciones_realizadas: Vec::new()
+            atenciones_realizadas: Vec::new(),
+            persistencia
         }
     }
 
-    
-    fn escribir_atenciones(&self) {
-        match File::create("./atenciones.json") {
-            Ok(mut file) => {
-                let b_s = serde_json::to_string_pretty(&self.atenciones_realizadas).unwrap();
-                file.write_all(&b_s.as_bytes()).expect("Error al escribir el archivo atenciones.json");
-            },
-            Err(error) => {
-                println!("Error al crear archivo: {error}");
-            }
-        };
+    fn escribir_atenciones(&self) -> Result<(), ErrorPersistencia> {
+        self.persistencia.guardar("atenciones.json", &self.atenciones_realizadas)
     }
-    
+
 
     pub fn agregar_cola(&mut self, mascota: Mascota) {
         self.cola.push_back(mascota);
@@ -126,49 +121,46 @@ impl Veterinaria {
             }
         }
 
-        if index.is_some() {
-            self.cola.remove(index.unwrap());
+        if let Some(i) = index {
+            self.cola.remove(i);
             true
         } else {
             false
         }
     }
 
-    pub fn registrar_atencion(&mut self, mascota: Mascota, diagnostico: String, tratamiento: String, prox_visita: Option<Fecha>) {
+    pub fn registrar_atencion(&mut self, mascota: Mascota, diagnostico: String, tratamiento: String, prox_visita: Option<Fecha>) -> Result<(), ErrorPersistencia> {
         self.atenciones_realizadas.push(Atencion::new(mascota, diagnostico, tratamiento, prox_visita));
-        self.escribir_atenciones();
+        self.escribir_atenciones()
     }
 
     pub fn buscar_atencion(&self, nombre_mascota: &str, nombre_duenio: &str, telefono_duenio: &str) -> Option<&Atencion> {
-        for m in self.atenciones_realizadas.iter() {
-            if m.mascota.nombre == nombre_mascota &&
+        self.atenciones_realizadas.iter().find(|m| {
+            m.mascota.nombre == nombre_mascota &&
                 m.mascota.duenio.nombre == nombre_duenio &&
-                m.mascota.duenio.telefono == telefono_duenio {
-                    return Some(m);
-                }
-        }
-        None
+                m.mascota.duenio.telefono == telefono_duenio
+        })
     }
 
-    pub fn modificar_diagnostico(&mut self, atencion: &Atencion, nuevo_diagnostico: &String) {
+    pub fn modificar_diagnostico(&mut self, atencion: &Atencion, nuevo_diagnostico: &str) -> Result<(), ErrorPersistencia> {
         for m in self.atenciones_realizadas.iter_mut() {
             if m == atencion {
-                m.diagnostico = nuevo_diagnostico.clone();
+                m.diagnostico = nuevo_diagnostico.to_string();
             }
         }
-        self.escribir_atenciones();
+        self.escribir_atenciones()
     }
 
-    pub fn modificar_fecha(&mut self, atencion: &Atencion, nueva_fecha: Option<Fecha>) {
+    pub fn modificar_fecha(&mut self, atencion: &Atencion, nueva_fecha: Option<Fecha>) -> Result<(), ErrorPersistencia> {
         for m in self.atenciones_realizadas.iter_mut() {
             if m == atencion {
                 m.visita = nueva_fecha.clone();
             }
         }
-        self.escribir_atenciones();
+        self.escribir_atenciones()
     }
 
-    pub fn eliminar_atencion(&mut self, atencion: &Atencion) {
+    pub fn eliminar_atencion(&mut self, atencion: &Atencion) -> Result<(), ErrorPersistencia> {
         let mut index = None;
 
         for (i, m) in self.atenciones_realizadas.iter().enumerate() {
@@ -177,10 +169,11 @@ impl Veterinaria {
             }
         }
 
-        if index.is_some() {
-            self.atenciones_realizadas.remove(index.unwrap());
-            self.escribir_atenciones();
+        if let Some(i) = index {
+            self.atenciones_realizadas.remove(i);
+            self.escribir_atenciones()?;
         }
+        Ok(())
     }
 }
 
@@ -226,7 +219,7 @@ fn test_atencion_new() {
 
 #[test]
 fn test_veterinaria_new() {
-    let v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5);
+    let v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5, Persistencia::memoria());
 
     assert_eq!(String::from("Test1"), v.nombre);
     assert_eq!(String::from("Test2"), v.direccion);
@@ -235,12 +228,12 @@ fn test_veterinaria_new() {
 
 #[test]
 fn test_veterinaria_agregar_cola() {
-    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5);
+    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5, Persistencia::memoria());
     
     let d = Duenio::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let m = Mascota::new(String::from("Test4"), 4, Animal::Perro, d);
 
-    assert_eq!(true, v.cola.is_empty());
+    assert!(v.cola.is_empty());
     
     v.agregar_cola(m);
 
@@ -249,7 +242,7 @@ fn test_veterinaria_agregar_cola() {
 
 #[test]
 fn test_veterinaria_agregar_cola_prioridad() {
-    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5);
+    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5, Persistencia::memoria());
     
     let d = Duenio::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let d1 = Duenio::new(String::from("Test2"), String::from("Test3"), String::from("Test4"));
@@ -264,7 +257,7 @@ fn test_veterinaria_agregar_cola_prioridad() {
 
 #[test]
 fn test_veterinaria_atender_prox() {
-    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5);
+    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5, Persistencia::memoria());
     
     let d = Duenio::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let d1 = Duenio::new(String::from("Test2"), String::from("Test3"), String::from("Test4"));
@@ -281,7 +274,7 @@ fn test_veterinaria_atender_prox() {
 
 #[test]
 fn test_veterinaria_eliminar() {
-    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5);
+    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5, Persistencia::memoria());
     
     let d = Duenio::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let d1 = Duenio::new(String::from("Test2"), String::from("Test3"), String::from("Test4"));
@@ -301,15 +294,15 @@ fn test_veterinaria_eliminar() {
 
 #[test]
 fn test_veterinaria_buscar_atencion() {
-    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5);
+    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5, Persistencia::memoria());
     
     let d = Duenio::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let d1 = Duenio::new(String::from("Test2"), String::from("Test3"), String::from("Test4"));
     let m = Mascota::new(String::from("Test4"), 4, Animal::Perro, d);
     let m1 = Mascota::new(String::from("Test5"), 8, Animal::Gato, d1.clone());
 
-    v.registrar_atencion(m, String::from("Test6"), String::from("Test7"), None);
-    v.registrar_atencion(m1, String::from("Test8"), String::from("Test9"), Some(Fecha::new(20, 7, 2024)));
+    v.registrar_atencion(m, String::from("Test6"), String::from("Test7"), None).unwrap();
+    v.registrar_atencion(m1, String::from("Test8"), String::from("Test9"), Some(Fecha::new(20, 7, 2024))).unwrap();
 
     assert_eq!(String::from("Test6"), v.buscar_atencion("Test4", "Test1", "Test3").unwrap().diagnostico);
     assert_eq!(None, v.buscar_atencion("Test200", "Test100", "Test23"));
@@ -317,17 +310,17 @@ fn test_veterinaria_buscar_atencion() {
 
 #[test]
 fn test_veterinaria_eliminar_atencion() {
-    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5);
+    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5, Persistencia::memoria());
     
     let d = Duenio::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let d1 = Duenio::new(String::from("Test2"), String::from("Test3"), String::from("Test4"));
     let m = Mascota::new(String::from("Test4"), 4, Animal::Perro, d);
     let m1 = Mascota::new(String::from("Test5"), 8, Animal::Gato, d1.clone());
 
-    v.registrar_atencion(m, String::from("Test6"), String::from("Test7"), None);
-    v.registrar_atencion(m1.clone(), String::from("Test8"), String::from("Test9"), Some(Fecha::new(20, 7, 2024)));
+    v.registrar_atencion(m, String::from("Test6"), String::from("Test7"), None).unwrap();
+    v.registrar_atencion(m1.clone(), String::from("Test8"), String::from("Test9"), Some(Fecha::new(20, 7, 2024))).unwrap();
 
-    v.eliminar_atencion(&Atencion::new(m1, String::from("Test8"), String::from("Test9"), Some(Fecha::new(20, 7, 2024))));
+    v.eliminar_atencion(&Atencion::new(m1, String::from("Test8"), String::from("Test9"), Some(Fecha::new(20, 7, 2024)))).unwrap();
 
     assert_eq!(1, v.atenciones_realizadas.len());
     // assert_eq!(None, v.buscar_atencion("Test200", "Test100", "Test23"));
@@ -335,26 +328,40 @@ fn test_veterinaria_eliminar_atencion() {
 
 #[test]
 fn test_veterinaria_modificar_diagnostico() {
-    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5);
+    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5, Persistencia::memoria());
     
     let d = Duenio::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let m = Mascota::new(String::from("Test4"), 4, Animal::Perro, d);
 
-    v.registrar_atencion(m, String::from("Test6"), String::from("Test7"), None);
+    v.registrar_atencion(m, String::from("Test6"), String::from("Test7"), None).unwrap();
 
-    v.modificar_diagnostico(&v.atenciones_realizadas[0].clone(), &String::from("Diagnostico"));
+    v.modificar_diagnostico(&v.atenciones_realizadas[0].clone(), &String::from("Diagnostico")).unwrap();
     assert_eq!(String::from("Diagnostico"), v.buscar_atencion("Test4", "Test1", "Test3").unwrap().diagnostico);
 }
 
 #[test]
 fn test_veterinaria_modificar_fecha() {
-    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5);
+    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5, Persistencia::memoria());
     
     let d = Duenio::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let m = Mascota::new(String::from("Test4"), 4, Animal::Perro, d);
 
-    v.registrar_atencion(m, String::from("Test6"), String::from("Test7"), None);
+    v.registrar_atencion(m, String::from("Test6"), String::from("Test7"), None).unwrap();
 
-    v.modificar_fecha(&v.atenciones_realizadas[0].clone(), Some(Fecha::new(10, 6, 2024)));
+    v.modificar_fecha(&v.atenciones_realizadas[0].clone(), Some(Fecha::new(10, 6, 2024))).unwrap();
     assert_eq!(10, v.buscar_atencion("Test4", "Test1", "Test3").unwrap().visita.as_ref().unwrap().dia);
 }
+
+#[test]
+fn test_veterinaria_guarda_atenciones() {
+    let persistencia = Persistencia::memoria();
+    let mut v = Veterinaria::new(String::from("Test1"), String::from("Test2"), 5, persistencia.clone());
+
+    let d = Duenio::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
+    let m = Mascota::new(String::from("Test4"), 4, Animal::Perro, d);
+    v.registrar_atencion(m, String::from("Test6"), String::from("Test7"), None).unwrap();
+    v.modificar_diagnostico(&v.atenciones_realizadas[0].clone(), "Diagnostico").unwrap();
+
+    let guardadas: Vec<Atencion> = persistencia.cargar("atenciones.json").unwrap().unwrap();
+    assert_eq!(v.atenciones_realizadas, guardadas);
+}
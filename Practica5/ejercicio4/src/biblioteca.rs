@@ -1,6 +1,7 @@
-use std::{collections::HashMap, fs::File, io::Write};
-use crate::fecha::Fecha;
-use chrono::prelude::*;
+use std::collections::HashMap;
+use fecha::Fecha;
+use persistencia::{ErrorPersistencia, Persistencia};
+use reloj::{Reloj, RelojSistema};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
@@ -11,7 +12,7 @@ pub enum Genero {
     Otros
 }
 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub enum Estado {
     Prestamo,
     Devuelto
@@ -33,7 +34,7 @@ pub struct Libro {
     genero: Genero
 }
 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub struct Prestamo {
     libro: Libro,
     cliente: Cliente,
@@ -43,10 +44,12 @@ pub struct Prestamo {
 }
 
 pub struct Biblioteca {
-    nombre: String,
-    direccion: String,
+    pub nombre: String,
+    pub direccion: String,
     disposicion: HashMap<u32, u32>,
-    prestamos: Vec<Prestamo>
+    prestamos: Vec<Prestamo>,
+    persistencia: Persistencia,
+    reloj: Box<dyn Reloj>
 }
 
 impl Cliente {
@@ -83,38 +86,27 @@ impl Prestamo {
 }
 
 impl Biblioteca {
-    pub fn new(nombre: String, direccion: String, disposicion: HashMap<u32, u32>, prestamos: Vec<Prestamo>) -> Biblioteca {
+    pub fn new(nombre: String, direccion: String, disposicion: HashMap<u32, u32>, prestamos: Vec<Prestamo>, persistencia: Persistencia) -> Biblioteca {
         Biblioteca {
             nombre,
             direccion,
             disposicion,
-            prestamos
+            prestamos,
+            persistencia,
+            reloj: Box::new(RelojSistema)
         }
     }
 
-    fn escribir_copias(&self) {
-        match File::create("./copias.json") {
-            Ok(mut file) => {
-                let b_s = serde_json::to_string_pretty(&self.disposicion).unwrap();
-                file.write_all(&b_s.as_bytes()).expect("Error al escribir el archivo copias.json");
-            },
-            Err(error) => {
-                println!("Error al crear archivo: {error}");
-            }
-        };
+    pub fn set_reloj(&mut self, reloj: impl Reloj + 'static) {
+        self.reloj = Box::new(reloj);
     }
-    
 
-    fn escribir_prestamos(&self) {
-        match File::create("./prestamos.json") {
-            Ok(mut file) => {
-                let t_s = serde_json::to_string_pretty(&self.prestamos).unwrap();
-                file.write_all(&t_s.as_bytes()).expect("Error al escribir el archivo prestamos.json");
-            },
-            Err(error) => {
-                println!("Error al crear archivo: {error}");
-            }
-        };
+    fn escribir_copias(&self) -> Result<(), ErrorPersistencia> {
+        self.persistencia.guardar("copias.json", &self.disposicion)
+    }
+
+    fn escribir_prestamos(&self) -> Result<(), ErrorPersistencia> {
+        self.persistencia.guardar("prestamos.json", &self.prestamos)
     }
 
     pub fn cantidad_copias(&self, isbn: u32) -> u32 {
@@ -124,30 +116,26 @@ impl Biblioteca {
         }
     }
 
-    pub fn decrementar_copias(&mut self, isbn: u32) {
+    pub fn decrementar_copias(&mut self, isbn: u32) -> Result<(), ErrorPersistencia> {
         let libro = self.disposicion.get_mut(&isbn);
         
-        match libro {
-            Some(value) => {
-                if *value > 0 {
-                    *value -= 1;
-                }
-                self.escribir_copias();
-            },
-            None => ()
+        if let Some(value) = libro {
+            if *value > 0 {
+                *value -= 1;
+            }
+            self.escribir_copias()?;
         }
+        Ok(())
     }
 
-    pub fn incrementar_copias(&mut self, isbn: u32) {
+    pub fn incrementar_copias(&mut self, isbn: u32) -> Result<(), ErrorPersistencia> {
         let libro = self.disposicion.get_mut(&isbn);
         
-        match libro {
-            Some(value) => {
-                *value += 1;
-                self.escribir_copias();
-            },
-            None => ()
+        if let Some(value) = libro {
+            *value += 1;
+            self.escribir_copias()?;
         }
+        Ok(())
     }
 
     pub fn prestamos_cliente(&self, cliente: &Cliente) -> u32 {
@@ -162,23 +150,21 @@ impl Biblioteca {
         total
     }
 
-    pub fn realizar_prestamo(&mut self, cliente: Cliente, libro: Libro, vencimiento: Fecha) -> bool {
+    pub fn realizar_prestamo(&mut self, cliente: Cliente, libro: Libro, vencimiento: Fecha) -> Result<bool, ErrorPersistencia> {
         if self.prestamos_cliente(&cliente) > 5 || self.cantidad_copias(libro.isbn) == 0 {
-            return false;
+            return Ok(false);
         }
 
-        println!("{}", libro.isbn);
-
         *self.disposicion.get_mut(&libro.isbn).unwrap() -= 1;
         self.prestamos.push(Prestamo::new(libro, cliente, vencimiento, Estado::Prestamo));
-        self.escribir_prestamos();
-        true
+        self.escribir_copias()?;
+        self.escribir_prestamos()?;
+        Ok(true)
     }
 
     pub fn prestamos_a_vencer(&self, dias: u32) -> Vec<&Prestamo> {
         let mut prestamos: Vec<&Prestamo> = Vec::new();
-        let actual: DateTime<Utc> = Utc::now(); 
-        let mut actual_fecha = Fecha::new(actual.day(), actual.month(), actual.year() as u32);
+        let mut actual_fecha = self.reloj.hoy();
         actual_fecha.sumar_dias(dias);
 
         for p in self.prestamos.iter() {
@@ -192,8 +178,7 @@ impl Biblioteca {
 
     pub fn prestamos_vencidos(&self) -> Vec<&Prestamo> {
         let mut prestamos: Vec<&Prestamo> = Vec::new();
-        let actual: DateTime<Utc> = Utc::now(); 
-        let actual_fecha = Fecha::new(actual.day(), actual.month(), actual.year() as u32);
+        let actual_fecha = self.reloj.hoy();
 
         for p in self.prestamos.iter() {
             if actual_fecha.es_mayor(&p.vencimiento) {
@@ -204,29 +189,27 @@ impl Biblioteca {
         prestamos
     }
 
-    pub fn devolver_libro(&mut self, isbn: u32, cliente: &Cliente) {
+    pub fn devolver_libro(&mut self, isbn: u32, cliente: &Cliente) -> Result<(), ErrorPersistencia> {
+        let actual_fecha = self.reloj.hoy();
         for p in self.prestamos.iter_mut() {
             if &p.cliente == cliente && p.estado == Estado::Prestamo && p.libro.isbn == isbn {
                 let libro = self.disposicion.get_mut(&isbn);
 
-                match libro {
-                    Some(value) => {
-                        let actual: DateTime<Utc> = Utc::now(); 
-                        let actual_fecha = Fecha::new(actual.day(), actual.month(), actual.year() as u32);
-                        
-                        p.estado = Estado::Devuelto;
-                        p.devolucion = Some(actual_fecha);
-                        *value += 1;
-                    },
-                    None => ()
+                if let Some(value) = libro {
+                    p.estado = Estado::Devuelto;
+                    p.devolucion = Some(actual_fecha.clone());
+                    *value += 1;
                 }
             }
         }
-        self.escribir_copias();
-        self.escribir_copias();
+        self.escribir_copias()?;
+        self.escribir_prestamos()
     }
 }
 
+#[cfg(test)]
+use reloj::RelojFijo;
+
 #[test]
 fn test_cliente_new() {
     let c = Cliente::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
@@ -264,12 +247,12 @@ fn test_prestamo_new() {
 
 #[test]
 fn test_biblioteca_new() {
-    let b = Biblioteca::new(String::from("Test1"), String::from("Test2"), HashMap::new(), Vec::new());
+    let b = Biblioteca::new(String::from("Test1"), String::from("Test2"), HashMap::new(), Vec::new(), Persistencia::memoria());
 
     assert_eq!(String::from("Test1"), b.nombre);
     assert_eq!(String::from("Test2"), b.direccion);
-    assert_eq!(true, b.disposicion.is_empty());
-    assert_eq!(true, b.prestamos.is_empty());
+    assert!(b.disposicion.is_empty());
+    assert!(b.prestamos.is_empty());
 }
 
 #[test]
@@ -279,7 +262,7 @@ fn test_biblioteca_cantidad_copias() {
         (134, 4),
         (394, 2),
     ]);
-    let b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new());
+    let b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new(), Persistencia::memoria());
 
     assert_eq!(20, b.cantidad_copias(275));
     assert_eq!(0, b.cantidad_copias(123213));
@@ -294,15 +277,15 @@ fn test_biblioteca_decrementar_copias() {
         (134, 4),
         (394, 2),
     ]);
-    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new());
-    b.decrementar_copias(275);
+    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new(), Persistencia::memoria());
+    b.decrementar_copias(275).unwrap();
     assert_eq!(19, b.cantidad_copias(275));
     
-    b.decrementar_copias(394);
-    b.decrementar_copias(394);
-    b.decrementar_copias(394);
-    b.decrementar_copias(394);
-    b.decrementar_copias(123123);
+    b.decrementar_copias(394).unwrap();
+    b.decrementar_copias(394).unwrap();
+    b.decrementar_copias(394).unwrap();
+    b.decrementar_copias(394).unwrap();
+    b.decrementar_copias(123123).unwrap();
     
     assert_eq!(0, b.cantidad_copias(394));
 }
@@ -314,15 +297,15 @@ fn test_biblioteca_incrementar_copias() {
         (134, 4),
         (394, 2),
     ]);
-    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new());
-    b.incrementar_copias(275);
+    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new(), Persistencia::memoria());
+    b.incrementar_copias(275).unwrap();
     assert_eq!(21, b.cantidad_copias(275));
     
-    b.incrementar_copias(394);
-    b.incrementar_copias(394);
-    b.incrementar_copias(394);
-    b.incrementar_copias(394);
-    b.incrementar_copias(123123);
+    b.incrementar_copias(394).unwrap();
+    b.incrementar_copias(394).unwrap();
+    b.incrementar_copias(394).unwrap();
+    b.incrementar_copias(394).unwrap();
+    b.incrementar_copias(123123).unwrap();
     
     assert_eq!(6, b.cantidad_copias(394));
 }
@@ -337,9 +320,9 @@ fn test_biblioteca_realizar_prestamo() {
     let c = Cliente::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let l = Libro::new(394, String::from("Test4"), String::from("Test5"), 234, Genero::Tecnico);
    
-    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new());
+    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new(), Persistencia::memoria());
     
-    b.realizar_prestamo(c, l, Fecha::new(12, 6, 2024));
+    b.realizar_prestamo(c, l, Fecha::new(12, 6, 2024)).unwrap();
     assert_eq!(1, b.cantidad_copias(394));
     assert_eq!(b.prestamos[0].estado, Estado::Prestamo);
     assert_eq!(b.prestamos[0].cliente.nombre, String::from("Test1"));
@@ -356,20 +339,20 @@ fn test_prestamos_cliente() {
     let l = Libro::new(394, String::from("Test4"), String::from("Test5"), 234, Genero::Tecnico);
     let l2 = Libro::new(275, String::from("Test6"), String::from("Test7"), 120, Genero::Otros);
    
-    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new());
+    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new(), Persistencia::memoria());
     
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(12, 6, 2024));
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(12, 6, 2024));
-    b.realizar_prestamo(c.clone(), l, Fecha::new(12, 6, 2024)); // No agrega, no hay mas copias
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(12, 6, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(12, 6, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l, Fecha::new(12, 6, 2024)).unwrap(); // No agrega, no hay mas copias
 
     assert_eq!(2, b.prestamos_cliente(&c));
 
-    b.realizar_prestamo(c.clone(), l2.clone(), Fecha::new(12, 6, 2024));
-    b.realizar_prestamo(c.clone(), l2.clone(), Fecha::new(12, 6, 2024));
-    b.realizar_prestamo(c.clone(), l2.clone(), Fecha::new(12, 6, 2024));
-    b.realizar_prestamo(c.clone(), l2.clone(), Fecha::new(12, 6, 2024));
-    b.realizar_prestamo(c.clone(), l2.clone(), Fecha::new(12, 6, 2024));
-    b.realizar_prestamo(c, l2, Fecha::new(12, 6, 2024));
+    b.realizar_prestamo(c.clone(), l2.clone(), Fecha::new(12, 6, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l2.clone(), Fecha::new(12, 6, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l2.clone(), Fecha::new(12, 6, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l2.clone(), Fecha::new(12, 6, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l2.clone(), Fecha::new(12, 6, 2024)).unwrap();
+    b.realizar_prestamo(c, l2, Fecha::new(12, 6, 2024)).unwrap();
     
     assert_eq!(6, b.prestamos_cliente(&Cliente::new(String::from("Test1"), String::from("Test2"), String::from("Test3"))));
     
@@ -384,12 +367,13 @@ fn test_biblioteca_prestamos_a_vencer() {
     ]);
     let c = Cliente::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let l = Libro::new(275, String::from("Test6"), String::from("Test7"), 120, Genero::Otros);
-    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new());
+    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new(), Persistencia::memoria());
+    b.set_reloj(RelojFijo::new(Fecha::new(12, 5, 2024)));
 
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(17, 5, 2024));
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(20, 6, 2024));
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(15, 5, 2024));
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(18, 5, 2024));
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(17, 5, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(20, 6, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(15, 5, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(18, 5, 2024)).unwrap();
 
     assert_eq!(3, b.prestamos_a_vencer(7).len());
 }
@@ -403,16 +387,46 @@ fn test_biblioteca_prestamos_vencidos() {
     ]);
     let c = Cliente::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let l = Libro::new(275, String::from("Test6"), String::from("Test7"), 120, Genero::Otros);
-    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new());
+    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new(), Persistencia::memoria());
+    b.set_reloj(RelojFijo::new(Fecha::new(15, 5, 2024)));
 
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(5, 4, 2024));
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(2, 4, 2024));
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(14, 5, 2024));
-    b.realizar_prestamo(c, l, Fecha::new(10, 5, 2024));
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(5, 4, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(2, 4, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(14, 5, 2024)).unwrap();
+    b.realizar_prestamo(c, l, Fecha::new(10, 5, 2024)).unwrap();
 
     assert_eq!(4, b.prestamos_vencidos().len());
 }
 
+#[test]
+fn test_biblioteca_vencimientos_fin_de_febrero() {
+    let libros: HashMap<u32, u32> = HashMap::from([(275, 20)]);
+    let c = Cliente::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
+    let l = Libro::new(275, String::from("Test6"), String::from("Test7"), 120, Genero::Otros);
+    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new(), Persistencia::memoria());
+    let reloj = RelojFijo::new(Fecha::new(27, 2, 2024));
+    b.set_reloj(reloj.clone());
+
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(29, 2, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(1, 3, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(2, 3, 2024)).unwrap();
+
+    // 2024 es bisiesto: a dos dias del 27 de febrero se llega al 29, a tres al 1 de marzo
+    assert_eq!(1, b.prestamos_a_vencer(2).len());
+    assert_eq!(2, b.prestamos_a_vencer(3).len());
+    assert!(b.prestamos_vencidos().is_empty());
+
+    reloj.avanzar_dias(3);
+    assert_eq!(2, b.prestamos_vencidos().len());
+    assert_eq!(3, b.prestamos_a_vencer(1).len());
+
+    // En 2023 el 27 de febrero mas dos dias ya es marzo
+    b.set_reloj(RelojFijo::new(Fecha::new(27, 2, 2023)));
+    b.realizar_prestamo(c, l, Fecha::new(1, 3, 2023)).unwrap();
+    assert!(b.prestamos_a_vencer(1).is_empty());
+    assert_eq!(1, b.prestamos_a_vencer(2).len());
+}
+
 #[test]
 fn test_biblioteca_devolver_libro() {
     let libros: HashMap<u32, u32> = HashMap::from([
@@ -423,29 +437,45 @@ fn test_biblioteca_devolver_libro() {
     let c = Cliente::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
     let l = Libro::new(275, String::from("Test6"), String::from("Test7"), 120, Genero::Otros);
     let l1 = Libro::new(394, String::from("Test23"), String::from("Tes123"), 300, Genero::Otros);
-    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new());
+    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new(), Persistencia::memoria());
+    b.set_reloj(RelojFijo::new(Fecha::new(31, 12, 2024)));
 
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(5, 4, 2024));
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(2, 4, 2024));
-    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(14, 5, 2024));
-    b.realizar_prestamo(c.clone(), l, Fecha::new(10, 5, 2024));
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(5, 4, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(2, 4, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l.clone(), Fecha::new(14, 5, 2024)).unwrap();
+    b.realizar_prestamo(c.clone(), l, Fecha::new(10, 5, 2024)).unwrap();
     
-    b.realizar_prestamo(c.clone(), l1, Fecha::new(10, 5, 2024));
+    b.realizar_prestamo(c.clone(), l1, Fecha::new(10, 5, 2024)).unwrap();
     b.disposicion.remove(&394);
 
     assert_eq!(16, b.cantidad_copias(275));
     
-    b.devolver_libro(275, &c);
-    b.devolver_libro(275, &c);
-    b.devolver_libro(275, &c);
-    b.devolver_libro(394, &c);
+    b.devolver_libro(275, &c).unwrap();
+    b.devolver_libro(275, &c).unwrap();
+    b.devolver_libro(275, &c).unwrap();
+    b.devolver_libro(394, &c).unwrap();
     
     
     assert_eq!(20, b.cantidad_copias(275));
     assert_eq!(1, b.prestamos_cliente(&c));
+    assert_eq!(Some(Fecha::new(31, 12, 2024)), b.prestamos[0].devolucion);
+    assert_eq!(None, b.prestamos[4].devolucion);
 }
 
 #[test]
 fn test_escribir_prestamos() {
+    let libros: HashMap<u32, u32> = HashMap::from([(275, 2)]);
+    let c = Cliente::new(String::from("Test1"), String::from("Test2"), String::from("Test3"));
+    let l = Libro::new(275, String::from("Test6"), String::from("Test7"), 120, Genero::Otros);
+    let persistencia = Persistencia::memoria();
+    let mut b = Biblioteca::new(String::from("Test1"), String::from("Test2"), libros, Vec::new(), persistencia.clone());
+
+    b.realizar_prestamo(c.clone(), l, Fecha::new(5, 4, 2024)).unwrap();
+    assert_eq!(Ok(Some(HashMap::from([(275, 1)]))), persistencia.cargar::<HashMap<u32, u32>>("copias.json"));
+    assert_eq!(Ok(Some(b.prestamos.clone())), persistencia.cargar::<Vec<Prestamo>>("prestamos.json"));
 
+    b.devolver_libro(275, &c).unwrap();
+    assert_eq!(Ok(Some(HashMap::from([(275, 2)]))), persistencia.cargar::<HashMap<u32, u32>>("copias.json"));
+    assert_eq!(Ok(Some(b.prestamos.clone())), persistencia.cargar::<Vec<Prestamo>>("prestamos.json"));
+    assert_eq!(Estado::Devuelto, b.prestamos[0].estado);
 }
\ No newline at end of file
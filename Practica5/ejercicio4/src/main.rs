@@ -1,5 +1,2 @@
-mod biblioteca;
-mod fecha;
-
 fn main() {
 }
@@ -1,9 +1,19 @@
-mod streaming_rust;
-mod fecha;
+use std::{env, process};
 
+use ejercicio5::streaming_rust::{MedioDePago, Plataforma};
+use persistencia::Persistencia;
 
+// ejercicio5 <directorio>
+// Da de alta un usuario guardando la plataforma en el directorio
 fn main() {
-    let mut plataforma = streaming_rust::Plataforma::new();
-    plataforma.crear_usuario(200, None, streaming_rust::MedioDePago::Cripto);
+    let Some(directorio) = env::args().nth(1) else {
+        eprintln!("Uso: ejercicio5 <directorio>");
+        process::exit(2)
+    };
 
+    let mut plataforma = Plataforma::new(Persistencia::directorio(directorio));
+    if let Err(error) = plataforma.crear_usuario(200, None, MedioDePago::Cripto) {
+        eprintln!("{error}");
+        process::exit(1)
+    }
 }
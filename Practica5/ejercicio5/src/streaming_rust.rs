@@ -1,7 +1,8 @@
-use std::{collections::HashMap, fs::File, io::Write};
-use chrono::*;
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
-use crate::fecha::Fecha;
+use fecha::Fecha;
+use persistencia::{ErrorPersistencia, Persistencia};
+use reloj::{Reloj, RelojSistema};
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Deserialize, Serialize)]
 pub enum MedioDePago {
@@ -37,42 +38,37 @@ pub struct Usuario {
 
 pub struct Plataforma {
     usuarios: Vec<Usuario>,
+    persistencia: Persistencia,
+    reloj: Box<dyn Reloj>
 }
 
 impl Plataforma {
-    pub fn new() -> Plataforma {
+    pub fn new(persistencia: Persistencia) -> Plataforma {
         Plataforma {
             usuarios: Vec::new(),
+            persistencia,
+            reloj: Box::new(RelojSistema)
         }
     }
 
-    fn escribir_subscripciones(&self) {
-        match File::create("./suscripciones.json") {
-            Ok(mut file) => {
-                let b_s = serde_json::to_string_pretty(&self.usuarios).unwrap();
-                file.write_all(&b_s.as_bytes()).expect("Error al escribir el archivo suscripciones.json");
-            },
-            Err(error) => {
-                println!("Error al crear archivo: {error}");
-            }
-        };
+    pub fn set_reloj(&mut self, reloj: impl Reloj + 'static) {
+        self.reloj = Box::new(reloj);
     }
-    
 
-    pub fn crear_usuario(&mut self, id: u32, sub: Option<SubscriptionInfo>, pago: MedioDePago) {
+    fn escribir_subscripciones(&self) -> Result<(), ErrorPersistencia> {
+        self.persistencia.guardar("suscripciones.json", &self.usuarios)
+    }
+
+    pub fn crear_usuario(&mut self, id: u32, sub: Option<SubscriptionInfo>, pago: MedioDePago) -> Result<(), ErrorPersistencia> {
         self.usuarios.push(Usuario::new(id, sub, pago));
-        self.escribir_subscripciones();
+        self.escribir_subscripciones()
     }
 
     pub fn buscar_usuario(&mut self, id: u32) -> Option<&mut Usuario> {
-        if let Some(user) = self.usuarios.iter_mut().find(|x| x.id == id) {
-            return Some(user);
-        } else {
-            None
-        }
+        self.usuarios.iter_mut().find(|x| x.id == id)
     }
 
-    pub fn downgrade(&mut self, id: u32) -> bool {
+    pub fn downgrade(&mut self, id: u32) -> Result<bool, ErrorPersistencia> {
         if let Some(user) = self.usuarios.iter_mut().find(|u| u.id == id) {
             if  let Some(sub) = &mut user.sub {
                 match &sub.tipo {
@@ -89,22 +85,22 @@ impl Plataforma {
                     }
                     
                 };
-                self.escribir_subscripciones();
-                true
+                self.escribir_subscripciones()?;
+                Ok(true)
             } else {
-                false // No sub
+                Ok(false) // No sub
             }
         } else {
-            false // Didnt find user
+            Ok(false) // Didnt find user
         }
     }
 
-    pub fn upgrade(&mut self, id: u32) -> bool {
+    pub fn upgrade(&mut self, id: u32) -> Result<bool, ErrorPersistencia> {
         if let Some(user) = self.usuarios.iter_mut().find(|u| u.id == id) {
             if  let Some(sub) = &mut user.sub {
                 match &sub.tipo {
                     SubscriptionType::Super => {
-                        return false;
+                        return Ok(false);
                     },
                     SubscriptionType::Clasic => {
                         sub.tipo = SubscriptionType::Super;
@@ -115,31 +111,32 @@ impl Plataforma {
                         sub.mensual = 10.99;
                     }
                 };
-                self.escribir_subscripciones();
-                true
+                self.escribir_subscripciones()?;
+                Ok(true)
             } else {
-                false // No sub
+                Ok(false) // No sub
             }
         } else {
-            false // Didnt find user
+            Ok(false) // Didnt find user
         }
     }
 
-    pub fn cancelar_usuario(&mut self, id: u32) -> bool {
+    pub fn cancelar_usuario(&mut self, id: u32) -> Result<bool, ErrorPersistencia> {
         if let Some(user) = self.usuarios.iter_mut().find(|u| u.id == id) {
             user.sub = None;
-            self.escribir_subscripciones();
-            true
+            self.escribir_subscripciones()?;
+            Ok(true)
         } else {
-            false
+            Ok(false)
         }
     }
 
     pub fn mayor_metodo_activo(&self) -> Option<MedioDePago> {
      let mut map = HashMap::new();
+        let hoy = self.reloj.hoy();
         
         for u in &self.usuarios {
-            if u.sub.as_ref().unwrap().es_activa() {
+            if u.sub.as_ref().unwrap().es_activa(&hoy) {
                 *map.entry(u.pago.clone()).or_insert(0) += 1;
             }
         }
@@ -149,9 +146,10 @@ impl Plataforma {
 
     pub fn mayor_suscripcion_activa(&self) -> Option<SubscriptionType> {
         let mut map = HashMap::new();
+        let hoy = self.reloj.hoy();
         
         for u in &self.usuarios {
-            if u.sub.as_ref().unwrap().es_activa() {
+            if u.sub.as_ref().unwrap().es_activa(&hoy) {
                 *map.entry(u.sub.as_ref().unwrap().tipo.clone()).or_insert(0) += 1;
             }
         }
@@ -191,18 +189,12 @@ impl SubscriptionInfo {
         }
     }
 
-    pub fn es_activa(&self) -> bool {
-        let actual: DateTime<Utc> = Utc::now(); 
-        let actual_fecha = Fecha::new(actual.day(), actual.month(), actual.year() as u32);
+    // Sigue activa hasta el ultimo dia pago inclusive, contando cada mes como 30 dias
+    pub fn es_activa(&self, hoy: &Fecha) -> bool {
         let mut sumado = self.inicio.clone();
         sumado.sumar_dias(30 * self.meses as u32);
 
-        if sumado.es_mayor(&actual_fecha) {
-            true
-        } else {
-            false
-        }
-    
+        sumado.es_mayor(hoy)
     }
 }
 
@@ -216,16 +208,19 @@ impl Usuario {
     }
 }
 
+#[cfg(test)]
+use reloj::RelojFijo;
+
 #[test]
 pub fn test_plataforma_new() {
-    let plataforma = Plataforma::new();
+    let plataforma = Plataforma::new(Persistencia::memoria());
     assert_eq!(plataforma.usuarios.len(), 0);
 }
 
 #[test]
 pub fn test_plataforma_crear_usuario() {
-    let mut plataforma = Plataforma::new();
-    plataforma.crear_usuario(1, None, MedioDePago::TarjetaCredito);
+    let mut plataforma = Plataforma::new(Persistencia::memoria());
+    plataforma.crear_usuario(1, None, MedioDePago::TarjetaCredito).unwrap();
     plataforma.crear_usuario(2,
     Some(SubscriptionInfo::new(
             SubscriptionType::Super,
@@ -236,7 +231,7 @@ pub fn test_plataforma_crear_usuario() {
             2024))
         ), 
     MedioDePago::TarjetaCredito
-    );
+    ).unwrap();
     
     assert_eq!(plataforma.usuarios.len(), 2);
     assert_eq!(plataforma.usuarios[0].id, 1);
@@ -250,8 +245,8 @@ pub fn test_plataforma_crear_usuario() {
 
 #[test]
 pub fn test_plataforma_buscar_usuario() {
-    let mut plataforma = Plataforma::new();
-    plataforma.crear_usuario(1, None, MedioDePago::TarjetaCredito);
+    let mut plataforma = Plataforma::new(Persistencia::memoria());
+    plataforma.crear_usuario(1, None, MedioDePago::TarjetaCredito).unwrap();
     plataforma.crear_usuario(2,
         Some(SubscriptionInfo::new(
                 SubscriptionType::Super,
@@ -262,7 +257,7 @@ pub fn test_plataforma_buscar_usuario() {
                 2024))
             ), 
         MedioDePago::TarjetaCredito
-        );
+        ).unwrap();
     plataforma.crear_usuario(3,
         Some(SubscriptionInfo::new(
                 SubscriptionType::Clasic,
@@ -273,7 +268,7 @@ pub fn test_plataforma_buscar_usuario() {
                 2024))
             ), 
         MedioDePago::TarjetaCredito
-        );
+        ).unwrap();
     let user = plataforma.buscar_usuario(1);
     assert_eq!(user.unwrap().id, 1);
 
@@ -290,21 +285,21 @@ pub fn test_plataforma_buscar_usuario() {
 
 #[test]
 pub fn test_plataforma_downgrade() {
-    let mut plataforma = Plataforma::new();
+    let mut plataforma = Plataforma::new(Persistencia::memoria());
     let sub_super = Some(SubscriptionInfo::new(SubscriptionType::Super, 13.99, 1, Fecha::new(10, 3, 2024)));
     let sub_clasic = Some(SubscriptionInfo::new(SubscriptionType::Clasic, 10.99, 1, Fecha::new(10, 3, 2024)));
     let sub_basica = Some(SubscriptionInfo::new(SubscriptionType::Basic, 7.99, 1, Fecha::new(5, 4, 2024)));
     
-    plataforma.crear_usuario(1, sub_super, MedioDePago::TarjetaCredito);
-    plataforma.crear_usuario(2, sub_basica, MedioDePago::Cripto);
-    plataforma.crear_usuario(3, sub_clasic, MedioDePago::Bancaria);
-    plataforma.crear_usuario(4, None, MedioDePago::Bancaria);
-
-    assert!(plataforma.downgrade(1));
-    assert!(plataforma.downgrade(2));
-    assert!(plataforma.downgrade(3));
-    assert_eq!(false, plataforma.downgrade(4));
-    assert_eq!(false, plataforma.downgrade(1000));
+    plataforma.crear_usuario(1, sub_super, MedioDePago::TarjetaCredito).unwrap();
+    plataforma.crear_usuario(2, sub_basica, MedioDePago::Cripto).unwrap();
+    plataforma.crear_usuario(3, sub_clasic, MedioDePago::Bancaria).unwrap();
+    plataforma.crear_usuario(4, None, MedioDePago::Bancaria).unwrap();
+
+    assert!(plataforma.downgrade(1).unwrap());
+    assert!(plataforma.downgrade(2).unwrap());
+    assert!(plataforma.downgrade(3).unwrap());
+    assert!(!plataforma.downgrade(4).unwrap());
+    assert!(!plataforma.downgrade(1000).unwrap());
     
     assert_eq!(plataforma.usuarios[0].sub.as_ref().unwrap().tipo, SubscriptionType::Clasic);
     assert_eq!(plataforma.usuarios[1].sub, None);
@@ -314,21 +309,21 @@ pub fn test_plataforma_downgrade() {
 
 #[test]
 pub fn test_plataforma_upgrade() {
-    let mut plataforma = Plataforma::new();
+    let mut plataforma = Plataforma::new(Persistencia::memoria());
     let sub_super = Some(SubscriptionInfo::new(SubscriptionType::Super, 13.99, 3, Fecha::new(4, 3, 2024)));
     let sub_clasic = Some(SubscriptionInfo::new(SubscriptionType::Clasic, 10.99, 1, Fecha::new(10, 3, 2024)));
     let sub_basica = Some(SubscriptionInfo::new(SubscriptionType::Basic, 7.99, 1, Fecha::new(2, 5, 2024)));
    
-    plataforma.crear_usuario(1, sub_super, MedioDePago::TarjetaCredito);
-    plataforma.crear_usuario(2, sub_basica, MedioDePago::Cripto);
-    plataforma.crear_usuario(3, sub_clasic, MedioDePago::Bancaria);
-    plataforma.crear_usuario(4, None, MedioDePago::Bancaria);
+    plataforma.crear_usuario(1, sub_super, MedioDePago::TarjetaCredito).unwrap();
+    plataforma.crear_usuario(2, sub_basica, MedioDePago::Cripto).unwrap();
+    plataforma.crear_usuario(3, sub_clasic, MedioDePago::Bancaria).unwrap();
+    plataforma.crear_usuario(4, None, MedioDePago::Bancaria).unwrap();
 
-    assert_eq!(false, plataforma.upgrade(1));
-    assert!(plataforma.upgrade(2));
-    assert!(plataforma.upgrade(3));
-    assert_eq!(false, plataforma.upgrade(4));
-    assert_eq!(false, plataforma.upgrade(1000));
+    assert!(!plataforma.upgrade(1).unwrap());
+    assert!(plataforma.upgrade(2).unwrap());
+    assert!(plataforma.upgrade(3).unwrap());
+    assert!(!plataforma.upgrade(4).unwrap());
+    assert!(!plataforma.upgrade(1000).unwrap());
 
     assert_eq!(plataforma.usuarios[0].sub.as_ref().unwrap().tipo, SubscriptionType::Super);
     assert_eq!(plataforma.usuarios[1].sub.as_ref().unwrap().tipo, SubscriptionType::Clasic);
@@ -338,98 +333,134 @@ pub fn test_plataforma_upgrade() {
 
 #[test]
 pub fn test_plataforma_cancelar_usuario() {
-    let mut plataforma = Plataforma::new();
+    let mut plataforma = Plataforma::new(Persistencia::memoria());
     let sub = Some(SubscriptionInfo::new(SubscriptionType::Basic, 7.99, 1, Fecha::new(1, 1, 2022)));
     
-    plataforma.crear_usuario(1, sub, MedioDePago::TarjetaCredito);
-    plataforma.cancelar_usuario(1);
+    plataforma.crear_usuario(1, sub, MedioDePago::TarjetaCredito).unwrap();
+    plataforma.cancelar_usuario(1).unwrap();
     
     assert!(plataforma.usuarios[0].sub.is_none());
 }
 
+#[test]
+pub fn test_subscription_es_activa_fin_de_febrero() {
+    // 30 dias desde el 30 de enero: el 29 de febrero en un año bisiesto, el 1 de marzo si no
+    let bisiesto = SubscriptionInfo::new(SubscriptionType::Basic, 7.99, 1, Fecha::new(30, 1, 2024));
+    assert!(bisiesto.es_activa(&Fecha::new(29, 2, 2024)));
+    assert!(!bisiesto.es_activa(&Fecha::new(1, 3, 2024)));
+
+    let comun = SubscriptionInfo::new(SubscriptionType::Basic, 7.99, 1, Fecha::new(30, 1, 2023));
+    assert!(comun.es_activa(&Fecha::new(1, 3, 2023)));
+    assert!(!comun.es_activa(&Fecha::new(2, 3, 2023)));
+
+    let reloj = RelojFijo::new(Fecha::new(29, 2, 2024));
+    let mut plataforma = Plataforma::new(Persistencia::memoria());
+    plataforma.set_reloj(reloj.clone());
+    plataforma.crear_usuario(1, Some(bisiesto), MedioDePago::Efectivo).unwrap();
+    assert_eq!(Some(SubscriptionType::Basic), plataforma.mayor_suscripcion_activa());
+
+    reloj.avanzar_dias(1);
+    assert_eq!(None, plataforma.mayor_suscripcion_activa());
+}
+
 #[test]
 pub fn test_plataforma_mayor_suscripcion_activa() {
-    let mut plataforma = Plataforma::new();
+    let mut plataforma = Plataforma::new(Persistencia::memoria());
+    plataforma.set_reloj(RelojFijo::new(Fecha::new(20, 5, 2024)));
 
     let sub_basic = Some(SubscriptionInfo::new(SubscriptionType::Basic, 7.99, 1, Fecha::new(5, 5, 2024)));
     let sub_clasic = Some(SubscriptionInfo::new(SubscriptionType::Clasic, 10.99, 3, Fecha::new(20, 3, 2024)));
     let sub_super = Some(SubscriptionInfo::new(SubscriptionType::Super, 13.99, 1, Fecha::new(15, 5, 2024)));
     let inactiva = Some(SubscriptionInfo::new(SubscriptionType::Clasic, 7.99, 1, Fecha::new(1, 4, 2024)));
-    plataforma.crear_usuario(1, sub_basic.clone(), MedioDePago::TarjetaCredito);
-    plataforma.crear_usuario(2, sub_basic.clone(), MedioDePago::Efectivo);
-    plataforma.crear_usuario(3, sub_clasic, MedioDePago::TarjetaCredito);
-    plataforma.crear_usuario(4, inactiva.clone(), MedioDePago::MercadoPago);
-    plataforma.crear_usuario(5, inactiva, MedioDePago::MercadoPago);
-    plataforma.crear_usuario(6, sub_super.clone(), MedioDePago::TarjetaCredito);
+    plataforma.crear_usuario(1, sub_basic.clone(), MedioDePago::TarjetaCredito).unwrap();
+    plataforma.crear_usuario(2, sub_basic.clone(), MedioDePago::Efectivo).unwrap();
+    plataforma.crear_usuario(3, sub_clasic, MedioDePago::TarjetaCredito).unwrap();
+    plataforma.crear_usuario(4, inactiva.clone(), MedioDePago::MercadoPago).unwrap();
+    plataforma.crear_usuario(5, inactiva, MedioDePago::MercadoPago).unwrap();
+    plataforma.crear_usuario(6, sub_super.clone(), MedioDePago::TarjetaCredito).unwrap();
 
     assert_eq!(SubscriptionType::Basic, plataforma.mayor_suscripcion_activa().unwrap());
 
-    let plataforma = Plataforma::new();
+    let plataforma = Plataforma::new(Persistencia::memoria());
     assert_eq!(None, plataforma.mayor_suscripcion_activa());
 }
 
 
 #[test]
 pub fn test_plataforma_mayor_suscripcion() {
-    let mut plataforma = Plataforma::new();
+    let mut plataforma = Plataforma::new(Persistencia::memoria());
 
     let sub_basic = Some(SubscriptionInfo::new(SubscriptionType::Basic, 7.99, 1, Fecha::new(5, 5, 2024)));
     let sub_clasic = Some(SubscriptionInfo::new(SubscriptionType::Clasic, 10.99, 3, Fecha::new(20, 3, 2024)));
     let sub_super = Some(SubscriptionInfo::new(SubscriptionType::Super, 13.99, 1, Fecha::new(15, 5, 2024)));
     let inactiva = Some(SubscriptionInfo::new(SubscriptionType::Clasic, 7.99, 1, Fecha::new(1, 4, 2024)));
-    plataforma.crear_usuario(1, sub_basic.clone(), MedioDePago::TarjetaCredito);
-    plataforma.crear_usuario(2, sub_basic.clone(), MedioDePago::Efectivo);
-    plataforma.crear_usuario(3, sub_clasic, MedioDePago::TarjetaCredito);
-    plataforma.crear_usuario(4, inactiva.clone(), MedioDePago::MercadoPago);
-    plataforma.crear_usuario(5, inactiva, MedioDePago::MercadoPago);
-    plataforma.crear_usuario(6, sub_super.clone(), MedioDePago::TarjetaCredito);
+    plataforma.crear_usuario(1, sub_basic.clone(), MedioDePago::TarjetaCredito).unwrap();
+    plataforma.crear_usuario(2, sub_basic.clone(), MedioDePago::Efectivo).unwrap();
+    plataforma.crear_usuario(3, sub_clasic, MedioDePago::TarjetaCredito).unwrap();
+    plataforma.crear_usuario(4, inactiva.clone(), MedioDePago::MercadoPago).unwrap();
+    plataforma.crear_usuario(5, inactiva, MedioDePago::MercadoPago).unwrap();
+    plataforma.crear_usuario(6, sub_super.clone(), MedioDePago::TarjetaCredito).unwrap();
 
     assert_eq!(SubscriptionType::Clasic, plataforma.mayor_suscripcion().unwrap());
 
-    let plataforma = Plataforma::new();
+    let plataforma = Plataforma::new(Persistencia::memoria());
     assert_eq!(None, plataforma.mayor_suscripcion());
 }
 
 #[test]
 pub fn test_plataforma_mayor_metodo_activo() {
-    let mut plataforma = Plataforma::new();
+    let mut plataforma = Plataforma::new(Persistencia::memoria());
+    plataforma.set_reloj(RelojFijo::new(Fecha::new(20, 5, 2024)));
 
     let sub_basic = Some(SubscriptionInfo::new(SubscriptionType::Basic, 7.99, 1, Fecha::new(5, 5, 2024)));
     let sub_clasic = Some(SubscriptionInfo::new(SubscriptionType::Clasic, 10.99, 3, Fecha::new(20, 3, 2024)));
     let sub_super = Some(SubscriptionInfo::new(SubscriptionType::Super, 13.99, 1, Fecha::new(15, 5, 2024)));
     let inactiva = Some(SubscriptionInfo::new(SubscriptionType::Clasic, 7.99, 1, Fecha::new(1, 4, 2024)));
-    plataforma.crear_usuario(1, sub_basic.clone(), MedioDePago::TarjetaCredito);
-    plataforma.crear_usuario(2, sub_basic.clone(), MedioDePago::Efectivo);
-    plataforma.crear_usuario(3, sub_clasic, MedioDePago::TarjetaCredito);
-    plataforma.crear_usuario(4, inactiva.clone(), MedioDePago::MercadoPago);
-    plataforma.crear_usuario(5, inactiva, MedioDePago::MercadoPago);
-    plataforma.crear_usuario(6, sub_super.clone(), MedioDePago::TarjetaCredito);
+    plataforma.crear_usuario(1, sub_basic.clone(), MedioDePago::TarjetaCredito).unwrap();
+    plataforma.crear_usuario(2, sub_basic.clone(), MedioDePago::Efectivo).unwrap();
+    plataforma.crear_usuario(3, sub_clasic, MedioDePago::TarjetaCredito).unwrap();
+    plataforma.crear_usuario(4, inactiva.clone(), MedioDePago::MercadoPago).unwrap();
+    plataforma.crear_usuario(5, inactiva, MedioDePago::MercadoPago).unwrap();
+    plataforma.crear_usuario(6, sub_super.clone(), MedioDePago::TarjetaCredito).unwrap();
 
     assert_eq!(MedioDePago::TarjetaCredito, plataforma.mayor_metodo_activo().unwrap());
 
-    let plataforma = Plataforma::new();
+    let plataforma = Plataforma::new(Persistencia::memoria());
     assert_eq!(None, plataforma.mayor_metodo_activo());
 }
 
 #[test]
 pub fn test_plataforma_mayor_metodo() {
-    let mut plataforma = Plataforma::new();
+    let mut plataforma = Plataforma::new(Persistencia::memoria());
 
     let sub_basic = Some(SubscriptionInfo::new(SubscriptionType::Basic, 7.99, 1, Fecha::new(5, 5, 2024)));
     let sub_clasic = Some(SubscriptionInfo::new(SubscriptionType::Clasic, 10.99, 3, Fecha::new(20, 3, 2024)));
     let sub_super = Some(SubscriptionInfo::new(SubscriptionType::Super, 13.99, 1, Fecha::new(15, 5, 2024)));
     let inactiva = Some(SubscriptionInfo::new(SubscriptionType::Clasic, 7.99, 1, Fecha::new(1, 4, 2024)));
-    plataforma.crear_usuario(1, sub_basic.clone(), MedioDePago::TarjetaCredito);
-    plataforma.crear_usuario(2, sub_basic.clone(), MedioDePago::Efectivo);
-    plataforma.crear_usuario(3, sub_clasic, MedioDePago::TarjetaCredito);
-    plataforma.crear_usuario(4, inactiva.clone(), MedioDePago::MercadoPago);
-    plataforma.crear_usuario(5, inactiva.clone(), MedioDePago::MercadoPago);
-    plataforma.crear_usuario(6, inactiva.clone(), MedioDePago::MercadoPago);
-    plataforma.crear_usuario(7, inactiva, MedioDePago::MercadoPago);
-    plataforma.crear_usuario(8, sub_super.clone(), MedioDePago::TarjetaCredito);
+    plataforma.crear_usuario(1, sub_basic.clone(), MedioDePago::TarjetaCredito).unwrap();
+    plataforma.crear_usuario(2, sub_basic.clone(), MedioDePago::Efectivo).unwrap();
+    plataforma.crear_usuario(3, sub_clasic, MedioDePago::TarjetaCredito).unwrap();
+    plataforma.crear_usuario(4, inactiva.clone(), MedioDePago::MercadoPago).unwrap();
+    plataforma.crear_usuario(5, inactiva.clone(), MedioDePago::MercadoPago).unwrap();
+    plataforma.crear_usuario(6, inactiva.clone(), MedioDePago::MercadoPago).unwrap();
+    plataforma.crear_usuario(7, inactiva, MedioDePago::MercadoPago).unwrap();
+    plataforma.crear_usuario(8, sub_super.clone(), MedioDePago::TarjetaCredito).unwrap();
 
     assert_eq!(MedioDePago::MercadoPago, plataforma.mayor_metodo().unwrap());
 
-    let plataforma = Plataforma::new();
+    let plataforma = Plataforma::new(Persistencia::memoria());
     assert_eq!(None, plataforma.mayor_metodo_activo());
-}
\ No newline at end of file
+}
+#[test]
+pub fn test_plataforma_guarda_suscripciones() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = Plataforma::new(persistencia.clone());
+    let sub = Some(SubscriptionInfo::new(SubscriptionType::Clasic, 10.99, 1, Fecha::new(10, 3, 2024)));
+
+    plataforma.crear_usuario(1, sub, MedioDePago::Cripto).unwrap();
+    plataforma.upgrade(1).unwrap();
+
+    let guardados: Vec<Usuario> = persistencia.cargar("suscripciones.json").unwrap().unwrap();
+    assert_eq!(1, guardados.len());
+    assert_eq!(SubscriptionType::Super, guardados[0].sub.as_ref().unwrap().tipo);
+}
@@ -0,0 +1,262 @@
+use std::{borrow::Cow, fmt};
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::FormatoDireccion;
+use crate::dinero::{valor_en_fiat, Limites, MontoCripto, MontoFiat};
+use persistencia::{ErrorPersistencia, Persistencia};
+use crate::redes::{Red, RegistroRedes};
+
+pub const ARCHIVO_ACTIVOS: &str = "activos.json";
+// Los montos son i64: con 9 decimales (como los gwei de ETH) todavia entran mas de 9.000 millones
+// de unidades. Un token de 18 decimales se lista con menos, como hace XYZ con ETH.
+pub const DECIMALES_MAXIMOS: u32 = 9;
+
+// Simbolo de un activo ("BTC", "USDT"). Se guarda como texto, igual que el viejo enum Prefijo.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Ticker(Cow<'static, str>);
+
+// De donde sale la cotizacion de un activo
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub enum FuentePrecio {
+    #[default]
+    Cotizador, // El cotizador de XYZ
+    Fija(MontoFiat) // Para stablecoins o activos sin mercado
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Activo {
+    pub ticker: Ticker,
+    pub nombre: String,
+    pub decimales: u32, // Decimales con los que se guardan los montos (satoshis, gwei, drops, lovelace...)
+    #[serde(default)]
+    pub redes: Vec<Red>,
+    #[serde(default)]
+    pub precio: FuentePrecio,
+    #[serde(default)]
+    pub limites: Limites<MontoCripto> // Por operacion, en unidades minimas
+}
+
+// Los activos que lista XYZ. Se puede armar en codigo o leer de un archivo de configuracion:
+// [{ "ticker": "USDT", "nombre": "Tether", "decimales": 6, "precio": { "Fija": 100 }, "limites": { "minimo": 1000000 }, "redes": [...] }, ...]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct RegistroActivos {
+    activos: Vec<Activo>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorActivo {
+    TickerInvalido(String),
+    TickerDesconocido(Ticker), // No esta listado en el registro
+    DemasiadosDecimales(u32),
+    DecimalesDistintos { ticker: Ticker, registrados: u32 }, // Los montos ya guardados dependen de los decimales
+    EnUso(Ticker), // Tiene movimientos, no se puede dejar de listar
+    Persistencia(ErrorPersistencia)
+}
+
+impl fmt::Display for ErrorActivo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorActivo::TickerInvalido(ticker) => write!(f, "El ticker {ticker:?} no es valido"),
+            ErrorActivo::TickerDesconocido(ticker) => write!(f, "{ticker} no esta listado"),
+            ErrorActivo::DemasiadosDecimales(decimales) => write!(f, "Un activo no puede tener {decimales} decimales (maximo {DECIMALES_MAXIMOS})"),
+            ErrorActivo::DecimalesDistintos { ticker, registrados } => write!(f, "{ticker} ya esta listado con {registrados} decimales"),
+            ErrorActivo::EnUso(ticker) => write!(f, "{ticker} ya tiene movimientos y no se puede dejar de listar"),
+            ErrorActivo::Persistencia(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorActivo {}
+
+impl From<ErrorPersistencia> for ErrorActivo {
+    fn from(e: ErrorPersistencia) -> Self {
+        ErrorActivo::Persistencia(e)
+    }
+}
+
+impl Ticker {
+    pub const BTC: Ticker = Ticker(Cow::Borrowed("BTC"));
+    pub const ETH: Ticker = Ticker(Cow::Borrowed("ETH"));
+    pub const LTC: Ticker = Ticker(Cow::Borrowed("LTC")); // Litecoin
+    pub const XRP: Ticker = Ticker(Cow::Borrowed("XRP")); // Ripple
+    pub const BCH: Ticker = Ticker(Cow::Borrowed("BCH")); // Bitcoin Cash
+    pub const ADA: Ticker = Ticker(Cow::Borrowed("ADA")); // Cardano
+
+    pub fn new(ticker: &str) -> Ticker {
+        Ticker(Cow::Owned(ticker.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    // De 1 a 10 mayusculas o digitos
+    pub fn es_valido(&self) -> bool {
+        (1..=10).contains(&self.0.len()) && self.0.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+    }
+}
+
+impl fmt::Display for Ticker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Como el enum: BTC y no Ticker("BTC")
+impl fmt::Debug for Ticker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn activos_por_defecto() -> Vec<Activo> {
+    let activo = |ticker: Ticker, nombre: &str, decimales: u32, redes: Vec<Red>| Activo {
+        ticker, nombre: nombre.to_string(), decimales, redes, precio: FuentePrecio::Cotizador, limites: Limites::default()
+    };
+    vec![
+        activo(Ticker::BTC, "Bitcoin", 8, vec![Red::new("Bitcoin", FormatoDireccion::Bitcoin, 6)]),
+        activo(Ticker::ETH, "Ethereum", 9, vec![Red::new("Ethereum", FormatoDireccion::Ethereum, 12), Red::new("Arbitrum", FormatoDireccion::Ethereum, 20)]),
+        activo(Ticker::LTC, "Litecoin", 8, vec![Red::new("Litecoin", FormatoDireccion::Litecoin, 6)]),
+        activo(Ticker::XRP, "Ripple", 6, vec![Red::new("Ripple", FormatoDireccion::Ripple, 1)]),
+        activo(Ticker::BCH, "Bitcoin Cash", 8, vec![Red::new("Bitcoin Cash", FormatoDireccion::Bitcoin, 6)]),
+        activo(Ticker::ADA, "Cardano", 6, vec![Red::new("Cardano", FormatoDireccion::Cardano, 15)])
+    ]
+}
+
+impl RegistroActivos {
+    pub fn new() -> RegistroActivos {
+        RegistroActivos { activos: Vec::new() }
+    }
+
+    // Los activos con los que arranca XYZ, cotizados por el cotizador
+    pub fn por_defecto() -> RegistroActivos {
+        RegistroActivos { activos: activos_por_defecto() }
+    }
+
+    // Si el archivo no existe se usan los activos por defecto
+    pub fn cargar(persistencia: &Persistencia) -> Result<RegistroActivos, ErrorActivo> {
+        let Some(activos) = persistencia.cargar::<Vec<Activo>>(ARCHIVO_ACTIVOS)? else {
+            return Ok(RegistroActivos::por_defecto());
+        };
+        let mut registro = RegistroActivos::new();
+        for activo in activos {
+            registro.agregar(activo)?;
+        }
+        Ok(registro)
+    }
+
+    // Reemplaza al activo con el mismo ticker, si ya estaba, siempre que no cambie de decimales
+    pub fn agregar(&mut self, activo: Activo) -> Result<(), ErrorActivo> {
+        if !activo.ticker.es_valido() {
+            return Err(ErrorActivo::TickerInvalido(activo.ticker.to_string()));
+        }
+        if activo.decimales > DECIMALES_MAXIMOS {
+            return Err(ErrorActivo::DemasiadosDecimales(activo.decimales));
+        }
+        self.mismos_decimales(&activo)?;
+        match self.activos.iter_mut().find(|a| a.ticker == activo.ticker) {
+            Some(anterior) => *anterior = activo,
+            None => self.activos.push(activo)
+        }
+        Ok(())
+    }
+
+    // Los montos ya guardados de un ticker se leen con los decimales con los que se listo
+    pub fn mismos_decimales(&self, activo: &Activo) -> Result<(), ErrorActivo> {
+        match self.activo(&activo.ticker) {
+            Some(anterior) if anterior.decimales != activo.decimales => Err(ErrorActivo::DecimalesDistintos { ticker: activo.ticker.clone(), registrados: anterior.decimales }),
+            _ => Ok(())
+        }
+    }
+
+    pub fn decimales(&self, ticker: &Ticker) -> Result<u32, ErrorActivo> {
+        self.activo(ticker).map(|a| a.decimales).ok_or_else(|| ErrorActivo::TickerDesconocido(ticker.clone()))
+    }
+
+    // None si el ticker no esta listado o el valor no entra en un monto
+    pub fn valor_en_fiat(&self, monto: MontoCripto, ticker: &Ticker, cotizacion: MontoFiat) -> Option<MontoFiat> {
+        valor_en_fiat(monto, self.decimales(ticker).ok()?, cotizacion)
+    }
+
+    pub fn activo(&self, ticker: &Ticker) -> Option<&Activo> {
+        self.activos.iter().find(|a| &a.ticker == ticker)
+    }
+
+    pub fn activos(&self) -> &[Activo] {
+        &self.activos
+    }
+
+    // Las redes de todos los activos, para configurarlas por separado
+    pub fn redes(&self) -> RegistroRedes {
+        let mut registro = RegistroRedes::new();
+        for activo in &self.activos {
+            for red in &activo.redes {
+                registro.agregar(activo.ticker.clone(), red.clone());
+            }
+        }
+        registro
+    }
+}
+
+impl Default for RegistroActivos {
+    fn default() -> Self {
+        RegistroActivos::por_defecto()
+    }
+}
+
+#[test]
+fn test_ticker() {
+    assert_eq!(Ticker::BTC, Ticker::new("BTC"));
+    assert_eq!("\"BTC\"", serde_json::to_string(&Ticker::BTC).unwrap());
+    assert_eq!(Ticker::ETH, serde_json::from_str::<Ticker>("\"ETH\"").unwrap());
+    assert_eq!("ADA", format!("{:?}", Ticker::ADA));
+    assert!(Ticker::new("USDC2").es_valido());
+    assert!(!Ticker::new("usdt").es_valido());
+    assert!(!Ticker::new("").es_valido());
+}
+
+#[test]
+fn test_registro_activos_agregar() {
+    let mut registro = RegistroActivos::por_defecto();
+    assert_eq!(6, registro.activos().len());
+    assert_eq!("Cardano", registro.activo(&Ticker::ADA).unwrap().nombre);
+
+    let dai = Activo { ticker: Ticker::new("DAI"), nombre: "Dai".to_string(), decimales: 9, redes: vec![Red::new("Ethereum", FormatoDireccion::Ethereum, 12)], precio: FuentePrecio::Fija(MontoFiat::from_centavos(100)), limites: Limites::default() };
+    registro.agregar(dai.clone()).unwrap();
+    assert_eq!(Ok(9), registro.decimales(&Ticker::new("DAI")));
+    assert_eq!(Ok(6), registro.decimales(&Ticker::XRP));
+    assert_eq!(Err(ErrorActivo::TickerDesconocido(Ticker::new("USDT"))), registro.decimales(&Ticker::new("USDT")));
+    assert_eq!(None, registro.valor_en_fiat(MontoCripto::from_unidades(1), &Ticker::new("USDT"), MontoFiat::from_centavos(100)));
+    assert_eq!(vec!["Ethereum"], registro.redes().redes(&Ticker::new("DAI")).iter().map(|r| r.nombre.as_str()).collect::<Vec<_>>());
+
+    assert_eq!(Err(ErrorActivo::DecimalesDistintos { ticker: Ticker::BTC, registrados: 8 }), registro.agregar(Activo { ticker: Ticker::BTC, decimales: 6, ..dai.clone() }));
+    assert_eq!(Err(ErrorActivo::DemasiadosDecimales(10)), registro.agregar(Activo { ticker: Ticker::new("MUCHOS"), decimales: 10, ..dai.clone() }));
+    assert_eq!(Err(ErrorActivo::TickerInvalido("dai".to_string())), registro.agregar(Activo { ticker: Ticker::new("dai"), ..dai }));
+    assert_eq!(7, registro.activos().len());
+}
+
+#[test]
+fn test_registro_activos_cargar() {
+    let persistencia = Persistencia::memoria();
+    assert_eq!(RegistroActivos::por_defecto(), RegistroActivos::cargar(&persistencia).unwrap());
+
+    let json = r#"[{"ticker": "USDT", "nombre": "Tether", "decimales": 6, "precio": {"Fija": 100},
+        "redes": [{"nombre": "Ethereum", "formato": "Ethereum", "confirmaciones": 12}]}, {"ticker": "BTC", "nombre": "Bitcoin", "decimales": 8}]"#;
+    persistencia.escribir(ARCHIVO_ACTIVOS, json).unwrap();
+    let registro = RegistroActivos::cargar(&persistencia).unwrap();
+    let usdt = registro.activo(&Ticker::new("USDT")).unwrap();
+    assert_eq!(FuentePrecio::Fija(MontoFiat::from_centavos(100)), usdt.precio);
+    assert_eq!(6, usdt.decimales);
+    assert_eq!(FuentePrecio::Cotizador, registro.activo(&Ticker::BTC).unwrap().precio);
+    assert!(registro.activo(&Ticker::ETH).is_none());
+
+    // Cada registro tiene sus propios activos: otro puede listar ETH con otros decimales
+    persistencia.escribir(ARCHIVO_ACTIVOS, r#"[{"ticker": "ETH", "nombre": "Ethereum", "decimales": 6}]"#).unwrap();
+    assert_eq!(Ok(6), RegistroActivos::cargar(&persistencia).unwrap().decimales(&Ticker::ETH));
+    assert_eq!(Ok(9), RegistroActivos::por_defecto().decimales(&Ticker::ETH));
+
+    persistencia.escribir(ARCHIVO_ACTIVOS, r#"[{"ticker": "ETH", "nombre": "Ethereum", "decimales": 18}]"#).unwrap();
+    assert_eq!(Err(ErrorActivo::DemasiadosDecimales(18)), RegistroActivos::cargar(&persistencia));
+}
@@ -0,0 +1,272 @@
+use std::{cmp::Ordering, collections::{BTreeMap, HashMap, HashSet}};
+use serde::{Deserialize, Serialize};
+
+use crate::activos::RegistroActivos;
+use crate::dinero::{MontoCripto, MontoFiat};
+use fecha::Fecha;
+use crate::xyz::Prefijo;
+
+// Tipo de operacion desde el punto de vista de un usuario. Una ejecucion de orden
+// es una compra para el comprador y una venta para el vendedor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum TipoOperacion {
+    Ingreso,
+    Compra,
+    Venta,
+    RetiroCripto,
+    RecepcionCripto,
+    RetiroFiat,
+    Reintegro,
+    Intercambio, // Una operacion por cada cripto: la que entrega y la que recibe
+    Recompensa // De staking
+}
+
+// Lo que hizo un usuario en una transaccion. Las de fiat no tienen cripto ni monto.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operacion {
+    pub fecha: Fecha,
+    pub dni: String,
+    pub tipo: TipoOperacion,
+    pub cripto: Option<Prefijo>,
+    pub monto: MontoCripto,
+    pub valor: MontoFiat, // En fiat, a la cotizacion de la operacion
+    pub neto: MontoCripto // Cuanto cambio el saldo de cripto del usuario, comisiones incluidas
+}
+
+// Que operaciones se miran. Lo que queda en None no filtra; las fechas se incluyen.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filtro {
+    pub desde: Option<Fecha>,
+    pub hasta: Option<Fecha>,
+    pub dni: Option<String>,
+    pub cripto: Option<Prefijo>,
+    pub tipos: Vec<TipoOperacion> // Vacio: todos
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Agregado {
+    pub operaciones: u32,
+    pub volumen: HashMap<Prefijo, MontoCripto>, // Por cripto: sumar criptos distintas no tiene sentido
+    pub volumen_fiat: MontoFiat,
+    pub usuarios: usize // Distintos
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Criterio {
+    Operaciones,
+    Volumen, // En unidades enteras de cada cripto (1 BTC contra 1 XRP)
+    VolumenFiat,
+    Usuarios
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Periodo {
+    Diario,
+    Mensual
+}
+
+impl Filtro {
+    pub fn incluye(&self, operacion: &Operacion) -> bool {
+        self.desde.as_ref().is_none_or(|desde| operacion.fecha.es_mayor(desde))
+            && self.hasta.as_ref().is_none_or(|hasta| hasta.es_mayor(&operacion.fecha))
+            && self.dni.as_ref().is_none_or(|dni| &operacion.dni == dni)
+            && self.cripto.as_ref().is_none_or(|cripto| operacion.cripto.as_ref() == Some(cripto))
+            && (self.tipos.is_empty() || self.tipos.contains(&operacion.tipo))
+    }
+}
+
+impl Agregado {
+    pub fn volumen_de(&self, cripto: &Prefijo) -> MontoCripto {
+        self.volumen.get(cripto).copied().unwrap_or_default()
+    }
+}
+
+impl Periodo {
+    // Primer dia del periodo que contiene a la fecha
+    pub fn inicio(&self, fecha: &Fecha) -> Fecha {
+        match self {
+            Periodo::Diario => fecha.clone(),
+            Periodo::Mensual => Fecha::new(1, fecha.mes, fecha.anio)
+        }
+    }
+
+    fn siguiente(&self, inicio: &Fecha) -> Fecha {
+        match self {
+            Periodo::Diario => {
+                let mut fecha = inicio.clone();
+                fecha.sumar_dias(1);
+                fecha
+            },
+            Periodo::Mensual if inicio.mes == 12 => Fecha::new(1, 1, inicio.anio + 1),
+            Periodo::Mensual => Fecha::new(1, inicio.mes + 1, inicio.anio)
+        }
+    }
+}
+
+fn clave(fecha: &Fecha) -> (u32, u32, u32) {
+    (fecha.anio, fecha.mes, fecha.dia)
+}
+
+pub fn agregar<'a>(operaciones: impl IntoIterator<Item = &'a Operacion>) -> Agregado {
+    let mut agregado = Agregado::default();
+    let mut usuarios = HashSet::new();
+    for operacion in operaciones {
+        agregado.operaciones += 1;
+        agregado.volumen_fiat += operacion.valor;
+        if let Some(cripto) = &operacion.cripto {
+            *agregado.volumen.entry(cripto.clone()).or_default() += operacion.monto;
+        }
+        usuarios.insert(operacion.dni.as_str());
+    }
+    agregado.usuarios = usuarios.len();
+    agregado
+}
+
+pub fn por_activo(operaciones: &[Operacion]) -> HashMap<Prefijo, Agregado> {
+    let mut grupos: HashMap<&Prefijo, Vec<&Operacion>> = HashMap::new();
+    for operacion in operaciones {
+        if let Some(cripto) = &operacion.cripto {
+            grupos.entry(cripto).or_default().push(operacion);
+        }
+    }
+    grupos.into_iter().map(|(cripto, grupo)| (cripto.clone(), agregar(grupo))).collect()
+}
+
+// Las criptos de mayor a menor segun el criterio. Devuelve las primeras n y ademas
+// las que empatan con la ultima; los empates se ordenan por ticker. Para comparar volumenes
+// hacen falta los decimales de cada cripto.
+pub fn ranking(operaciones: &[Operacion], criterio: Criterio, n: usize, activos: &RegistroActivos) -> Vec<(Prefijo, Agregado)> {
+    let mut lista: Vec<(Prefijo, Agregado)> = por_activo(operaciones).into_iter().collect();
+    let comparar = |a: &(Prefijo, Agregado), b: &(Prefijo, Agregado)| comparar(criterio, a, b, activos);
+    lista.sort_by(|a, b| comparar(b, a).then_with(|| a.0.cmp(&b.0)));
+
+    let Some(ultimo) = n.checked_sub(1).and_then(|i| lista.get(i)).cloned() else {
+        return if n == 0 { Vec::new() } else { lista };
+    };
+    lista.into_iter().enumerate()
+        .take_while(|(i, grupo)| *i < n || comparar(grupo, &ultimo) == Ordering::Equal)
+        .map(|(_, grupo)| grupo)
+        .collect()
+}
+
+fn comparar(criterio: Criterio, (pa, a): &(Prefijo, Agregado), (pb, b): &(Prefijo, Agregado), activos: &RegistroActivos) -> Ordering {
+    match criterio {
+        Criterio::Operaciones => a.operaciones.cmp(&b.operaciones),
+        // Las operaciones salen de transacciones de criptos listadas: siempre tienen decimales
+        Criterio::Volumen => match (activos.decimales(pa), activos.decimales(pb)) {
+            (Ok(da), Ok(db)) => a.volumen_de(pa).comparar(da, &b.volumen_de(pb), db),
+            _ => Ordering::Equal
+        },
+        Criterio::VolumenFiat => a.volumen_fiat.cmp(&b.volumen_fiat),
+        Criterio::Usuarios => a.usuarios.cmp(&b.usuarios)
+    }
+}
+
+// Un agregado por dia o por mes, con el inicio de cada periodo, incluidos los que no tienen
+// operaciones. Va desde `desde` (o la primera operacion) hasta `hasta` (o la ultima).
+pub fn serie(operaciones: &[Operacion], periodo: Periodo, desde: Option<&Fecha>, hasta: Option<&Fecha>) -> Vec<(Fecha, Agregado)> {
+    let mut grupos: BTreeMap<(u32, u32, u32), Vec<&Operacion>> = BTreeMap::new();
+    for operacion in operaciones {
+        grupos.entry(clave(&periodo.inicio(&operacion.fecha))).or_default().push(operacion);
+    }
+
+    let primero = desde.map(|f| periodo.inicio(f)).or_else(|| operaciones.iter().min_by_key(|o| clave(&o.fecha)).map(|o| periodo.inicio(&o.fecha)));
+    let ultimo = hasta.map(|f| periodo.inicio(f)).or_else(|| operaciones.iter().max_by_key(|o| clave(&o.fecha)).map(|o| periodo.inicio(&o.fecha)));
+    let (Some(mut actual), Some(ultimo)) = (primero, ultimo) else {
+        return Vec::new();
+    };
+
+    let mut serie = Vec::new();
+    while clave(&actual) <= clave(&ultimo) {
+        let agregado = grupos.remove(&clave(&actual)).map_or_else(Agregado::default, agregar);
+        let siguiente = periodo.siguiente(&actual);
+        serie.push((actual, agregado));
+        actual = siguiente;
+    }
+    serie
+}
+
+// Cuanto entro (positivo) o salio (negativo) de cada cripto en las cuentas de los usuarios
+pub fn flujo_neto(operaciones: &[Operacion]) -> HashMap<Prefijo, MontoCripto> {
+    let mut flujo: HashMap<Prefijo, MontoCripto> = HashMap::new();
+    for operacion in operaciones {
+        if let Some(cripto) = &operacion.cripto {
+            *flujo.entry(cripto.clone()).or_default() += operacion.neto;
+        }
+    }
+    flujo
+}
+
+#[cfg(test)]
+fn operacion(fecha: Fecha, dni: &str, tipo: TipoOperacion, cripto: Prefijo, unidades: i64, centavos: i64) -> Operacion {
+    let monto = MontoCripto::from_unidades(unidades);
+    let neto = if matches!(tipo, TipoOperacion::Venta | TipoOperacion::RetiroCripto) { -monto } else { monto };
+    Operacion { fecha, dni: dni.to_string(), tipo, cripto: Some(cripto), monto, valor: MontoFiat::from_centavos(centavos), neto }
+}
+
+#[test]
+fn test_filtro_y_agregado() {
+    let operaciones = [
+        operacion(Fecha::new(1, 3, 2024), "a", TipoOperacion::Compra, Prefijo::BTC, 10, 500),
+        operacion(Fecha::new(5, 3, 2024), "b", TipoOperacion::Compra, Prefijo::BTC, 20, 1000),
+        operacion(Fecha::new(9, 3, 2024), "a", TipoOperacion::Venta, Prefijo::ETH, 5, 300),
+    ];
+    let filtro = Filtro { desde: Some(Fecha::new(1, 3, 2024)), hasta: Some(Fecha::new(5, 3, 2024)), ..Filtro::default() };
+    let agregado = agregar(operaciones.iter().filter(|o| filtro.incluye(o)));
+    assert_eq!(2, agregado.operaciones);
+    assert_eq!(2, agregado.usuarios);
+    assert_eq!(MontoCripto::from_unidades(30), agregado.volumen_de(&Prefijo::BTC));
+    assert_eq!(MontoFiat::from_centavos(1500), agregado.volumen_fiat);
+
+    let filtro = Filtro { dni: Some("a".to_string()), tipos: vec![TipoOperacion::Venta], ..Filtro::default() };
+    assert_eq!(vec![&operaciones[2]], operaciones.iter().filter(|o| filtro.incluye(o)).collect::<Vec<_>>());
+    let filtro = Filtro { cripto: Some(Prefijo::ETH), ..Filtro::default() };
+    assert_eq!(1, operaciones.iter().filter(|o| filtro.incluye(o)).count());
+}
+
+#[test]
+fn test_ranking_con_empates() {
+    let hoy = Fecha::new(1, 1, 2024);
+    let activos = RegistroActivos::por_defecto();
+    let operaciones = vec![
+        operacion(hoy.clone(), "a", TipoOperacion::Compra, Prefijo::XRP, 1, 100),
+        operacion(hoy.clone(), "a", TipoOperacion::Compra, Prefijo::ETH, 1, 100),
+        operacion(hoy.clone(), "b", TipoOperacion::Compra, Prefijo::ETH, 1, 100),
+        operacion(hoy.clone(), "a", TipoOperacion::Compra, Prefijo::BTC, 1, 100),
+        operacion(hoy.clone(), "b", TipoOperacion::Compra, Prefijo::BTC, 1, 100),
+        operacion(hoy, "a", TipoOperacion::Compra, Prefijo::ADA, 1, 100),
+    ];
+    let tickers = |lista: Vec<(Prefijo, Agregado)>| lista.into_iter().map(|(p, _)| p).collect::<Vec<_>>();
+
+    // BTC y ETH empatan en el primer puesto
+    assert_eq!(vec![Prefijo::BTC, Prefijo::ETH], tickers(ranking(&operaciones, Criterio::Operaciones, 1, &activos)));
+    assert_eq!(vec![Prefijo::BTC, Prefijo::ETH, Prefijo::ADA, Prefijo::XRP], tickers(ranking(&operaciones, Criterio::Operaciones, 3, &activos)));
+    assert_eq!(vec![Prefijo::BTC, Prefijo::ETH], tickers(ranking(&operaciones, Criterio::Usuarios, 2, &activos)));
+    assert!(ranking(&operaciones, Criterio::Operaciones, 0, &activos).is_empty());
+    assert_eq!(4, ranking(&operaciones, Criterio::Operaciones, 10, &activos).len());
+
+    // Una unidad minima de BTC (8 decimales) es menos que una de ADA o XRP (6 decimales)
+    assert_eq!(vec![Prefijo::ADA, Prefijo::XRP], tickers(ranking(&operaciones, Criterio::Volumen, 1, &activos)));
+}
+
+#[test]
+fn test_serie_y_flujo_neto() {
+    let operaciones = vec![
+        operacion(Fecha::new(30, 1, 2024), "a", TipoOperacion::Compra, Prefijo::BTC, 10, 500),
+        operacion(Fecha::new(1, 2, 2024), "a", TipoOperacion::Venta, Prefijo::BTC, 4, 200),
+        operacion(Fecha::new(1, 2, 2024), "b", TipoOperacion::RecepcionCripto, Prefijo::ETH, 7, 100),
+    ];
+
+    let diaria = serie(&operaciones, Periodo::Diario, None, None);
+    assert_eq!(vec![Fecha::new(30, 1, 2024), Fecha::new(31, 1, 2024), Fecha::new(1, 2, 2024)], diaria.iter().map(|(f, _)| f.clone()).collect::<Vec<_>>());
+    assert_eq!(vec![1, 0, 2], diaria.iter().map(|(_, a)| a.operaciones).collect::<Vec<_>>());
+
+    let mensual = serie(&operaciones, Periodo::Mensual, Some(&Fecha::new(15, 12, 2023)), Some(&Fecha::new(1, 3, 2024)));
+    assert_eq!(vec![Fecha::new(1, 12, 2023), Fecha::new(1, 1, 2024), Fecha::new(1, 2, 2024), Fecha::new(1, 3, 2024)], mensual.iter().map(|(f, _)| f.clone()).collect::<Vec<_>>());
+    assert_eq!(MontoFiat::from_centavos(300), mensual[2].1.volumen_fiat);
+    assert!(serie(&[], Periodo::Diario, None, None).is_empty());
+
+    let flujo = flujo_neto(&operaciones);
+    assert_eq!(MontoCripto::from_unidades(6), flujo[&Prefijo::BTC]);
+    assert_eq!(MontoCripto::from_unidades(7), flujo[&Prefijo::ETH]);
+}
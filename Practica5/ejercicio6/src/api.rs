@@ -0,0 +1,329 @@
+use std::{collections::BTreeMap, fmt, thread};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::dinero::{MontoCripto, MontoFiat};
+use fecha::Fecha;
+use crate::kyc::Identidad;
+use crate::servicio::Servicio;
+use crate::xyz::{IdTransaccion, Medio, Prefijo, XyzError, XYZ};
+
+// API JSON de XYZ para los clientes web y mobile. Los montos se mandan como texto ("0.5", "100.25")
+// y las transacciones se devuelven tal como se guardan, con los montos en unidades minimas.
+//
+//   POST /usuarios                          { nombre, apellido, email, dni }
+//   GET  /usuarios/{dni}                    estado y saldos
+//   POST /usuarios/{dni}/identidad          { documento, email, fecha_nacimiento: { dia, mes, anio } }
+//   POST /usuarios/{dni}/aprobar            { revisor, nota }, igual /rechazar
+//   POST /usuarios/{dni}/depositos          { monto }
+//   POST /usuarios/{dni}/compras            { cripto, monto }, igual /ventas
+//   POST /usuarios/{dni}/retiros            { medio, monto }
+//   POST /usuarios/{dni}/retiros-cripto     { cripto, blockchain, destino, monto }
+//   GET  /usuarios/{dni}/extracto?desde=1/1/2024&hasta=31/12/2024
+//   GET  /transacciones/{id}
+//   GET  /top/{ventas|compras|volumen-ventas|volumen-compras}
+#[derive(Clone)]
+pub struct Api {
+    servicio: Servicio
+}
+
+// Estado HTTP y cuerpo de una respuesta
+#[derive(Debug, Clone, PartialEq)]
+pub struct Respuesta {
+    pub estado: u16,
+    pub cuerpo: Value
+}
+
+// Los errores se devuelven como { "error": { "codigo": "SaldoInsuficiente", "mensaje": "..." } }
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorApi {
+    RutaInexistente,
+    CuerpoInvalido(String),
+    ParametroInvalido(String), // Un monto, una fecha o un id que no se puede leer
+    Xyz(XyzError)
+}
+
+#[derive(Deserialize)]
+struct NuevoUsuario {
+    nombre: String,
+    apellido: String,
+    email: String,
+    dni: String
+}
+
+#[derive(Deserialize)]
+struct Revision {
+    revisor: String,
+    #[serde(default)]
+    nota: String
+}
+
+#[derive(Deserialize)]
+struct Deposito {
+    monto: String
+}
+
+#[derive(Deserialize)]
+struct Operacion {
+    cripto: Prefijo,
+    monto: String
+}
+
+#[derive(Deserialize)]
+struct RetiroFiat {
+    medio: Medio,
+    monto: String
+}
+
+#[derive(Deserialize)]
+struct RetiroCripto {
+    cripto: Prefijo,
+    blockchain: String,
+    destino: String,
+    monto: String
+}
+
+impl fmt::Display for ErrorApi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorApi::RutaInexistente => write!(f, "No existe esa ruta"),
+            ErrorApi::CuerpoInvalido(e) => write!(f, "El cuerpo del pedido no es valido: {e}"),
+            ErrorApi::ParametroInvalido(e) => write!(f, "{e}"),
+            ErrorApi::Xyz(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorApi {}
+
+impl From<XyzError> for ErrorApi {
+    fn from(error: XyzError) -> Self {
+        ErrorApi::Xyz(error)
+    }
+}
+
+impl ErrorApi {
+    fn estado(&self) -> u16 {
+        match self {
+            ErrorApi::RutaInexistente => 404,
+            ErrorApi::CuerpoInvalido(_) | ErrorApi::ParametroInvalido(_) => 400,
+            ErrorApi::Xyz(e) => match e {
+                XyzError::UsuarioNoEncontrado | XyzError::OrdenNoEncontrada | XyzError::RetiroNoEncontrado
+                    | XyzError::RetiroCriptoNoEncontrado | XyzError::DepositoNoEncontrado => 404,
+                XyzError::UsuarioExistente | XyzError::DepositoDuplicado => 409,
+                XyzError::RetiroRetenido(_) => 202, // Se recibio, pero espera a un revisor
+                XyzError::CotizacionNoDisponible => 503,
+                XyzError::Persistencia(_) | XyzError::Contable(_) | XyzError::Inconsistente => 500,
+                _ => 422
+            }
+        }
+    }
+
+    // El nombre de la variante, sin sus datos
+    fn codigo(&self) -> String {
+        let debug = match self {
+            ErrorApi::Xyz(e) => format!("{e:?}"),
+            e => format!("{e:?}")
+        };
+        debug.split(|c: char| !c.is_alphanumeric()).next().unwrap_or_default().to_string()
+    }
+
+    pub fn respuesta(&self) -> Respuesta {
+        let mut cuerpo = json!({ "error": { "codigo": self.codigo(), "mensaje": self.to_string() } });
+        if let ErrorApi::Xyz(XyzError::RetiroRetenido(id)) = self {
+            cuerpo["error"]["retenido"] = json!(id);
+        }
+        Respuesta { estado: self.estado(), cuerpo }
+    }
+}
+
+impl Api {
+    pub fn new(servicio: Servicio) -> Api {
+        Api { servicio }
+    }
+
+    pub fn atender(&self, metodo: &str, url: &str, cuerpo: &str) -> Respuesta {
+        let (ruta, consulta) = url.split_once('?').unwrap_or((url, ""));
+        let partes: Vec<&str> = ruta.split('/').filter(|p| !p.is_empty()).collect();
+        let resultado = match (metodo, partes.as_slice()) {
+            ("POST", ["usuarios"]) => self.crear_usuario(cuerpo),
+            ("GET", ["usuarios", dni]) => self.usuario(dni),
+            ("POST", ["usuarios", dni, "identidad"]) => self.enviar_identidad(dni, cuerpo),
+            ("POST", ["usuarios", dni, accion @ ("aprobar" | "rechazar")]) => self.revisar(dni, accion, cuerpo),
+            ("POST", ["usuarios", dni, "depositos"]) => self.depositar(dni, cuerpo),
+            ("POST", ["usuarios", dni, accion @ ("compras" | "ventas")]) => self.operar(dni, accion, cuerpo),
+            ("POST", ["usuarios", dni, "retiros"]) => self.retirar_fiat(dni, cuerpo),
+            ("POST", ["usuarios", dni, "retiros-cripto"]) => self.retirar_cripto(dni, cuerpo),
+            ("GET", ["usuarios", dni, "extracto"]) => self.extracto(dni, consulta),
+            ("GET", ["transacciones", id]) => self.transaccion(id),
+            ("GET", ["top", criterio]) => self.top(criterio),
+            _ => Err(ErrorApi::RutaInexistente)
+        };
+        resultado.unwrap_or_else(|e| e.respuesta())
+    }
+
+    // Lee el pedido, lo atiende y contesta; los pedidos que no se pueden contestar se descartan
+    pub fn responder(&self, mut pedido: Request) {
+        let mut cuerpo = String::new();
+        let respuesta = match pedido.as_reader().read_to_string(&mut cuerpo) {
+            Ok(_) => self.atender(pedido.method().as_str(), pedido.url(), &cuerpo),
+            Err(e) => ErrorApi::CuerpoInvalido(e.to_string()).respuesta()
+        };
+        let json = Header::from_bytes("Content-Type", "application/json").unwrap();
+        let _ = pedido.respond(Response::from_string(respuesta.cuerpo.to_string()).with_status_code(respuesta.estado).with_header(json));
+    }
+
+    fn crear_usuario(&self, cuerpo: &str) -> Result<Respuesta, ErrorApi> {
+        let NuevoUsuario { nombre, apellido, email, dni } = leer(cuerpo)?;
+        self.servicio.con(|xyz| xyz.crear_usuario(nombre, apellido, email, dni.clone()))??;
+        Ok(Respuesta { estado: 201, cuerpo: json!({ "dni": dni }) })
+    }
+
+    fn usuario(&self, dni: &str) -> Result<Respuesta, ErrorApi> {
+        self.servicio.con(|xyz| {
+            let u = xyz.usuario(dni).ok_or(XyzError::UsuarioNoEncontrado)?;
+            let cripto: BTreeMap<String, String> = u.balances().iter().filter(|(_, m)| **m != MontoCripto::CERO)
+                .map(|(c, m)| Ok((c.to_string(), m.formatear(xyz.decimales(c)?)))).collect::<Result<_, XyzError>>()?;
+            Ok(ok(json!({
+                "dni": dni,
+                "estado": xyz.estado_kyc(dni),
+                "fiat": u.fiat().to_string(),
+                "cripto": cripto,
+                "ordenes_abiertas": xyz.ordenes_abiertas(dni).len()
+            })))
+        })?
+    }
+
+    fn enviar_identidad(&self, dni: &str, cuerpo: &str) -> Result<Respuesta, ErrorApi> {
+        let identidad: Identidad = leer(cuerpo)?;
+        let estado = self.servicio.con(|xyz| xyz.enviar_identidad(dni.to_string(), identidad).map(|_| xyz.estado_kyc(dni)))??;
+        Ok(ok(json!({ "dni": dni, "estado": estado })))
+    }
+
+    fn revisar(&self, dni: &str, accion: &str, cuerpo: &str) -> Result<Respuesta, ErrorApi> {
+        let Revision { revisor, nota } = leer(cuerpo)?;
+        let estado = self.servicio.con(|xyz| {
+            match accion {
+                "aprobar" => xyz.aprobar_usuario(dni.to_string(), revisor, nota),
+                _ => xyz.rechazar_usuario(dni.to_string(), revisor, nota)
+            }.map(|_| xyz.estado_kyc(dni))
+        })??;
+        Ok(ok(json!({ "dni": dni, "estado": estado })))
+    }
+
+    fn depositar(&self, dni: &str, cuerpo: &str) -> Result<Respuesta, ErrorApi> {
+        let Deposito { monto } = leer(cuerpo)?;
+        let monto = fiat(&monto)?;
+        self.con_transaccion(|xyz| xyz.ingresar_dinero(dni.to_string(), monto))
+    }
+
+    fn operar(&self, dni: &str, accion: &str, cuerpo: &str) -> Result<Respuesta, ErrorApi> {
+        let Operacion { cripto, monto } = leer(cuerpo)?;
+        let monto = self.monto_cripto(&monto, &cripto)?;
+        self.con_transaccion(|xyz| match accion {
+            "compras" => xyz.comprar_cripto(dni.to_string(), cripto, monto),
+            _ => xyz.vender_cripto(dni.to_string(), cripto, monto)
+        })
+    }
+
+    fn retirar_fiat(&self, dni: &str, cuerpo: &str) -> Result<Respuesta, ErrorApi> {
+        let RetiroFiat { medio, monto } = leer(cuerpo)?;
+        let monto = fiat(&monto)?;
+        self.con_transaccion(|xyz| xyz.retirar_fiat(dni.to_string(), medio, monto))
+    }
+
+    fn retirar_cripto(&self, dni: &str, cuerpo: &str) -> Result<Respuesta, ErrorApi> {
+        let RetiroCripto { cripto, blockchain, destino, monto } = leer(cuerpo)?;
+        let monto = self.monto_cripto(&monto, &cripto)?;
+        self.con_transaccion(|xyz| xyz.retirar_blockchain(dni.to_string(), cripto, blockchain, destino, monto))
+    }
+
+    // Sin desde ni hasta, toda la historia hasta hoy
+    fn extracto(&self, dni: &str, consulta: &str) -> Result<Respuesta, ErrorApi> {
+        let parametros: BTreeMap<&str, &str> = consulta.split('&').filter_map(|p| p.split_once('=')).collect();
+        let fecha = |nombre: &str| parametros.get(nombre).map(|f| f.parse::<Fecha>().map_err(|e| ErrorApi::ParametroInvalido(e.to_string()))).transpose();
+        let (desde, hasta) = (fecha("desde")?, fecha("hasta")?);
+        let extracto = self.servicio.con(|xyz| {
+            let hasta = hasta.unwrap_or_else(|| xyz.hoy());
+            xyz.extracto(dni, &desde.unwrap_or(Fecha::new(1, 1, 1970)), &hasta)
+        })??;
+        Ok(ok(serde_json::to_value(extracto).unwrap_or_default()))
+    }
+
+    fn transaccion(&self, id: &str) -> Result<Respuesta, ErrorApi> {
+        let id: IdTransaccion = id.parse().map_err(|_| ErrorApi::ParametroInvalido(format!("{id} no es un id de transaccion")))?;
+        let t = self.servicio.con(|xyz| xyz.transacciones().get(id).cloned())?.ok_or(ErrorApi::RutaInexistente)?;
+        Ok(ok(json!({ "id": id, "transaccion": t })))
+    }
+
+    fn top(&self, criterio: &str) -> Result<Respuesta, ErrorApi> {
+        let top = self.servicio.con(|xyz| match criterio {
+            "ventas" => Ok(xyz.top_cripto_ventas()),
+            "compras" => Ok(xyz.top_cripto_compras()),
+            "volumen-ventas" => Ok(xyz.top_volumen_ventas()),
+            "volumen-compras" => Ok(xyz.top_volumen_compras()),
+            _ => Err(ErrorApi::RutaInexistente)
+        })??;
+        Ok(ok(json!({ "criterio": criterio, "cripto": top })))
+    }
+
+    // Con los decimales de la cripto en el XYZ que se atiende
+    fn monto_cripto(&self, monto: &str, cripto: &Prefijo) -> Result<MontoCripto, ErrorApi> {
+        let decimales = self.servicio.con(|xyz| xyz.decimales(cripto))??;
+        MontoCripto::parse(monto, decimales).map_err(|e| ErrorApi::ParametroInvalido(e.to_string()))
+    }
+
+    // Hace la operacion y devuelve la transaccion que anoto, leida bajo el mismo lock
+    fn con_transaccion(&self, operacion: impl FnOnce(&mut XYZ) -> Result<IdTransaccion, XyzError>) -> Result<Respuesta, ErrorApi> {
+        let (id, t) = self.servicio.con(|xyz| operacion(xyz).map(|id| (id, xyz.transacciones()[id].clone())))??;
+        Ok(Respuesta { estado: 201, cuerpo: json!({ "id": id, "transaccion": t }) })
+    }
+}
+
+// Atiende pedidos con varios hilos hasta que se cierre el servidor
+pub fn servir(servidor: &Server, api: &Api, hilos: usize) {
+    thread::scope(|s| {
+        for _ in 0..hilos.max(1) {
+            s.spawn(|| {
+                for pedido in servidor.incoming_requests() {
+                    api.responder(pedido);
+                }
+            });
+        }
+    });
+}
+
+fn ok(cuerpo: Value) -> Respuesta {
+    Respuesta { estado: 200, cuerpo }
+}
+
+fn leer<T: DeserializeOwned>(cuerpo: &str) -> Result<T, ErrorApi> {
+    serde_json::from_str(cuerpo).map_err(|e| ErrorApi::CuerpoInvalido(e.to_string()))
+}
+
+fn fiat(monto: &str) -> Result<MontoFiat, ErrorApi> {
+    monto.parse().map_err(|e: crate::dinero::MontoMalFormado| ErrorApi::ParametroInvalido(e.to_string()))
+}
+
+#[cfg(test)]
+use persistencia::Persistencia;
+
+#[test]
+fn test_api_errores() {
+    let xyz = XYZ::new(Persistencia::memoria());
+    let api = Api::new(Servicio::new(xyz));
+
+    let respuesta = api.atender("GET", "/usuarios/1", "");
+    assert_eq!(404, respuesta.estado);
+    assert_eq!("UsuarioNoEncontrado", respuesta.cuerpo["error"]["codigo"]);
+    assert_eq!(404, api.atender("DELETE", "/usuarios/1", "").estado);
+    assert_eq!("RutaInexistente", api.atender("GET", "/top/cualquiera", "").cuerpo["error"]["codigo"]);
+
+    assert_eq!(201, api.atender("POST", "/usuarios", r#"{"nombre":"John","apellido":"Doe","email":"j@d.com","dni":"1"}"#).estado);
+    assert_eq!(409, api.atender("POST", "/usuarios", r#"{"nombre":"John","apellido":"Doe","email":"j@d.com","dni":"1"}"#).estado);
+    assert_eq!("CuerpoInvalido", api.atender("POST", "/usuarios", "{").cuerpo["error"]["codigo"]);
+    assert_eq!(400, api.atender("POST", "/usuarios/1/depositos", r#"{"monto":"1.234"}"#).estado);
+    assert_eq!("UsuarioNoValidado", api.atender("POST", "/usuarios/1/depositos", r#"{"monto":"100"}"#).cuerpo["error"]["codigo"]);
+    assert_eq!(400, api.atender("GET", "/usuarios/1/extracto?desde=31/4/2024", "").estado);
+}
@@ -0,0 +1,27 @@
+use std::{env, process};
+use tiny_http::Server;
+
+use ejercicio6::api::{self, Api};
+use ejercicio6::servicio::Servicio;
+use ejercicio6::xyz::XYZ;
+
+const HILOS: usize = 8;
+
+// servidor [directorio] [direccion]
+// Atiende la API JSON de XYZ guardando en el directorio (por defecto el actual)
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let directorio = args.first().map(String::as_str).unwrap_or(".");
+    let direccion = args.get(1).map(String::as_str).unwrap_or("127.0.0.1:8080");
+
+    let xyz = XYZ::abrir(directorio).unwrap_or_else(|e| salir(&format!("No se pudo cargar XYZ de {directorio}: {e}")));
+
+    let servidor = Server::http(direccion).unwrap_or_else(|e| salir(&format!("No se pudo escuchar en {direccion}: {e}")));
+    println!("XYZ escuchando en http://{direccion}");
+    api::servir(&servidor, &Api::new(Servicio::new(xyz)), HILOS);
+}
+
+fn salir(mensaje: &str) -> ! {
+    eprintln!("{mensaje}");
+    process::exit(1)
+}
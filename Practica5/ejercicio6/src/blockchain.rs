@@ -0,0 +1,325 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+use crate::dinero::MontoCripto;
+use fecha::Fecha;
+use crate::xyz::{IdTransaccion, Prefijo};
+
+const ALFABETO_BITCOIN: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const ALFABETO_RIPPLE: &str = "rpshnaf39wBUDNEGHJKLM4PQRST7VWXYZ2bcdeCg65jkm8oFqi1tuvAxyz";
+const ALFABETO_BECH32: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorDireccion {
+    Formato, // Caracteres o largo que no corresponden a la red
+    Checksum,
+    Version // Bien formada, pero de otra red o de un tipo que no se acepta
+}
+
+impl fmt::Display for ErrorDireccion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorDireccion::Formato => write!(f, "La direccion no tiene el formato de la red"),
+            ErrorDireccion::Checksum => write!(f, "El checksum de la direccion no coincide"),
+            ErrorDireccion::Version => write!(f, "La direccion es de otra red"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorDireccion {}
+
+// Formato de las direcciones de una red:
+// - Bitcoin y Litecoin: Base58Check (en Bitcoin tambien Bitcoin Cash legacy, no CashAddr; sin bech32)
+// - Ripple: Base58Check con el alfabeto de Ripple
+// - Ethereum: 0x y 40 hexa; si mezcla mayusculas y minusculas, el checksum EIP-55. Tambien Arbitrum y los tokens ERC-20
+// - Cardano: bech32 con prefijo addr (Shelley)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FormatoDireccion {
+    Bitcoin,
+    Litecoin,
+    Ripple,
+    Ethereum,
+    Cardano
+}
+
+// Un deposito que llego a una direccion de deposito de un usuario. Se acredita recien
+// cuando tiene las confirmaciones que pide la red.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Deposito {
+    pub fecha: Fecha,
+    pub dni: String,
+    pub cripto: Prefijo,
+    pub blockchain: String,
+    pub direccion: String,
+    pub tx_id: String, // Id de la transaccion en la red; no se puede repetir en la misma blockchain
+    pub monto: MontoCripto,
+    pub confirmaciones: u32,
+    pub acreditado: Option<IdTransaccion> // La RecepcionCripto que lo acredito
+}
+
+// Controla que la direccion sea valida para una red con ese formato
+pub fn validar_direccion(formato: FormatoDireccion, direccion: &str) -> Result<(), ErrorDireccion> {
+    match formato {
+        FormatoDireccion::Bitcoin => validar_base58check(direccion, ALFABETO_BITCOIN, &[0x00, 0x05]),
+        FormatoDireccion::Litecoin => validar_base58check(direccion, ALFABETO_BITCOIN, &[0x30, 0x32, 0x05]),
+        FormatoDireccion::Ripple => validar_base58check(direccion, ALFABETO_RIPPLE, &[0x00]),
+        FormatoDireccion::Ethereum => validar_eth(direccion),
+        FormatoDireccion::Cardano => validar_bech32(direccion, "addr")
+    }
+}
+
+// La forma con la que se comparan dos direcciones. Las de Ethereum y las bech32 no distinguen
+// mayusculas y van en minusculas; en Base58Check cada letra cuenta y quedan como estan.
+pub fn direccion_canonica(direccion: &str) -> String {
+    let minusculas = direccion.to_ascii_lowercase();
+    if minusculas.starts_with("0x") || minusculas.starts_with("addr1") {
+        minusculas
+    } else {
+        direccion.to_string()
+    }
+}
+
+// Una direccion valida para la red, derivada de la semilla: la misma semilla da siempre la misma direccion
+pub fn generar_direccion(formato: FormatoDireccion, semilla: &str) -> String {
+    let hash = Sha256::digest(semilla.as_bytes());
+    match formato {
+        FormatoDireccion::Bitcoin => codificar_base58check(0x00, &hash[..20], ALFABETO_BITCOIN),
+        FormatoDireccion::Litecoin => codificar_base58check(0x30, &hash[..20], ALFABETO_BITCOIN),
+        FormatoDireccion::Ripple => codificar_base58check(0x00, &hash[..20], ALFABETO_RIPPLE),
+        FormatoDireccion::Ethereum => checksum_eth(&hex(&hash[..20])),
+        FormatoDireccion::Cardano => {
+            let mut datos = vec![0x61]; // Direccion enterprise de mainnet
+            datos.extend(&hash[..28]);
+            codificar_bech32("addr", &datos)
+        }
+    }
+}
+
+// SHA-256 en hexa de los datos, separados por '|'. Los mismos datos dan siempre el mismo id.
+pub fn hash_hex(datos: &[&str]) -> String {
+    hex(&Sha256::digest(datos.join("|").as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn codificar_base58check(version: u8, datos: &[u8], alfabeto: &str) -> String {
+    let mut bytes = vec![version];
+    bytes.extend(datos);
+    let checksum = Sha256::digest(Sha256::digest(&bytes));
+    bytes.extend(&checksum[..4]);
+
+    let caracteres: Vec<char> = alfabeto.chars().collect();
+    let ceros = bytes.iter().take_while(|b| **b == 0).count();
+    let mut digitos: Vec<u32> = Vec::new(); // En base 58, el menos significativo primero
+    for byte in &bytes[ceros..] {
+        let mut acarreo = *byte as u32;
+        for digito in digitos.iter_mut() {
+            acarreo += *digito << 8;
+            *digito = acarreo % 58;
+            acarreo /= 58;
+        }
+        while acarreo > 0 {
+            digitos.push(acarreo % 58);
+            acarreo /= 58;
+        }
+    }
+
+    let mut texto: String = std::iter::repeat_n(caracteres[0], ceros).collect();
+    texto.extend(digitos.iter().rev().map(|d| caracteres[*d as usize]));
+    texto
+}
+
+// Version (1 byte) + hash de 20 bytes + checksum de 4 bytes
+fn validar_base58check(direccion: &str, alfabeto: &str, versiones: &[u8]) -> Result<(), ErrorDireccion> {
+    let bytes = decodificar_base58(direccion, alfabeto).ok_or(ErrorDireccion::Formato)?;
+    if bytes.len() != 25 {
+        return Err(ErrorDireccion::Formato);
+    }
+
+    let (datos, checksum) = bytes.split_at(21);
+    if Sha256::digest(Sha256::digest(datos))[..4] != *checksum {
+        return Err(ErrorDireccion::Checksum);
+    }
+    if !versiones.contains(&datos[0]) {
+        return Err(ErrorDireccion::Version);
+    }
+    Ok(())
+}
+
+// Cada caracter inicial que vale cero es un byte cero; el resto es un numero en base 58
+fn decodificar_base58(texto: &str, alfabeto: &str) -> Option<Vec<u8>> {
+    let cero = alfabeto.chars().next()?;
+    let ceros = texto.chars().take_while(|c| *c == cero).count();
+    let mut numero: Vec<u8> = Vec::new(); // Big endian
+
+    for c in texto.chars().skip(ceros) {
+        let mut acarreo = alfabeto.find(c)? as u32;
+        for byte in numero.iter_mut().rev() {
+            acarreo += *byte as u32 * 58;
+            *byte = (acarreo & 0xff) as u8;
+            acarreo >>= 8;
+        }
+        while acarreo > 0 {
+            numero.insert(0, (acarreo & 0xff) as u8);
+            acarreo >>= 8;
+        }
+    }
+
+    let mut bytes = vec![0; ceros];
+    bytes.extend(numero);
+    Some(bytes)
+}
+
+fn validar_eth(direccion: &str) -> Result<(), ErrorDireccion> {
+    let hexa = direccion.strip_prefix("0x").ok_or(ErrorDireccion::Formato)?;
+    if hexa.len() != 40 || !hexa.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ErrorDireccion::Formato);
+    }
+    if hexa == hexa.to_lowercase() || hexa == hexa.to_uppercase() {
+        return Ok(()); // Sin checksum
+    }
+
+    if checksum_eth(hexa) != direccion {
+        return Err(ErrorDireccion::Checksum);
+    }
+    Ok(())
+}
+
+// EIP-55: cada letra va en mayuscula si el nibble del keccak de la direccion en minusculas es >= 8
+fn checksum_eth(hexa: &str) -> String {
+    let minusculas = hexa.to_lowercase();
+    let hash = hex(&Keccak256::digest(minusculas.as_bytes()));
+    let con_checksum: String = minusculas.chars().zip(hash.chars()).map(|(c, h)| {
+        if h.to_digit(16).unwrap_or(0) >= 8 { c.to_ascii_uppercase() } else { c }
+    }).collect();
+    format!("0x{con_checksum}")
+}
+
+fn validar_bech32(direccion: &str, prefijo: &str) -> Result<(), ErrorDireccion> {
+    if direccion != direccion.to_lowercase() && direccion != direccion.to_uppercase() {
+        return Err(ErrorDireccion::Formato);
+    }
+    let direccion = direccion.to_lowercase();
+    let (hrp, datos) = direccion.rsplit_once('1').ok_or(ErrorDireccion::Formato)?;
+    if hrp != prefijo {
+        return Err(ErrorDireccion::Version);
+    }
+    let valores: Vec<u32> = datos.chars().map(|c| ALFABETO_BECH32.find(c).map(|v| v as u32)).collect::<Option<_>>().ok_or(ErrorDireccion::Formato)?;
+    if valores.len() < 6 {
+        return Err(ErrorDireccion::Formato);
+    }
+
+    let mut entrada = expandir_hrp(hrp);
+    entrada.extend(valores);
+    if polimodo_bech32(&entrada) != 1 {
+        return Err(ErrorDireccion::Checksum);
+    }
+    Ok(())
+}
+
+fn codificar_bech32(hrp: &str, datos: &[u8]) -> String {
+    // Los bytes se reagrupan de a 5 bits
+    let mut valores: Vec<u32> = Vec::new();
+    let (mut acumulado, mut bits) = (0u32, 0);
+    for byte in datos {
+        acumulado = (acumulado << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            valores.push((acumulado >> bits) & 31);
+        }
+    }
+    if bits > 0 {
+        valores.push((acumulado << (5 - bits)) & 31);
+    }
+
+    let mut entrada = expandir_hrp(hrp);
+    entrada.extend(&valores);
+    entrada.extend([0; 6]);
+    let polimodo = polimodo_bech32(&entrada) ^ 1;
+    valores.extend((0..6).map(|i| (polimodo >> (5 * (5 - i))) & 31));
+
+    let alfabeto: Vec<char> = ALFABETO_BECH32.chars().collect();
+    format!("{hrp}1{}", valores.iter().map(|v| alfabeto[*v as usize]).collect::<String>())
+}
+
+fn expandir_hrp(hrp: &str) -> Vec<u32> {
+    let mut valores: Vec<u32> = hrp.bytes().map(|b| b as u32 >> 5).collect();
+    valores.push(0);
+    valores.extend(hrp.bytes().map(|b| b as u32 & 31));
+    valores
+}
+
+fn polimodo_bech32(valores: &[u32]) -> u32 {
+    const GENERADOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut chk = 1;
+    for v in valores {
+        let tope = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ v;
+        for (i, g) in GENERADOR.iter().enumerate() {
+            if (tope >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+#[test]
+fn test_validar_direccion_base58check() {
+    assert_eq!(Ok(()), validar_direccion(FormatoDireccion::Bitcoin, "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"));
+    assert_eq!(Ok(()), validar_direccion(FormatoDireccion::Bitcoin, "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"));
+    assert_eq!(Err(ErrorDireccion::Checksum), validar_direccion(FormatoDireccion::Bitcoin, "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb"));
+    assert_eq!(Err(ErrorDireccion::Formato), validar_direccion(FormatoDireccion::Bitcoin, "1A1zP1eP5QGefi2DMPTfTL5SLmv7Divf0a")); // El 0 no esta en el alfabeto
+    assert_eq!(Err(ErrorDireccion::Formato), validar_direccion(FormatoDireccion::Bitcoin, "1A1zP1eP5QGefi2"));
+
+    assert_eq!(Ok(()), validar_direccion(FormatoDireccion::Litecoin, "LKKHMBjCU89fyFNgSRprDoD8Jb25N8uWvd"));
+    assert_eq!(Err(ErrorDireccion::Version), validar_direccion(FormatoDireccion::Litecoin, "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"));
+    assert_eq!(Err(ErrorDireccion::Version), validar_direccion(FormatoDireccion::Bitcoin, "LKKHMBjCU89fyFNgSRprDoD8Jb25N8uWvd"));
+    assert_eq!(Ok(()), validar_direccion(FormatoDireccion::Ripple, "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh"));
+}
+
+#[test]
+fn test_validar_direccion_eth_y_ada() {
+    assert_eq!(Ok(()), validar_direccion(FormatoDireccion::Ethereum, "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"));
+    assert_eq!(Ok(()), validar_direccion(FormatoDireccion::Ethereum, "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359"));
+    assert_eq!(Ok(()), validar_direccion(FormatoDireccion::Ethereum, "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"));
+    assert_eq!(Err(ErrorDireccion::Checksum), validar_direccion(FormatoDireccion::Ethereum, "0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"));
+    assert_eq!(Err(ErrorDireccion::Formato), validar_direccion(FormatoDireccion::Ethereum, "5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"));
+    assert_eq!(Err(ErrorDireccion::Formato), validar_direccion(FormatoDireccion::Ethereum, "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAe"));
+
+    let ada = "addr1qyqqzqsrqszsvpcgpy9qkrqdpc83qygjzv2p29shrqv35xcur50p7gppyg3jgffxyu5zj23t9skjutesxyerxdp4xcmskm46z7";
+    assert_eq!(Ok(()), validar_direccion(FormatoDireccion::Cardano, ada));
+    assert_eq!(Err(ErrorDireccion::Checksum), validar_direccion(FormatoDireccion::Cardano, &ada.replace("z7", "z8")));
+    assert_eq!(Err(ErrorDireccion::Version), validar_direccion(FormatoDireccion::Cardano, &ada.replace("addr1", "stake1")));
+}
+
+#[test]
+fn test_direccion_canonica() {
+    assert_eq!(direccion_canonica("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"), direccion_canonica("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"));
+    assert_eq!(direccion_canonica("addr1qyqqzqsrqs"), direccion_canonica("ADDR1QYQQZQSRQS"));
+    assert_ne!(direccion_canonica("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"), direccion_canonica("1a1zp1ep5qgefi2dmptftl5slmv7divfna"));
+}
+
+#[test]
+fn test_generar_direccion() {
+    for formato in [FormatoDireccion::Bitcoin, FormatoDireccion::Litecoin, FormatoDireccion::Ripple, FormatoDireccion::Ethereum, FormatoDireccion::Cardano] {
+        let direccion = generar_direccion(formato, "123|red");
+        assert_eq!(Ok(()), validar_direccion(formato, &direccion), "{formato:?}: {direccion}");
+        assert_eq!(direccion, generar_direccion(formato, "123|red"));
+        assert_ne!(direccion, generar_direccion(formato, "124|red"));
+    }
+    assert!(generar_direccion(FormatoDireccion::Litecoin, "1").starts_with('L'));
+    assert!(generar_direccion(FormatoDireccion::Ripple, "1").starts_with('r'));
+}
+
+#[test]
+fn test_hash_hex() {
+    assert_eq!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", hash_hex(&[""]));
+    assert_eq!(hash_hex(&["a", "b"]), hash_hex(&["a", "b"]));
+    assert_ne!(hash_hex(&["a", "b"]), hash_hex(&["ab"]));
+}
@@ -0,0 +1,393 @@
+use std::{fmt, path::PathBuf};
+use serde_json::{json, Value};
+
+use crate::analitica::{Criterio, Filtro, TipoOperacion};
+use crate::dinero::{MontoCripto, MontoFiat};
+use fecha::Fecha;
+use crate::kyc::Identidad;
+use crate::xyz::{IdTransaccion, Medio, Prefijo, XyzError, XYZ};
+
+pub const USO: &str = "\
+uso: ejercicio6 [--dir DIRECTORIO] [--json] [--usuario DNI] COMANDO
+
+  usuario crear DNI NOMBRE APELLIDO EMAIL
+  usuario identidad DNI DOCUMENTO EMAIL NACIMIENTO    (NACIMIENTO como 15/6/1990)
+  usuario validar DNI REVISOR [NOTA]
+  usuario rechazar DNI REVISOR [NOTA]
+  usuario ver DNI
+
+  con --usuario DNI:
+  depositar MONTO
+  comprar CRIPTO MONTO
+  vender CRIPTO MONTO
+  retirar MONTO [MercadoPago|TransferenciaBancaria]
+  retirar CRIPTO MONTO BLOCKCHAIN DESTINO
+  reporte extracto [DESDE [HASTA]]
+
+  reporte top-volumen|top-operaciones [compras|ventas]
+
+El estado se carga del directorio (por defecto el actual) y cada operacion queda guardada ahi.";
+
+// Lo que va antes del comando
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opciones {
+    pub directorio: PathBuf,
+    pub json: bool,
+    pub usuario: Option<String>
+}
+
+// Los montos quedan como texto: los decimales de cada cripto se conocen recien con los activos cargados
+#[derive(Debug, Clone, PartialEq)]
+pub enum Comando {
+    CrearUsuario { dni: String, nombre: String, apellido: String, email: String },
+    EnviarIdentidad { dni: String, identidad: Identidad },
+    Validar { dni: String, revisor: String, nota: String },
+    Rechazar { dni: String, revisor: String, nota: String },
+    VerUsuario { dni: String },
+    Depositar { dni: String, monto: String },
+    Comprar { dni: String, cripto: Prefijo, monto: String },
+    Vender { dni: String, cripto: Prefijo, monto: String },
+    RetirarFiat { dni: String, medio: Medio, monto: String },
+    RetirarCripto { dni: String, cripto: Prefijo, monto: String, blockchain: String, destino: String },
+    Extracto { dni: String, desde: Option<Fecha>, hasta: Option<Fecha> },
+    Top { criterio: Criterio, tipos: Vec<TipoOperacion> }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorCli {
+    Uso(String),
+    ParametroInvalido(String), // Un monto, una fecha o un medio que no se puede leer
+    Xyz(XyzError)
+}
+
+// Una tabla para mostrar en la terminal, con las columnas alineadas
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tabla {
+    pub titulo: String,
+    pub columnas: Vec<String>,
+    pub filas: Vec<Vec<String>>
+}
+
+// El resultado de un comando en sus dos formas: para leer y para scripts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Salida {
+    pub tabla: Tabla,
+    pub json: Value
+}
+
+impl fmt::Display for ErrorCli {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorCli::Uso(e) => write!(f, "{e}"),
+            ErrorCli::ParametroInvalido(e) => write!(f, "{e}"),
+            ErrorCli::Xyz(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorCli {}
+
+impl From<XyzError> for ErrorCli {
+    fn from(error: XyzError) -> Self {
+        ErrorCli::Xyz(error)
+    }
+}
+
+impl ErrorCli {
+    // El nombre de la variante, sin sus datos
+    pub fn codigo(&self) -> String {
+        let debug = match self {
+            ErrorCli::Xyz(e) => format!("{e:?}"),
+            e => format!("{e:?}")
+        };
+        debug.split(|c: char| !c.is_alphanumeric()).next().unwrap_or_default().to_string()
+    }
+
+    pub fn json(&self) -> Value {
+        json!({ "error": { "codigo": self.codigo(), "mensaje": self.to_string() } })
+    }
+}
+
+impl Tabla {
+    pub fn new(titulo: impl Into<String>, columnas: &[&str]) -> Tabla {
+        Tabla { titulo: titulo.into(), columnas: columnas.iter().map(|c| c.to_string()).collect(), filas: Vec::new() }
+    }
+
+    pub fn fila(&mut self, fila: Vec<String>) {
+        self.filas.push(fila);
+    }
+}
+
+impl fmt::Display for Tabla {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.titulo.is_empty() {
+            writeln!(f, "{}", self.titulo)?;
+        }
+        let mut anchos: Vec<usize> = self.columnas.iter().map(|c| c.chars().count()).collect();
+        for fila in &self.filas {
+            for (ancho, celda) in anchos.iter_mut().zip(fila) {
+                *ancho = (*ancho).max(celda.chars().count());
+            }
+        }
+        let linea = |f: &mut fmt::Formatter, celdas: &[String]| {
+            let celdas: Vec<String> = celdas.iter().zip(&anchos).map(|(c, ancho)| format!("{c:<ancho$}")).collect();
+            writeln!(f, "{}", celdas.join("  ").trim_end())
+        };
+        linea(f, &self.columnas)?;
+        linea(f, &anchos.iter().map(|a| "-".repeat(*a)).collect::<Vec<_>>())?;
+        for fila in &self.filas {
+            linea(f, fila)?;
+        }
+        if self.filas.is_empty() {
+            writeln!(f, "(vacio)")?;
+        }
+        Ok(())
+    }
+}
+
+// Separa las opciones del comando; las opciones pueden ir en cualquier lugar
+pub fn parsear(args: &[String]) -> Result<(Opciones, Comando), ErrorCli> {
+    let mut opciones = Opciones { directorio: PathBuf::from("."), json: false, usuario: None };
+    let mut resto: Vec<&str> = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => opciones.json = true,
+            "--dir" => opciones.directorio = PathBuf::from(valor(args.next(), "--dir")?),
+            "--usuario" | "-u" => opciones.usuario = Some(valor(args.next(), "--usuario")?.to_string()),
+            _ => resto.push(arg)
+        }
+    }
+
+    let usuario = || opciones.usuario.clone().ok_or(ErrorCli::Uso("Falta --usuario DNI".to_string()));
+    let texto = |s: &str| s.to_string();
+    let comando = match resto.as_slice() {
+        ["usuario", "crear", dni, nombre, apellido, email] => Comando::CrearUsuario { dni: texto(dni), nombre: texto(nombre), apellido: texto(apellido), email: texto(email) },
+        ["usuario", "identidad", dni, documento, email, nacimiento] => {
+            let identidad = Identidad { documento: texto(documento), email: texto(email), fecha_nacimiento: fecha(nacimiento)? };
+            Comando::EnviarIdentidad { dni: texto(dni), identidad }
+        },
+        ["usuario", accion @ ("validar" | "rechazar"), dni, revisor, nota @ ..] => {
+            let (dni, revisor, nota) = (texto(dni), texto(revisor), nota.join(" "));
+            match *accion {
+                "validar" => Comando::Validar { dni, revisor, nota },
+                _ => Comando::Rechazar { dni, revisor, nota }
+            }
+        },
+        ["usuario", "ver", dni] => Comando::VerUsuario { dni: texto(dni) },
+        ["depositar", monto] => Comando::Depositar { dni: usuario()?, monto: texto(monto) },
+        ["comprar", cripto, monto] => Comando::Comprar { dni: usuario()?, cripto: Prefijo::new(cripto), monto: texto(monto) },
+        ["vender", cripto, monto] => Comando::Vender { dni: usuario()?, cripto: Prefijo::new(cripto), monto: texto(monto) },
+        ["retirar", monto] => Comando::RetirarFiat { dni: usuario()?, medio: Medio::TransferenciaBancaria, monto: texto(monto) },
+        ["retirar", monto, medio] => Comando::RetirarFiat { dni: usuario()?, medio: leer_medio(medio)?, monto: texto(monto) },
+        ["retirar", cripto, monto, blockchain, destino] => Comando::RetirarCripto {
+            dni: usuario()?, cripto: Prefijo::new(cripto), monto: texto(monto), blockchain: texto(blockchain), destino: texto(destino)
+        },
+        ["reporte", "extracto", fechas @ ..] if fechas.len() <= 2 => {
+            let desde = fechas.first().map(|f| fecha(f)).transpose()?;
+            let hasta = fechas.get(1).map(|f| fecha(f)).transpose()?;
+            Comando::Extracto { dni: usuario()?, desde, hasta }
+        },
+        ["reporte", top, lado @ ..] if top.starts_with("top-") && lado.len() <= 1 => {
+            let criterio = match &top[4..] {
+                "volumen" => Criterio::Volumen,
+                "operaciones" => Criterio::Operaciones,
+                otro => return Err(ErrorCli::Uso(format!("No hay un reporte top-{otro}")))
+            };
+            let tipos = match lado.first().copied() {
+                None => vec![TipoOperacion::Compra, TipoOperacion::Venta],
+                Some("compras") => vec![TipoOperacion::Compra],
+                Some("ventas") => vec![TipoOperacion::Venta],
+                Some(otro) => return Err(ErrorCli::Uso(format!("{otro} no es compras ni ventas")))
+            };
+            Comando::Top { criterio, tipos }
+        },
+        [] => return Err(ErrorCli::Uso("Falta el comando".to_string())),
+        _ => return Err(ErrorCli::Uso(format!("Comando desconocido: {}", resto.join(" "))))
+    };
+    Ok((opciones, comando))
+}
+
+pub fn ejecutar(xyz: &mut XYZ, comando: Comando) -> Result<Salida, ErrorCli> {
+    match comando {
+        Comando::CrearUsuario { dni, nombre, apellido, email } => {
+            xyz.crear_usuario(nombre, apellido, email, dni.clone())?;
+            usuario(xyz, &dni)
+        },
+        Comando::EnviarIdentidad { dni, identidad } => {
+            xyz.enviar_identidad(dni.clone(), identidad)?;
+            usuario(xyz, &dni)
+        },
+        Comando::Validar { dni, revisor, nota } => {
+            xyz.aprobar_usuario(dni.clone(), revisor, nota)?;
+            usuario(xyz, &dni)
+        },
+        Comando::Rechazar { dni, revisor, nota } => {
+            xyz.rechazar_usuario(dni.clone(), revisor, nota)?;
+            usuario(xyz, &dni)
+        },
+        Comando::VerUsuario { dni } => usuario(xyz, &dni),
+        Comando::Depositar { dni, monto } => {
+            let id = xyz.ingresar_dinero(dni.clone(), fiat(&monto)?)?;
+            transaccion(xyz, &dni, id)
+        },
+        Comando::Comprar { dni, cripto, monto } => {
+            let monto = cripto_monto(xyz, &monto, &cripto)?;
+            let id = xyz.comprar_cripto(dni.clone(), cripto, monto)?;
+            transaccion(xyz, &dni, id)
+        },
+        Comando::Vender { dni, cripto, monto } => {
+            let monto = cripto_monto(xyz, &monto, &cripto)?;
+            let id = xyz.vender_cripto(dni.clone(), cripto, monto)?;
+            transaccion(xyz, &dni, id)
+        },
+        Comando::RetirarFiat { dni, medio, monto } => {
+            let id = xyz.retirar_fiat(dni.clone(), medio, fiat(&monto)?)?;
+            transaccion(xyz, &dni, id)
+        },
+        Comando::RetirarCripto { dni, cripto, monto, blockchain, destino } => {
+            let monto = cripto_monto(xyz, &monto, &cripto)?;
+            let id = xyz.retirar_blockchain(dni.clone(), cripto, blockchain, destino, monto)?;
+            transaccion(xyz, &dni, id)
+        },
+        Comando::Extracto { dni, desde, hasta } => {
+            let hasta = hasta.unwrap_or_else(|| xyz.hoy());
+            let extracto = xyz.extracto(&dni, &desde.unwrap_or(Fecha::new(1, 1, 1970)), &hasta)?;
+            let mut tabla = Tabla::new(format!("Extracto de {dni} del {} al {}", extracto.desde, extracto.hasta), &["Fecha", "Transaccion"]);
+            for t in &extracto.transacciones {
+                tabla.fila(vec![t.fecha().to_string(), tipo(&serde_json::to_value(t).unwrap_or_default())]);
+            }
+            tabla.fila(vec![String::new(), format!("Ganancia realizada: {}", extracto.ganancia_realizada)]);
+            tabla.fila(vec![String::new(), format!("Ganancia no realizada: {}", extracto.ganancia_no_realizada)]);
+            Ok(Salida { tabla, json: serde_json::to_value(&extracto).unwrap_or_default() })
+        },
+        Comando::Top { criterio, tipos } => {
+            let filtro = Filtro { tipos: tipos.clone(), ..Filtro::default() };
+            let nombre = if criterio == Criterio::Volumen { "volumen" } else { "operaciones" };
+            let mut tabla = Tabla::new(format!("Criptos por {nombre} ({})", tipos.iter().map(|t| format!("{t:?}")).collect::<Vec<_>>().join(", ")), &["Cripto", "Operaciones", "Volumen", "Volumen fiat"]);
+            let mut filas = Vec::new();
+            for (cripto, agregado) in xyz.ranking(&filtro, criterio, usize::MAX) {
+                let volumen = agregado.volumen_de(&cripto).formatear(xyz.decimales(&cripto)?);
+                filas.push(json!({ "cripto": cripto, "operaciones": agregado.operaciones, "volumen": volumen, "volumen_fiat": agregado.volumen_fiat.to_string() }));
+                tabla.fila(vec![cripto.to_string(), agregado.operaciones.to_string(), volumen, agregado.volumen_fiat.to_string()]);
+            }
+            Ok(Salida { tabla, json: json!({ "criterio": nombre, "ranking": filas }) })
+        }
+    }
+}
+
+// El estado del usuario y sus saldos disponibles
+fn usuario(xyz: &XYZ, dni: &str) -> Result<Salida, ErrorCli> {
+    let u = xyz.usuario(dni).ok_or(XyzError::UsuarioNoEncontrado)?;
+    let estado = xyz.estado_kyc(dni);
+    let titulo = match &estado {
+        Some(estado) => format!("Usuario {dni} ({estado:?})"),
+        None => format!("Usuario {dni}")
+    };
+    let mut tabla = Tabla::new(titulo, &["Moneda", "Saldo"]);
+    tabla.fila(vec!["Fiat".to_string(), u.fiat().to_string()]);
+
+    let mut cripto: Vec<(&Prefijo, String)> = u.balances().iter().filter(|(_, m)| **m != MontoCripto::CERO)
+        .map(|(c, m)| Ok((c, m.formatear(xyz.decimales(c)?)))).collect::<Result<_, XyzError>>()?;
+    cripto.sort();
+    for (c, m) in &cripto {
+        tabla.fila(vec![c.to_string(), m.clone()]);
+    }
+    let cripto: serde_json::Map<String, Value> = cripto.into_iter().map(|(c, m)| (c.to_string(), json!(m))).collect();
+    Ok(Salida { tabla, json: json!({ "dni": dni, "estado": estado, "fiat": u.fiat().to_string(), "cripto": cripto }) })
+}
+
+// La transaccion que anoto la operacion, y como le quedaron los saldos al usuario
+fn transaccion(xyz: &XYZ, dni: &str, id: IdTransaccion) -> Result<Salida, ErrorCli> {
+    let t = serde_json::to_value(&xyz.transacciones()[id]).unwrap_or_default();
+    let Salida { mut tabla, json: saldos } = usuario(xyz, dni)?;
+    tabla.titulo = format!("Transaccion {id}: {}\n{}", tipo(&t), tabla.titulo);
+    Ok(Salida { tabla, json: json!({ "id": id, "transaccion": t, "usuario": saldos }) })
+}
+
+// El nombre de la variante de una transaccion serializada
+fn tipo(transaccion: &Value) -> String {
+    transaccion.as_object().and_then(|t| t.keys().next()).cloned().unwrap_or_default()
+}
+
+fn valor<'a>(arg: Option<&'a String>, opcion: &str) -> Result<&'a str, ErrorCli> {
+    arg.map(String::as_str).ok_or(ErrorCli::Uso(format!("Falta el valor de {opcion}")))
+}
+
+fn fecha(s: &str) -> Result<Fecha, ErrorCli> {
+    s.parse().map_err(|e: fecha::FechaInvalida| ErrorCli::ParametroInvalido(e.to_string()))
+}
+
+fn leer_medio(s: &str) -> Result<Medio, ErrorCli> {
+    serde_json::from_value(json!(s)).map_err(|_| ErrorCli::ParametroInvalido(format!("{s} no es un medio de retiro")))
+}
+
+fn fiat(monto: &str) -> Result<MontoFiat, ErrorCli> {
+    monto.parse().map_err(|e: crate::dinero::MontoMalFormado| ErrorCli::ParametroInvalido(e.to_string()))
+}
+
+fn cripto_monto(xyz: &XYZ, monto: &str, cripto: &Prefijo) -> Result<MontoCripto, ErrorCli> {
+    MontoCripto::parse(monto, xyz.decimales(cripto)?).map_err(|e| ErrorCli::ParametroInvalido(e.to_string()))
+}
+
+#[cfg(test)]
+use persistencia::Persistencia;
+
+#[cfg(test)]
+fn args(linea: &str) -> Vec<String> {
+    linea.split_whitespace().map(str::to_string).collect()
+}
+
+#[test]
+fn test_cli_parsear() {
+    let (opciones, comando) = parsear(&args("--dir estado comprar BTC 0.5 --usuario 1 --json")).unwrap();
+    assert_eq!(Opciones { directorio: PathBuf::from("estado"), json: true, usuario: Some("1".to_string()) }, opciones);
+    assert_eq!(Comando::Comprar { dni: "1".to_string(), cripto: Prefijo::BTC, monto: "0.5".to_string() }, comando);
+
+    let (_, comando) = parsear(&args("usuario validar 1 ana todo en orden")).unwrap();
+    assert_eq!(Comando::Validar { dni: "1".to_string(), revisor: "ana".to_string(), nota: "todo en orden".to_string() }, comando);
+    let (_, comando) = parsear(&args("-u 1 retirar 100 MercadoPago")).unwrap();
+    assert_eq!(Comando::RetirarFiat { dni: "1".to_string(), medio: Medio::MercadoPago, monto: "100".to_string() }, comando);
+    let (_, comando) = parsear(&args("reporte top-volumen ventas")).unwrap();
+    assert_eq!(Comando::Top { criterio: Criterio::Volumen, tipos: vec![TipoOperacion::Venta] }, comando);
+
+    assert_eq!("Uso", parsear(&args("comprar BTC 0.5")).unwrap_err().codigo()); // Sin --usuario
+    assert_eq!("Uso", parsear(&args("")).unwrap_err().codigo());
+    assert_eq!("Uso", parsear(&args("reporte top-nada")).unwrap_err().codigo());
+    assert_eq!("ParametroInvalido", parsear(&args("-u 1 retirar 100 Efectivo")).unwrap_err().codigo());
+    assert_eq!("ParametroInvalido", parsear(&args("usuario identidad 1 30.123.456 j@d.com 31/4/1990")).unwrap_err().codigo());
+}
+
+#[test]
+fn test_cli_ejecutar() {
+    let mut xyz = XYZ::new(Persistencia::memoria());
+    let mut correr = |linea: &str| parsear(&args(linea)).and_then(|(_, comando)| ejecutar(&mut xyz, comando));
+
+    correr("usuario crear 1 John Doe john@doe.com").unwrap();
+    assert_eq!(ErrorCli::Xyz(XyzError::UsuarioNoValidado), correr("-u 1 depositar 100").unwrap_err());
+    correr("usuario identidad 1 30.123.456 john@doe.com 15/6/1990").unwrap();
+    assert_eq!(json!("Aprobado"), correr("usuario validar 1 ana").unwrap().json["estado"]);
+
+    correr("-u 1 depositar 100000").unwrap();
+    let compra = correr("-u 1 comprar BTC 0.5").unwrap();
+    assert_eq!(json!("0.5"), compra.json["usuario"]["cripto"]["BTC"]);
+    assert_eq!("Transaccion 1: CompraCripto\nUsuario 1 (Aprobado)", compra.tabla.titulo);
+    correr("-u 1 vender BTC 0.1").unwrap();
+    correr("-u 1 comprar ETH 2").unwrap();
+    assert_eq!("ParametroInvalido", correr("-u 1 comprar BTC 0.000000001").unwrap_err().codigo());
+
+    let top = correr("reporte top-volumen").unwrap();
+    assert_eq!(vec!["ETH", "BTC"], top.tabla.filas.iter().map(|f| f[0].as_str()).collect::<Vec<_>>());
+    assert_eq!(json!("0.6"), top.json["ranking"][1]["volumen"]);
+    assert_eq!(json!("BTC"), correr("reporte top-operaciones ventas").unwrap().json["ranking"][0]["cripto"]);
+    assert_eq!(6, correr("-u 1 reporte extracto").unwrap().tabla.filas.len()); // 4 transacciones y las dos ganancias
+}
+
+#[test]
+fn test_tabla_display() {
+    let mut tabla = Tabla::new("Saldos", &["Moneda", "Saldo"]);
+    tabla.fila(vec!["Fiat".to_string(), "100.00".to_string()]);
+    tabla.fila(vec!["BTC".to_string(), "0.5".to_string()]);
+    assert_eq!("Saldos\nMoneda  Saldo\n------  ------\nFiat    100.00\nBTC     0.5\n", tabla.to_string());
+    assert_eq!("Moneda\n------\n(vacio)\n", Tabla::new("", &["Moneda"]).to_string());
+}
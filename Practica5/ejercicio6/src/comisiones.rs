@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::dinero::{Monto, MontoCripto, MontoFiat, Redondeo};
+use crate::xyz::{Medio, Prefijo};
+
+pub const BPS_MAXIMO: u32 = 10000;
+
+// Comisiones que cobra XYZ. Los porcentajes van en puntos basicos (1 bps = 0.01%)
+// y se redondean hacia arriba, asi toda operacion con valor paga algo. Una tabla leida
+// de un archivo puede tener mas de 10000 bps, asi que la comision puede no entrar en un monto.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TablaComisiones {
+    maker_bps: u32, // Orden que estaba esperando en el libro
+    taker_bps: u32, // Orden que cruza el libro, y las compras/ventas directas contra XYZ
+    #[serde(default)]
+    spread_bps: u32, // Intercambios de una cripto por otra
+    retiro_fiat: HashMap<Medio, MontoFiat>
+}
+
+// Lo que XYZ cobro en comisiones, en fiat y en cada cripto
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Recaudacion {
+    pub fiat: MontoFiat,
+    pub cripto: HashMap<Prefijo, MontoCripto>
+}
+
+impl TablaComisiones {
+    // Sin spread ni comisiones de retiro; los bps de mas se recortan al 100%
+    pub fn new(maker_bps: u32, taker_bps: u32) -> TablaComisiones {
+        TablaComisiones {
+            maker_bps: maker_bps.min(BPS_MAXIMO),
+            taker_bps: taker_bps.min(BPS_MAXIMO),
+            spread_bps: 0,
+            retiro_fiat: HashMap::new()
+        }
+    }
+
+    pub fn set_spread(&mut self, bps: u32) {
+        self.spread_bps = bps.min(BPS_MAXIMO);
+    }
+
+    pub fn set_retiro_fiat(&mut self, medio: Medio, comision: MontoFiat) {
+        self.retiro_fiat.insert(medio, comision);
+    }
+
+    pub fn maker_fiat(&self, valor: MontoFiat) -> Option<MontoFiat> {
+        valor.porcentaje(self.maker_bps, Redondeo::Arriba)
+    }
+
+    pub fn taker_fiat(&self, valor: MontoFiat) -> Option<MontoFiat> {
+        valor.porcentaje(self.taker_bps, Redondeo::Arriba)
+    }
+
+    pub fn maker_cripto(&self, monto: MontoCripto) -> Option<MontoCripto> {
+        monto.porcentaje(self.maker_bps, Redondeo::Arriba)
+    }
+
+    pub fn taker_cripto(&self, monto: MontoCripto) -> Option<MontoCripto> {
+        monto.porcentaje(self.taker_bps, Redondeo::Arriba)
+    }
+
+    pub fn spread_cripto(&self, monto: MontoCripto) -> Option<MontoCripto> {
+        monto.porcentaje(self.spread_bps, Redondeo::Arriba)
+    }
+
+    pub fn retiro_fiat(&self, medio: &Medio) -> MontoFiat {
+        self.retiro_fiat.get(medio).copied().unwrap_or_default()
+    }
+}
+
+impl Recaudacion {
+    pub fn new() -> Recaudacion {
+        Recaudacion::default()
+    }
+
+    pub fn sumar(&mut self, comision: &Monto) {
+        match comision {
+            Monto::Fiat(monto) => self.fiat += *monto,
+            Monto::Cripto(cripto, monto) => *self.cripto.entry(cripto.clone()).or_default() += *monto
+        }
+    }
+
+    pub fn cripto(&self, cripto: &Prefijo) -> MontoCripto {
+        self.cripto.get(cripto).copied().unwrap_or_default()
+    }
+}
+
+#[test]
+fn test_tabla_comisiones() {
+    let mut tabla = TablaComisiones::new(10, 25);
+    tabla.set_retiro_fiat(Medio::TransferenciaBancaria, MontoFiat::from_centavos(150));
+
+    let valor = MontoFiat::from_centavos(100000);
+    assert_eq!(Some(MontoFiat::from_centavos(100)), tabla.maker_fiat(valor));
+    assert_eq!(Some(MontoFiat::from_centavos(250)), tabla.taker_fiat(valor));
+    assert_eq!(Some(MontoCripto::from_unidades(3)), tabla.taker_cripto(MontoCripto::from_unidades(1000)));
+    assert_eq!(Some(MontoCripto::CERO), tabla.spread_cripto(MontoCripto::from_unidades(1000)));
+    tabla.set_spread(50);
+    assert_eq!(Some(MontoCripto::from_unidades(5)), tabla.spread_cripto(MontoCripto::from_unidades(1000)));
+
+    assert_eq!(MontoFiat::from_centavos(150), tabla.retiro_fiat(&Medio::TransferenciaBancaria));
+    assert_eq!(MontoFiat::CERO, tabla.retiro_fiat(&Medio::MercadoPago));
+
+    assert_eq!(TablaComisiones::new(BPS_MAXIMO, BPS_MAXIMO), TablaComisiones::new(20000, 10001));
+}
+
+#[test]
+fn test_recaudacion_sumar() {
+    let mut recaudacion = Recaudacion::new();
+    recaudacion.sumar(&Monto::Fiat(MontoFiat::from_centavos(150)));
+    recaudacion.sumar(&Monto::Fiat(MontoFiat::from_centavos(50)));
+    recaudacion.sumar(&Monto::Cripto(Prefijo::BTC, MontoCripto::from_unidades(10)));
+
+    assert_eq!(MontoFiat::from_centavos(200), recaudacion.fiat);
+    assert_eq!(MontoCripto::from_unidades(10), recaudacion.cripto(&Prefijo::BTC));
+    assert_eq!(MontoCripto::CERO, recaudacion.cripto(&Prefijo::ETH));
+}
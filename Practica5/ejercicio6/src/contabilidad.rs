@@ -0,0 +1,329 @@
+use std::{collections::HashMap, fmt};
+use serde::Serialize;
+
+use crate::activos::RegistroActivos;
+use crate::dinero::{Monto, MontoCripto, MontoFiat};
+use crate::extracto::Saldos;
+use fecha::Fecha;
+use crate::xyz::{IdTransaccion, Prefijo, Transaccion};
+
+// Cuentas del libro mayor de XYZ, cada una con un saldo por moneda
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum Cuenta {
+    Usuario(String), // Lo que XYZ le debe al usuario, por dni: disponible, retenido en ordenes y bloqueado en staking
+    CajaFiat, // El fiat de los usuarios en las cuentas bancarias de XYZ
+    BilleteraCaliente(String), // La cripto que XYZ tiene en cada red
+    Tesoreria, // Posicion propia de XYZ, contraparte de las compras, ventas e intercambios directos
+    Comisiones, // Lo cobrado, igual que la Recaudacion
+    Recompensas // Lo pagado por staking
+}
+
+// Una linea de un asiento: al debe si el monto es positivo, al haber si es negativo
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Partida {
+    pub cuenta: Cuenta,
+    pub monto: Monto
+}
+
+// Las partidas de una transaccion. En cada moneda el debe y el haber tienen que dar lo mismo.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Asiento {
+    pub transaccion: IdTransaccion,
+    pub fecha: Fecha,
+    pub partidas: Vec<Partida>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorContable {
+    AsientoDesbalanceado { transaccion: IdTransaccion, diferencia: Monto },
+    SaldoFueraDeRango { transaccion: IdTransaccion, cuenta: Cuenta }, // El saldo que dejaria el asiento no entra en un monto
+    MontoFueraDeRango { transaccion: IdTransaccion }, // Una partida, o la valuacion de la que sale, no entra en un monto
+    RetiroDesconocido { transaccion: IdTransaccion, retiro: IdTransaccion }, // El reintegro no apunta a un retiro de cripto
+    BalanceDesbalanceado { diferencia: Monto }, // Los saldos de todas las cuentas no suman cero
+    SaldoNoCoincide { cuenta: Cuenta, mayor: Monto, calculado: Monto }
+}
+
+// Los asientos de todas las transacciones y el saldo que dejan en cada cuenta.
+// No se guarda: sale de repetir las transacciones, como los saldos.
+#[derive(Debug, Clone, Default)]
+pub struct Mayor {
+    asientos: Vec<Asiento>,
+    saldos: HashMap<Cuenta, Saldos> // Debe menos haber
+}
+
+impl fmt::Display for ErrorContable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorContable::AsientoDesbalanceado { transaccion, diferencia } => write!(f, "El asiento de la transaccion {transaccion} no balancea: sobran {diferencia} al debe"),
+            ErrorContable::SaldoFueraDeRango { transaccion, cuenta } => write!(f, "El asiento de la transaccion {transaccion} deja a la cuenta {cuenta:?} fuera del rango representable"),
+            ErrorContable::MontoFueraDeRango { transaccion } => write!(f, "Una partida de la transaccion {transaccion} excede el rango representable"),
+            ErrorContable::RetiroDesconocido { transaccion, retiro } => write!(f, "La transaccion {transaccion} reintegra la {retiro}, que no es un retiro de cripto"),
+            ErrorContable::BalanceDesbalanceado { diferencia } => write!(f, "El balance de comprobacion no cierra: sobran {diferencia} al debe"),
+            ErrorContable::SaldoNoCoincide { cuenta, mayor, calculado } => write!(f, "La cuenta {cuenta:?} tiene {mayor} en el mayor pero {calculado} segun los saldos"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorContable {}
+
+impl Cuenta {
+    // Las del activo y los gastos crecen por el debe; las demas por el haber
+    pub fn es_deudora(&self) -> bool {
+        matches!(self, Cuenta::CajaFiat | Cuenta::BilleteraCaliente(_) | Cuenta::Recompensas)
+    }
+}
+
+impl Asiento {
+    pub fn new(transaccion: IdTransaccion, fecha: Fecha) -> Asiento {
+        Asiento { transaccion, fecha, partidas: Vec::new() }
+    }
+
+    // Los montos en cero no se anotan
+    pub fn debe(&mut self, cuenta: Cuenta, monto: Monto) {
+        if monto != monto.cero() {
+            self.partidas.push(Partida { cuenta, monto });
+        }
+    }
+
+    pub fn haber(&mut self, cuenta: Cuenta, monto: Monto) {
+        self.debe(cuenta, -monto);
+    }
+
+    // Lo que sobra al debe en cada moneda; vacio si balancea
+    pub fn diferencias(&self) -> Vec<Monto> {
+        let mut total = Saldos::default();
+        for p in &self.partidas {
+            total.aplicar(&p.monto);
+        }
+        distintos_de_cero(&total)
+    }
+}
+
+impl Mayor {
+    pub fn new() -> Mayor {
+        Mayor::default()
+    }
+
+    // Un asiento que no balancea no se asienta
+    pub fn asentar(&mut self, asiento: Asiento) -> Result<(), ErrorContable> {
+        if let Some(diferencia) = asiento.diferencias().into_iter().next() {
+            return Err(ErrorContable::AsientoDesbalanceado { transaccion: asiento.transaccion, diferencia });
+        }
+        // Se suma aparte, asi un saldo que se pasa del rango no deja el asiento a medias
+        let mut nuevos: HashMap<Cuenta, Saldos> = HashMap::new();
+        for p in &asiento.partidas {
+            let saldos = nuevos.entry(p.cuenta.clone()).or_insert_with(|| self.saldos.get(&p.cuenta).cloned().unwrap_or_default());
+            saldos.sumar(&p.monto).ok_or_else(|| ErrorContable::SaldoFueraDeRango { transaccion: asiento.transaccion, cuenta: p.cuenta.clone() })?;
+        }
+        self.saldos.extend(nuevos);
+        self.asientos.push(asiento);
+        Ok(())
+    }
+
+    pub fn asientos(&self) -> &[Asiento] {
+        &self.asientos
+    }
+
+    // Con el signo de la cuenta: lo que hay en las deudoras, lo que se debe o se gano en las acreedoras
+    pub fn saldos(&self, cuenta: &Cuenta) -> Saldos {
+        let saldos = self.saldos.get(cuenta).cloned().unwrap_or_default();
+        if cuenta.es_deudora() { saldos } else { negar(&saldos) }
+    }
+
+    // En la moneda del monto
+    pub fn saldo(&self, cuenta: &Cuenta, moneda: &Monto) -> Monto {
+        self.saldos(cuenta).en(moneda)
+    }
+
+    pub fn cuentas(&self) -> impl Iterator<Item = &Cuenta> {
+        self.saldos.keys()
+    }
+
+    // Vuelve a sumar todos los asientos desde cero: el debe y el haber tienen que dar lo mismo en
+    // cada moneda, y cada cuenta el saldo que se fue llevando. Devuelve los saldos con su signo.
+    pub fn balance_de_comprobacion(&self) -> Result<HashMap<Cuenta, Saldos>, ErrorContable> {
+        let mut saldos: HashMap<Cuenta, Saldos> = HashMap::new();
+        let mut total = Saldos::default();
+        for p in self.asientos.iter().flat_map(|a| &a.partidas) {
+            saldos.entry(p.cuenta.clone()).or_default().aplicar(&p.monto);
+            total.aplicar(&p.monto);
+        }
+        if let Some(diferencia) = distintos_de_cero(&total).into_iter().next() {
+            return Err(ErrorContable::BalanceDesbalanceado { diferencia });
+        }
+
+        let mut cuentas: Vec<Cuenta> = self.saldos.keys().chain(saldos.keys()).cloned().collect();
+        cuentas.sort();
+        cuentas.dedup();
+        for cuenta in cuentas {
+            let llevado = self.saldos(&cuenta);
+            let calculado = saldos.remove(&cuenta).unwrap_or_default();
+            let calculado = if cuenta.es_deudora() { calculado } else { negar(&calculado) };
+            if let Some(diferencia) = distintos_de_cero(&restar(&calculado, &llevado)).into_iter().next() {
+                return Err(ErrorContable::SaldoNoCoincide { cuenta, mayor: llevado.en(&diferencia), calculado: calculado.en(&diferencia) });
+            }
+            saldos.insert(cuenta, calculado);
+        }
+        Ok(saldos)
+    }
+}
+
+// El asiento de una transaccion. El historial hace falta para saber por que red salio el retiro
+// que se reintegra. Si alguna partida no se puede calcular no hay asiento: uno a medias no balancea
+// o, peor, balancea con montos equivocados.
+pub fn asiento(id: IdTransaccion, t: &Transaccion, historial: &[Transaccion], activos: &RegistroActivos) -> Result<Asiento, ErrorContable> {
+    let mut a = Asiento::new(id, t.fecha().clone());
+    let fiat = Monto::Fiat;
+    let usuario = |dni: &String| Cuenta::Usuario(dni.clone());
+    let fuera = || ErrorContable::MontoFueraDeRango { transaccion: id };
+
+    match t {
+        Transaccion::IngresoDinero { dni, monto, .. } => {
+            a.debe(Cuenta::CajaFiat, fiat(*monto));
+            a.haber(usuario(dni), fiat(*monto));
+        },
+        Transaccion::RetiroFiat { dni, monto, comision, .. } => {
+            a.debe(usuario(dni), fiat(monto.sumar(*comision).ok_or_else(fuera)?));
+            a.haber(Cuenta::CajaFiat, fiat(*monto));
+            a.haber(Cuenta::Comisiones, fiat(*comision));
+        },
+        Transaccion::CompraCripto { dni, cripto, cotizacion, monto, comision, .. } => {
+            let precio = activos.valor_en_fiat(*monto, cripto, *cotizacion).ok_or_else(fuera)?;
+            a.debe(usuario(dni), fiat(precio.sumar(*comision).ok_or_else(fuera)?));
+            a.haber(Cuenta::Tesoreria, fiat(precio));
+            a.haber(Cuenta::Comisiones, fiat(*comision));
+            a.debe(Cuenta::Tesoreria, Monto::Cripto(cripto.clone(), *monto));
+            a.haber(usuario(dni), Monto::Cripto(cripto.clone(), *monto));
+        },
+        Transaccion::VentaCripto { dni, cripto, cotizacion, monto, comision, .. } => {
+            let valor = activos.valor_en_fiat(*monto, cripto, *cotizacion).ok_or_else(fuera)?;
+            a.debe(usuario(dni), Monto::Cripto(cripto.clone(), *monto));
+            a.haber(Cuenta::Tesoreria, Monto::Cripto(cripto.clone(), *monto));
+            a.debe(Cuenta::Tesoreria, fiat(valor));
+            a.haber(usuario(dni), fiat(valor.restar(*comision).ok_or_else(fuera)?));
+            a.haber(Cuenta::Comisiones, fiat(*comision));
+        },
+        Transaccion::RetiroCripto { dni, blockchain, cripto, monto, comision, .. } => {
+            a.debe(usuario(dni), Monto::Cripto(cripto.clone(), monto.sumar(*comision).ok_or_else(fuera)?));
+            a.haber(Cuenta::BilleteraCaliente(blockchain.clone()), Monto::Cripto(cripto.clone(), *monto));
+            a.haber(Cuenta::Comisiones, Monto::Cripto(cripto.clone(), *comision));
+        },
+        Transaccion::ReintegroRetiro { dni, retiro, cripto, monto, comision, .. } => {
+            let Some(Transaccion::RetiroCripto { blockchain, .. }) = historial.get(*retiro) else {
+                return Err(ErrorContable::RetiroDesconocido { transaccion: id, retiro: *retiro });
+            };
+            a.debe(Cuenta::BilleteraCaliente(blockchain.clone()), Monto::Cripto(cripto.clone(), *monto));
+            a.debe(Cuenta::Comisiones, Monto::Cripto(cripto.clone(), *comision));
+            a.haber(usuario(dni), Monto::Cripto(cripto.clone(), monto.sumar(*comision).ok_or_else(fuera)?));
+        },
+        Transaccion::RecepcionCripto { dni, blockchain, cripto, monto, .. } => {
+            a.debe(Cuenta::BilleteraCaliente(blockchain.clone()), Monto::Cripto(cripto.clone(), *monto));
+            a.haber(usuario(dni), Monto::Cripto(cripto.clone(), *monto));
+        },
+        Transaccion::EjecucionOrden { comprador, vendedor, cripto, precio, monto, comision_comprador, comision_vendedor, .. } => {
+            let valor = activos.valor_en_fiat(*monto, cripto, *precio).ok_or_else(fuera)?;
+            a.debe(usuario(comprador), fiat(valor));
+            a.haber(usuario(vendedor), fiat(valor.restar(*comision_vendedor).ok_or_else(fuera)?));
+            a.haber(Cuenta::Comisiones, fiat(*comision_vendedor));
+            a.debe(usuario(vendedor), Monto::Cripto(cripto.clone(), *monto));
+            a.haber(usuario(comprador), Monto::Cripto(cripto.clone(), monto.restar(*comision_comprador).ok_or_else(fuera)?));
+            a.haber(Cuenta::Comisiones, Monto::Cripto(cripto.clone(), *comision_comprador));
+        },
+        Transaccion::Intercambio { dni, origen, monto_origen, destino, monto_destino, spread, .. } => {
+            a.debe(usuario(dni), Monto::Cripto(origen.clone(), *monto_origen));
+            a.haber(Cuenta::Tesoreria, Monto::Cripto(origen.clone(), *monto_origen));
+            a.debe(Cuenta::Tesoreria, Monto::Cripto(destino.clone(), monto_destino.sumar(*spread).ok_or_else(fuera)?));
+            a.haber(usuario(dni), Monto::Cripto(destino.clone(), *monto_destino));
+            a.haber(Cuenta::Comisiones, Monto::Cripto(destino.clone(), *spread));
+        },
+        // Lo bloqueado sigue en la cuenta del usuario
+        Transaccion::Staking { .. } => (),
+        Transaccion::RecompensaStaking { dni, cripto, monto, .. } => {
+            a.debe(Cuenta::Recompensas, Monto::Cripto(cripto.clone(), *monto));
+            a.haber(usuario(dni), Monto::Cripto(cripto.clone(), *monto));
+        }
+    }
+    Ok(a)
+}
+
+fn negar(saldos: &Saldos) -> Saldos {
+    Saldos { fiat: -saldos.fiat, cripto: saldos.cripto.iter().map(|(c, m)| (c.clone(), -*m)).collect() }
+}
+
+fn restar(a: &Saldos, b: &Saldos) -> Saldos {
+    let mut resta = a.clone();
+    resta.aplicar(&Monto::Fiat(-b.fiat));
+    for (cripto, monto) in &b.cripto {
+        resta.aplicar(&Monto::Cripto(cripto.clone(), -*monto));
+    }
+    resta
+}
+
+// Ordenados por ticker, asi el error que se informa es siempre el mismo
+fn distintos_de_cero(saldos: &Saldos) -> Vec<Monto> {
+    let mut criptos: Vec<(&Prefijo, &MontoCripto)> = saldos.cripto.iter().filter(|(_, m)| **m != MontoCripto::CERO).collect();
+    criptos.sort();
+    let mut montos: Vec<Monto> = criptos.into_iter().map(|(c, m)| Monto::Cripto(c.clone(), *m)).collect();
+    if saldos.fiat != MontoFiat::CERO {
+        montos.insert(0, Monto::Fiat(saldos.fiat));
+    }
+    montos
+}
+
+#[test]
+fn test_mayor_asentar_y_balance() {
+    let fiat = |m: &str| Monto::Fiat(m.parse().unwrap());
+    let mut mayor = Mayor::new();
+
+    let mut ingreso = Asiento::new(0, Fecha::new(1, 3, 2024));
+    ingreso.debe(Cuenta::CajaFiat, fiat("100"));
+    ingreso.haber(Cuenta::Usuario("1".to_string()), fiat("100"));
+    ingreso.haber(Cuenta::Comisiones, fiat("0")); // No se anota
+    assert_eq!(2, ingreso.partidas.len());
+    mayor.asentar(ingreso).unwrap();
+
+    let mut desbalanceado = Asiento::new(1, Fecha::new(1, 3, 2024));
+    desbalanceado.debe(Cuenta::Usuario("1".to_string()), fiat("10"));
+    desbalanceado.haber(Cuenta::CajaFiat, fiat("9"));
+    assert_eq!(Err(ErrorContable::AsientoDesbalanceado { transaccion: 1, diferencia: fiat("1") }), mayor.asentar(desbalanceado));
+    assert_eq!(1, mayor.asientos().len());
+
+    let mut grande = Asiento::new(1, Fecha::new(1, 3, 2024));
+    grande.debe(Cuenta::CajaFiat, Monto::Fiat(MontoFiat::from_centavos(i64::MAX)));
+    grande.haber(Cuenta::Tesoreria, Monto::Fiat(MontoFiat::from_centavos(i64::MAX)));
+    assert_eq!(Err(ErrorContable::SaldoFueraDeRango { transaccion: 1, cuenta: Cuenta::CajaFiat }), mayor.asentar(grande));
+    assert_eq!(fiat("0"), mayor.saldo(&Cuenta::Tesoreria, &fiat("0")));
+    assert_eq!(1, mayor.asientos().len());
+
+    // Cada cuenta con su signo: la caja tiene 100 y XYZ le debe 100 al usuario
+    assert_eq!(fiat("100"), mayor.saldo(&Cuenta::CajaFiat, &fiat("0")));
+    assert_eq!(fiat("100"), mayor.saldo(&Cuenta::Usuario("1".to_string()), &fiat("0")));
+    assert_eq!(fiat("0"), mayor.saldo(&Cuenta::Comisiones, &fiat("0")));
+    assert_eq!(2, mayor.balance_de_comprobacion().unwrap().len());
+
+    // Una partida cambiada despues de asentada
+    mayor.asientos[0].partidas[0].monto = fiat("90");
+    assert_eq!(Err(ErrorContable::BalanceDesbalanceado { diferencia: fiat("-10") }), mayor.balance_de_comprobacion());
+    mayor.asientos[0].partidas[1].monto = fiat("-90");
+    assert_eq!(Err(ErrorContable::SaldoNoCoincide { cuenta: Cuenta::Usuario("1".to_string()), mayor: fiat("100"), calculado: fiat("90") }), mayor.balance_de_comprobacion());
+}
+
+#[test]
+fn test_asiento_que_no_se_puede_calcular() {
+    let activos = RegistroActivos::por_defecto();
+    let fecha = Fecha::new(1, 3, 2024);
+
+    // La compra vale mas de lo que entra en un monto
+    let compra = Transaccion::CompraCripto {
+        fecha: fecha.clone(), dni: "1".to_string(), cripto: Prefijo::BTC, cotizacion: MontoFiat::from_centavos(i64::MAX),
+        monto: MontoCripto::from_unidades(i64::MAX), comision: MontoFiat::CERO, saldos: Vec::new()
+    };
+    assert_eq!(Err(ErrorContable::MontoFueraDeRango { transaccion: 0 }), asiento(0, &compra, &[], &activos));
+
+    // Sin el retiro no se sabe a que billetera vuelve la cripto
+    let reintegro = Transaccion::ReintegroRetiro {
+        fecha, dni: "1".to_string(), retiro: 0, cripto: Prefijo::BTC, cotizacion: MontoFiat::CERO,
+        monto: MontoCripto::from_unidades(10), comision: MontoCripto::CERO, saldos: Vec::new()
+    };
+    assert_eq!(Err(ErrorContable::RetiroDesconocido { transaccion: 1, retiro: 0 }), asiento(1, &reintegro, &[compra], &activos));
+}
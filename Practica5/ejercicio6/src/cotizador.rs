@@ -0,0 +1,243 @@
+use std::{collections::HashMap, fmt, fs, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+use serde::{Deserialize, Serialize};
+
+use crate::dinero::{MontoFiat, MontoMalFormado};
+use crate::xyz::Prefijo;
+
+// Fuente de precios (en fiat por unidad de cripto) que usa XYZ para operar
+pub trait Cotizador: Send {
+    fn cotizacion(&self, cripto: &Prefijo) -> Option<MontoFiat>;
+}
+
+// Cotizaciones fijas, las que tenia originalmente Prefijo::cotizacion
+pub struct CotizadorFijo;
+
+impl Cotizador for CotizadorFijo {
+    fn cotizacion(&self, cripto: &Prefijo) -> Option<MontoFiat> {
+        let centavos = match cripto.as_str() {
+            "BTC" => 4500000,
+            "ETH" => 300000,
+            "LTC" => 20000,
+            "XRP" => 100,
+            "BCH" => 50000,
+            "ADA" => 123,
+            _ => return None
+        };
+        Some(MontoFiat::from_centavos(centavos))
+    }
+}
+
+// Cotizaciones en memoria que se pueden modificar mientras XYZ las usa.
+// Los clones comparten la misma tabla, asi un test puede mover el mercado.
+#[derive(Clone, Default)]
+pub struct CotizadorMemoria {
+    precios: Arc<Mutex<HashMap<Prefijo, MontoFiat>>>
+}
+
+impl CotizadorMemoria {
+    pub fn new() -> CotizadorMemoria {
+        CotizadorMemoria::default()
+    }
+
+    pub fn set_cotizacion(&self, cripto: Prefijo, valor: MontoFiat) {
+        self.precios.lock().unwrap().insert(cripto, valor);
+    }
+
+    pub fn quitar_cotizacion(&self, cripto: &Prefijo) {
+        self.precios.lock().unwrap().remove(cripto);
+    }
+}
+
+impl Cotizador for CotizadorMemoria {
+    fn cotizacion(&self, cripto: &Prefijo) -> Option<MontoFiat> {
+        self.precios.lock().unwrap().get(cripto).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegistroCotizacion {
+    pub cripto: Prefijo,
+    pub valor: MontoFiat,
+    pub timestamp: i64
+}
+
+// Formato de cada registro en el .json, el valor se escribe como numero decimal ("valor": 45000.5)
+#[derive(Deserialize, Serialize)]
+struct RegistroJson {
+    cripto: Prefijo,
+    valor: serde_json::Number,
+    timestamp: i64
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ErrorCotizaciones {
+    Lectura(String),
+    Formato(String)
+}
+
+impl fmt::Display for ErrorCotizaciones {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorCotizaciones::Lectura(e) => write!(f, "Error al leer la tabla de cotizaciones: {e}"),
+            ErrorCotizaciones::Formato(e) => write!(f, "Tabla de cotizaciones invalida: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorCotizaciones {}
+
+// Tabla de cotizaciones leida de un archivo .json o .csv (cripto,valor,timestamp).
+// Para cada cripto se usa el registro con el timestamp mas reciente.
+pub struct CotizadorArchivo {
+    ruta: PathBuf,
+    registros: Vec<RegistroCotizacion>
+}
+
+impl CotizadorArchivo {
+    pub fn cargar(ruta: impl AsRef<Path>) -> Result<CotizadorArchivo, ErrorCotizaciones> {
+        let ruta = ruta.as_ref().to_path_buf();
+        let registros = leer_registros(&ruta)?;
+        Ok(CotizadorArchivo { ruta, registros })
+    }
+
+    pub fn recargar(&mut self) -> Result<(), ErrorCotizaciones> {
+        self.registros = leer_registros(&self.ruta)?;
+        Ok(())
+    }
+
+    // Ultima cotizacion conocida en o antes del timestamp dado
+    pub fn cotizacion_en(&self, cripto: &Prefijo, timestamp: i64) -> Option<MontoFiat> {
+        self.registros.iter()
+            .filter(|r| &r.cripto == cripto && r.timestamp <= timestamp)
+            .max_by_key(|r| r.timestamp)
+            .map(|r| r.valor)
+    }
+}
+
+impl Cotizador for CotizadorArchivo {
+    fn cotizacion(&self, cripto: &Prefijo) -> Option<MontoFiat> {
+        self.cotizacion_en(cripto, i64::MAX)
+    }
+}
+
+fn leer_registros(ruta: &Path) -> Result<Vec<RegistroCotizacion>, ErrorCotizaciones> {
+    let contenido = fs::read_to_string(ruta).map_err(|e| ErrorCotizaciones::Lectura(e.to_string()))?;
+
+    let registros = match ruta.extension().and_then(|e| e.to_str()) {
+        Some("json") => parsear_json(&contenido)?,
+        Some("csv") => parsear_csv(&contenido)?,
+        _ => return Err(ErrorCotizaciones::Formato(format!("extension no soportada: {}", ruta.display())))
+    };
+
+    if let Some(r) = registros.iter().find(|r| !r.cripto.es_valido()) {
+        return Err(ErrorCotizaciones::Formato(format!("ticker invalido: {:?}", r.cripto.as_str())));
+    }
+    if let Some(r) = registros.iter().find(|r| !r.valor.es_positivo()) {
+        return Err(ErrorCotizaciones::Formato(format!("cotizacion invalida para {:?}: {}", r.cripto, r.valor)));
+    }
+    Ok(registros)
+}
+
+fn parsear_json(contenido: &str) -> Result<Vec<RegistroCotizacion>, ErrorCotizaciones> {
+    let registros: Vec<RegistroJson> = serde_json::from_str(contenido).map_err(|e| ErrorCotizaciones::Formato(e.to_string()))?;
+
+    registros.into_iter().map(|r| {
+        let valor = r.valor.to_string().parse().map_err(|e: MontoMalFormado| ErrorCotizaciones::Formato(e.to_string()))?;
+        Ok(RegistroCotizacion { cripto: r.cripto, valor, timestamp: r.timestamp })
+    }).collect()
+}
+
+fn parsear_csv(contenido: &str) -> Result<Vec<RegistroCotizacion>, ErrorCotizaciones> {
+    let mut registros = Vec::new();
+
+    for (i, linea) in contenido.lines().enumerate() {
+        let linea = linea.trim();
+        if linea.is_empty() || (i == 0 && linea.starts_with("cripto")) {
+            continue; // Encabezado o linea vacia
+        }
+
+        let error = || ErrorCotizaciones::Formato(format!("linea {}: {linea}", i + 1));
+        let campos: Vec<&str> = linea.split(',').map(|c| c.trim()).collect();
+        if campos.len() != 3 {
+            return Err(error());
+        }
+
+        let cripto: Prefijo = serde_json::from_value(serde_json::Value::String(campos[0].to_string())).map_err(|_| error())?;
+        let valor: MontoFiat = campos[1].parse().map_err(|_| error())?;
+        let timestamp: i64 = campos[2].parse().map_err(|_| error())?;
+        registros.push(RegistroCotizacion { cripto, valor, timestamp });
+    }
+
+    Ok(registros)
+}
+
+#[cfg(test)]
+fn fiat(monto: &str) -> MontoFiat {
+    monto.parse().unwrap()
+}
+
+#[test]
+fn test_cotizador_fijo() {
+    assert_eq!(Some(fiat("45000")), CotizadorFijo.cotizacion(&Prefijo::BTC));
+    assert_eq!(Some(fiat("1.23")), CotizadorFijo.cotizacion(&Prefijo::ADA));
+}
+
+#[test]
+fn test_cotizador_memoria() {
+    let cotizador = CotizadorMemoria::new();
+    let compartido = cotizador.clone();
+
+    assert_eq!(None, cotizador.cotizacion(&Prefijo::BTC));
+    compartido.set_cotizacion(Prefijo::BTC, fiat("60000"));
+    assert_eq!(Some(fiat("60000")), cotizador.cotizacion(&Prefijo::BTC));
+    compartido.quitar_cotizacion(&Prefijo::BTC);
+    assert_eq!(None, cotizador.cotizacion(&Prefijo::BTC));
+}
+
+#[test]
+fn test_cotizador_archivo_json() {
+    let ruta = std::env::temp_dir().join(format!("xyz_cotizaciones_{}.json", std::process::id()));
+    fs::write(&ruta, r#"[
+        {"cripto": "BTC", "valor": 40000.0, "timestamp": 100},
+        {"cripto": "BTC", "valor": 42000.25, "timestamp": 300},
+        {"cripto": "BTC", "valor": 41000, "timestamp": 200},
+        {"cripto": "ETH", "valor": 2500.0, "timestamp": 100}
+    ]"#).unwrap();
+
+    let mut cotizador = CotizadorArchivo::cargar(&ruta).unwrap();
+    assert_eq!(Some(fiat("42000.25")), cotizador.cotizacion(&Prefijo::BTC));
+    assert_eq!(Some(fiat("41000")), cotizador.cotizacion_en(&Prefijo::BTC, 250));
+    assert_eq!(None, cotizador.cotizacion_en(&Prefijo::BTC, 50));
+    assert_eq!(None, cotizador.cotizacion(&Prefijo::ADA));
+
+    fs::write(&ruta, r#"[{"cripto": "BTC", "valor": 50000.0, "timestamp": 400}]"#).unwrap();
+    cotizador.recargar().unwrap();
+    assert_eq!(Some(fiat("50000")), cotizador.cotizacion(&Prefijo::BTC));
+    assert_eq!(None, cotizador.cotizacion(&Prefijo::ETH));
+
+    fs::remove_file(&ruta).unwrap();
+}
+
+#[test]
+fn test_cotizador_archivo_csv() {
+    let ruta = std::env::temp_dir().join(format!("xyz_cotizaciones_{}.csv", std::process::id()));
+    fs::write(&ruta, "cripto,valor,timestamp\nETH,3100.5,10\nETH,3200,20\nXRP, 0.5, 10\n").unwrap();
+
+    let cotizador = CotizadorArchivo::cargar(&ruta).unwrap();
+    assert_eq!(Some(fiat("3200")), cotizador.cotizacion(&Prefijo::ETH));
+    assert_eq!(Some(fiat("3100.5")), cotizador.cotizacion_en(&Prefijo::ETH, 15));
+    assert_eq!(Some(fiat("0.5")), cotizador.cotizacion(&Prefijo::XRP));
+
+    // Cualquier ticker bien formado, aunque XYZ no lo liste
+    fs::write(&ruta, "cripto,valor,timestamp\nDOGE,1,10\n").unwrap();
+    assert_eq!(Some(fiat("1")), CotizadorArchivo::cargar(&ruta).unwrap().cotizacion(&Prefijo::new("DOGE")));
+    fs::write(&ruta, "cripto,valor,timestamp\ndoge,1,10\n").unwrap();
+    assert!(matches!(CotizadorArchivo::cargar(&ruta), Err(ErrorCotizaciones::Formato(_))));
+    fs::write(&ruta, "cripto,valor,timestamp\nBTC,-1,10\n").unwrap();
+    assert!(matches!(CotizadorArchivo::cargar(&ruta), Err(ErrorCotizaciones::Formato(_))));
+    fs::write(&ruta, "cripto,valor,timestamp\nBTC,1.001,10\n").unwrap();
+    assert!(matches!(CotizadorArchivo::cargar(&ruta), Err(ErrorCotizaciones::Formato(_))));
+
+    fs::remove_file(&ruta).unwrap();
+    assert!(matches!(CotizadorArchivo::cargar(&ruta), Err(ErrorCotizaciones::Lectura(_))));
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::Deposito;
+use crate::kyc::{CambioEstado, Identidad};
+use crate::ordenes::{IdOrden, Mercado, Orden};
+use crate::reglas::{EstadoRetenido, EventoMarcado, IdRetiroRetenido, Nivel, RetiroRetenido};
+use crate::xyz::{EstadoRetiro, IdTransaccion, Transaccion, Usuario};
+
+pub const ARCHIVO_DIARIO: &str = "eventos.jsonl";
+pub const ARCHIVO_SNAPSHOT: &str = "snapshot.json";
+
+// Cada cambio de estado de XYZ. Los saldos no se anotan: salen de las transacciones, de lo que
+// retienen las ordenes que quedan en el libro y de lo bloqueado en staking.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum Evento {
+    UsuarioCreado(Usuario),
+    UsuarioValidado(String), // Solo en diarios de antes del circuito KYC
+    Kyc { dni: String, identidad: Option<Identidad>, cambio: CambioEstado },
+    Transaccion(Box<Transaccion>),
+    OrdenColocada(Orden), // Como quedo despues de cruzar el libro; si no esta completa y es limite, queda esperando
+    OrdenActualizada(Orden), // Una orden del libro que se ejecuto en parte
+    OrdenQuitada(IdOrden), // Ejecutada por completo o cancelada
+    Nivel { dni: String, nivel: Nivel },
+    Marcado(EventoMarcado),
+    EstadoRetiro { id: IdTransaccion, estado: EstadoRetiro },
+    DireccionDeposito { dni: String, blockchain: String, direccion: String },
+    Deposito(Deposito), // Nuevo o con mas confirmaciones
+    RetiroRetenido(RetiroRetenido),
+    RetiroResuelto { id: IdRetiroRetenido, estado: EstadoRetenido, revisor: String, nota: String }
+}
+
+// Una linea de eventos.jsonl: los eventos de una operacion, que se aplican todos juntos
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Registro {
+    pub numero: u64,
+    pub eventos: Vec<Evento>
+}
+
+// Estado completo de XYZ hasta el registro `ultimo` inclusive. Los saldos son los disponibles,
+// sin lo retenido en ordenes ni lo bloqueado en staking, igual que en balances.json.
+#[derive(Debug, Default, Deserialize)]
+pub struct Snapshot {
+    pub ultimo: u64,
+    pub usuarios: Vec<Usuario>,
+    pub transacciones: Vec<Transaccion>,
+    pub mercado: Mercado,
+    #[serde(default)]
+    pub marcados: Vec<EventoMarcado>,
+    #[serde(default)]
+    pub retenidos: Vec<RetiroRetenido>,
+    #[serde(default)]
+    pub depositos: Vec<Deposito>
+}
+
+// Lo mismo que Snapshot pero prestado, para escribirlo sin clonar todo XYZ
+#[derive(Serialize)]
+pub struct SnapshotRef<'a> {
+    pub ultimo: u64,
+    pub usuarios: &'a [Usuario],
+    pub transacciones: &'a [Transaccion],
+    pub mercado: &'a Mercado,
+    pub marcados: &'a [EventoMarcado],
+    pub retenidos: &'a [RetiroRetenido],
+    pub depositos: &'a [Deposito]
+}
+
+// Si se corta la luz mientras se agrega un registro, la ultima linea queda a medias y sin el salto
+// de linea final: esa operacion no llego a confirmarse y se ignora. Cualquier otra linea rota es un error.
+pub fn leer_registros(contenido: &str) -> Result<Vec<Registro>, String> {
+    let lineas: Vec<&str> = contenido.split('\n').collect();
+    let mut registros: Vec<Registro> = Vec::new();
+
+    for (i, linea) in lineas.iter().enumerate() {
+        if linea.trim().is_empty() {
+            continue;
+        }
+        let registro: Registro = match serde_json::from_str(linea) {
+            Ok(registro) => registro,
+            Err(_) if i == lineas.len() - 1 => break,
+            Err(e) => return Err(format!("linea {}: {e}", i + 1))
+        };
+        if registros.last().is_some_and(|r| r.numero >= registro.numero) {
+            return Err(format!("linea {}: el registro {} esta fuera de orden", i + 1, registro.numero));
+        }
+        registros.push(registro);
+    }
+    Ok(registros)
+}
+
+// El diario arreglado para seguir agregandole lineas si la ultima quedo sin el salto de linea:
+// si esta a medias se descarta y si no, se lo agrega. None si no hace falta tocarlo.
+pub fn reparar(contenido: &str) -> Option<String> {
+    if contenido.is_empty() || contenido.ends_with('\n') {
+        return None;
+    }
+    let inicio = contenido.rfind('\n').map_or(0, |i| i + 1);
+    match serde_json::from_str::<Registro>(&contenido[inicio..]) {
+        Ok(_) => Some(format!("{contenido}\n")),
+        Err(_) => Some(contenido[..inicio].to_string())
+    }
+}
+
+#[test]
+fn test_leer_registros() {
+    let contenido = "{\"numero\":1,\"eventos\":[{\"UsuarioValidado\":\"1\"}]}\n\n{\"numero\":2,\"eventos\":[]}\n";
+    let registros = leer_registros(contenido).unwrap();
+    assert_eq!(vec![1, 2], registros.iter().map(|r| r.numero).collect::<Vec<_>>());
+    assert!(matches!(&registros[0].eventos[..], [Evento::UsuarioValidado(dni)] if dni == "1"));
+
+    // Ultima linea cortada a mitad de escritura
+    let cortado = format!("{contenido}{{\"numero\":3,\"even");
+    assert_eq!(2, leer_registros(&cortado).unwrap().len());
+
+    // Una linea rota en el medio no se puede saltear
+    let corrupto = format!("{{\"numero\":3,\"even\n{contenido}");
+    assert!(leer_registros(&corrupto).unwrap_err().starts_with("linea 1:"));
+
+    let desordenado = "{\"numero\":2,\"eventos\":[]}\n{\"numero\":1,\"eventos\":[]}\n";
+    assert!(leer_registros(desordenado).is_err());
+}
+
+#[test]
+fn test_reparar() {
+    let contenido = "{\"numero\":1,\"eventos\":[]}\n";
+    assert_eq!(None, reparar(contenido));
+    assert_eq!(None, reparar(""));
+    assert_eq!(Some(contenido.to_string()), reparar(&format!("{contenido}{{\"numero\":2,\"even")));
+    assert_eq!(Some(String::new()), reparar("{\"numero\":1,"));
+
+    // Se llego a escribir el registro pero no el salto de linea
+    let sin_salto = format!("{contenido}{{\"numero\":2,\"eventos\":[]}}");
+    assert_eq!(Some(format!("{sin_salto}\n")), reparar(&sin_salto));
+}
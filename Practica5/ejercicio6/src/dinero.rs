@@ -0,0 +1,516 @@
+use std::{cmp::Ordering, fmt, iter::Sum, ops::{Add, AddAssign, Neg, Sub, SubAssign}, str::FromStr};
+use serde::{Deserialize, Serialize};
+
+use crate::xyz::Prefijo;
+
+// Montos en punto fijo: nada de f64 en los saldos, asi las cuentas cierran exactas.
+
+// Monto en fiat guardado en centavos
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct MontoFiat(i64);
+
+// Monto de una cripto guardado en unidades minimas del Prefijo (satoshis para BTC, gwei para ETH...)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct MontoCripto(i64);
+
+// Monto con su moneda, para mostrar saldos y errores. Sin el registro de activos a mano,
+// la cripto se muestra en unidades minimas.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Monto {
+    Fiat(MontoFiat),
+    Cripto(Prefijo, MontoCripto)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MontoMalFormado(pub String);
+
+impl fmt::Display for MontoMalFormado {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Monto mal formado: {}", self.0)
+    }
+}
+
+impl std::error::Error for MontoMalFormado {}
+
+pub const DECIMALES_FIAT: u32 = 2;
+
+impl MontoFiat {
+    pub const CERO: MontoFiat = MontoFiat(0);
+
+    pub fn from_centavos(centavos: i64) -> MontoFiat {
+        MontoFiat(centavos)
+    }
+
+    pub fn centavos(&self) -> i64 {
+        self.0
+    }
+
+    pub fn es_positivo(&self) -> bool {
+        self.0 > 0
+    }
+}
+
+impl MontoCripto {
+    pub const CERO: MontoCripto = MontoCripto(0);
+
+    pub fn from_unidades(unidades: i64) -> MontoCripto {
+        MontoCripto(unidades)
+    }
+
+    pub fn unidades(&self) -> i64 {
+        self.0
+    }
+
+    pub fn es_positivo(&self) -> bool {
+        self.0 > 0
+    }
+
+    // Los decimales son los del activo en el RegistroActivos
+    pub fn parse(s: &str, decimales: u32) -> Result<MontoCripto, MontoMalFormado> {
+        parsear_decimal(s, decimales).map(MontoCripto)
+    }
+
+    pub fn formatear(&self, decimales: u32) -> String {
+        let s = formatear_decimal(self.0, decimales);
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            s
+        }
+    }
+
+    // Compara montos de criptos distintas en unidades enteras (1 BTC contra 1 XRP)
+    pub fn comparar(&self, decimales: u32, otro: &MontoCripto, otros_decimales: u32) -> Ordering {
+        let a = self.0 as i128 * potencia(otros_decimales);
+        let b = otro.0 as i128 * potencia(decimales);
+        a.cmp(&b)
+    }
+}
+
+// Un monto que ya paso los controles de entrada de XYZ: positivo y dentro de los limites de su moneda.
+// La precision la da el tipo: no hay fracciones de unidad minima, ni NaN ni infinitos.
+// Solo se arma con Limites::validar, asi una operacion no puede recibir un monto sin controlar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Valido<T>(T);
+
+// Minimo y maximo por operacion de una moneda; sin limite si no estan
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Limites<T> {
+    #[serde(default)]
+    pub minimo: Option<T>,
+    #[serde(default)]
+    pub maximo: Option<T>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FueraDeLimites<T> {
+    NoPositivo,
+    MenorAlMinimo(T),
+    MayorAlMaximo(T)
+}
+
+impl<T: Copy> Valido<T> {
+    pub fn valor(&self) -> T {
+        self.0
+    }
+}
+
+impl<T: Copy + Ord + Default> Limites<T> {
+    pub fn new(minimo: Option<T>, maximo: Option<T>) -> Limites<T> {
+        Limites { minimo, maximo }
+    }
+
+    pub fn validar(&self, monto: T) -> Result<Valido<T>, FueraDeLimites<T>> {
+        if monto <= T::default() {
+            return Err(FueraDeLimites::NoPositivo);
+        }
+        match (self.minimo, self.maximo) {
+            (Some(minimo), _) if monto < minimo => Err(FueraDeLimites::MenorAlMinimo(minimo)),
+            (_, Some(maximo)) if monto > maximo => Err(FueraDeLimites::MayorAlMaximo(maximo)),
+            _ => Ok(Valido(monto))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Redondeo {
+    Cercano,
+    Abajo,
+    Arriba
+}
+
+// Valor en fiat de un monto de cripto a una cotizacion (fiat por unidad entera).
+// Se redondea al centavo mas cercano; compra y venta usan la misma cuenta,
+// por lo que comprar y vender el mismo monto a la misma cotizacion deja el fiat igual.
+pub fn valor_en_fiat(monto: MontoCripto, decimales: u32, cotizacion: MontoFiat) -> Option<MontoFiat> {
+    valor_en_fiat_con(monto, decimales, cotizacion, Redondeo::Cercano)
+}
+
+pub fn valor_en_fiat_con(monto: MontoCripto, decimales: u32, cotizacion: MontoFiat, redondeo: Redondeo) -> Option<MontoFiat> {
+    let producto = monto.0 as i128 * cotizacion.0 as i128;
+    let centavos = dividir(producto, potencia(decimales), redondeo);
+    i64::try_from(centavos).ok().map(MontoFiat)
+}
+
+// Maximo monto de cripto que se puede pagar con el fiat dado, es decir, cuyo valor
+// redondeado al centavo mas cercano (como en valor_en_fiat) no supera al fiat
+pub fn cantidad_por_fiat(fiat: MontoFiat, decimales: u32, cotizacion: MontoFiat) -> Option<MontoCripto> {
+    if !cotizacion.es_positivo() {
+        return None;
+    }
+    let unidades = dividir((2 * fiat.0 as i128 + 1) * potencia(decimales) - 1, 2 * cotizacion.0 as i128, Redondeo::Abajo);
+    i64::try_from(unidades).ok().map(MontoCripto)
+}
+
+// Cuanto de la cripto de destino vale el monto de origen, a las dos cotizaciones.
+// Se redondea hacia abajo: lo que no llega a una unidad minima de destino no se entrega.
+pub fn convertir(monto: MontoCripto, decimales_origen: u32, cotizacion_origen: MontoFiat, decimales_destino: u32, cotizacion_destino: MontoFiat) -> Option<MontoCripto> {
+    if !cotizacion_destino.es_positivo() {
+        return None;
+    }
+    let numerador = (monto.0 as i128).checked_mul(cotizacion_origen.0 as i128)?.checked_mul(potencia(decimales_destino))?;
+    let denominador = (cotizacion_destino.0 as i128).checked_mul(potencia(decimales_origen))?;
+    i64::try_from(dividir(numerador, denominador, Redondeo::Abajo)).ok().map(MontoCripto)
+}
+
+// La parte del monto que corresponde a `parte` de `total`, como el costo de parte de un lote.
+// None si no entra en un monto, lo que solo pasa si la parte es mayor al total.
+pub fn proporcional(monto: MontoFiat, parte: MontoCripto, total: MontoCripto) -> Option<MontoFiat> {
+    if !total.es_positivo() {
+        return Some(MontoFiat::CERO);
+    }
+    i64::try_from(dividir(monto.0 as i128 * parte.0 as i128, total.0 as i128, Redondeo::Cercano)).ok().map(MontoFiat)
+}
+
+fn potencia(decimales: u32) -> i128 {
+    10i128.pow(decimales)
+}
+
+fn dividir(a: i128, b: i128, redondeo: Redondeo) -> i128 {
+    match redondeo {
+        Redondeo::Abajo => a.div_euclid(b),
+        Redondeo::Arriba => -(-a).div_euclid(b),
+        Redondeo::Cercano => {
+            let cociente = a / b;
+            let resto = a % b;
+            if resto.abs() * 2 >= b {
+                cociente + a.signum()
+            } else {
+                cociente
+            }
+        }
+    }
+}
+
+fn parsear_decimal(s: &str, decimales: u32) -> Result<i64, MontoMalFormado> {
+    let error = || MontoMalFormado(s.to_string());
+    let texto = s.trim();
+    let (negativo, texto) = match texto.strip_prefix('-') {
+        Some(resto) => (true, resto),
+        None => (false, texto)
+    };
+    let (entera, fraccion) = texto.split_once('.').unwrap_or((texto, ""));
+
+    if entera.is_empty() || !entera.bytes().all(|b| b.is_ascii_digit()) || !fraccion.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(error());
+    }
+    // Se aceptan ceros de mas ("45000.000") pero no precision que no se puede representar
+    let fraccion = fraccion.trim_end_matches('0');
+    if fraccion.len() > decimales as usize {
+        return Err(error());
+    }
+
+    let digitos = format!("{entera}{fraccion:0<width$}", width = decimales as usize);
+    let valor: i64 = digitos.parse().map_err(|_| error())?;
+    Ok(if negativo { -valor } else { valor })
+}
+
+fn formatear_decimal(valor: i64, decimales: u32) -> String {
+    let signo = if valor < 0 { "-" } else { "" };
+    let absoluto = valor.unsigned_abs();
+    if decimales == 0 {
+        return format!("{signo}{absoluto}");
+    }
+    let escala = 10u64.pow(decimales);
+    format!("{signo}{}.{:0width$}", absoluto / escala, absoluto % escala, width = decimales as usize)
+}
+
+impl FromStr for MontoFiat {
+    type Err = MontoMalFormado;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parsear_decimal(s, DECIMALES_FIAT).map(MontoFiat)
+    }
+}
+
+impl fmt::Display for MontoFiat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", formatear_decimal(self.0, DECIMALES_FIAT))
+    }
+}
+
+impl fmt::Display for Monto {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Monto::Fiat(monto) => write!(f, "{monto}"),
+            Monto::Cripto(cripto, monto) => write!(f, "{} unidades minimas de {cripto}", monto.0),
+        }
+    }
+}
+
+impl Monto {
+    pub fn es_negativo(&self) -> bool {
+        match self {
+            Monto::Fiat(monto) => monto.0 < 0,
+            Monto::Cripto(_, monto) => monto.0 < 0
+        }
+    }
+
+    // Cero en la misma moneda
+    pub fn cero(&self) -> Monto {
+        match self {
+            Monto::Fiat(_) => Monto::Fiat(MontoFiat::CERO),
+            Monto::Cripto(cripto, _) => Monto::Cripto(cripto.clone(), MontoCripto::CERO)
+        }
+    }
+
+    // Suma dos montos de la misma moneda; None si son de monedas distintas o la suma no entra en un monto
+    pub fn sumar(&self, otro: &Monto) -> Option<Monto> {
+        match (self, otro) {
+            (Monto::Fiat(a), Monto::Fiat(b)) => a.sumar(*b).map(Monto::Fiat),
+            (Monto::Cripto(x, a), Monto::Cripto(y, b)) if x == y => a.sumar(*b).map(|suma| Monto::Cripto(x.clone(), suma)),
+            _ => None
+        }
+    }
+}
+
+impl Neg for Monto {
+    type Output = Monto;
+    fn neg(self) -> Monto {
+        match self {
+            Monto::Fiat(monto) => Monto::Fiat(-monto),
+            Monto::Cripto(cripto, monto) => Monto::Cripto(cripto, -monto)
+        }
+    }
+}
+
+macro_rules! operaciones {
+    ($tipo:ident) => {
+        // Los operadores no controlan el rango; los saldos se mueven con sumar y restar
+        impl $tipo {
+            // Porcentaje del monto en puntos basicos (1 bps = 0.01%), no mas que el monto si bps <= 10000.
+            // None si no entra en un monto.
+            pub fn porcentaje(self, bps: u32, redondeo: Redondeo) -> Option<$tipo> {
+                i64::try_from(dividir(self.0 as i128 * bps as i128, 10000, redondeo)).ok().map($tipo)
+            }
+
+            // Como + y -, pero None si el resultado no entra en un monto
+            pub fn sumar(self, otro: $tipo) -> Option<$tipo> {
+                self.0.checked_add(otro.0).map($tipo)
+            }
+
+            pub fn restar(self, otro: $tipo) -> Option<$tipo> {
+                self.0.checked_sub(otro.0).map($tipo)
+            }
+        }
+
+        impl Add for $tipo {
+            type Output = $tipo;
+            fn add(self, otro: $tipo) -> $tipo {
+                $tipo(self.0 + otro.0)
+            }
+        }
+
+        impl Sub for $tipo {
+            type Output = $tipo;
+            fn sub(self, otro: $tipo) -> $tipo {
+                $tipo(self.0 - otro.0)
+            }
+        }
+
+        impl Neg for $tipo {
+            type Output = $tipo;
+            fn neg(self) -> $tipo {
+                $tipo(-self.0)
+            }
+        }
+
+        impl AddAssign for $tipo {
+            fn add_assign(&mut self, otro: $tipo) {
+                self.0 += otro.0;
+            }
+        }
+
+        impl SubAssign for $tipo {
+            fn sub_assign(&mut self, otro: $tipo) {
+                self.0 -= otro.0;
+            }
+        }
+
+        impl Sum for $tipo {
+            fn sum<I: Iterator<Item = $tipo>>(iter: I) -> $tipo {
+                iter.fold($tipo(0), |a, b| a + b)
+            }
+        }
+    };
+}
+
+operaciones!(MontoFiat);
+operaciones!(MontoCripto);
+
+#[test]
+fn test_monto_fiat_parse() {
+    assert_eq!(Ok(MontoFiat::from_centavos(4500000)), "45000".parse());
+    assert_eq!(Ok(MontoFiat::from_centavos(123)), "1.23".parse());
+    assert_eq!(Ok(MontoFiat::from_centavos(150)), "1.5".parse());
+    assert_eq!(Ok(MontoFiat::from_centavos(100)), "1.000".parse());
+    assert_eq!(Ok(MontoFiat::from_centavos(-50)), "-0.5".parse());
+    assert!("1.234".parse::<MontoFiat>().is_err());
+    assert!("abc".parse::<MontoFiat>().is_err());
+    assert!(".5".parse::<MontoFiat>().is_err());
+    assert!("".parse::<MontoFiat>().is_err());
+}
+
+#[test]
+fn test_monto_fiat_display() {
+    assert_eq!("32500.00", MontoFiat::from_centavos(3250000).to_string());
+    assert_eq!("0.05", MontoFiat::from_centavos(5).to_string());
+    assert_eq!("-1.50", MontoFiat::from_centavos(-150).to_string());
+}
+
+#[test]
+fn test_monto_cripto_parse() {
+    assert_eq!(Ok(MontoCripto::from_unidades(150_000_000)), MontoCripto::parse("1.5", 8));
+    assert_eq!(Ok(MontoCripto::from_unidades(1)), MontoCripto::parse("0.00000001", 8));
+    assert_eq!(Ok(MontoCripto::from_unidades(1)), MontoCripto::parse("0.000001", 6));
+    assert!(MontoCripto::parse("0.0000001", 6).is_err());
+    assert!(MontoCripto::parse("1e5", 8).is_err());
+}
+
+#[test]
+fn test_monto_cripto_formatear() {
+    assert_eq!("1.5", MontoCripto::from_unidades(150_000_000).formatear(8));
+    assert_eq!("2", MontoCripto::from_unidades(200_000_000).formatear(8));
+    assert_eq!("0.03", MontoCripto::from_unidades(3_000_000).formatear(8));
+    assert_eq!("-0.000001", MontoCripto::from_unidades(-1).formatear(6));
+    assert_eq!("50000000 unidades minimas de BTC", Monto::Cripto(Prefijo::BTC, MontoCripto::from_unidades(50_000_000)).to_string());
+}
+
+#[test]
+fn test_monto_cripto_comparar() {
+    let un_btc = MontoCripto::parse("1", 8).unwrap();
+    let un_xrp = MontoCripto::parse("1", 6).unwrap();
+    let dos_xrp = MontoCripto::parse("2", 6).unwrap();
+
+    assert_eq!(Ordering::Equal, un_btc.comparar(8, &un_xrp, 6));
+    assert_eq!(Ordering::Less, un_btc.comparar(8, &dos_xrp, 6));
+    assert_eq!(Ordering::Greater, dos_xrp.comparar(6, &un_btc, 8));
+}
+
+#[test]
+fn test_valor_en_fiat() {
+    let cotizacion: MontoFiat = "45000".parse().unwrap();
+    let monto = MontoCripto::parse("1.5", 8).unwrap();
+    assert_eq!(Some("67500".parse().unwrap()), valor_en_fiat(monto, 8, cotizacion));
+
+    // 0.333333 ADA a 1.23 = 0.40999959, se redondea a 0.41
+    let ada = MontoCripto::parse("0.333333", 6).unwrap();
+    assert_eq!(Some("0.41".parse().unwrap()), valor_en_fiat(ada, 6, "1.23".parse().unwrap()));
+
+    // 0.00001 BTC a 45000 = 0.45
+    let poco = MontoCripto::parse("0.00001", 8).unwrap();
+    assert_eq!(Some("0.45".parse().unwrap()), valor_en_fiat(poco, 8, cotizacion));
+    assert_eq!(Some("-0.45".parse().unwrap()), valor_en_fiat(-poco, 8, cotizacion));
+
+    assert_eq!(None, valor_en_fiat(MontoCripto::from_unidades(i64::MAX), 8, MontoFiat::from_centavos(i64::MAX)));
+}
+
+#[test]
+fn test_valor_en_fiat_con_redondeo() {
+    let ada = MontoCripto::parse("0.333333", 6).unwrap();
+    let cotizacion: MontoFiat = "1.23".parse().unwrap();
+
+    assert_eq!(Some("0.40".parse().unwrap()), valor_en_fiat_con(ada, 6, cotizacion, Redondeo::Abajo));
+    assert_eq!(Some("0.41".parse().unwrap()), valor_en_fiat_con(ada, 6, cotizacion, Redondeo::Arriba));
+    assert_eq!(Some("0.41".parse().unwrap()), valor_en_fiat_con(ada, 6, cotizacion, Redondeo::Cercano));
+
+    let exacto = MontoCripto::parse("2", 6).unwrap();
+    assert_eq!(Some("2.46".parse().unwrap()), valor_en_fiat_con(exacto, 6, cotizacion, Redondeo::Arriba));
+}
+
+#[test]
+fn test_cantidad_por_fiat() {
+    let cotizacion: MontoFiat = "45000".parse().unwrap();
+
+    // 1.00000011 BTC vale 45000.00495, que se redondea a 45000
+    assert_eq!(Some(MontoCripto::parse("1.00000011", 8).unwrap()), cantidad_por_fiat("45000".parse().unwrap(), 8, cotizacion));
+
+    // Con 1 centavo alcanza para 0.00000033 BTC (vale 0.01485, que se redondea a 0.01)
+    let cantidad = cantidad_por_fiat("0.01".parse().unwrap(), 8, cotizacion).unwrap();
+    assert_eq!(MontoCripto::from_unidades(33), cantidad);
+    assert_eq!(Some("0.01".parse().unwrap()), valor_en_fiat(cantidad, 8, cotizacion));
+    assert_eq!(Some("0.02".parse().unwrap()), valor_en_fiat(cantidad + MontoCripto::from_unidades(1), 8, cotizacion));
+
+    // Sin fiat solo alcanza para montos que valen menos de medio centavo
+    let migajas = cantidad_por_fiat(MontoFiat::CERO, 8, cotizacion).unwrap();
+    assert_eq!(Some(MontoFiat::CERO), valor_en_fiat(migajas, 8, cotizacion));
+
+    assert_eq!(None, cantidad_por_fiat("1".parse().unwrap(), 8, MontoFiat::CERO));
+}
+
+#[test]
+fn test_convertir() {
+    let btc = MontoCripto::parse("0.1", 8).unwrap();
+    let (cotizacion_btc, cotizacion_eth) = ("45000".parse().unwrap(), "3000".parse().unwrap());
+    assert_eq!(Some(MontoCripto::parse("1.5", 9).unwrap()), convertir(btc, 8, cotizacion_btc, 9, cotizacion_eth));
+
+    // 1 XRP a 1.00 son 0.00002222... BTC: lo que no llega a un satoshi se pierde
+    let xrp = MontoCripto::parse("1", 6).unwrap();
+    assert_eq!(Some(MontoCripto::from_unidades(2222)), convertir(xrp, 6, "1".parse().unwrap(), 8, cotizacion_btc));
+
+    assert_eq!(None, convertir(btc, 8, cotizacion_btc, 9, MontoFiat::CERO));
+    assert_eq!(None, convertir(MontoCripto::from_unidades(i64::MAX), 8, MontoFiat::from_centavos(i64::MAX), 9, MontoFiat::from_centavos(1)));
+}
+
+#[test]
+fn test_porcentaje_bps() {
+    let valor: MontoFiat = "100".parse().unwrap();
+    assert_eq!(Some(MontoFiat::from_centavos(25)), valor.porcentaje(25, Redondeo::Arriba));
+    assert_eq!(Some(valor), valor.porcentaje(10000, Redondeo::Arriba));
+    assert_eq!(Some(MontoFiat::CERO), valor.porcentaje(0, Redondeo::Arriba));
+
+    // 0.1% de 1.23 son 0.00123: se cobra al menos un centavo
+    let chico: MontoFiat = "1.23".parse().unwrap();
+    assert_eq!(Some(MontoFiat::from_centavos(1)), chico.porcentaje(10, Redondeo::Arriba));
+    assert_eq!(Some(MontoFiat::CERO), chico.porcentaje(10, Redondeo::Abajo));
+
+    assert_eq!(Some(MontoCripto::from_unidades(150)), MontoCripto::from_unidades(100000).porcentaje(15, Redondeo::Cercano));
+    assert_eq!(None, MontoFiat::from_centavos(i64::MAX).porcentaje(20000, Redondeo::Abajo));
+}
+
+#[test]
+fn test_monto_sumar_restar() {
+    let maximo = MontoFiat::from_centavos(i64::MAX);
+    assert_eq!(Some(MontoFiat::from_centavos(300)), MontoFiat::from_centavos(100).sumar(MontoFiat::from_centavos(200)));
+    assert_eq!(None, maximo.sumar(MontoFiat::from_centavos(1)));
+    assert_eq!(None, MontoCripto::from_unidades(i64::MIN).restar(MontoCripto::from_unidades(1)));
+    assert_eq!(None, Monto::Fiat(maximo).sumar(&Monto::Fiat(maximo)));
+    assert_eq!(Some(MontoFiat::from_centavos(i64::MAX / 2 + 1)), proporcional(maximo, MontoCripto::from_unidades(1), MontoCripto::from_unidades(2)));
+    assert_eq!(None, proporcional(maximo, MontoCripto::from_unidades(3), MontoCripto::from_unidades(2)));
+}
+
+#[test]
+fn test_limites_validar() {
+    let sin_limites: Limites<MontoFiat> = Limites::default();
+    assert_eq!(Ok(MontoFiat::from_centavos(1)), sin_limites.validar(MontoFiat::from_centavos(1)).map(|m| m.valor()));
+    assert_eq!(Err(FueraDeLimites::NoPositivo), sin_limites.validar(MontoFiat::CERO));
+    assert_eq!(Err(FueraDeLimites::NoPositivo), sin_limites.validar(MontoFiat::from_centavos(-100)));
+
+    let limites = Limites::new(Some(MontoCripto::from_unidades(10)), Some(MontoCripto::from_unidades(100)));
+    assert_eq!(Err(FueraDeLimites::MenorAlMinimo(MontoCripto::from_unidades(10))), limites.validar(MontoCripto::from_unidades(9)));
+    assert_eq!(Err(FueraDeLimites::MayorAlMaximo(MontoCripto::from_unidades(100))), limites.validar(MontoCripto::from_unidades(101)));
+    assert!(limites.validar(MontoCripto::from_unidades(10)).is_ok());
+    assert!(limites.validar(MontoCripto::from_unidades(100)).is_ok());
+}
@@ -0,0 +1,154 @@
+use std::collections::{HashMap, VecDeque};
+use serde::Serialize;
+
+use crate::dinero::{proporcional, Monto, MontoCripto, MontoFiat};
+use fecha::Fecha;
+use crate::xyz::{Prefijo, Transaccion};
+
+// Resumen de la cuenta de un usuario entre dos fechas, ambas incluidas
+#[derive(Debug, Clone, Serialize)]
+pub struct Extracto {
+    pub dni: String,
+    pub desde: Fecha,
+    pub hasta: Fecha,
+    pub transacciones: Vec<Transaccion>,
+    pub apertura: Saldos, // Antes de la primera transaccion del periodo
+    pub cierre: Saldos, // Despues de la ultima transaccion del periodo
+    pub cartera: Valuacion, // Lo que tiene hoy, a las cotizaciones de hoy
+    pub ganancia_realizada: MontoFiat, // Por las ventas del periodo
+    pub ganancia_no_realizada: MontoFiat // De lo que tiene hoy contra lo que le costo
+}
+
+// Saldos totales de un usuario: disponible mas retenido en ordenes y bloqueado en staking
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Saldos {
+    pub fiat: MontoFiat,
+    pub cripto: HashMap<Prefijo, MontoCripto>
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Valuacion {
+    pub fiat: MontoFiat,
+    pub cripto: HashMap<Prefijo, MontoFiat>,
+    pub total: MontoFiat
+}
+
+// Lo que se compro de una cripto en una misma transaccion y lo que costo, comisiones incluidas
+#[derive(Debug, Clone, PartialEq)]
+struct Lote {
+    cantidad: MontoCripto,
+    costo: MontoFiat
+}
+
+// Lotes de cada cripto en orden de llegada; las salidas se llevan primero los mas viejos (FIFO)
+#[derive(Debug, Default)]
+pub struct Lotes {
+    lotes: HashMap<Prefijo, VecDeque<Lote>>
+}
+
+impl Saldos {
+    pub fn aplicar(&mut self, movimiento: &Monto) {
+        match movimiento {
+            Monto::Fiat(monto) => self.fiat += *monto,
+            Monto::Cripto(cripto, monto) => *self.cripto.entry(cripto.clone()).or_default() += *monto
+        }
+    }
+
+    // Como aplicar, pero None si el saldo no entra en un monto, y entonces no cambia
+    pub fn sumar(&mut self, movimiento: &Monto) -> Option<()> {
+        match movimiento {
+            Monto::Fiat(monto) => self.fiat = self.fiat.sumar(*monto)?,
+            Monto::Cripto(cripto, monto) => {
+                let suma = self.cripto(cripto).sumar(*monto)?;
+                self.cripto.insert(cripto.clone(), suma);
+            }
+        }
+        Some(())
+    }
+
+    pub fn cripto(&self, cripto: &Prefijo) -> MontoCripto {
+        self.cripto.get(cripto).copied().unwrap_or_default()
+    }
+
+    // El saldo en la moneda del monto
+    pub fn en(&self, moneda: &Monto) -> Monto {
+        match moneda {
+            Monto::Fiat(_) => Monto::Fiat(self.fiat),
+            Monto::Cripto(cripto, _) => Monto::Cripto(cripto.clone(), self.cripto(cripto))
+        }
+    }
+}
+
+impl Lotes {
+    pub fn new() -> Lotes {
+        Lotes::default()
+    }
+
+    pub fn entrada(&mut self, cripto: &Prefijo, cantidad: MontoCripto, costo: MontoFiat) {
+        if cantidad.es_positivo() {
+            self.lotes.entry(cripto.clone()).or_default().push_back(Lote { cantidad, costo });
+        }
+    }
+
+    // Saca la cantidad de los lotes mas viejos y devuelve lo que costo.
+    // Lo que no tiene lote (no deberia pasar) sale con costo cero.
+    pub fn salida(&mut self, cripto: &Prefijo, mut cantidad: MontoCripto) -> MontoFiat {
+        let mut costo = MontoFiat::CERO;
+        let Some(lotes) = self.lotes.get_mut(cripto) else {
+            return costo;
+        };
+
+        while cantidad.es_positivo() {
+            let Some(lote) = lotes.front_mut() else {
+                break;
+            };
+            if lote.cantidad <= cantidad {
+                cantidad -= lote.cantidad;
+                costo += lote.costo;
+                lotes.pop_front();
+            } else {
+                let parte = proporcional(lote.costo, cantidad, lote.cantidad).unwrap_or(lote.costo); // La parte es menor al lote, siempre entra
+                lote.cantidad -= cantidad;
+                lote.costo -= parte;
+                costo += parte;
+                cantidad = MontoCripto::CERO;
+            }
+        }
+        costo
+    }
+
+    pub fn cantidad(&self, cripto: &Prefijo) -> MontoCripto {
+        self.lotes.get(cripto).map_or(MontoCripto::CERO, |l| l.iter().map(|lote| lote.cantidad).sum())
+    }
+
+    pub fn costo(&self, cripto: &Prefijo) -> MontoFiat {
+        self.lotes.get(cripto).map_or(MontoFiat::CERO, |l| l.iter().map(|lote| lote.costo).sum())
+    }
+}
+
+#[test]
+fn test_lotes_fifo() {
+    let mut lotes = Lotes::new();
+    lotes.entrada(&Prefijo::BTC, MontoCripto::from_unidades(100), MontoFiat::from_centavos(1000));
+    lotes.entrada(&Prefijo::BTC, MontoCripto::from_unidades(100), MontoFiat::from_centavos(3000));
+
+    // Se lleva el primer lote entero y la mitad del segundo
+    assert_eq!(MontoFiat::from_centavos(2500), lotes.salida(&Prefijo::BTC, MontoCripto::from_unidades(150)));
+    assert_eq!(MontoCripto::from_unidades(50), lotes.cantidad(&Prefijo::BTC));
+    assert_eq!(MontoFiat::from_centavos(1500), lotes.costo(&Prefijo::BTC));
+
+    assert_eq!(MontoFiat::from_centavos(1500), lotes.salida(&Prefijo::BTC, MontoCripto::from_unidades(80)));
+    assert_eq!(MontoFiat::CERO, lotes.salida(&Prefijo::ETH, MontoCripto::from_unidades(1)));
+}
+
+#[test]
+fn test_saldos_aplicar() {
+    let mut saldos = Saldos::default();
+    saldos.aplicar(&Monto::Fiat(MontoFiat::from_centavos(500)));
+    saldos.aplicar(&Monto::Cripto(Prefijo::ETH, MontoCripto::from_unidades(7)));
+    saldos.aplicar(&Monto::Fiat(MontoFiat::from_centavos(-200)));
+
+    assert_eq!(MontoFiat::from_centavos(300), saldos.fiat);
+    assert_eq!(MontoCripto::from_unidades(7), saldos.cripto(&Prefijo::ETH));
+    assert_eq!(MontoCripto::CERO, saldos.cripto(&Prefijo::BTC));
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+use fecha::Fecha;
+
+pub const EDAD_MINIMA: u32 = 18;
+
+// Estado de la verificacion de identidad (KYC) de un usuario de XYZ
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum EstadoKyc {
+    #[default]
+    Pendiente, // Todavia no mando sus datos
+    EnRevision,
+    Aprobado,
+    Rechazado, // Puede volver a mandar sus datos
+    Suspendido // Puede retirar lo que tiene, pero no operar
+}
+
+// Datos que manda el usuario para que lo verifiquen
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Identidad {
+    pub documento: String,
+    pub email: String,
+    pub fecha_nacimiento: Fecha
+}
+
+// Una entrada del historial de verificacion: quien cambio el estado, cuando y por que
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CambioEstado {
+    pub fecha: Fecha,
+    pub anterior: EstadoKyc,
+    pub nuevo: EstadoKyc,
+    pub revisor: Option<String>, // None si lo hizo el propio usuario
+    pub nota: String
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorIdentidad {
+    DocumentoInvalido,
+    EmailInvalido,
+    FechaInvalida,
+    MenorDeEdad,
+    DocumentoEnUso, // Ya esta verificado, o en revision, en otra cuenta
+    EmailDistinto // No es el email de la cuenta
+}
+
+impl fmt::Display for ErrorIdentidad {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorIdentidad::DocumentoInvalido => write!(f, "El DNI debe tener 7 u 8 digitos"),
+            ErrorIdentidad::EmailInvalido => write!(f, "El email no tiene un formato valido"),
+            ErrorIdentidad::FechaInvalida => write!(f, "La fecha de nacimiento no es valida"),
+            ErrorIdentidad::MenorDeEdad => write!(f, "El usuario debe tener al menos {EDAD_MINIMA} anios"),
+            ErrorIdentidad::DocumentoEnUso => write!(f, "El DNI ya esta verificado en otra cuenta"),
+            ErrorIdentidad::EmailDistinto => write!(f, "El email no es el de la cuenta"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorIdentidad {}
+
+impl EstadoKyc {
+    // Comprar, vender, ingresar y colocar ordenes
+    pub fn puede_operar(&self) -> bool {
+        *self == EstadoKyc::Aprobado
+    }
+
+    pub fn puede_retirar(&self) -> bool {
+        matches!(self, EstadoKyc::Aprobado | EstadoKyc::Suspendido)
+    }
+
+    // Los pasos validos del circuito de verificacion
+    pub fn puede_pasar_a(&self, nuevo: EstadoKyc) -> bool {
+        matches!((self, nuevo),
+            (EstadoKyc::Pendiente | EstadoKyc::Rechazado, EstadoKyc::EnRevision) |
+            (EstadoKyc::EnRevision, EstadoKyc::Aprobado | EstadoKyc::Rechazado) |
+            (EstadoKyc::Aprobado, EstadoKyc::Suspendido) |
+            (EstadoKyc::Suspendido, EstadoKyc::Aprobado))
+    }
+}
+
+impl Identidad {
+    // Controla el formato de los datos y la edad del usuario a la fecha dada
+    pub fn validar(&self, hoy: &Fecha) -> Result<(), ErrorIdentidad> {
+        if !es_dni_valido(&self.documento) {
+            return Err(ErrorIdentidad::DocumentoInvalido);
+        }
+        if !es_email_valido(&self.email) {
+            return Err(ErrorIdentidad::EmailInvalido);
+        }
+        if !self.fecha_nacimiento.es_fecha_valida() || !hoy.es_mayor(&self.fecha_nacimiento) {
+            return Err(ErrorIdentidad::FechaInvalida);
+        }
+        if self.fecha_nacimiento.anios_hasta(hoy) < EDAD_MINIMA {
+            return Err(ErrorIdentidad::MenorDeEdad);
+        }
+        Ok(())
+    }
+}
+
+// 7 u 8 digitos, con o sin los puntos de miles (12.345.678)
+pub fn es_dni_valido(dni: &str) -> bool {
+    let partes: Vec<&str> = dni.split('.').collect();
+    let con_puntos = partes.len() > 1 && partes[1..].iter().all(|p| p.len() == 3) && (1..=3).contains(&partes[0].len());
+    if partes.len() > 1 && !con_puntos {
+        return false;
+    }
+
+    let digitos: String = partes.concat();
+    (7..=8).contains(&digitos.len()) && digitos.chars().all(|c| c.is_ascii_digit())
+}
+
+// Sin los puntos de miles, para comparar dos documentos escritos distinto
+pub fn normalizar_dni(dni: &str) -> String {
+    dni.replace('.', "")
+}
+
+// Algo@dominio.tld, sin espacios
+pub fn es_email_valido(email: &str) -> bool {
+    let Some((usuario, dominio)) = email.split_once('@') else {
+        return false;
+    };
+    let Some((nombre, tld)) = dominio.rsplit_once('.') else {
+        return false;
+    };
+    !usuario.is_empty() && !nombre.is_empty() && tld.len() >= 2 && !dominio.contains('@') && !email.contains(char::is_whitespace)
+}
+
+#[test]
+fn test_es_dni_valido() {
+    assert!(es_dni_valido("12345678"));
+    assert!(es_dni_valido("1234567"));
+    assert!(es_dni_valido("12.345.678"));
+    assert!(es_dni_valido("1.234.567"));
+    assert!(!es_dni_valido("123456"));
+    assert!(!es_dni_valido("123456789"));
+    assert!(!es_dni_valido("12.34.5678"));
+    assert!(!es_dni_valido("1234567a"));
+    assert_eq!(normalizar_dni("12345678"), normalizar_dni("12.345.678"));
+}
+
+#[test]
+fn test_es_email_valido() {
+    assert!(es_email_valido("john.doe@gmail.com"));
+    assert!(!es_email_valido("john.doe"));
+    assert!(!es_email_valido("@gmail.com"));
+    assert!(!es_email_valido("john@gmail"));
+    assert!(!es_email_valido("john@@gmail.com"));
+    assert!(!es_email_valido("john doe@gmail.com"));
+}
+
+#[test]
+fn test_identidad_validar() {
+    let hoy = Fecha::new(10, 3, 2024);
+    let identidad = Identidad { documento: "30.123.456".to_string(), email: "a@b.com".to_string(), fecha_nacimiento: Fecha::new(10, 3, 2006) };
+    assert_eq!(Ok(()), identidad.validar(&hoy));
+
+    let menor = Identidad { fecha_nacimiento: Fecha::new(11, 3, 2006), ..identidad.clone() };
+    assert_eq!(Err(ErrorIdentidad::MenorDeEdad), menor.validar(&hoy));
+    let futura = Identidad { fecha_nacimiento: Fecha::new(1, 1, 2030), ..identidad.clone() };
+    assert_eq!(Err(ErrorIdentidad::FechaInvalida), futura.validar(&hoy));
+    let sin_email = Identidad { email: "b.com".to_string(), ..identidad };
+    assert_eq!(Err(ErrorIdentidad::EmailInvalido), sin_email.validar(&hoy));
+}
+
+#[test]
+fn test_estado_kyc_transiciones() {
+    assert!(EstadoKyc::Pendiente.puede_pasar_a(EstadoKyc::EnRevision));
+    assert!(EstadoKyc::Rechazado.puede_pasar_a(EstadoKyc::EnRevision));
+    assert!(EstadoKyc::EnRevision.puede_pasar_a(EstadoKyc::Aprobado));
+    assert!(!EstadoKyc::Pendiente.puede_pasar_a(EstadoKyc::Aprobado));
+    assert!(!EstadoKyc::Rechazado.puede_pasar_a(EstadoKyc::Suspendido));
+
+    assert!(EstadoKyc::Suspendido.puede_retirar());
+    assert!(!EstadoKyc::Suspendido.puede_operar());
+    assert!(!EstadoKyc::EnRevision.puede_retirar());
+}
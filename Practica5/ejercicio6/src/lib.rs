@@ -0,0 +1,18 @@
+pub mod xyz;
+pub mod activos;
+pub mod api;
+pub mod analitica;
+pub mod blockchain;
+pub mod cli;
+pub mod cotizador;
+pub mod diario;
+pub mod extracto;
+pub mod comisiones;
+pub mod contabilidad;
+pub mod dinero;
+pub mod kyc;
+pub mod ordenes;
+pub mod redes;
+pub mod reglas;
+pub mod servicio;
+pub mod staking;
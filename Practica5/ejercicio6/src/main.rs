@@ -1,5 +1,32 @@
-mod xyz;
-mod fecha;
+use std::{env, process};
+
+use ejercicio6::cli::{self, ErrorCli, Opciones};
+use ejercicio6::xyz::XYZ;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let json = args.iter().any(|a| a == "--json");
+    let (opciones, comando) = cli::parsear(&args).unwrap_or_else(|e| salir(json, &e, 2));
+    let Opciones { directorio, .. } = &opciones;
+
+    let mut xyz = XYZ::abrir(directorio).unwrap_or_else(|e| {
+        eprintln!("No se pudo cargar XYZ de {}: {e}", directorio.display());
+        process::exit(1)
+    });
+
+    match cli::ejecutar(&mut xyz, comando) {
+        Ok(salida) if opciones.json => println!("{}", serde_json::to_string_pretty(&salida.json).unwrap_or_default()),
+        Ok(salida) => print!("{}", salida.tabla),
+        Err(e) => salir(opciones.json, &e, 1)
+    }
+}
+
+// Con --json el error tambien sale como JSON, por stdout, para el script que lo llamo
+fn salir(json: bool, error: &ErrorCli, codigo: i32) -> ! {
+    match (json, error) {
+        (true, _) => println!("{}", error.json()),
+        (false, ErrorCli::Uso(e)) => eprintln!("{e}\n\n{}", cli::USO),
+        (false, e) => eprintln!("{e}")
+    }
+    process::exit(codigo)
 }
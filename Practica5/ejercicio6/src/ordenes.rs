@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::dinero::{Monto, MontoCripto, MontoFiat};
+use crate::xyz::{IdTransaccion, Prefijo};
+
+pub type IdOrden = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Lado {
+    Compra,
+    Venta
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum TipoOrden {
+    Limite(MontoFiat), // Precio maximo (compra) o minimo (venta) por unidad entera
+    Mercado
+}
+
+// Una orden de compra o venta de cripto contra fiat.
+// Solo las ordenes limite quedan en el libro; las de mercado se ejecutan contra lo que haya.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Orden {
+    pub id: IdOrden,
+    pub dni: String,
+    pub cripto: Prefijo,
+    pub lado: Lado,
+    pub precio: Option<MontoFiat>,
+    pub cantidad: MontoCripto,
+    pub pendiente: MontoCripto,
+    pub reserva_fiat: MontoFiat // Fiat retenido para pagar una compra limite
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultadoOrden {
+    pub id: IdOrden,
+    pub ejecuciones: Vec<IdTransaccion>, // Una transaccion EjecucionOrden por cada cruce
+    pub ejecutado: MontoCripto,
+    pub en_libro: bool // Si quedo un remanente esperando en el libro
+}
+
+// Libro de un par cripto/fiat con prioridad precio-tiempo:
+// primero el mejor precio y, a igual precio, la orden mas antigua.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LibroOrdenes {
+    compras: Vec<Orden>,
+    ventas: Vec<Orden>
+}
+
+// Todos los libros de XYZ, uno por Prefijo, junto con el proximo id de orden
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Mercado {
+    libros: HashMap<Prefijo, LibroOrdenes>,
+    proximo_id: IdOrden
+}
+
+impl Lado {
+    pub fn opuesto(&self) -> Lado {
+        match self {
+            Lado::Compra => Lado::Venta,
+            Lado::Venta => Lado::Compra
+        }
+    }
+}
+
+impl Orden {
+    // Si la orden acepta operar al precio de una orden del otro lado
+    pub fn cruza(&self, precio: MontoFiat) -> bool {
+        match (self.lado, self.precio) {
+            (_, None) => true,
+            (Lado::Compra, Some(limite)) => precio <= limite,
+            (Lado::Venta, Some(limite)) => precio >= limite
+        }
+    }
+
+    pub fn esta_completa(&self) -> bool {
+        self.pendiente == MontoCripto::CERO
+    }
+
+    // Lo que la orden le tiene retenido al usuario mientras espera en el libro
+    pub fn retenido(&self) -> Monto {
+        match self.lado {
+            Lado::Compra => Monto::Fiat(self.reserva_fiat),
+            Lado::Venta => Monto::Cripto(self.cripto.clone(), self.pendiente)
+        }
+    }
+}
+
+impl LibroOrdenes {
+    pub fn new() -> LibroOrdenes {
+        LibroOrdenes::default()
+    }
+
+    pub fn ordenes(&self, lado: Lado) -> &[Orden] {
+        match lado {
+            Lado::Compra => &self.compras,
+            Lado::Venta => &self.ventas
+        }
+    }
+
+    fn lado_mut(&mut self, lado: Lado) -> &mut Vec<Orden> {
+        match lado {
+            Lado::Compra => &mut self.compras,
+            Lado::Venta => &mut self.ventas
+        }
+    }
+
+    pub fn insertar(&mut self, orden: Orden) {
+        let lado = orden.lado;
+        let precio = orden.precio;
+        let ordenes = self.lado_mut(lado);
+
+        // Queda detras de todas las que tienen igual o mejor precio
+        let posicion = ordenes.iter().position(|o| match lado {
+            Lado::Compra => o.precio < precio,
+            Lado::Venta => o.precio > precio
+        }).unwrap_or(ordenes.len());
+        ordenes.insert(posicion, orden);
+    }
+
+    pub fn mejor(&self, lado: Lado) -> Option<&Orden> {
+        self.ordenes(lado).first()
+    }
+
+    pub fn mejor_mut(&mut self, lado: Lado) -> Option<&mut Orden> {
+        self.lado_mut(lado).first_mut()
+    }
+
+    pub fn quitar_mejor(&mut self, lado: Lado) -> Option<Orden> {
+        let ordenes = self.lado_mut(lado);
+        if ordenes.is_empty() {
+            None
+        } else {
+            Some(ordenes.remove(0))
+        }
+    }
+
+    pub fn quitar(&mut self, id: IdOrden) -> Option<Orden> {
+        for lado in [Lado::Compra, Lado::Venta] {
+            let ordenes = self.lado_mut(lado);
+            if let Some(i) = ordenes.iter().position(|o| o.id == id) {
+                return Some(ordenes.remove(i));
+            }
+        }
+        None
+    }
+
+    pub fn buscar(&self, id: IdOrden) -> Option<&Orden> {
+        self.compras.iter().chain(self.ventas.iter()).find(|o| o.id == id)
+    }
+
+    // Actualiza una orden sin cambiarle el lugar en la fila. false si no esta en el libro
+    pub fn reemplazar(&mut self, orden: Orden) -> bool {
+        match self.lado_mut(orden.lado).iter_mut().find(|o| o.id == orden.id) {
+            Some(o) => {
+                *o = orden;
+                true
+            },
+            None => false
+        }
+    }
+}
+
+impl Mercado {
+    pub fn new() -> Mercado {
+        Mercado::default()
+    }
+
+    pub fn nuevo_id(&mut self) -> IdOrden {
+        self.proximo_id += 1;
+        self.proximo_id
+    }
+
+    // Para reconstruir el mercado: que nuevo_id no repita un id ya usado
+    pub fn registrar_id(&mut self, id: IdOrden) {
+        self.proximo_id = self.proximo_id.max(id);
+    }
+
+    pub fn libro(&self, cripto: &Prefijo) -> Option<&LibroOrdenes> {
+        self.libros.get(cripto)
+    }
+
+    pub fn libro_mut(&mut self, cripto: &Prefijo) -> &mut LibroOrdenes {
+        self.libros.entry(cripto.clone()).or_default()
+    }
+
+    pub fn buscar(&self, id: IdOrden) -> Option<&Orden> {
+        self.libros.values().find_map(|l| l.buscar(id))
+    }
+
+    pub fn quitar(&mut self, id: IdOrden) -> Option<Orden> {
+        self.libros.values_mut().find_map(|l| l.quitar(id))
+    }
+
+    pub fn reemplazar(&mut self, orden: Orden) -> bool {
+        self.libros.get_mut(&orden.cripto).is_some_and(|l| l.reemplazar(orden))
+    }
+
+    pub fn ordenes(&self) -> impl Iterator<Item = &Orden> {
+        self.libros.values().flat_map(|l| l.compras.iter().chain(l.ventas.iter()))
+    }
+
+    pub fn ordenes_de<'a>(&'a self, dni: &'a str) -> impl Iterator<Item = &'a Orden> + 'a {
+        self.ordenes().filter(move |o| o.dni == dni)
+    }
+}
+
+#[cfg(test)]
+fn orden(id: IdOrden, lado: Lado, precio: &str) -> Orden {
+    Orden {
+        id,
+        dni: "1".to_string(),
+        cripto: Prefijo::BTC,
+        lado,
+        precio: Some(precio.parse().unwrap()),
+        cantidad: MontoCripto::from_unidades(1),
+        pendiente: MontoCripto::from_unidades(1),
+        reserva_fiat: MontoFiat::CERO
+    }
+}
+
+#[test]
+fn test_libro_prioridad_precio_tiempo() {
+    let mut libro = LibroOrdenes::new();
+    libro.insertar(orden(1, Lado::Compra, "100"));
+    libro.insertar(orden(2, Lado::Compra, "101"));
+    libro.insertar(orden(3, Lado::Compra, "100"));
+    libro.insertar(orden(4, Lado::Compra, "99"));
+
+    libro.insertar(orden(5, Lado::Venta, "105"));
+    libro.insertar(orden(6, Lado::Venta, "103"));
+    libro.insertar(orden(7, Lado::Venta, "105"));
+
+    let compras: Vec<IdOrden> = libro.ordenes(Lado::Compra).iter().map(|o| o.id).collect();
+    let ventas: Vec<IdOrden> = libro.ordenes(Lado::Venta).iter().map(|o| o.id).collect();
+    assert_eq!(vec![2, 1, 3, 4], compras);
+    assert_eq!(vec![6, 5, 7], ventas);
+
+    assert_eq!(Some(2), libro.mejor(Lado::Compra).map(|o| o.id));
+    assert_eq!(Some(6), libro.quitar_mejor(Lado::Venta).map(|o| o.id));
+    assert_eq!(Some(5), libro.mejor(Lado::Venta).map(|o| o.id));
+}
+
+#[test]
+fn test_libro_quitar() {
+    let mut libro = LibroOrdenes::new();
+    libro.insertar(orden(1, Lado::Compra, "100"));
+    libro.insertar(orden(2, Lado::Venta, "110"));
+
+    assert_eq!(Some(2), libro.quitar(2).map(|o| o.id));
+    assert_eq!(None, libro.quitar(2));
+    assert!(libro.ordenes(Lado::Venta).is_empty());
+    assert_eq!(None, libro.quitar_mejor(Lado::Venta));
+    assert!(libro.buscar(1).is_some());
+}
+
+#[test]
+fn test_libro_reemplazar_mantiene_prioridad() {
+    let mut libro = LibroOrdenes::new();
+    libro.insertar(orden(1, Lado::Venta, "100"));
+    libro.insertar(orden(2, Lado::Venta, "100"));
+
+    let parcial = Orden { pendiente: MontoCripto::from_unidades(0), ..orden(1, Lado::Venta, "100") };
+    assert!(libro.reemplazar(parcial.clone()));
+    assert_eq!(Some(&parcial), libro.mejor(Lado::Venta));
+    assert!(!libro.reemplazar(orden(3, Lado::Venta, "100")));
+}
+
+#[test]
+fn test_orden_cruza() {
+    let compra = orden(1, Lado::Compra, "100");
+    let venta = orden(2, Lado::Venta, "100");
+
+    assert!(compra.cruza("99".parse().unwrap()));
+    assert!(compra.cruza("100".parse().unwrap()));
+    assert!(!compra.cruza("101".parse().unwrap()));
+    assert!(venta.cruza("101".parse().unwrap()));
+    assert!(!venta.cruza("99".parse().unwrap()));
+
+    let mercado = Orden { precio: None, ..compra };
+    assert!(mercado.cruza("1000000".parse().unwrap()));
+}
+
+#[test]
+fn test_mercado_ids_y_busqueda() {
+    let mut mercado = Mercado::new();
+    let a = mercado.nuevo_id();
+    let b = mercado.nuevo_id();
+    assert_ne!(a, b);
+
+    mercado.libro_mut(&Prefijo::BTC).insertar(orden(a, Lado::Compra, "100"));
+    mercado.libro_mut(&Prefijo::ETH).insertar(Orden { cripto: Prefijo::ETH, dni: "2".to_string(), ..orden(b, Lado::Venta, "50") });
+
+    assert_eq!(1, mercado.ordenes_de("1").count());
+    assert_eq!(Some(Prefijo::ETH), mercado.buscar(b).map(|o| o.cripto.clone()));
+    assert!(mercado.quitar(a).is_some());
+    assert!(mercado.buscar(a).is_none());
+}
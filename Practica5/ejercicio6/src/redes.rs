@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::activos::RegistroActivos;
+use crate::blockchain::FormatoDireccion;
+use crate::dinero::MontoCripto;
+use persistencia::{ErrorPersistencia, Persistencia};
+use crate::xyz::Prefijo;
+
+pub const ARCHIVO_REDES: &str = "redes.json";
+
+// Una red por la que se mueve una cripto, con lo que cobra y pide XYZ para usarla
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Red {
+    pub nombre: String,
+    pub formato: FormatoDireccion, // Como son las direcciones; un token usa el de la red donde circula
+    #[serde(default)]
+    pub comision: MontoCripto, // Fija por retiro, en la cripto que se retira
+    #[serde(default)]
+    pub retiro_minimo: MontoCripto,
+    pub confirmaciones: u32, // Las que necesita un envio o un deposito para darse por confirmado
+    #[serde(default = "habilitada")]
+    pub habilitada: bool // Deshabilitada no se puede retirar ni pedir direccion de deposito
+}
+
+fn habilitada() -> bool {
+    true
+}
+
+// Las redes de cada cripto. Se puede armar en codigo o leer de un archivo de configuracion:
+// { "ETH": [{ "nombre": "Ethereum", "confirmaciones": 12 }, { "nombre": "Arbitrum", ... }], ... }
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct RegistroRedes {
+    redes: HashMap<Prefijo, Vec<Red>>
+}
+
+impl Red {
+    pub fn new(nombre: &str, formato: FormatoDireccion, confirmaciones: u32) -> Red {
+        Red { nombre: nombre.to_string(), formato, comision: MontoCripto::CERO, retiro_minimo: MontoCripto::CERO, confirmaciones, habilitada: true }
+    }
+}
+
+impl RegistroRedes {
+    pub fn new() -> RegistroRedes {
+        RegistroRedes { redes: HashMap::new() }
+    }
+
+    // Las redes de los activos con los que arranca XYZ, sin comisiones ni minimos
+    pub fn por_defecto() -> RegistroRedes {
+        RegistroActivos::por_defecto().redes()
+    }
+
+    // Si el archivo no existe se usan las redes de los activos
+    pub fn cargar(persistencia: &Persistencia, activos: &RegistroActivos) -> Result<RegistroRedes, ErrorPersistencia> {
+        Ok(persistencia.cargar(ARCHIVO_REDES)?.unwrap_or_else(|| activos.redes()))
+    }
+
+    // Reemplaza la red de la cripto con el mismo nombre, si ya estaba
+    pub fn agregar(&mut self, cripto: Prefijo, red: Red) {
+        let redes = self.redes.entry(cripto).or_default();
+        match redes.iter_mut().find(|r| r.nombre == red.nombre) {
+            Some(anterior) => *anterior = red,
+            None => redes.push(red)
+        }
+    }
+
+    // Devuelve false si la cripto no tiene esa red
+    pub fn habilitar(&mut self, cripto: &Prefijo, nombre: &str, habilitada: bool) -> bool {
+        match self.red_mut(cripto, nombre) {
+            Some(red) => {
+                red.habilitada = habilitada;
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn red(&self, cripto: &Prefijo, nombre: &str) -> Option<&Red> {
+        self.redes(cripto).iter().find(|r| r.nombre == nombre)
+    }
+
+    pub fn red_mut(&mut self, cripto: &Prefijo, nombre: &str) -> Option<&mut Red> {
+        self.redes.get_mut(cripto)?.iter_mut().find(|r| r.nombre == nombre)
+    }
+
+    pub fn redes(&self, cripto: &Prefijo) -> &[Red] {
+        self.redes.get(cripto).map_or(&[], |r| r.as_slice())
+    }
+}
+
+impl Default for RegistroRedes {
+    fn default() -> Self {
+        RegistroRedes::por_defecto()
+    }
+}
+
+#[test]
+fn test_registro_redes_por_defecto() {
+    let mut registro = RegistroRedes::por_defecto();
+    assert_eq!(vec!["Ethereum", "Arbitrum"], registro.redes(&Prefijo::ETH).iter().map(|r| r.nombre.as_str()).collect::<Vec<_>>());
+    assert_eq!(Some(6), registro.red(&Prefijo::BTC, "Bitcoin").map(|r| r.confirmaciones));
+    assert_eq!(None, registro.red(&Prefijo::BTC, "Ethereum"));
+
+    assert!(registro.habilitar(&Prefijo::ETH, "Arbitrum", false));
+    assert!(!registro.red(&Prefijo::ETH, "Arbitrum").unwrap().habilitada);
+    assert!(!registro.habilitar(&Prefijo::XRP, "Arbitrum", false));
+
+    registro.agregar(Prefijo::BTC, Red { comision: MontoCripto::from_unidades(500), ..Red::new("Bitcoin", FormatoDireccion::Bitcoin, 3) });
+    assert_eq!(1, registro.redes(&Prefijo::BTC).len());
+    assert_eq!(3, registro.red(&Prefijo::BTC, "Bitcoin").unwrap().confirmaciones);
+}
+
+#[test]
+fn test_registro_redes_cargar() {
+    let persistencia = Persistencia::memoria();
+    let activos = RegistroActivos::por_defecto();
+    assert_eq!(activos.redes(), RegistroRedes::cargar(&persistencia, &activos).unwrap());
+
+    let json = r#"{"ETH": [{"nombre": "Ethereum", "formato": "Ethereum", "comision": 100000, "confirmaciones": 12}, {"nombre": "Base", "formato": "Ethereum", "confirmaciones": 10, "habilitada": false}]}"#;
+    persistencia.escribir(ARCHIVO_REDES, json).unwrap();
+    let registro = RegistroRedes::cargar(&persistencia, &activos).unwrap();
+    assert_eq!(MontoCripto::from_unidades(100000), registro.red(&Prefijo::ETH, "Ethereum").unwrap().comision);
+    assert_eq!(MontoCripto::CERO, registro.red(&Prefijo::ETH, "Ethereum").unwrap().retiro_minimo);
+    assert!(!registro.red(&Prefijo::ETH, "Base").unwrap().habilitada);
+    assert!(registro.redes(&Prefijo::BTC).is_empty());
+
+    persistencia.escribir(ARCHIVO_REDES, "{\"ETH\": 1}").unwrap();
+    assert!(matches!(RegistroRedes::cargar(&persistencia, &activos), Err(ErrorPersistencia::Serializacion { .. })));
+}
@@ -0,0 +1,238 @@
+use std::{collections::{HashMap, HashSet}, fmt};
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::direccion_canonica;
+use crate::dinero::{Monto, MontoCripto, MontoFiat};
+use fecha::Fecha;
+use crate::xyz::{Medio, Prefijo};
+
+pub const SEGUNDOS_POR_HORA: i64 = 3600;
+
+// Nivel de cuenta del usuario; cada nivel puede tener sus propios topes de retiro
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Nivel {
+    #[default]
+    Basico,
+    Intermedio,
+    Premium
+}
+
+// Cuanto puede retirar un usuario, valuado en fiat, en el dia y en el mes calendario
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Topes {
+    pub diario: MontoFiat,
+    pub mensual: MontoFiat
+}
+
+// Reglas que se evaluan antes de cada retiro. Lo que no esta configurado no limita.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReglasRetiro {
+    topes: HashMap<Nivel, Topes>,
+    maximo_por_hora: Option<u32>,
+    umbral_aprobacion: Option<MontoFiat>, // Desde este valor el retiro espera aprobacion manual
+    direcciones_bloqueadas: HashSet<String> // En su forma canonica
+}
+
+// Lo que se sabe de un retiro y de los anteriores del usuario al momento de evaluarlo
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solicitud<'a> {
+    pub nivel: Nivel,
+    pub valor: MontoFiat,
+    pub destino: Option<&'a str>,
+    pub retirado_hoy: MontoFiat,
+    pub retirado_mes: MontoFiat,
+    pub retiros_ultima_hora: u32
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum MotivoRechazo {
+    DireccionBloqueada(String),
+    Velocidad { maximo_por_hora: u32 },
+    TopeDiario { tope: MontoFiat, retirado: MontoFiat, pedido: MontoFiat },
+    TopeMensual { tope: MontoFiat, retirado: MontoFiat, pedido: MontoFiat },
+    RequiereAprobacion { umbral: MontoFiat }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum PedidoRetiro {
+    Fiat { medio: Medio, monto: MontoFiat },
+    Cripto { cripto: Prefijo, blockchain: String, destino: String, monto: MontoCripto }
+}
+
+// Una operacion que las reglas frenaron, para que la revise compliance
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct EventoMarcado {
+    pub fecha: Fecha,
+    pub dni: String,
+    pub pedido: PedidoRetiro,
+    pub motivo: MotivoRechazo
+}
+
+pub type IdRetiroRetenido = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum EstadoRetenido {
+    Pendiente,
+    Aprobado,
+    Rechazado
+}
+
+// Retiro grande que espera que un revisor lo apruebe. Los fondos no se retienen:
+// al aprobarlo se vuelve a controlar el saldo.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RetiroRetenido {
+    pub fecha: Fecha,
+    pub dni: String,
+    pub pedido: PedidoRetiro,
+    pub estado: EstadoRetenido,
+    pub revisor: Option<String>,
+    pub nota: String
+}
+
+impl fmt::Display for MotivoRechazo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MotivoRechazo::DireccionBloqueada(direccion) => write!(f, "La direccion {direccion} esta bloqueada"),
+            MotivoRechazo::Velocidad { maximo_por_hora } => write!(f, "Se supero el maximo de {maximo_por_hora} retiros por hora"),
+            MotivoRechazo::TopeDiario { tope, retirado, pedido } => write!(f, "El retiro de {pedido} supera el tope diario de {tope} (ya retirado hoy: {retirado})"),
+            MotivoRechazo::TopeMensual { tope, retirado, pedido } => write!(f, "El retiro de {pedido} supera el tope mensual de {tope} (ya retirado este mes: {retirado})"),
+            MotivoRechazo::RequiereAprobacion { umbral } => write!(f, "Los retiros desde {umbral} requieren aprobacion manual"),
+        }
+    }
+}
+
+impl ReglasRetiro {
+    pub fn new() -> ReglasRetiro {
+        ReglasRetiro::default()
+    }
+
+    pub fn set_topes(&mut self, nivel: Nivel, topes: Topes) {
+        self.topes.insert(nivel, topes);
+    }
+
+    pub fn set_maximo_por_hora(&mut self, maximo: u32) {
+        self.maximo_por_hora = Some(maximo);
+    }
+
+    pub fn set_umbral_aprobacion(&mut self, umbral: MontoFiat) {
+        self.umbral_aprobacion = Some(umbral);
+    }
+
+    pub fn bloquear_direccion(&mut self, direccion: &str) {
+        self.direcciones_bloqueadas.insert(direccion_canonica(direccion));
+    }
+
+    pub fn desbloquear_direccion(&mut self, direccion: &str) {
+        self.direcciones_bloqueadas.remove(&direccion_canonica(direccion));
+    }
+
+    // Cambiarle las mayusculas a una direccion que no las distingue no la saca de la lista
+    pub fn esta_bloqueada(&self, direccion: &str) -> bool {
+        self.direcciones_bloqueadas.contains(&direccion_canonica(direccion))
+    }
+
+    // El primer motivo por el que el retiro no puede seguir. RequiereAprobacion solo
+    // aparece si el retiro cumple todo lo demas.
+    pub fn evaluar(&self, solicitud: &Solicitud) -> Result<(), MotivoRechazo> {
+        if let Some(destino) = solicitud.destino.filter(|d| self.esta_bloqueada(d)) {
+            return Err(MotivoRechazo::DireccionBloqueada(destino.to_string()));
+        }
+        if let Some(maximo) = self.maximo_por_hora.filter(|m| solicitud.retiros_ultima_hora >= *m) {
+            return Err(MotivoRechazo::Velocidad { maximo_por_hora: maximo });
+        }
+        // Un total que no entra en un monto supera cualquier tope
+        let supera = |retirado: MontoFiat, tope: MontoFiat| retirado.sumar(solicitud.valor).is_none_or(|total| total > tope);
+        if let Some(topes) = self.topes.get(&solicitud.nivel) {
+            if supera(solicitud.retirado_hoy, topes.diario) {
+                return Err(MotivoRechazo::TopeDiario { tope: topes.diario, retirado: solicitud.retirado_hoy, pedido: solicitud.valor });
+            }
+            if supera(solicitud.retirado_mes, topes.mensual) {
+                return Err(MotivoRechazo::TopeMensual { tope: topes.mensual, retirado: solicitud.retirado_mes, pedido: solicitud.valor });
+            }
+        }
+        if let Some(umbral) = self.umbral_aprobacion.filter(|u| solicitud.valor >= *u) {
+            return Err(MotivoRechazo::RequiereAprobacion { umbral });
+        }
+        Ok(())
+    }
+}
+
+impl RetiroRetenido {
+    pub fn resolver(&mut self, estado: EstadoRetenido, revisor: String, nota: String) {
+        self.estado = estado;
+        self.revisor = Some(revisor);
+        self.nota = nota;
+    }
+}
+
+impl PedidoRetiro {
+    pub fn monto(&self) -> Monto {
+        match self {
+            PedidoRetiro::Fiat { monto, .. } => Monto::Fiat(*monto),
+            PedidoRetiro::Cripto { cripto, monto, .. } => Monto::Cripto(cripto.clone(), *monto)
+        }
+    }
+
+    pub fn destino(&self) -> Option<&str> {
+        match self {
+            PedidoRetiro::Fiat { .. } => None,
+            PedidoRetiro::Cripto { destino, .. } => Some(destino)
+        }
+    }
+}
+
+#[cfg(test)]
+fn solicitud(valor: i64) -> Solicitud<'static> {
+    Solicitud {
+        nivel: Nivel::Basico,
+        valor: MontoFiat::from_centavos(valor),
+        destino: Some("destino"),
+        retirado_hoy: MontoFiat::CERO,
+        retirado_mes: MontoFiat::CERO,
+        retiros_ultima_hora: 0
+    }
+}
+
+#[test]
+fn test_reglas_sin_configurar_no_limitan() {
+    assert_eq!(Ok(()), ReglasRetiro::new().evaluar(&Solicitud { retiros_ultima_hora: 1000, ..solicitud(i64::MAX / 2) }));
+}
+
+#[test]
+fn test_reglas_topes_por_nivel() {
+    let mut reglas = ReglasRetiro::new();
+    reglas.set_topes(Nivel::Basico, Topes { diario: MontoFiat::from_centavos(1000), mensual: MontoFiat::from_centavos(5000) });
+
+    assert_eq!(Ok(()), reglas.evaluar(&Solicitud { retirado_hoy: MontoFiat::from_centavos(400), ..solicitud(600) }));
+    assert!(matches!(reglas.evaluar(&Solicitud { retirado_hoy: MontoFiat::from_centavos(401), ..solicitud(600) }), Err(MotivoRechazo::TopeDiario { .. })));
+    assert!(matches!(reglas.evaluar(&Solicitud { retirado_mes: MontoFiat::from_centavos(4500), ..solicitud(600) }), Err(MotivoRechazo::TopeMensual { .. })));
+    // Otro nivel sin topes configurados
+    assert_eq!(Ok(()), reglas.evaluar(&Solicitud { nivel: Nivel::Premium, ..solicitud(100000) }));
+
+    // Lo retirado mas lo pedido no entra en un monto: supera el tope aunque sea el maximo
+    let maximo = MontoFiat::from_centavos(i64::MAX);
+    reglas.set_topes(Nivel::Premium, Topes { diario: maximo, mensual: maximo });
+    assert!(matches!(reglas.evaluar(&Solicitud { nivel: Nivel::Premium, retirado_hoy: maximo, ..solicitud(1) }), Err(MotivoRechazo::TopeDiario { .. })));
+    assert!(matches!(reglas.evaluar(&Solicitud { nivel: Nivel::Premium, retirado_mes: maximo, ..solicitud(1) }), Err(MotivoRechazo::TopeMensual { .. })));
+}
+
+#[test]
+fn test_reglas_velocidad_bloqueo_y_aprobacion() {
+    let mut reglas = ReglasRetiro::new();
+    reglas.set_maximo_por_hora(3);
+    reglas.set_umbral_aprobacion(MontoFiat::from_centavos(10000));
+    reglas.bloquear_direccion("destino");
+
+    assert_eq!(Err(MotivoRechazo::DireccionBloqueada("destino".to_string())), reglas.evaluar(&solicitud(100)));
+    reglas.desbloquear_direccion("destino");
+    assert_eq!(Ok(()), reglas.evaluar(&Solicitud { retiros_ultima_hora: 2, ..solicitud(100) }));
+
+    reglas.bloquear_direccion("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+    let destino = Some("0x5AAEB6053F3E94C9B9A09F33669435E7EF1BEAED");
+    assert!(matches!(reglas.evaluar(&Solicitud { destino, ..solicitud(100) }), Err(MotivoRechazo::DireccionBloqueada(_))));
+    reglas.desbloquear_direccion("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed");
+    assert_eq!(Ok(()), reglas.evaluar(&Solicitud { destino, ..solicitud(100) }));
+
+    assert_eq!(Err(MotivoRechazo::Velocidad { maximo_por_hora: 3 }), reglas.evaluar(&Solicitud { retiros_ultima_hora: 3, ..solicitud(100) }));
+    assert_eq!(Err(MotivoRechazo::RequiereAprobacion { umbral: MontoFiat::from_centavos(10000) }), reglas.evaluar(&solicitud(10000)));
+}
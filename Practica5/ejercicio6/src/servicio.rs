@@ -0,0 +1,185 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::xyz::{XyzError, XYZ};
+
+// XYZ compartido entre hilos, para atender a varios clientes a la vez. Los clones son el mismo XYZ.
+// Un lock por usuario no alcanza: toda operacion toca estado comun (el libro de ordenes, el mayor,
+// la recaudacion, el diario). Asi que las operaciones se hacen de a una: cada una ve todo lo que
+// dejaron las anteriores y se escribe en el diario antes de soltar el lock, en el mismo orden en
+// que se aplico. Las de un mismo usuario quedan en el orden en que las pidio cada hilo.
+#[derive(Clone)]
+pub struct Servicio {
+    xyz: Arc<Mutex<XYZ>>
+}
+
+impl Servicio {
+    pub fn new(xyz: XYZ) -> Servicio {
+        Servicio { xyz: Arc::new(Mutex::new(xyz)) }
+    }
+
+    // Corre la operacion con XYZ para ella sola y devuelve lo que devuelva
+    pub fn con<R>(&self, operacion: impl FnOnce(&mut XYZ) -> R) -> Result<R, XyzError> {
+        Ok(operacion(&mut *self.bloquear()?))
+    }
+
+    // Un panico a mitad de una operacion pudo dejar saldos a medio aplicar; no se sigue operando
+    // sobre eso. Lo que ya estaba en el diario se recupera cargandolo de nuevo.
+    fn bloquear(&self) -> Result<MutexGuard<'_, XYZ>, XyzError> {
+        self.xyz.lock().map_err(|_| XyzError::Inconsistente)
+    }
+}
+
+#[cfg(test)]
+use std::{collections::HashMap, thread};
+#[cfg(test)]
+use crate::comisiones::TablaComisiones;
+#[cfg(test)]
+use crate::dinero::{Monto, MontoCripto, MontoFiat};
+#[cfg(test)]
+use fecha::Fecha;
+#[cfg(test)]
+use crate::kyc::Identidad;
+#[cfg(test)]
+use crate::ordenes::{Lado, TipoOrden};
+#[cfg(test)]
+use persistencia::Persistencia;
+#[cfg(test)]
+use crate::xyz::{Prefijo, Transaccion};
+
+#[cfg(test)]
+fn servicio_de_prueba(persistencia: &Persistencia, usuarios: usize, fiat: &str) -> Servicio {
+    let mut xyz = XYZ::new(persistencia.clone());
+    xyz.set_comisiones(TablaComisiones::new(10, 50));
+    for i in 0..usuarios {
+        let dni = i.to_string();
+        xyz.crear_usuario("John".to_string(), "Doe".to_string(), format!("{i}@mail.com"), dni.clone()).unwrap();
+        let identidad = Identidad { documento: (30_000_000 + i).to_string(), email: format!("{i}@mail.com"), fecha_nacimiento: Fecha::new(1, 1, 1990) };
+        xyz.enviar_identidad(dni.clone(), identidad).unwrap();
+        xyz.aprobar_usuario(dni.clone(), "revisor".to_string(), String::new()).unwrap();
+        xyz.ingresar_dinero(dni, fiat.parse().unwrap()).unwrap();
+    }
+    Servicio::new(xyz)
+}
+
+// Disponible mas lo retenido en ordenes y lo cobrado de comisiones, en cada moneda
+#[cfg(test)]
+fn en_circulacion(xyz: &XYZ) -> (MontoFiat, HashMap<Prefijo, MontoCripto>) {
+    let mut fiat = xyz.recaudacion().fiat;
+    let mut cripto = xyz.recaudacion().cripto.clone();
+    for u in xyz.usuarios() {
+        fiat += u.fiat();
+        for (c, m) in u.balances() {
+            *cripto.entry(c.clone()).or_default() += *m;
+        }
+        for orden in xyz.ordenes_abiertas(u.dni()) {
+            match orden.retenido() {
+                Monto::Fiat(m) => fiat += m,
+                Monto::Cripto(c, m) => *cripto.entry(c).or_default() += m
+            }
+        }
+    }
+    cripto.retain(|_, m| *m != MontoCripto::CERO);
+    (fiat, cripto)
+}
+
+#[test]
+fn test_servicio_compras_y_ventas_concurrentes() {
+    let persistencia = Persistencia::memoria();
+    let servicio = servicio_de_prueba(&persistencia, 8, "1000000");
+    let (fiat_inicial, _) = servicio.con(|xyz| en_circulacion(xyz)).unwrap();
+
+    // 16 hilos, dos por usuario, con compras y ventas contra XYZ y ordenes que se cruzan entre usuarios
+    thread::scope(|s| {
+        for hilo in 0..16 {
+            let servicio = servicio.clone();
+            s.spawn(move || {
+                let dni = (hilo % 8).to_string();
+                let cripto = if hilo % 3 == 0 { Prefijo::ETH } else { Prefijo::BTC };
+                for i in 0..250i64 {
+                    let monto = MontoCripto::from_unidades(1_000_000 + (hilo * 7919 + i * 104_729) % 5_000_000);
+                    let _ = match i % 5 {
+                        0 | 1 => servicio.con(|xyz| xyz.comprar_cripto(dni.clone(), cripto.clone(), monto).map(|_| ())),
+                        2 | 3 => servicio.con(|xyz| xyz.vender_cripto(dni.clone(), cripto.clone(), monto).map(|_| ())),
+                        _ => {
+                            let lado = if hilo % 2 == 0 { Lado::Compra } else { Lado::Venta };
+                            let precio = if cripto == Prefijo::BTC { "45000" } else { "3000" };
+                            servicio.con(|xyz| xyz.colocar_orden(dni.clone(), cripto.clone(), lado, TipoOrden::Limite(precio.parse().unwrap()), monto).map(|_| ()))
+                        }
+                    };
+                }
+            });
+        }
+    });
+
+    servicio.con(|xyz| {
+        // Contra XYZ el fiat sale y la cripto entra al precio anotado; entre usuarios solo cambian de manos
+        let (mut fiat_esperado, mut cripto_esperada) = (fiat_inicial, HashMap::new());
+        let mut operaciones = 0;
+        for t in xyz.transacciones() {
+            match t {
+                Transaccion::CompraCripto { cripto, cotizacion, monto, .. } => {
+                    fiat_esperado -= xyz.activos().valor_en_fiat(*monto, cripto, *cotizacion).unwrap();
+                    *cripto_esperada.entry(cripto.clone()).or_insert(MontoCripto::CERO) += *monto;
+                },
+                Transaccion::VentaCripto { cripto, cotizacion, monto, .. } => {
+                    fiat_esperado += xyz.activos().valor_en_fiat(*monto, cripto, *cotizacion).unwrap();
+                    *cripto_esperada.entry(cripto.clone()).or_insert(MontoCripto::CERO) -= *monto;
+                },
+                Transaccion::EjecucionOrden { .. } => (),
+                _ => continue
+            }
+            operaciones += 1;
+        }
+        cripto_esperada.retain(|_, m| *m != MontoCripto::CERO);
+        assert!(operaciones > 1000, "solo se hicieron {operaciones} operaciones");
+        assert!(xyz.transacciones().iter().any(|t| matches!(t, Transaccion::EjecucionOrden { .. })));
+        assert_eq!((fiat_esperado, cripto_esperada), en_circulacion(xyz));
+        assert!(xyz.balance_de_comprobacion().is_ok());
+
+        // Las operaciones de cada usuario quedaron una detras de otra: cada saldo anotado parte del anterior
+        let mut ultimo: HashMap<(String, String), Monto> = HashMap::new();
+        for t in xyz.transacciones() {
+            for cambio in t.saldos() {
+                let clave = (cambio.dni.clone(), cambio.antes.cero().to_string());
+                if let Some(despues) = ultimo.get(&clave) {
+                    assert_eq!(despues, &cambio.antes);
+                }
+                ultimo.insert(clave, cambio.despues.clone());
+            }
+        }
+
+        // El diario tiene lo mismo que la memoria
+        let cargada = XYZ::cargar_desde(persistencia.clone()).unwrap();
+        assert_eq!(xyz.transacciones().len(), cargada.transacciones().len());
+        assert_eq!(en_circulacion(xyz), en_circulacion(&cargada));
+        assert_eq!(xyz.balance_de_comprobacion(), cargada.balance_de_comprobacion());
+    }).unwrap();
+}
+
+#[test]
+fn test_servicio_no_gasta_dos_veces() {
+    let servicio = servicio_de_prueba(&Persistencia::memoria(), 1, "45000");
+    servicio.con(|xyz| xyz.comprar_cripto("0".to_string(), Prefijo::BTC, MontoCripto::from_unidades(50_000_000))).unwrap().unwrap();
+
+    // 0.5 BTC alcanzan para 10 ventas de 0.05, por mas que las pidan 32 hilos a la vez
+    let vendidas: usize = thread::scope(|s| {
+        let hilos: Vec<_> = (0..32).map(|_| {
+            let servicio = servicio.clone();
+            s.spawn(move || servicio.con(|xyz| xyz.vender_cripto("0".to_string(), Prefijo::BTC, MontoCripto::from_unidades(5_000_000))).unwrap().is_ok())
+        }).collect();
+        hilos.into_iter().map(|h| h.join().unwrap() as usize).sum()
+    });
+    assert_eq!(10, vendidas);
+    assert_eq!(Ok(Some(MontoCripto::CERO)), servicio.con(|xyz| xyz.usuario("0").unwrap().balances().get(&Prefijo::BTC).copied()));
+}
+
+#[test]
+fn test_servicio_no_opera_despues_de_un_panico() {
+    let servicio = servicio_de_prueba(&Persistencia::memoria(), 1, "1000");
+    let otro = servicio.clone();
+    assert!(thread::spawn(move || otro.con(|_| panic!("una operacion que queda a medias"))).join().is_err());
+
+    // Ni consultas ni cambios hasta volver a cargar XYZ
+    assert_eq!(Err(XyzError::Inconsistente), servicio.con(|xyz| xyz.usuarios().len()));
+    assert_eq!(Err(XyzError::Inconsistente), servicio.con(|xyz| xyz.ingresar_dinero("0".to_string(), MontoFiat::from_centavos(100))));
+}
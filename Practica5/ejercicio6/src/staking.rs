@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::dinero::MontoCripto;
+use fecha::Fecha;
+use crate::xyz::{Prefijo, Transaccion};
+
+pub const DIAS_POR_ANIO: u32 = 365;
+
+// Lo que paga una cripto bloqueada. El APR va en puntos basicos (500 = 5% anual)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct PlanStaking {
+    pub apr_bps: u32,
+    pub enfriamiento: u32 // Dias desde que se pide el desbloqueo hasta que vuelve al saldo disponible
+}
+
+// Las criptos que se pueden bloquear; la que no tiene plan no
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct TablaStaking {
+    planes: HashMap<Prefijo, PlanStaking>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum MovimientoStaking {
+    Bloqueo,
+    Desbloqueo { disponible: Fecha }, // Deja de generar recompensas pero sigue bloqueado hasta esa fecha
+    Liberacion // Vuelven al disponible los desbloqueos que ya cumplieron el enfriamiento
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Desbloqueo {
+    pub monto: MontoCripto,
+    pub disponible: Fecha
+}
+
+// Lo que tiene bloqueado un usuario en una cripto. Como lo retenido en ordenes,
+// sigue siendo suyo pero no figura en su saldo disponible.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Posicion {
+    pub dni: String,
+    pub cripto: Prefijo,
+    pub bloqueado: MontoCripto, // Lo que genera recompensas
+    pub desde: Fecha, // Ultimo dia ya recompensado
+    pub desbloqueos: Vec<Desbloqueo>
+}
+
+// Las posiciones de todos los usuarios. No se guardan: salen de repetir las transacciones de staking.
+#[derive(Debug, Clone, Default)]
+pub struct Staking {
+    posiciones: Vec<Posicion>
+}
+
+impl TablaStaking {
+    pub fn new() -> TablaStaking {
+        TablaStaking::default()
+    }
+
+    pub fn set_plan(&mut self, cripto: Prefijo, plan: PlanStaking) {
+        self.planes.insert(cripto, plan);
+    }
+
+    // Lo ya bloqueado deja de generar recompensas pero se puede desbloquear sin enfriamiento
+    pub fn quitar_plan(&mut self, cripto: &Prefijo) {
+        self.planes.remove(cripto);
+    }
+
+    pub fn plan(&self, cripto: &Prefijo) -> Option<PlanStaking> {
+        self.planes.get(cripto).copied()
+    }
+}
+
+impl Posicion {
+    // Bloqueado mas lo que esta en enfriamiento
+    pub fn total(&self) -> MontoCripto {
+        self.bloqueado + self.desbloqueos.iter().map(|d| d.monto).sum()
+    }
+
+    pub fn liberable(&self, hoy: &Fecha) -> MontoCripto {
+        self.desbloqueos.iter().filter(|d| hoy.es_mayor(&d.disponible)).map(|d| d.monto).sum()
+    }
+
+    pub fn dias_sin_recompensa(&self, hoy: &Fecha) -> u32 {
+        self.desde.dias_hasta(hoy)
+    }
+}
+
+impl Staking {
+    pub fn new() -> Staking {
+        Staking::default()
+    }
+
+    pub fn posicion(&self, dni: &str, cripto: &Prefijo) -> Option<&Posicion> {
+        self.posiciones.iter().find(|p| p.dni == dni && &p.cripto == cripto)
+    }
+
+    pub fn posiciones(&self) -> &[Posicion] {
+        &self.posiciones
+    }
+
+    pub fn de<'a>(&'a self, dni: &'a str) -> impl Iterator<Item = &'a Posicion> {
+        self.posiciones.iter().filter(move |p| p.dni == dni)
+    }
+
+    // Actualiza las posiciones con una transaccion del historial; las que no son de staking no cambian nada
+    pub fn aplicar(&mut self, t: &Transaccion) {
+        match t {
+            Transaccion::Staking { fecha, dni, cripto, monto, movimiento, .. } => {
+                let i = match self.posiciones.iter().position(|p| &p.dni == dni && &p.cripto == cripto) {
+                    Some(i) => i,
+                    None => {
+                        self.posiciones.push(Posicion { dni: dni.clone(), cripto: cripto.clone(), bloqueado: MontoCripto::CERO, desde: fecha.clone(), desbloqueos: Vec::new() });
+                        self.posiciones.len() - 1
+                    }
+                };
+                let p = &mut self.posiciones[i];
+                match movimiento {
+                    // Lo que se debia hasta aca ya se pago; desde hoy cuenta con el monto nuevo
+                    MovimientoStaking::Bloqueo => {
+                        p.bloqueado += *monto;
+                        p.desde = fecha.clone();
+                    },
+                    MovimientoStaking::Desbloqueo { disponible } => {
+                        p.bloqueado -= *monto;
+                        p.desde = fecha.clone();
+                        p.desbloqueos.push(Desbloqueo { monto: *monto, disponible: disponible.clone() });
+                    },
+                    MovimientoStaking::Liberacion => p.desbloqueos.retain(|d| !fecha.es_mayor(&d.disponible))
+                }
+            },
+            Transaccion::RecompensaStaking { dni, cripto, hasta, .. } => {
+                if let Some(p) = self.posiciones.iter_mut().find(|p| &p.dni == dni && &p.cripto == cripto) {
+                    p.desde = hasta.clone();
+                }
+            },
+            _ => ()
+        }
+    }
+}
+
+// Interes simple por dia sobre lo bloqueado, redondeado hacia abajo en la unidad minima de la cripto.
+// None si no entra en un monto.
+pub fn recompensa(bloqueado: MontoCripto, apr_bps: u32, dias: u32) -> Option<MontoCripto> {
+    let unidades = bloqueado.unidades() as i128 * apr_bps as i128 * dias as i128 / (10000 * DIAS_POR_ANIO as i128);
+    i64::try_from(unidades).ok().map(MontoCripto::from_unidades)
+}
+
+#[test]
+fn test_recompensa() {
+    // 10 BTC al 5% anual durante un año son 0.5 BTC
+    let diez = MontoCripto::from_unidades(1_000_000_000);
+    assert_eq!(Some(MontoCripto::from_unidades(50_000_000)), recompensa(diez, 500, DIAS_POR_ANIO));
+    assert_eq!(Some(MontoCripto::from_unidades(136_986)), recompensa(diez, 500, 1)); // 136986.3 hacia abajo
+    assert_eq!(Some(MontoCripto::CERO), recompensa(MontoCripto::from_unidades(100), 500, 30));
+    assert_eq!(Some(MontoCripto::CERO), recompensa(diez, 0, 30));
+    assert_eq!(None, recompensa(MontoCripto::from_unidades(i64::MAX), 10000, 2 * DIAS_POR_ANIO));
+}
+
+#[test]
+fn test_staking_aplicar() {
+    let mut staking = Staking::new();
+    let mov = |dia: u32, monto: i64, movimiento| Transaccion::Staking {
+        fecha: Fecha::new(dia, 3, 2024), dni: "1".to_string(), cripto: Prefijo::ETH, monto: MontoCripto::from_unidades(monto), movimiento, saldos: Vec::new()
+    };
+
+    staking.aplicar(&mov(1, 100, MovimientoStaking::Bloqueo));
+    staking.aplicar(&mov(5, 50, MovimientoStaking::Bloqueo));
+    staking.aplicar(&mov(10, 30, MovimientoStaking::Desbloqueo { disponible: Fecha::new(17, 3, 2024) }));
+    let p = staking.posicion("1", &Prefijo::ETH).unwrap();
+    assert_eq!((MontoCripto::from_unidades(120), MontoCripto::from_unidades(150)), (p.bloqueado, p.total()));
+    assert_eq!(Fecha::new(10, 3, 2024), p.desde);
+    assert_eq!(MontoCripto::CERO, p.liberable(&Fecha::new(16, 3, 2024)));
+    assert_eq!(MontoCripto::from_unidades(30), p.liberable(&Fecha::new(17, 3, 2024)));
+
+    staking.aplicar(&mov(17, 30, MovimientoStaking::Liberacion));
+    assert_eq!(MontoCripto::from_unidades(120), staking.posicion("1", &Prefijo::ETH).unwrap().total());
+    assert!(staking.posicion("1", &Prefijo::BTC).is_none());
+    assert_eq!(1, staking.de("1").count());
+}
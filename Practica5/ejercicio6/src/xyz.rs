@@ -1,358 +1,2209 @@
-
-use std::{borrow::Borrow, collections::HashMap, fs::File, io::Write};
-use rand::prelude::*;
-use chrono::prelude::*;
+use std::{collections::{HashMap, HashSet}, fmt, path::Path};
 use serde::{Deserialize, Serialize};
 
 
-use crate::fecha::Fecha;
-
-#[derive(Clone, PartialEq)]
-struct Blockchain {
-    nombre: String,
-    prefijo: String,
-}
-
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Deserialize, Serialize)]
-pub enum Prefijo {
-    BTC,
-    ETH,
-    LTC, // Litecoin
-    XRP, // Ripple
-    BCH, // Bitcoin Cash
-    ADA, // Cardano
-}
-
-#[derive(Clone)]
-pub struct CriptoMoneda {
-    valor: f64,
-    blockchains: Vec<Blockchain>
-}
+use crate::analitica::{self, Agregado, Criterio, Filtro, Operacion, Periodo, TipoOperacion};
+use crate::activos::{Activo, ErrorActivo, FuentePrecio, RegistroActivos, Ticker};
+use crate::blockchain::{generar_direccion, hash_hex, validar_direccion, Deposito, ErrorDireccion};
+use crate::comisiones::{Recaudacion, TablaComisiones};
+use crate::contabilidad::{self, Cuenta, ErrorContable, Mayor};
+use crate::cotizador::{Cotizador, CotizadorFijo};
+use crate::diario::{leer_registros, reparar, Evento, Registro, Snapshot, SnapshotRef, ARCHIVO_DIARIO, ARCHIVO_SNAPSHOT};
+use crate::extracto::{Extracto, Lotes, Saldos, Valuacion};
+use crate::dinero::{cantidad_por_fiat, convertir, valor_en_fiat, valor_en_fiat_con, FueraDeLimites, Limites, Monto, MontoCripto, MontoFiat, Redondeo, Valido};
+use fecha::Fecha;
+use crate::kyc::{normalizar_dni, CambioEstado, ErrorIdentidad, EstadoKyc, Identidad};
+use crate::ordenes::{IdOrden, Lado, LibroOrdenes, Mercado, Orden, ResultadoOrden, TipoOrden};
+use persistencia::{ErrorPersistencia, Persistencia};
+use crate::redes::RegistroRedes;
+use reloj::{Reloj, RelojSistema};
+use crate::staking::{recompensa, MovimientoStaking, Posicion, Staking, TablaStaking};
+use crate::reglas::{EstadoRetenido, EventoMarcado, IdRetiroRetenido, MotivoRechazo, Nivel, PedidoRetiro, ReglasRetiro, RetiroRetenido, Solicitud, SEGUNDOS_POR_HORA};
+
+// Antes un enum cerrado; ahora cualquier ticker listado en el RegistroActivos.
+// Se guarda igual ("BTC"), asi los archivos viejos se siguen leyendo.
+pub type Prefijo = Ticker;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-struct Usuario {
+pub struct Usuario {
     nombre: String,
     apellido: String,
     email: String,
     dni: String,
-    validado: bool,
-    fiat: f64,
-    balances: HashMap<Prefijo, f64>
+    #[serde(alias = "validado", deserialize_with = "estado_o_validado")]
+    estado: EstadoKyc,
+    #[serde(default)]
+    identidad: Option<Identidad>,
+    #[serde(default)]
+    historial_kyc: Vec<CambioEstado>,
+    #[serde(default)]
+    nivel: Nivel,
+    #[serde(default)]
+    direcciones_deposito: HashMap<String, String>, // Por blockchain
+    fiat: MontoFiat,
+    balances: HashMap<Prefijo, MontoCripto>
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum Medio {
     MercadoPago,
     TransferenciaBancaria
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+// Las comisiones se guardan aparte del monto: en una compra se paga monto + comision,
+// en una venta se recibe el valor - comision y en un retiro sale monto + comision.
+// Cada transaccion referencia a sus usuarios por dni y anota como quedan sus saldos.
 pub enum Transaccion {
-    IngresoDinero { fecha: Fecha, usuario: Usuario, monto: f64 },
-    CompraCripto { fecha: Fecha, usuario: Usuario, cripto: Prefijo, cotizacion: f64, monto: f64 }, // Cotizacion esta dentro de cripto
-    VentaCripto { fecha: Fecha, usuario: Usuario, cripto: Prefijo, cotizacion: f64, monto: f64 },
-    RetiroCripto { fecha: Fecha, usuario: Usuario, blockchain: String, hash: String, cripto: Prefijo, cotizacion: f64, monto: f64 }, // Cotizacion esta dentro de cripto
-    RecepcionCripto { fecha: Fecha, usuario: Usuario, blockchain: String, cripto: Prefijo, cotizacion: f64, monto: f64 }, // Cotizacion esta dentro de cripto
-    RetiroFiat { fecha: Fecha, usuario: Usuario, monto: f64, medio: Medio } // Cotizacion esta dentro de cripto
+    IngresoDinero {
+        fecha: Fecha,
+        #[serde(alias = "usuario", deserialize_with = "dni_o_usuario")] dni: String,
+        monto: MontoFiat,
+        #[serde(default)] saldos: Vec<CambioSaldo>
+    },
+    CompraCripto {
+        fecha: Fecha,
+        #[serde(alias = "usuario", deserialize_with = "dni_o_usuario")] dni: String,
+        cripto: Prefijo, cotizacion: MontoFiat, monto: MontoCripto, // Cotizacion esta dentro de cripto
+        #[serde(default)] comision: MontoFiat,
+        #[serde(default)] saldos: Vec<CambioSaldo>
+    },
+    VentaCripto {
+        fecha: Fecha,
+        #[serde(alias = "usuario", deserialize_with = "dni_o_usuario")] dni: String,
+        cripto: Prefijo, cotizacion: MontoFiat, monto: MontoCripto,
+        #[serde(default)] comision: MontoFiat,
+        #[serde(default)] saldos: Vec<CambioSaldo>
+    },
+    RetiroCripto {
+        fecha: Fecha,
+        #[serde(alias = "usuario", deserialize_with = "dni_o_usuario")] dni: String,
+        blockchain: String, hash: String, cripto: Prefijo, cotizacion: MontoFiat, monto: MontoCripto,
+        #[serde(default)] destino: String, // Vacio en retiros de antes de las reglas de retiro
+        #[serde(default)] comision: MontoCripto,
+        #[serde(default = "retiro_anterior")] estado: EstadoRetiro,
+        #[serde(default)] momento: i64, // Segundos desde 1970; 0 en retiros de antes del limite por hora
+        #[serde(default)] saldos: Vec<CambioSaldo>
+    },
+    // Devolucion de un retiro de cripto que fallo, comision incluida
+    ReintegroRetiro {
+        fecha: Fecha,
+        dni: String,
+        retiro: IdTransaccion,
+        cripto: Prefijo, cotizacion: MontoFiat, monto: MontoCripto, comision: MontoCripto,
+        #[serde(default)] saldos: Vec<CambioSaldo>
+    },
+    RecepcionCripto {
+        fecha: Fecha,
+        #[serde(alias = "usuario", deserialize_with = "dni_o_usuario")] dni: String,
+        blockchain: String, cripto: Prefijo, cotizacion: MontoFiat, monto: MontoCripto,
+        #[serde(default)] tx_id: String, // Vacio en recepciones de antes de las direcciones de deposito
+        #[serde(default)] saldos: Vec<CambioSaldo>
+    },
+    RetiroFiat {
+        fecha: Fecha,
+        #[serde(alias = "usuario", deserialize_with = "dni_o_usuario")] dni: String,
+        monto: MontoFiat, medio: Medio,
+        #[serde(default)] comision: MontoFiat,
+        #[serde(default)] momento: i64,
+        #[serde(default)] saldos: Vec<CambioSaldo>
+    },
+    // Cruce de dos ordenes del libro; el comprador paga su comision en cripto y el vendedor en fiat
+    EjecucionOrden {
+        fecha: Fecha,
+        #[serde(deserialize_with = "dni_o_usuario")] comprador: String,
+        #[serde(deserialize_with = "dni_o_usuario")] vendedor: String,
+        cripto: Prefijo, precio: MontoFiat, monto: MontoCripto,
+        #[serde(default)] comision_comprador: MontoCripto,
+        #[serde(default)] comision_vendedor: MontoFiat,
+        #[serde(default)] saldos: Vec<CambioSaldo>
+    },
+    // Una cripto por otra, a las dos cotizaciones. El spread se cobra en la cripto de destino
+    // y monto_destino es lo que recibe el usuario, ya sin el spread.
+    Intercambio {
+        fecha: Fecha,
+        dni: String,
+        origen: Prefijo, cotizacion_origen: MontoFiat, monto_origen: MontoCripto,
+        destino: Prefijo, cotizacion_destino: MontoFiat, monto_destino: MontoCripto,
+        spread: MontoCripto,
+        saldos: Vec<CambioSaldo>
+    },
+    // Cripto que entra o sale de staking. No cambia el saldo total: lo bloqueado sigue siendo
+    // del usuario, como lo retenido en ordenes, asi que no anota saldos.
+    Staking {
+        fecha: Fecha,
+        dni: String,
+        cripto: Prefijo, monto: MontoCripto,
+        movimiento: MovimientoStaking,
+        #[serde(default)] saldos: Vec<CambioSaldo>
+    },
+    // Lo que genero lo bloqueado entre las dos fechas al APR del plan; se paga al disponible
+    RecompensaStaking {
+        fecha: Fecha,
+        dni: String,
+        cripto: Prefijo, cotizacion: MontoFiat, monto: MontoCripto,
+        desde: Fecha, hasta: Fecha, apr_bps: u32,
+        saldos: Vec<CambioSaldo>
+    }
+}
+
+// Estado del envio de un retiro de cripto a la red
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum EstadoRetiro {
+    Pendiente, // Todavia no se mando a la red
+    Enviada { confirmaciones: u32 },
+    Confirmada,
+    Fallida(String) // Con el motivo; los fondos se reintegran
+}
+
+// Los retiros de antes del seguimiento ya salieron
+fn retiro_anterior() -> EstadoRetiro {
+    EstadoRetiro::Confirmada
+}
+
+// Saldo total (disponible mas retenido en ordenes) de un usuario en una moneda, antes y despues de una transaccion
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CambioSaldo {
+    pub dni: String,
+    pub antes: Monto,
+    pub despues: Monto
+}
+
+// Antes del circuito KYC un usuario solo estaba validado o no
+fn estado_o_validado<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<EstadoKyc, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum EstadoOValidado {
+        Estado(EstadoKyc),
+        Validado(bool)
+    }
+
+    Ok(match EstadoOValidado::deserialize(deserializer)? {
+        EstadoOValidado::Estado(estado) => estado,
+        EstadoOValidado::Validado(true) => EstadoKyc::Aprobado,
+        EstadoOValidado::Validado(false) => EstadoKyc::Pendiente
+    })
+}
+
+// Los archivos viejos guardaban una copia entera del usuario en cada transaccion; de ahi solo se usa el dni
+fn dni_o_usuario<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum DniOUsuario {
+        Dni(String),
+        Usuario { dni: String }
+    }
+
+    match DniOUsuario::deserialize(deserializer)? {
+        DniOUsuario::Dni(dni) | DniOUsuario::Usuario { dni } => Ok(dni)
+    }
 }
 
+// Posicion de la transaccion dentro del historial de XYZ
+pub type IdTransaccion = usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum XyzError {
+    UsuarioNoEncontrado,
+    UsuarioExistente,
+    UsuarioNoValidado,
+    UsuarioSuspendido,
+    Identidad(ErrorIdentidad),
+    TransicionKyc { desde: EstadoKyc, hasta: EstadoKyc },
+    SaldoInsuficiente { disponible: Monto, requerido: Monto },
+    BlockchainInvalida,
+    RedDeshabilitada,
+    RetiroMenorAlMinimo { minimo: MontoCripto },
+    DireccionInvalida(ErrorDireccion),
+    CriptoNoPoseida,
+    CotizacionNoDisponible,
+    ActivoDesconocido,
+    ActivoInvalido(ErrorActivo),
+    MontoFueraDeRango,
+    OrdenInvalida,
+    OrdenNoEncontrada,
+    RetiroRechazado(MotivoRechazo),
+    RetiroRetenido(IdRetiroRetenido), // Espera la aprobacion de un revisor
+    RetiroNoEncontrado,
+    RetiroCriptoNoEncontrado,
+    MontoNoPositivo,
+    MontoMenorAlMinimo { minimo: Monto }, // Limites por operacion del activo o del fiat
+    MontoMayorAlMaximo { maximo: Monto },
+    IntercambioMismaCripto,
+    StakingNoDisponible, // La cripto no tiene plan de staking
+    DireccionDesconocida, // No es la direccion de deposito de ningun usuario en esa red
+    DepositoDuplicado,
+    DepositoNoEncontrado,
+    TransicionRetiro { desde: EstadoRetiro }, // El retiro no puede pasar al estado pedido desde el que tiene
+    Persistencia(ErrorPersistencia), // Si no se pudo escribir el diario la operacion no se hizo
+    Inconsistente, // Una operacion fallida no se pudo deshacer; hay que volver a cargar XYZ
+    Contable(ErrorContable), // La transaccion no se pudo asentar en el mayor
+}
+
+impl fmt::Display for XyzError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            XyzError::UsuarioNoEncontrado => write!(f, "No se encontro el usuario"),
+            XyzError::UsuarioExistente => write!(f, "Ya existe un usuario con ese dni"),
+            XyzError::UsuarioNoValidado => write!(f, "El usuario no esta validado"),
+            XyzError::UsuarioSuspendido => write!(f, "El usuario esta suspendido: solo puede retirar sus fondos"),
+            XyzError::Identidad(e) => write!(f, "Datos de identidad invalidos: {e}"),
+            XyzError::TransicionKyc { desde, hasta } => write!(f, "El usuario no puede pasar de {desde:?} a {hasta:?}"),
+            XyzError::SaldoInsuficiente { disponible, requerido } => write!(f, "Saldo insuficiente: disponible {disponible}, requerido {requerido}"),
+            XyzError::BlockchainInvalida => write!(f, "La blockchain no es valida para la cripto"),
+            XyzError::RedDeshabilitada => write!(f, "La red esta deshabilitada"),
+            XyzError::RetiroMenorAlMinimo { minimo } => write!(f, "El retiro es menor al minimo de la red ({} unidades minimas)", minimo.unidades()),
+            XyzError::DireccionInvalida(e) => write!(f, "Direccion de destino invalida: {e}"),
+            XyzError::CriptoNoPoseida => write!(f, "El usuario no posee la cripto especificada"),
+            XyzError::CotizacionNoDisponible => write!(f, "No hay cotizacion disponible para la cripto"),
+            XyzError::ActivoDesconocido => write!(f, "La cripto no esta listada en XYZ"),
+            XyzError::ActivoInvalido(e) => write!(f, "No se puede listar el activo: {e}"),
+            XyzError::MontoFueraDeRango => write!(f, "El monto excede el rango representable"),
+            XyzError::OrdenInvalida => write!(f, "La orden debe tener cantidad y precio positivos"),
+            XyzError::OrdenNoEncontrada => write!(f, "No se encontro la orden"),
+            XyzError::RetiroRechazado(motivo) => write!(f, "Retiro rechazado: {motivo}"),
+            XyzError::RetiroRetenido(id) => write!(f, "El retiro quedo retenido ({id}) hasta que lo apruebe un revisor"),
+            XyzError::RetiroNoEncontrado => write!(f, "No hay un retiro retenido pendiente con ese id"),
+            XyzError::RetiroCriptoNoEncontrado => write!(f, "La transaccion no es un retiro de cripto"),
+            XyzError::MontoNoPositivo => write!(f, "El monto debe ser positivo"),
+            XyzError::MontoMenorAlMinimo { minimo } => write!(f, "El monto es menor al minimo por operacion ({minimo})"),
+            XyzError::MontoMayorAlMaximo { maximo } => write!(f, "El monto es mayor al maximo por operacion ({maximo})"),
+            XyzError::IntercambioMismaCripto => write!(f, "No se puede intercambiar una cripto por si misma"),
+            XyzError::StakingNoDisponible => write!(f, "La cripto no tiene un plan de staking"),
+            XyzError::DireccionDesconocida => write!(f, "La direccion no es de deposito de ningun usuario en esa red"),
+            XyzError::DepositoDuplicado => write!(f, "Ya se recibio un deposito con ese id de transaccion"),
+            XyzError::DepositoNoEncontrado => write!(f, "No hay un deposito con ese id de transaccion"),
+            XyzError::TransicionRetiro { desde } => write!(f, "El retiro esta {desde:?} y no puede pasar a ese estado"),
+            XyzError::Persistencia(e) => write!(f, "No se pudieron guardar los cambios: {e}"),
+            XyzError::Inconsistente => write!(f, "El estado quedo a medias por una operacion fallida; no se aceptan cambios hasta volver a cargar XYZ"),
+            XyzError::Contable(e) => write!(f, "No se pudo asentar la transaccion: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for XyzError {}
+
+impl From<ErrorContable> for XyzError {
+    fn from(error: ErrorContable) -> Self {
+        XyzError::Contable(error)
+    }
+}
+
+impl From<ErrorPersistencia> for XyzError {
+    fn from(error: ErrorPersistencia) -> Self {
+        XyzError::Persistencia(error)
+    }
+}
+
+// Problemas al levantar XYZ desde el snapshot y el diario de eventos
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorCarga {
+    Lectura { archivo: String, error: String },
+    Formato { archivo: String, error: String },
+    UsuarioDuplicado(String),
+    UsuarioDesconocido { transaccion: IdTransaccion, dni: String },
+    MontoInvalido(IdTransaccion),
+    SaldoNegativo { transaccion: IdTransaccion, dni: String, saldo: Monto },
+    OrdenDeUsuarioDesconocido { orden: IdOrden, dni: String },
+    SaldoNoCoincide { dni: String, guardado: Monto, calculado: Monto },
+    RegistroInvalido { registro: u64, error: String }, // Un evento del diario que no se puede aplicar
+    SaldoAnotadoNoCoincide { transaccion: IdTransaccion, dni: String, anotado: Monto, calculado: Monto },
+    Contable(ErrorContable), // El mayor no cierra o no coincide con los saldos de los usuarios
+    Activos(ErrorActivo) // Sin los activos no se sabe con cuantos decimales estan guardados los montos
+}
+
+impl fmt::Display for ErrorCarga {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorCarga::Lectura { archivo, error } => write!(f, "No se pudo leer {archivo}: {error}"),
+            ErrorCarga::Formato { archivo, error } => write!(f, "{archivo} esta corrupto: {error}"),
+            ErrorCarga::UsuarioDuplicado(dni) => write!(f, "El usuario {dni} aparece mas de una vez en los balances"),
+            ErrorCarga::UsuarioDesconocido { transaccion, dni } => write!(f, "La transaccion {transaccion} es del usuario {dni}, que no esta en los balances"),
+            ErrorCarga::MontoInvalido(transaccion) => write!(f, "La transaccion {transaccion} tiene montos fuera de rango"),
+            ErrorCarga::SaldoNegativo { transaccion, dni, saldo } => write!(f, "Al repetir la transaccion {transaccion} el usuario {dni} queda con saldo negativo ({saldo})"),
+            ErrorCarga::OrdenDeUsuarioDesconocido { orden, dni } => write!(f, "La orden {orden} es del usuario {dni}, que no esta en los balances"),
+            ErrorCarga::SaldoNoCoincide { dni, guardado, calculado } => write!(f, "El saldo guardado del usuario {dni} ({guardado}) no coincide con el de las transacciones ({calculado})"),
+            ErrorCarga::RegistroInvalido { registro, error } => write!(f, "El registro {registro} del diario no se puede aplicar: {error}"),
+            ErrorCarga::SaldoAnotadoNoCoincide { transaccion, dni, anotado, calculado } => write!(f, "La transaccion {transaccion} anota para el usuario {dni} un saldo de {anotado}, pero repitiendo el historial da {calculado}"),
+            ErrorCarga::Contable(error) => write!(f, "{error}"),
+            ErrorCarga::Activos(error) => write!(f, "No se pudieron cargar los activos: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorCarga {}
+
+impl From<ErrorActivo> for ErrorCarga {
+    fn from(error: ErrorActivo) -> Self {
+        ErrorCarga::Activos(error)
+    }
+}
+
+impl From<ErrorContable> for ErrorCarga {
+    fn from(error: ErrorContable) -> Self {
+        ErrorCarga::Contable(error)
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct XYZ {
     usuarios: Vec<Usuario>,
-    transacciones: Vec<Transaccion>
+    transacciones: Vec<Transaccion>,
+    cotizador: Box<dyn Cotizador>,
+    reloj: Box<dyn Reloj>, // De donde salen las fechas de las transacciones y la hora de los retiros
+    mercado: Mercado,
+    comisiones: TablaComisiones,
+    limites_fiat: Limites<MontoFiat>, // Los de cada cripto estan en su activo
+    activos: RegistroActivos,
+    redes: RegistroRedes, // Arranca con las redes de los activos, pero se configura aparte
+    recaudacion: Recaudacion, // Cuenta de la plataforma donde se acumulan las comisiones
+    persistencia: Persistencia, // Donde se escriben eventos.jsonl y snapshot.json
+    pendientes: Vec<Evento>, // Eventos de la operacion en curso, se escriben juntos al confirmarla
+    ultimo_registro: u64,
+    intervalo_snapshot: u64, // Cada cuantos registros del diario se guarda un snapshot
+    reglas: ReglasRetiro,
+    planes_staking: TablaStaking,
+    staking: Staking, // Posiciones de los usuarios; salen de las transacciones
+    mayor: Mayor, // Asientos de doble partida de las transacciones; tampoco se guarda
+    marcados: Vec<EventoMarcado>, // Operaciones que frenaron las reglas, para compliance
+    retenidos: Vec<RetiroRetenido>,
+    depositos: Vec<Deposito>,
+    inconsistente: bool // Fallo una operacion y no se pudo deshacer
 }
 
-fn get_fecha_actual() -> Fecha {
-    let actual: DateTime<Utc> = Utc::now(); 
-    Fecha::new(actual.day(), actual.month(), actual.year() as u32)
+pub const INTERVALO_SNAPSHOT: u64 = 1000;
+
+impl From<ErrorPersistencia> for ErrorCarga {
+    fn from(error: ErrorPersistencia) -> Self {
+        match error {
+            ErrorPersistencia::Io { archivo, error } => ErrorCarga::Lectura { archivo, error },
+            ErrorPersistencia::Serializacion { archivo, error } => ErrorCarga::Formato { archivo, error }
+        }
+    }
+}
+
+fn leer_obligatorio<T: serde::de::DeserializeOwned>(persistencia: &Persistencia, archivo: &str) -> Result<T, ErrorCarga> {
+    persistencia.cargar(archivo)?.ok_or_else(|| ErrorCarga::Lectura { archivo: archivo.to_string(), error: "no existe".to_string() })
+}
+
+// Verifica un estado guardado: repite todas las transacciones desde saldo cero y tienen que dar
+// los saldos guardados mas lo retenido en ordenes abiertas y lo bloqueado en staking; si no, los archivos no son confiables.
+fn verificar(usuarios: &[Usuario], transacciones: &[Transaccion], mercado: &Mercado, activos: &RegistroActivos) -> Result<(), ErrorCarga> {
+    let mut calculados: Vec<Usuario> = Vec::new();
+    for u in usuarios {
+        if calculados.iter().any(|c| c.dni == u.dni) {
+            return Err(ErrorCarga::UsuarioDuplicado(u.dni.clone()));
+        }
+        calculados.push(Usuario::new(u.nombre.clone(), u.apellido.clone(), u.email.clone(), u.dni.clone()));
+    }
+
+    let mut staking = Staking::new();
+    for (id, t) in transacciones.iter().enumerate() {
+        repetir_transaccion(&mut calculados, id, t, activos)?;
+        staking.aplicar(t);
+    }
+
+    let mut guardados = usuarios.to_vec();
+    sumar_retenido(&mut guardados, mercado, &staking, 0)?;
+
+    for (guardado, calculado) in guardados.iter().zip(calculados.iter()) {
+        if guardado.fiat != calculado.fiat {
+            return Err(ErrorCarga::SaldoNoCoincide { dni: guardado.dni.clone(), guardado: Monto::Fiat(guardado.fiat), calculado: Monto::Fiat(calculado.fiat) });
+        }
+        for cripto in guardado.balances.keys().chain(calculado.balances.keys()) {
+            let a = guardado.balances.get(cripto).copied().unwrap_or_default();
+            let b = calculado.balances.get(cripto).copied().unwrap_or_default();
+            if a != b {
+                return Err(ErrorCarga::SaldoNoCoincide { dni: guardado.dni.clone(), guardado: Monto::Cripto(cripto.clone(), a), calculado: Monto::Cripto(cripto.clone(), b) });
+            }
+        }
+    }
+    Ok(())
+}
+
+// Aplica la transaccion sobre saldos totales, controlando los saldos anotados en ella antes y despues
+fn repetir_transaccion(usuarios: &mut [Usuario], id: IdTransaccion, t: &Transaccion, activos: &RegistroActivos) -> Result<(), ErrorCarga> {
+    let controlar = |usuarios: &[Usuario], anotado: &Monto, dni: &str| {
+        let u = usuarios.iter().find(|u| u.dni == dni).ok_or_else(|| ErrorCarga::UsuarioDesconocido { transaccion: id, dni: dni.to_string() })?;
+        let calculado = u.saldo(anotado);
+        if &calculado != anotado {
+            return Err(ErrorCarga::SaldoAnotadoNoCoincide { transaccion: id, dni: dni.to_string(), anotado: anotado.clone(), calculado });
+        }
+        Ok(())
+    };
+
+    for cambio in t.saldos() {
+        controlar(usuarios, &cambio.antes, &cambio.dni)?;
+    }
+    for (dni, movimiento) in t.movimientos(activos).ok_or(ErrorCarga::MontoInvalido(id))? {
+        let u = usuarios.iter_mut().find(|u| u.dni == dni).ok_or_else(|| ErrorCarga::UsuarioDesconocido { transaccion: id, dni: dni.clone() })?;
+        match u.aplicar(&movimiento) {
+            None => return Err(ErrorCarga::MontoInvalido(id)),
+            Some(saldo) if saldo.es_negativo() => return Err(ErrorCarga::SaldoNegativo { transaccion: id, dni, saldo }),
+            Some(_) => ()
+        }
+    }
+    for cambio in t.saldos() {
+        controlar(usuarios, &cambio.despues, &cambio.dni)?;
+    }
+    Ok(())
+}
+
+// Junta los movimientos de un mismo usuario en una misma moneda
+fn agrupar(movimientos: Vec<(String, Monto)>) -> Vec<(String, Monto)> {
+    let mut agrupados: Vec<(String, Monto)> = Vec::new();
+    for (dni, movimiento) in movimientos {
+        match agrupados.iter_mut().find_map(|(d, m)| if *d == dni { m.sumar(&movimiento).map(|suma| (m, suma)) } else { None }) {
+            Some((m, suma)) => *m = suma,
+            None => agrupados.push((dni, movimiento))
+        }
+    }
+    agrupados
+}
+
+// Lo retenido en ordenes abiertas y lo bloqueado en staking sigue siendo del usuario aunque no figure en su saldo disponible
+fn sumar_retenido(usuarios: &mut [Usuario], mercado: &Mercado, staking: &Staking, registro: u64) -> Result<(), ErrorCarga> {
+    mover_retenido(usuarios, mercado, staking, registro, false)
+}
+
+fn en_rango<T>(monto: Option<T>) -> Result<T, XyzError> {
+    monto.ok_or(XyzError::MontoFueraDeRango)
+}
+
+fn fuera_de_limites<T>(error: FueraDeLimites<T>, moneda: impl Fn(T) -> Monto) -> XyzError {
+    match error {
+        FueraDeLimites::NoPositivo => XyzError::MontoNoPositivo,
+        FueraDeLimites::MenorAlMinimo(minimo) => XyzError::MontoMenorAlMinimo { minimo: moneda(minimo) },
+        FueraDeLimites::MayorAlMaximo(maximo) => XyzError::MontoMayorAlMaximo { maximo: moneda(maximo) }
+    }
+}
+
+fn restar_retenido(usuarios: &mut [Usuario], mercado: &Mercado, staking: &Staking, registro: u64) -> Result<(), ErrorCarga> {
+    mover_retenido(usuarios, mercado, staking, registro, true)
+}
+
+fn mover_retenido(usuarios: &mut [Usuario], mercado: &Mercado, staking: &Staking, registro: u64, restar: bool) -> Result<(), ErrorCarga> {
+    for orden in mercado.ordenes() {
+        let u = usuarios.iter_mut().find(|u| u.dni == orden.dni).ok_or_else(|| ErrorCarga::OrdenDeUsuarioDesconocido { orden: orden.id, dni: orden.dni.clone() })?;
+        let retenido = if restar { -orden.retenido() } else { orden.retenido() };
+        if u.aplicar(&retenido).is_none_or(|saldo| saldo.es_negativo()) {
+            return Err(ErrorCarga::RegistroInvalido { registro, error: format!("el usuario {} no tiene con que cubrir la orden {}", orden.dni, orden.id) });
+        }
+    }
+    for posicion in staking.posiciones() {
+        let error = || ErrorCarga::RegistroInvalido { registro, error: format!("el usuario {} no tiene con que cubrir lo bloqueado en {}", posicion.dni, posicion.cripto) };
+        let u = usuarios.iter_mut().find(|u| u.dni == posicion.dni).ok_or_else(error)?;
+        let bloqueado = Monto::Cripto(posicion.cripto.clone(), posicion.total());
+        if u.aplicar(&if restar { -bloqueado } else { bloqueado }).is_none_or(|saldo| saldo.es_negativo()) {
+            return Err(error());
+        }
+    }
+    Ok(())
 }
 
 
 impl XYZ {
-    pub fn new() -> XYZ {
+    // Arranca vacio y escribe el diario en `persistencia`; para seguir uno que ya existe, cargar o abrir
+    pub fn new(persistencia: Persistencia) -> XYZ {
+        XYZ::con_cotizador(CotizadorFijo, persistencia)
+    }
+
+    pub fn con_cotizador(cotizador: impl Cotizador + 'static, persistencia: Persistencia) -> XYZ {
         XYZ {
             usuarios: Vec::new(),
-            transacciones: Vec::new()
+            transacciones: Vec::new(),
+            cotizador: Box::new(cotizador),
+            reloj: Box::new(RelojSistema),
+            mercado: Mercado::new(),
+            comisiones: TablaComisiones::default(),
+            limites_fiat: Limites::default(),
+            activos: RegistroActivos::por_defecto(),
+            redes: RegistroRedes::por_defecto(),
+            recaudacion: Recaudacion::new(),
+            persistencia,
+            pendientes: Vec::new(),
+            ultimo_registro: 0,
+            intervalo_snapshot: INTERVALO_SNAPSHOT,
+            reglas: ReglasRetiro::new(),
+            planes_staking: TablaStaking::new(),
+            staking: Staking::new(),
+            mayor: Mayor::new(),
+            marcados: Vec::new(),
+            retenidos: Vec::new(),
+            depositos: Vec::new(),
+            inconsistente: false
+        }
+    }
+
+    pub fn cargar(directorio: impl AsRef<Path>) -> Result<XYZ, ErrorCarga> {
+        XYZ::cargar_desde(Persistencia::directorio(directorio.as_ref()))
+    }
+
+    // Como cargar, pero si en el directorio todavia no hay nada guardado arranca vacio y guarda ahi
+    pub fn abrir(directorio: impl AsRef<Path>) -> Result<XYZ, ErrorCarga> {
+        let persistencia = Persistencia::directorio(directorio.as_ref());
+        for archivo in [ARCHIVO_DIARIO, ARCHIVO_SNAPSHOT, "balances.json"] {
+            if persistencia.leer(archivo)?.is_some() {
+                return XYZ::cargar_desde(persistencia);
+            }
+        }
+        let activos = RegistroActivos::cargar(&persistencia)?;
+        let redes = RegistroRedes::cargar(&persistencia, &activos)?;
+        Ok(XYZ { redes, activos, ..XYZ::new(persistencia) })
+    }
+
+    // Levanta XYZ desde el ultimo snapshot, verificado, y le repite encima los registros
+    // del diario posteriores a el. Los activos salen del mismo lugar, donde despues sigue escribiendo.
+    pub fn cargar_desde(persistencia: Persistencia) -> Result<XYZ, ErrorCarga> {
+        if persistencia.leer(ARCHIVO_DIARIO)?.is_none() && persistencia.leer(ARCHIVO_SNAPSHOT)?.is_none() {
+            if persistencia.leer("balances.json")?.is_some() {
+                return XYZ::cargar_archivos_viejos(persistencia);
+            }
+            return Err(ErrorCarga::Lectura { archivo: ARCHIVO_DIARIO.to_string(), error: "no existe".to_string() });
         }
+
+        let activos = RegistroActivos::cargar(&persistencia)?;
+        let redes = RegistroRedes::cargar(&persistencia, &activos)?;
+        let mut xyz = XYZ { redes, activos, ..XYZ::new(persistencia) };
+        xyz.restaurar()?;
+        Ok(xyz)
     }
 
-    fn escribir_archivos(&self) {
-        self.escribir_balances();
-        self.escribir_transacciones();
+    // Arma el estado desde cero con el snapshot y el diario, sin tocar la configuracion
+    fn restaurar(&mut self) -> Result<(), ErrorCarga> {
+        let diario = self.persistencia.leer(ARCHIVO_DIARIO)?.unwrap_or_default();
+        let Snapshot { ultimo, usuarios, transacciones, mercado, marcados, retenidos, depositos } = self.persistencia.cargar(ARCHIVO_SNAPSHOT)?.unwrap_or_default();
+        verificar(&usuarios, &transacciones, &mercado, &self.activos)?;
+        let registros = leer_registros(&diario).map_err(|error| ErrorCarga::Formato { archivo: ARCHIVO_DIARIO.to_string(), error })?;
+
+        self.usuarios = usuarios;
+        self.transacciones = Vec::new();
+        self.mercado = mercado;
+        self.recaudacion = Recaudacion::new();
+        self.staking = Staking::new();
+        self.mayor = Mayor::new();
+        self.marcados = marcados;
+        self.retenidos = retenidos;
+        self.depositos = depositos;
+        self.pendientes.clear();
+        self.ultimo_registro = ultimo;
+        for t in transacciones {
+            self.anotar(t)?;
+        }
+
+        // Las transacciones mueven el saldo total, asi que se repiten con lo retenido sumado
+        // y al final se descuenta lo que retienen las ordenes que quedaron en el libro y el staking
+        sumar_retenido(&mut self.usuarios, &self.mercado, &self.staking, ultimo)?;
+        for registro in registros.into_iter().filter(|r| r.numero > ultimo) {
+            for evento in registro.eventos {
+                self.repetir(registro.numero, evento)?;
+            }
+            self.ultimo_registro = registro.numero;
+        }
+        restar_retenido(&mut self.usuarios, &self.mercado, &self.staking, self.ultimo_registro)?;
+        self.balance_de_comprobacion()?;
+
+        // Si no, el proximo registro quedaria pegado a la linea sin terminar
+        if let Some(reparado) = reparar(&diario) {
+            self.persistencia.escribir(ARCHIVO_DIARIO, &reparado)?;
+        }
+        Ok(())
+    }
+
+    // Carga archivos de cualquier formato anterior y los reescribe en el actual: las transacciones
+    // pasan a referenciar a los usuarios por dni y a anotar los saldos antes y despues.
+    pub fn migrar(persistencia: Persistencia) -> Result<XYZ, ErrorCarga> {
+        let mut xyz = XYZ::cargar_desde(persistencia)?;
+        xyz.completar_saldos();
+        xyz.compactar()?;
+        Ok(xyz)
+    }
+
+    // Archivos de antes del diario: balances.json, transacciones.json y ordenes.json.
+    // Se verifican igual que un snapshot y se pasan al formato nuevo.
+    fn cargar_archivos_viejos(persistencia: Persistencia) -> Result<XYZ, ErrorCarga> {
+        let usuarios: Vec<Usuario> = leer_obligatorio(&persistencia, "balances.json")?;
+        let transacciones: Vec<Transaccion> = leer_obligatorio(&persistencia, "transacciones.json")?;
+        let mercado: Mercado = persistencia.cargar("ordenes.json")?.unwrap_or_default(); // No existe en archivos de antes del libro de ordenes
+        let activos = RegistroActivos::cargar(&persistencia)?;
+        verificar(&usuarios, &transacciones, &mercado, &activos)?;
+
+        let redes = RegistroRedes::cargar(&persistencia, &activos)?;
+        let mut xyz = XYZ { usuarios, mercado, redes, activos, ..XYZ::new(persistencia) };
+        for t in transacciones {
+            xyz.anotar(t)?;
+        }
+        xyz.completar_saldos();
+        xyz.compactar()?;
+        Ok(xyz)
+    }
+
+    // Las transacciones de formatos viejos no anotan saldos; se calculan repitiendo el historial desde cero
+    fn completar_saldos(&mut self) {
+        let mut totales: Vec<Usuario> = self.usuarios.iter().map(|u| Usuario::new(u.nombre.clone(), u.apellido.clone(), u.email.clone(), u.dni.clone())).collect();
+        for t in &mut self.transacciones {
+            let movimientos = agrupar(t.movimientos(&self.activos).unwrap_or_default());
+            let mut saldos = Vec::new();
+            for (dni, movimiento) in movimientos {
+                if let Some(u) = totales.iter_mut().find(|u| u.dni == dni) {
+                    let antes = u.saldo(&movimiento);
+                    u.aplicar(&movimiento);
+                    saldos.push(CambioSaldo { dni, despues: u.saldo(&antes), antes });
+                }
+            }
+            if t.saldos().is_empty() {
+                *t.saldos_mut() = saldos;
+            }
+        }
     }
 
-    fn escribir_balances(&self) {
-        match File::create("./balances.json") {
-            Ok(mut file) => {
-                let b_s = serde_json::to_string_pretty(&self.usuarios).unwrap();
-                file.write_all(&b_s.as_bytes()).expect("Error al escribir el archivo balances.json");
+    // Aplica un evento del diario sobre saldos totales (disponible mas retenido)
+    fn repetir(&mut self, registro: u64, evento: Evento) -> Result<(), ErrorCarga> {
+        let invalido = |error: String| ErrorCarga::RegistroInvalido { registro, error };
+        match evento {
+            Evento::UsuarioCreado(u) => {
+                if self.usuarios.iter().any(|otro| otro.dni == u.dni) {
+                    return Err(ErrorCarga::UsuarioDuplicado(u.dni));
+                }
+                self.usuarios.push(u);
             },
-            Err(error) => {
-                println!("Error al crear archivo: {error}");
+            // De diarios anteriores al circuito KYC
+            Evento::UsuarioValidado(dni) => {
+                let u = self.usuarios.iter_mut().find(|u| u.dni == dni).ok_or_else(|| invalido(format!("valida al usuario {dni}, que no existe")))?;
+                u.estado = EstadoKyc::Aprobado;
+            },
+            Evento::Kyc { dni, identidad, cambio } => {
+                let u = self.usuarios.iter_mut().find(|u| u.dni == dni).ok_or_else(|| invalido(format!("cambia el estado del usuario {dni}, que no existe")))?;
+                u.cambiar_estado(cambio, identidad);
+            },
+            Evento::Transaccion(t) => {
+                repetir_transaccion(&mut self.usuarios, self.transacciones.len(), &t, &self.activos)?;
+                self.anotar(*t)?;
+            },
+            Evento::OrdenColocada(orden) => {
+                if !self.usuarios.iter().any(|u| u.dni == orden.dni) {
+                    return Err(ErrorCarga::OrdenDeUsuarioDesconocido { orden: orden.id, dni: orden.dni });
+                }
+                self.mercado.registrar_id(orden.id);
+                if !orden.esta_completa() && orden.precio.is_some() {
+                    self.mercado.libro_mut(&orden.cripto.clone()).insertar(orden);
+                }
+            },
+            Evento::OrdenActualizada(orden) => {
+                let id = orden.id;
+                if !self.mercado.reemplazar(orden) {
+                    return Err(invalido(format!("actualiza la orden {id}, que no esta en el libro")));
+                }
+            },
+            Evento::OrdenQuitada(id) => {
+                if self.mercado.quitar(id).is_none() {
+                    return Err(invalido(format!("quita la orden {id}, que no esta en el libro")));
+                }
+            },
+            Evento::Nivel { dni, nivel } => {
+                let u = self.usuarios.iter_mut().find(|u| u.dni == dni).ok_or_else(|| invalido(format!("cambia el nivel del usuario {dni}, que no existe")))?;
+                u.nivel = nivel;
+            },
+            Evento::Marcado(marcado) => self.marcados.push(marcado),
+            Evento::DireccionDeposito { dni, blockchain, direccion } => {
+                let u = self.usuarios.iter_mut().find(|u| u.dni == dni).ok_or_else(|| invalido(format!("da una direccion al usuario {dni}, que no existe")))?;
+                u.direcciones_deposito.insert(blockchain, direccion);
+            },
+            Evento::Deposito(deposito) => {
+                match self.depositos.iter_mut().find(|d| d.blockchain == deposito.blockchain && d.tx_id == deposito.tx_id) {
+                    Some(anterior) => *anterior = deposito,
+                    None => self.depositos.push(deposito)
+                }
+            },
+            Evento::EstadoRetiro { id, estado: nuevo } => {
+                let Some(Transaccion::RetiroCripto { estado, .. }) = self.transacciones.get_mut(id) else {
+                    return Err(invalido(format!("cambia el estado del retiro {id}, que no es un retiro de cripto")));
+                };
+                *estado = nuevo;
+            },
+            Evento::RetiroRetenido(retenido) => self.retenidos.push(retenido),
+            Evento::RetiroResuelto { id, estado, revisor, nota } => {
+                let retenido = self.retenidos.get_mut(id).ok_or_else(|| invalido(format!("resuelve el retiro retenido {id}, que no existe")))?;
+                retenido.resolver(estado, revisor, nota);
             }
+        }
+        Ok(())
+    }
+
+    pub fn set_persistencia(&mut self, persistencia: Persistencia) {
+        self.persistencia = persistencia;
+    }
+
+    pub fn set_reloj(&mut self, reloj: impl Reloj + 'static) {
+        self.reloj = Box::new(reloj);
+    }
+
+    pub fn hoy(&self) -> Fecha {
+        self.reloj.hoy()
+    }
+
+    // Con 0 se toma 1: un snapshot por registro
+    pub fn set_intervalo_snapshot(&mut self, registros: u64) {
+        self.intervalo_snapshot = registros.max(1);
+    }
+
+    pub fn set_limites_fiat(&mut self, limites: Limites<MontoFiat>) {
+        self.limites_fiat = limites;
+    }
+
+    pub fn limites_fiat(&self) -> &Limites<MontoFiat> {
+        &self.limites_fiat
+    }
+
+    pub fn set_comisiones(&mut self, comisiones: TablaComisiones) {
+        self.comisiones = comisiones;
+    }
+
+    pub fn comisiones(&self) -> &TablaComisiones {
+        &self.comisiones
+    }
+
+    // Reemplaza tambien las redes por las de los activos. Lo que ya se movio no puede dejar de
+    // listarse ni cambiar de decimales: el historial se lee con los decimales de cada activo.
+    pub fn set_activos(&mut self, activos: RegistroActivos) -> Result<(), XyzError> {
+        for activo in activos.activos() {
+            self.activos.mismos_decimales(activo).map_err(XyzError::ActivoInvalido)?;
+        }
+        if let Some(cripto) = self.criptos_movidas().into_iter().find(|c| activos.activo(c).is_none()) {
+            return Err(XyzError::ActivoInvalido(ErrorActivo::EnUso(cripto)));
+        }
+        self.redes = activos.redes();
+        self.activos = activos;
+        Ok(())
+    }
+
+    // Las que aparecen en alguna cuenta del mayor, aunque hoy nadie tenga saldo
+    fn criptos_movidas(&self) -> HashSet<Prefijo> {
+        self.mayor.cuentas().flat_map(|cuenta| self.mayor.saldos(cuenta).cripto.into_keys()).collect()
+    }
+
+    pub fn activos(&self) -> &RegistroActivos {
+        &self.activos
+    }
+
+    // Lista un activo nuevo (o reemplaza uno listado) junto con sus redes
+    pub fn agregar_activo(&mut self, activo: Activo) -> Result<(), XyzError> {
+        let (ticker, redes) = (activo.ticker.clone(), activo.redes.clone());
+        self.activos.agregar(activo).map_err(XyzError::ActivoInvalido)?;
+        for red in redes {
+            self.redes.agregar(ticker.clone(), red);
+        }
+        Ok(())
+    }
+
+    pub fn set_redes(&mut self, redes: RegistroRedes) {
+        self.redes = redes;
+    }
+
+    pub fn redes(&self) -> &RegistroRedes {
+        &self.redes
+    }
+
+    // Para habilitar, deshabilitar o cambiar una red sin armar el registro de nuevo
+    pub fn redes_mut(&mut self) -> &mut RegistroRedes {
+        &mut self.redes
+    }
+
+    pub fn recaudacion(&self) -> &Recaudacion {
+        &self.recaudacion
+    }
+
+    // Comisiones cobradas entre las dos fechas, ambas incluidas
+    pub fn reporte_comisiones(&self, desde: &Fecha, hasta: &Fecha) -> Recaudacion {
+        let mut reporte = Recaudacion::new();
+        for t in &self.transacciones {
+            let fecha = t.fecha();
+            if fecha.es_mayor(desde) && hasta.es_mayor(fecha) {
+                t.comisiones().iter().for_each(|c| reporte.sumar(c));
+            }
+        }
+        reporte
+    }
+
+    // Agrega los eventos de la operacion al diario como un solo registro, y cada
+    // intervalo_snapshot registros guarda ademas un snapshot para que la carga repita poco.
+    // Si el registro no llega al diario la operacion no se hizo, y se deshace tambien en memoria.
+    fn confirmar(&mut self) -> Result<(), XyzError> {
+        if self.inconsistente {
+            return Err(XyzError::Inconsistente);
+        }
+        if self.pendientes.is_empty() {
+            return Ok(());
+        }
+
+        let registro = Registro { numero: self.ultimo_registro + 1, eventos: self.pendientes.clone() };
+        let escrito = serde_json::to_string(&registro)
+            .map_err(|e| ErrorPersistencia::Serializacion { archivo: ARCHIVO_DIARIO.to_string(), error: e.to_string() })
+            .and_then(|linea| self.persistencia.agregar_linea(ARCHIVO_DIARIO, &linea));
+        if let Err(error) = escrito {
+            self.deshacer();
+            return Err(error.into());
+        }
+        self.pendientes.clear();
+        self.ultimo_registro = registro.numero;
+
+        if self.ultimo_registro.is_multiple_of(self.intervalo_snapshot) {
+            self.escribir_snapshot()?;
+        }
+        Ok(())
+    }
+
+    // Descarta lo que la operacion en curso cambio en memoria volviendo a lo que dice el diario.
+    // Si ni eso se puede, el estado queda a medias y no se escribe nada mas hasta volver a cargar.
+    fn deshacer(&mut self) {
+        self.inconsistente = self.restaurar().is_err();
+    }
+
+    fn escribir_snapshot(&self) -> Result<(), ErrorPersistencia> {
+        let snapshot = SnapshotRef {
+            ultimo: self.ultimo_registro,
+            usuarios: &self.usuarios,
+            transacciones: &self.transacciones,
+            mercado: &self.mercado,
+            marcados: &self.marcados,
+            retenidos: &self.retenidos,
+            depositos: &self.depositos
         };
+        self.persistencia.guardar(ARCHIVO_SNAPSHOT, &snapshot)
     }
-    
 
-    fn escribir_transacciones(&self) {
-        match File::create("./transacciones.json") {
-            Ok(mut file) => {
-                let t_s = serde_json::to_string_pretty(&self.transacciones).unwrap();
-                file.write_all(&t_s.as_bytes()).expect("Error al escribir el archivo transacciones.json");
-            },
+    // Guarda un snapshot con todo el estado y vacia el diario. Si se corta entre los dos pasos
+    // no pasa nada: los registros que quedan en el diario ya estan en el snapshot y se saltean.
+    pub fn compactar(&mut self) -> Result<(), ErrorPersistencia> {
+        self.escribir_snapshot()?;
+        self.persistencia.escribir(ARCHIVO_DIARIO, "")
+    }
+
+    fn registrar(&mut self, transaccion: Transaccion) -> Result<IdTransaccion, XyzError> {
+        let id = self.nueva_transaccion(transaccion)?;
+        self.confirmar()?;
+        Ok(id)
+    }
+
+    // Anota una transaccion de la operacion en curso, con los saldos que deja; se escribe al confirmar.
+    // El saldo de antes es el de la cuenta del usuario en el mayor.
+    fn nueva_transaccion(&mut self, mut transaccion: Transaccion) -> Result<IdTransaccion, XyzError> {
+        let movimientos = agrupar(transaccion.movimientos(&self.activos).unwrap_or_default());
+        *transaccion.saldos_mut() = movimientos.into_iter().filter_map(|(dni, movimiento)| {
+            let antes = self.mayor.saldo(&Cuenta::Usuario(dni.clone()), &movimiento);
+            let despues = antes.sumar(&movimiento)?;
+            Some(CambioSaldo { dni, antes, despues })
+        }).collect();
+
+        // Si no se puede asentar se deshace toda la operacion en curso, para no escribir un diario que no cierra
+        let evento = Evento::Transaccion(Box::new(transaccion.clone()));
+        match self.anotar(transaccion) {
+            Ok(id) => {
+                self.pendientes.push(evento);
+                Ok(id)
+            }
             Err(error) => {
-                println!("Error al crear archivo: {error}");
+                self.deshacer();
+                Err(error.into())
             }
-        };
+        }
     }
-    
 
-    pub fn crear_usuario(&mut self, nombre: String, apellido: String, email: String, dni: String) -> bool {
-        let user = self.usuarios.iter_mut().find(|u| u.dni == dni);
+    // Agrega la transaccion al historial sin escribir el diario, acredita sus comisiones a la plataforma
+    // y la asienta en el mayor
+    fn anotar(&mut self, transaccion: Transaccion) -> Result<IdTransaccion, ErrorContable> {
+        let id = self.transacciones.len();
+        self.mayor.asentar(contabilidad::asiento(id, &transaccion, &self.transacciones, &self.activos)?)?;
+        for comision in transaccion.comisiones() {
+            self.recaudacion.sumar(&comision);
+        }
+        self.staking.aplicar(&transaccion);
+        self.transacciones.push(transaccion);
+        Ok(id)
+    }
 
-        if user.is_some() {
-            false // Usuario ya existe
-        } else {
-            self.usuarios.push(Usuario::new(nombre, apellido, email, dni));
-            true
+    // Una sola consulta al cotizador por operacion, asi el precio cobrado y el registrado coinciden
+    fn cotizar(&self, cripto: &Prefijo) -> Result<MontoFiat, XyzError> {
+        match self.activos.activo(cripto).ok_or(XyzError::ActivoDesconocido)?.precio {
+            FuentePrecio::Fija(valor) => Ok(valor),
+            FuentePrecio::Cotizador => self.cotizador.cotizacion(cripto).ok_or(XyzError::CotizacionNoDisponible)
         }
     }
 
-    pub fn validar_usuario(&mut self, dni: String) -> bool {
-        let user = self.usuarios.iter_mut().find(|u| u.dni == dni);
+    // Con los que se guardan, se leen y se muestran los montos de la cripto
+    pub fn decimales(&self, cripto: &Prefijo) -> Result<u32, XyzError> {
+        self.activos.decimales(cripto).map_err(|_| XyzError::ActivoDesconocido)
+    }
+
+    // Controles de entrada de todo monto que pide un usuario. Los depositos por blockchain no pasan
+    // por aca: los fondos ya llegaron, sean del monto que sean.
+    fn validar_fiat(&self, monto: MontoFiat) -> Result<Valido<MontoFiat>, XyzError> {
+        self.limites_fiat.validar(monto).map_err(|e| fuera_de_limites(e, Monto::Fiat))
+    }
 
-        if let Some(u) = user {
-            u.validado = true;
-            true
-        } else {
-            false
+    fn validar_cripto(&self, cripto: &Prefijo, monto: MontoCripto) -> Result<Valido<MontoCripto>, XyzError> {
+        let activo = self.activos.activo(cripto).ok_or(XyzError::ActivoDesconocido)?;
+        activo.limites.validar(monto).map_err(|e| fuera_de_limites(e, |m| Monto::Cripto(cripto.clone(), m)))
+    }
+
+    // Un usuario que puede operar: aprobado
+    fn buscar_validado(&mut self, dni: &str) -> Result<&mut Usuario, XyzError> {
+        let u = self.usuarios.iter_mut().find(|u| u.dni == dni).ok_or(XyzError::UsuarioNoEncontrado)?;
+
+        match u.estado {
+            EstadoKyc::Suspendido => Err(XyzError::UsuarioSuspendido),
+            estado if !estado.puede_operar() => Err(XyzError::UsuarioNoValidado),
+            _ => Ok(u)
+        }
+    }
+
+    // Un usuario que puede sacar sus fondos: aprobado o suspendido
+    fn buscar_para_retirar(&mut self, dni: &str) -> Result<&mut Usuario, XyzError> {
+        let u = self.usuarios.iter_mut().find(|u| u.dni == dni).ok_or(XyzError::UsuarioNoEncontrado)?;
+
+        if !u.estado.puede_retirar() {
+            return Err(XyzError::UsuarioNoValidado);
+        }
+        Ok(u)
+    }
+
+
+    pub fn crear_usuario(&mut self, nombre: String, apellido: String, email: String, dni: String) -> Result<(), XyzError> {
+        if self.usuarios.iter().any(|u| u.dni == dni) {
+            return Err(XyzError::UsuarioExistente);
+        }
+
+        let u = Usuario::new(nombre, apellido, email, dni);
+        self.pendientes.push(Evento::UsuarioCreado(u.clone()));
+        self.usuarios.push(u);
+        self.confirmar()?;
+        Ok(())
+    }
+
+    // El usuario manda sus datos y queda en revision. Si lo habian rechazado puede volver a mandarlos.
+    // El email tiene que ser el de la cuenta, y el documento no puede estar verificado en otra:
+    // un mismo DNI no valida dos cuentas. Se guarda sin los puntos.
+    pub fn enviar_identidad(&mut self, dni: String, mut identidad: Identidad) -> Result<(), XyzError> {
+        identidad.validar(&self.reloj.hoy()).map_err(XyzError::Identidad)?;
+        identidad.documento = normalizar_dni(&identidad.documento);
+        let u = self.usuarios.iter().find(|u| u.dni == dni).ok_or(XyzError::UsuarioNoEncontrado)?;
+        if !u.email.eq_ignore_ascii_case(&identidad.email) {
+            return Err(XyzError::Identidad(ErrorIdentidad::EmailDistinto));
+        }
+        let en_uso = self.usuarios.iter().any(|otro| {
+            otro.dni != dni
+                && matches!(otro.estado, EstadoKyc::EnRevision | EstadoKyc::Aprobado | EstadoKyc::Suspendido)
+                && otro.identidad.as_ref().is_some_and(|i| normalizar_dni(&i.documento) == identidad.documento)
+        });
+        if en_uso {
+            return Err(XyzError::Identidad(ErrorIdentidad::DocumentoEnUso));
         }
+        self.cambiar_estado_kyc(&dni, EstadoKyc::EnRevision, None, "Datos de identidad enviados".to_string(), Some(identidad))?;
+        self.confirmar()?;
+        Ok(())
     }
 
-    pub fn ingresar_dinero(&mut self, dni: String, monto: f64) -> bool {
-        let user = self.usuarios.iter_mut().find(|u| u.dni == dni);
-        
-        if let Some(u) = user {
-            if !u.validado {
-               return false // No esta validado
+    pub fn aprobar_usuario(&mut self, dni: String, revisor: String, nota: String) -> Result<(), XyzError> {
+        self.cambiar_estado_kyc(&dni, EstadoKyc::Aprobado, Some(revisor), nota, None)?;
+        self.confirmar()?;
+        Ok(())
+    }
+
+    pub fn rechazar_usuario(&mut self, dni: String, revisor: String, nota: String) -> Result<(), XyzError> {
+        self.cambiar_estado_kyc(&dni, EstadoKyc::Rechazado, Some(revisor), nota, None)?;
+        self.confirmar()?;
+        Ok(())
+    }
+
+    // Un suspendido no opera, asi que sus ordenes salen del libro y se le devuelve lo retenido
+    pub fn suspender_usuario(&mut self, dni: String, revisor: String, nota: String) -> Result<(), XyzError> {
+        self.cambiar_estado_kyc(&dni, EstadoKyc::Suspendido, Some(revisor), nota, None)?;
+
+        let ids: Vec<IdOrden> = self.mercado.ordenes_de(&dni).map(|o| o.id).collect();
+        for id in ids {
+            if let Some(orden) = self.mercado.quitar(id) {
+                self.liberar_reserva(&orden)?;
+                self.pendientes.push(Evento::OrdenQuitada(id));
             }
+        }
+        self.confirmar()?;
+        Ok(())
+    }
 
-            u.fiat += monto;
-            self.transacciones.push(Transaccion::IngresoDinero { fecha: get_fecha_actual(), usuario: u.clone(), monto });
-            self.escribir_archivos();
-            true
-        } else {
-            false // No esta el usuario
+    // Levanta la suspension; el usuario vuelve a estar aprobado
+    pub fn rehabilitar_usuario(&mut self, dni: String, revisor: String, nota: String) -> Result<(), XyzError> {
+        self.cambiar_estado_kyc(&dni, EstadoKyc::Aprobado, Some(revisor), nota, None)?;
+        self.confirmar()?;
+        Ok(())
+    }
+
+    pub fn estado_kyc(&self, dni: &str) -> Option<EstadoKyc> {
+        self.usuarios.iter().find(|u| u.dni == dni).map(|u| u.estado)
+    }
+
+    pub fn historial_kyc(&self, dni: &str) -> Option<&[CambioEstado]> {
+        self.usuarios.iter().find(|u| u.dni == dni).map(|u| u.historial_kyc.as_slice())
+    }
+
+    // Anota el cambio en el historial del usuario; se escribe al confirmar
+    fn cambiar_estado_kyc(&mut self, dni: &str, nuevo: EstadoKyc, revisor: Option<String>, nota: String, identidad: Option<Identidad>) -> Result<(), XyzError> {
+        let u = self.usuarios.iter_mut().find(|u| u.dni == dni).ok_or(XyzError::UsuarioNoEncontrado)?;
+        if !u.estado.puede_pasar_a(nuevo) {
+            return Err(XyzError::TransicionKyc { desde: u.estado, hasta: nuevo });
         }
+
+        let cambio = CambioEstado { fecha: self.reloj.hoy(), anterior: u.estado, nuevo, revisor, nota };
+        u.cambiar_estado(cambio.clone(), identidad.clone());
+        self.pendientes.push(Evento::Kyc { dni: dni.to_string(), identidad, cambio });
+        Ok(())
     }
 
-    pub fn comprar_cripto(&mut self, dni: String, cripto: Prefijo, monto_cripto: f64) -> bool {
-        let user = self.usuarios.iter_mut().find(|u| u.dni == dni);
+    pub fn ingresar_dinero(&mut self, dni: String, monto: MontoFiat) -> Result<IdTransaccion, XyzError> {
+        let monto = self.validar_fiat(monto)?.valor();
+        let hoy = self.reloj.hoy();
+        let u = self.buscar_validado(&dni)?;
 
-        if let Some(u) = user {
-            let precio = monto_cripto * cripto.cotizacion();
-            if !u.validado || u.fiat < precio {
-                return false; // No esta validado/No hay balance suficiente
+        u.mover(&[Monto::Fiat(monto)])?;
+        let t = Transaccion::IngresoDinero { fecha: hoy, dni: u.dni.clone(), monto, saldos: Vec::new() };
+        self.registrar(t)
+    }
+
+    pub fn comprar_cripto(&mut self, dni: String, cripto: Prefijo, monto_cripto: MontoCripto) -> Result<IdTransaccion, XyzError> {
+        let monto_cripto = self.validar_cripto(&cripto, monto_cripto)?.valor();
+        let cotizacion = self.cotizar(&cripto)?;
+        let precio = valor_en_fiat(monto_cripto, self.decimales(&cripto)?, cotizacion).ok_or(XyzError::MontoFueraDeRango)?;
+        let comision = en_rango(self.comisiones.taker_fiat(precio))?;
+        let total = en_rango(precio.sumar(comision))?;
+        let hoy = self.reloj.hoy();
+        let u = self.buscar_validado(&dni)?;
+
+        if u.fiat < total {
+            return Err(XyzError::SaldoInsuficiente { disponible: Monto::Fiat(u.fiat), requerido: Monto::Fiat(total) });
+        }
+
+        u.mover(&[Monto::Cripto(cripto.clone(), monto_cripto), Monto::Fiat(-total)])?;
+        let t = Transaccion::CompraCripto { fecha: hoy, dni: u.dni.clone(), cripto, cotizacion, monto: monto_cripto, comision, saldos: Vec::new() };
+        self.registrar(t)
+    }
+
+    pub fn vender_cripto(&mut self, dni: String, cripto: Prefijo, monto_cripto: MontoCripto) -> Result<IdTransaccion, XyzError> {
+        let monto_cripto = self.validar_cripto(&cripto, monto_cripto)?.valor();
+        let cotizacion = self.cotizar(&cripto)?;
+        let valor = valor_en_fiat(monto_cripto, self.decimales(&cripto)?, cotizacion).ok_or(XyzError::MontoFueraDeRango)?;
+        let comision = en_rango(self.comisiones.taker_fiat(valor))?;
+        let hoy = self.reloj.hoy();
+        let u = self.buscar_validado(&dni)?;
+
+        let bal = u.balances.get(&cripto).copied().ok_or(XyzError::CriptoNoPoseida)?;
+        if bal < monto_cripto {
+            return Err(XyzError::SaldoInsuficiente { disponible: Monto::Cripto(cripto.clone(), bal), requerido: Monto::Cripto(cripto, monto_cripto) });
+        }
+        u.mover(&[Monto::Cripto(cripto.clone(), -monto_cripto), Monto::Fiat(valor - comision)])?;
+        let t = Transaccion::VentaCripto { fecha: hoy, dni: u.dni.clone(), cripto, cotizacion, monto: monto_cripto, comision, saldos: Vec::new() };
+        self.registrar(t)
+    }
+
+    // Cambia monto_origen de una cripto por lo que vale en la otra, menos el spread
+    pub fn swap(&mut self, dni: String, origen: Prefijo, destino: Prefijo, monto_origen: MontoCripto) -> Result<IdTransaccion, XyzError> {
+        if origen == destino {
+            return Err(XyzError::IntercambioMismaCripto);
+        }
+        let monto_origen = self.validar_cripto(&origen, monto_origen)?.valor();
+        let cotizacion_origen = self.cotizar(&origen)?;
+        let cotizacion_destino = self.cotizar(&destino)?;
+        let bruto = convertir(monto_origen, self.decimales(&origen)?, cotizacion_origen, self.decimales(&destino)?, cotizacion_destino).ok_or(XyzError::MontoFueraDeRango)?;
+        let spread = en_rango(self.comisiones.spread_cripto(bruto))?;
+        let monto_destino = bruto - spread;
+        if !monto_destino.es_positivo() {
+            return Err(XyzError::MontoNoPositivo); // Tan chico que no alcanza para una unidad minima de destino
+        }
+        let hoy = self.reloj.hoy();
+        let u = self.buscar_validado(&dni)?;
+
+        let bal = u.balances.get(&origen).copied().ok_or(XyzError::CriptoNoPoseida)?;
+        if bal < monto_origen {
+            return Err(XyzError::SaldoInsuficiente { disponible: Monto::Cripto(origen.clone(), bal), requerido: Monto::Cripto(origen, monto_origen) });
+        }
+        u.mover(&[Monto::Cripto(origen.clone(), -monto_origen), Monto::Cripto(destino.clone(), monto_destino)])?;
+        let t = Transaccion::Intercambio {
+            fecha: hoy, dni: u.dni.clone(), origen, cotizacion_origen, monto_origen,
+            destino, cotizacion_destino, monto_destino, spread, saldos: Vec::new()
+        };
+        self.registrar(t)
+    }
+
+    // Pasa cripto del disponible a staking. Antes paga lo que ya genero la posicion, si tenia.
+    pub fn bloquear_staking(&mut self, dni: String, cripto: Prefijo, monto: MontoCripto) -> Result<IdTransaccion, XyzError> {
+        let monto = self.validar_cripto(&cripto, monto)?.valor();
+        let plan = self.planes_staking.plan(&cripto).ok_or(XyzError::StakingNoDisponible)?;
+        let hoy = self.reloj.hoy();
+        let cotizacion = self.cotizar(&cripto)?;
+        let u = self.buscar_validado(&dni)?;
+        let bal = u.balances.get(&cripto).copied().ok_or(XyzError::CriptoNoPoseida)?;
+        if bal < monto {
+            return Err(XyzError::SaldoInsuficiente { disponible: Monto::Cripto(cripto.clone(), bal), requerido: Monto::Cripto(cripto, monto) });
+        }
+
+        self.recompensar(&dni, &cripto, plan.apr_bps, cotizacion, &hoy)?;
+        let u = self.buscar_validado(&dni)?;
+        u.mover(&[Monto::Cripto(cripto.clone(), -monto)])?;
+        let t = Transaccion::Staking { fecha: hoy, dni, cripto, monto, movimiento: MovimientoStaking::Bloqueo, saldos: Vec::new() };
+        self.registrar(t)
+    }
+
+    // Deja de generar recompensas enseguida, pero vuelve al disponible cuando termina el enfriamiento
+    // del plan. Devuelve la transaccion del desbloqueo; si no hay enfriamiento se libera en la misma operacion.
+    pub fn desbloquear_staking(&mut self, dni: String, cripto: Prefijo, monto: MontoCripto) -> Result<IdTransaccion, XyzError> {
+        let monto = self.validar_cripto(&cripto, monto)?.valor();
+        self.buscar_para_retirar(&dni)?;
+        let bloqueado = self.staking.posicion(&dni, &cripto).map_or(MontoCripto::CERO, |p| p.bloqueado);
+        if bloqueado < monto {
+            return Err(XyzError::SaldoInsuficiente { disponible: Monto::Cripto(cripto.clone(), bloqueado), requerido: Monto::Cripto(cripto, monto) });
+        }
+        let hoy = self.reloj.hoy();
+        let mut disponible = hoy.clone();
+
+        // Sin plan ya no genera nada y sale sin enfriamiento
+        if let Some(plan) = self.planes_staking.plan(&cripto) {
+            let cotizacion = self.cotizar(&cripto)?;
+            self.recompensar(&dni, &cripto, plan.apr_bps, cotizacion, &hoy)?;
+            disponible.sumar_dias(plan.enfriamiento);
+        }
+        let t = Transaccion::Staking { fecha: hoy.clone(), dni: dni.clone(), cripto: cripto.clone(), monto, movimiento: MovimientoStaking::Desbloqueo { disponible }, saldos: Vec::new() };
+        let id = self.nueva_transaccion(t)?;
+        self.liberar(&dni, &cripto, &hoy)?;
+        self.confirmar()?;
+        Ok(id)
+    }
+
+    // Paga lo que genero cada posicion hasta hoy y devuelve al disponible lo que termino el enfriamiento.
+    // Pensado para correr una vez por dia, pero da lo mismo correrlo mas seguido o saltear dias.
+    pub fn procesar_staking(&mut self) -> Result<Vec<IdTransaccion>, XyzError> {
+        let hoy = self.reloj.hoy();
+        let mut cotizaciones = HashMap::new();
+        for p in self.staking.posiciones() {
+            if p.bloqueado.es_positivo() && self.planes_staking.plan(&p.cripto).is_some() && !cotizaciones.contains_key(&p.cripto) {
+                cotizaciones.insert(p.cripto.clone(), self.cotizar(&p.cripto)?);
             }
-            
-            let info = cripto.get_info();
+        }
 
-            *u.balances.entry(cripto.clone()).or_insert(0.0) += monto_cripto;
-            u.fiat -= precio;
-            self.transacciones.push(Transaccion::CompraCripto { fecha: get_fecha_actual(), usuario: u.clone(), cripto, cotizacion: info.valor,  monto: monto_cripto });
-            self.escribir_archivos();
-            true
-        } else {
-            false // No se encontro el usuario
+        let posiciones: Vec<(String, Prefijo)> = self.staking.posiciones().iter().map(|p| (p.dni.clone(), p.cripto.clone())).collect();
+        let mut ids = Vec::new();
+        for (dni, cripto) in posiciones {
+            if let (Some(plan), Some(cotizacion)) = (self.planes_staking.plan(&cripto), cotizaciones.get(&cripto)) {
+                ids.extend(self.recompensar(&dni, &cripto, plan.apr_bps, *cotizacion, &hoy)?);
+            }
+            ids.extend(self.liberar(&dni, &cripto, &hoy)?);
+        }
+        self.confirmar()?;
+        Ok(ids)
+    }
+
+    // Anota la recompensa de la posicion desde la ultima que se le pago. Si todavia no llega
+    // a una unidad minima no anota nada y los dias se siguen acumulando.
+    fn recompensar(&mut self, dni: &str, cripto: &Prefijo, apr_bps: u32, cotizacion: MontoFiat, hoy: &Fecha) -> Result<Option<IdTransaccion>, XyzError> {
+        let Some(posicion) = self.staking.posicion(dni, cripto) else { return Ok(None) };
+        let monto = en_rango(recompensa(posicion.bloqueado, apr_bps, posicion.dias_sin_recompensa(hoy)))?;
+        if !monto.es_positivo() {
+            return Ok(None);
+        }
+        let desde = posicion.desde.clone();
+        let Some(u) = self.usuarios.iter_mut().find(|u| u.dni == dni) else { return Ok(None) };
+        u.mover(&[Monto::Cripto(cripto.clone(), monto)])?;
+        let t = Transaccion::RecompensaStaking {
+            fecha: hoy.clone(), dni: dni.to_string(), cripto: cripto.clone(), cotizacion, monto,
+            desde, hasta: hoy.clone(), apr_bps, saldos: Vec::new()
+        };
+        self.nueva_transaccion(t).map(Some)
+    }
+
+    fn liberar(&mut self, dni: &str, cripto: &Prefijo, hoy: &Fecha) -> Result<Option<IdTransaccion>, XyzError> {
+        let monto = self.staking.posicion(dni, cripto).map_or(MontoCripto::CERO, |p| p.liberable(hoy));
+        if !monto.es_positivo() {
+            return Ok(None);
         }
+        let Some(u) = self.usuarios.iter_mut().find(|u| u.dni == dni) else { return Ok(None) };
+        u.mover(&[Monto::Cripto(cripto.clone(), monto)])?;
+        let t = Transaccion::Staking { fecha: hoy.clone(), dni: dni.to_string(), cripto: cripto.clone(), monto, movimiento: MovimientoStaking::Liberacion, saldos: Vec::new() };
+        self.nueva_transaccion(t).map(Some)
+    }
+
+    pub fn set_planes_staking(&mut self, planes: TablaStaking) {
+        self.planes_staking = planes;
     }
 
-    pub fn vender_cripto(&mut self, dni: String, cripto: Prefijo, monto_cripto: f64) -> bool {
-        let user = self.usuarios.iter_mut().find(|u| u.dni == dni);
+    pub fn planes_staking_mut(&mut self) -> &mut TablaStaking {
+        &mut self.planes_staking
+    }
+
+    pub fn mayor(&self) -> &Mayor {
+        &self.mayor
+    }
 
-        if let Some(u) = user {
-            if !u.validado {
-                return false;
+    // El balance de comprobacion del mayor, y ademas que la cuenta de cada usuario diga lo mismo
+    // que su saldo disponible mas lo retenido en ordenes y lo bloqueado en staking
+    pub fn balance_de_comprobacion(&self) -> Result<HashMap<Cuenta, Saldos>, ErrorContable> {
+        let balance = self.mayor.balance_de_comprobacion()?;
+        for u in &self.usuarios {
+            let cuenta = Cuenta::Usuario(u.dni.clone());
+            let mut calculado = Saldos { fiat: u.fiat, cripto: u.balances.clone() };
+            for orden in self.mercado.ordenes_de(&u.dni) {
+                calculado.aplicar(&orden.retenido());
+            }
+            for posicion in self.staking.de(&u.dni) {
+                calculado.aplicar(&Monto::Cripto(posicion.cripto.clone(), posicion.total()));
             }
 
-            if let Some(bal) = u.balances.get(&cripto) {
-                if *bal < monto_cripto {
-                    return false; // No hay balance suficiente 
+            let mayor = balance.get(&cuenta).cloned().unwrap_or_default();
+            let mut criptos: Vec<&Prefijo> = mayor.cripto.keys().chain(calculado.cripto.keys()).collect();
+            criptos.sort();
+            criptos.dedup();
+            let monedas = std::iter::once(Monto::Fiat(MontoFiat::CERO)).chain(criptos.into_iter().map(|c| Monto::Cripto(c.clone(), MontoCripto::CERO)));
+            for moneda in monedas {
+                if mayor.en(&moneda) != calculado.en(&moneda) {
+                    return Err(ErrorContable::SaldoNoCoincide { cuenta, mayor: mayor.en(&moneda), calculado: calculado.en(&moneda) });
                 }
-                let info = cripto.get_info();
+            }
+        }
+        Ok(balance)
+    }
+
+    pub fn posicion_staking(&self, dni: &str, cripto: &Prefijo) -> Option<&Posicion> {
+        self.staking.posicion(dni, cripto)
+    }
+
+    pub fn retirar_blockchain(&mut self, dni: String, cripto: Prefijo, blockchain: String, destino: String, monto_cripto: MontoCripto) -> Result<IdTransaccion, XyzError> {
+        self.retiro(&dni, PedidoRetiro::Cripto { cripto, blockchain, destino, monto: monto_cripto }, false)
+    }
+
+    // La direccion del usuario para depositar en esa red. La primera vez se genera y se guarda.
+    pub fn direccion_deposito(&mut self, dni: String, cripto: Prefijo, blockchain: String) -> Result<String, XyzError> {
+        let red = self.redes.red(&cripto, &blockchain).ok_or(XyzError::BlockchainInvalida)?;
+        if !red.habilitada {
+            return Err(XyzError::RedDeshabilitada);
+        }
+        let (nombre, formato) = (red.nombre.clone(), red.formato);
+        let u = self.buscar_validado(&dni)?;
+        if let Some(direccion) = u.direcciones_deposito.get(&nombre) {
+            return Ok(direccion.clone());
+        }
+
+        let direccion = generar_direccion(formato, &format!("{}|{}", u.dni, nombre));
+        u.direcciones_deposito.insert(nombre.clone(), direccion.clone());
+        self.pendientes.push(Evento::DireccionDeposito { dni, blockchain: nombre, direccion: direccion.clone() });
+        self.confirmar()?;
+        Ok(direccion)
+    }
+
+    // Un deposito que se vio en la red. Si ya tiene las confirmaciones que pide la red se acredita
+    // y devuelve la transaccion; si no, queda pendiente hasta que confirmar_deposito las complete.
+    // Se acepta aunque la red este deshabilitada: los fondos ya llegaron.
+    pub fn recepcion_blockchain(&mut self, direccion: String, cripto: Prefijo, blockchain: String, tx_id: String, monto_cripto: MontoCripto, confirmaciones: u32) -> Result<Option<IdTransaccion>, XyzError> {
+        let red = self.redes.red(&cripto, &blockchain).ok_or(XyzError::BlockchainInvalida)?.nombre.clone();
+        if !monto_cripto.es_positivo() {
+            return Err(XyzError::MontoNoPositivo);
+        }
+        if self.depositos.iter().any(|d| d.blockchain == red && d.tx_id == tx_id) {
+            return Err(XyzError::DepositoDuplicado);
+        }
+        let u = self.usuarios.iter().find(|u| u.direcciones_deposito.get(&red) == Some(&direccion)).ok_or(XyzError::DireccionDesconocida)?;
+
+        let deposito = Deposito {
+            fecha: self.reloj.hoy(), dni: u.dni.clone(), cripto, blockchain: red, direccion, tx_id,
+            monto: monto_cripto, confirmaciones: 0, acreditado: None
+        };
+        self.actualizar_deposito(deposito, None, confirmaciones)
+    }
+
+    pub fn confirmar_deposito(&mut self, blockchain: &str, tx_id: &str, confirmaciones: u32) -> Result<Option<IdTransaccion>, XyzError> {
+        let i = self.depositos.iter().position(|d| d.blockchain == blockchain && d.tx_id == tx_id).ok_or(XyzError::DepositoNoEncontrado)?;
+        self.actualizar_deposito(self.depositos[i].clone(), Some(i), confirmaciones)
+    }
+
+    pub fn depositos_de<'a>(&'a self, dni: &'a str) -> Vec<&'a Deposito> {
+        self.depositos.iter().filter(|d| d.dni == dni).collect()
+    }
+
+    // Anota las confirmaciones y, si alcanzan, acredita el deposito. Devuelve la transaccion si lo acredito ahora.
+    fn actualizar_deposito(&mut self, mut deposito: Deposito, indice: Option<usize>, confirmaciones: u32) -> Result<Option<IdTransaccion>, XyzError> {
+        deposito.confirmaciones = deposito.confirmaciones.max(confirmaciones);
+        let requeridas = self.redes.red(&deposito.cripto, &deposito.blockchain).map_or(1, |r| r.confirmaciones);
+        let acreditar = deposito.acreditado.is_none() && deposito.confirmaciones >= requeridas;
+        let cotizacion = if acreditar { Some(self.cotizar(&deposito.cripto)?) } else { None };
+
+        let mut acreditado = None;
+        if let Some(cotizacion) = cotizacion {
+            let u = self.usuarios.iter_mut().find(|u| u.dni == deposito.dni).ok_or(XyzError::UsuarioNoEncontrado)?;
+            u.mover(&[Monto::Cripto(deposito.cripto.clone(), deposito.monto)])?;
+            let t = Transaccion::RecepcionCripto {
+                fecha: self.reloj.hoy(), dni: deposito.dni.clone(), blockchain: deposito.blockchain.clone(), cripto: deposito.cripto.clone(),
+                cotizacion, monto: deposito.monto, tx_id: deposito.tx_id.clone(), saldos: Vec::new()
+            };
+            acreditado = Some(self.nueva_transaccion(t)?);
+            deposito.acreditado = acreditado;
+        }
+
+        self.pendientes.push(Evento::Deposito(deposito.clone()));
+        match indice {
+            Some(i) => self.depositos[i] = deposito,
+            None => self.depositos.push(deposito)
+        }
+        self.confirmar()?;
+        Ok(acreditado)
+    }
+
+    pub fn retirar_fiat(&mut self, dni: String, medio: Medio, monto_fiat: MontoFiat) -> Result<IdTransaccion, XyzError> {
+        self.retiro(&dni, PedidoRetiro::Fiat { medio, monto: monto_fiat }, false)
+    }
+
+    pub fn set_reglas(&mut self, reglas: ReglasRetiro) {
+        self.reglas = reglas;
+    }
 
-                *u.balances.entry(cripto.clone()).or_insert(0.0) -= monto_cripto;
-                u.fiat += monto_cripto / cripto.cotizacion() ;
-                self.transacciones.push(Transaccion::VentaCripto { fecha: get_fecha_actual(), usuario: u.clone(), cripto, cotizacion: info.valor, monto: monto_cripto });
-                self.escribir_archivos();
-                true
-            } else {
-                false // No tiene la cripto especificada
+    pub fn reglas(&self) -> &ReglasRetiro {
+        &self.reglas
+    }
+
+    // Para bloquear o desbloquear direcciones sin armar las reglas de nuevo
+    pub fn reglas_mut(&mut self) -> &mut ReglasRetiro {
+        &mut self.reglas
+    }
+
+    pub fn set_nivel(&mut self, dni: String, nivel: Nivel) -> Result<(), XyzError> {
+        let u = self.usuarios.iter_mut().find(|u| u.dni == dni).ok_or(XyzError::UsuarioNoEncontrado)?;
+        u.nivel = nivel;
+        self.pendientes.push(Evento::Nivel { dni, nivel });
+        self.confirmar()?;
+        Ok(())
+    }
+
+    pub fn eventos_marcados(&self) -> &[EventoMarcado] {
+        &self.marcados
+    }
+
+    pub fn marcados_de<'a>(&'a self, dni: &'a str) -> Vec<&'a EventoMarcado> {
+        self.marcados.iter().filter(|m| m.dni == dni).collect()
+    }
+
+    // Todos los retiros que se retuvieron alguna vez; la posicion es su id
+    pub fn retiros_retenidos(&self) -> &[RetiroRetenido] {
+        &self.retenidos
+    }
+
+    // Ejecuta un retiro retenido sin volver a pasarlo por las reglas. Si el usuario ya no
+    // tiene saldo o no puede retirar, devuelve el error y el retiro sigue pendiente.
+    pub fn aprobar_retiro(&mut self, id: IdRetiroRetenido, revisor: String, nota: String) -> Result<IdTransaccion, XyzError> {
+        let RetiroRetenido { dni, pedido, .. } = self.retenido_pendiente(id)?.clone();
+        self.preparar_retiro(&dni, &pedido)?;
+        self.resolver_retiro(id, EstadoRetenido::Aprobado, revisor, nota);
+        self.retiro(&dni, pedido, true)
+    }
+
+    pub fn rechazar_retiro(&mut self, id: IdRetiroRetenido, revisor: String, nota: String) -> Result<(), XyzError> {
+        self.retenido_pendiente(id)?;
+        self.resolver_retiro(id, EstadoRetenido::Rechazado, revisor, nota);
+        self.confirmar()?;
+        Ok(())
+    }
+
+    fn retenido_pendiente(&self, id: IdRetiroRetenido) -> Result<&RetiroRetenido, XyzError> {
+        self.retenidos.get(id).filter(|r| r.estado == EstadoRetenido::Pendiente).ok_or(XyzError::RetiroNoEncontrado)
+    }
+
+    fn resolver_retiro(&mut self, id: IdRetiroRetenido, estado: EstadoRetenido, revisor: String, nota: String) {
+        self.retenidos[id].resolver(estado, revisor.clone(), nota.clone());
+        self.pendientes.push(Evento::RetiroResuelto { id, estado, revisor, nota });
+    }
+
+    // Controla el pedido, lo pasa por las reglas (salvo que ya lo haya aprobado un revisor) y lo ejecuta
+    fn retiro(&mut self, dni: &str, pedido: PedidoRetiro, aprobado: bool) -> Result<IdTransaccion, XyzError> {
+        let (t, valor) = self.preparar_retiro(dni, &pedido)?;
+        if !aprobado {
+            self.controlar_retiro(dni, pedido, valor)?;
+        }
+
+        let movimientos: Vec<Monto> = t.movimientos(&self.activos).ok_or(XyzError::MontoFueraDeRango)?.into_iter().map(|(_, m)| m).collect();
+        let u = self.buscar_para_retirar(dni)?;
+        u.mover(&movimientos)?;
+        self.registrar(t)
+    }
+
+    // Arma la transaccion del retiro y su valor en fiat, sin tocar los saldos
+    fn preparar_retiro(&mut self, dni: &str, pedido: &PedidoRetiro) -> Result<(Transaccion, MontoFiat), XyzError> {
+        match pedido {
+            PedidoRetiro::Fiat { medio, monto } => {
+                let monto = &self.validar_fiat(*monto)?.valor();
+                let comision = self.comisiones.retiro_fiat(medio);
+                let total = en_rango(monto.sumar(comision))?;
+                let hoy = self.reloj.hoy();
+                let u = self.buscar_para_retirar(dni)?;
+
+                if u.fiat < total {
+                    return Err(XyzError::SaldoInsuficiente { disponible: Monto::Fiat(u.fiat), requerido: Monto::Fiat(total) });
+                }
+                let t = Transaccion::RetiroFiat { fecha: hoy, dni: u.dni.clone(), monto: *monto, medio: *medio, comision, momento: self.reloj.momento(), saldos: Vec::new() };
+                Ok((t, *monto))
+            },
+            PedidoRetiro::Cripto { cripto, blockchain, destino, monto } => {
+                let monto = &self.validar_cripto(cripto, *monto)?.valor();
+                let cotizacion = self.cotizar(cripto)?;
+                let decimales = self.decimales(cripto)?;
+                let red = self.redes.red(cripto, blockchain).ok_or(XyzError::BlockchainInvalida)?;
+                if !red.habilitada {
+                    return Err(XyzError::RedDeshabilitada);
+                }
+                if *monto < red.retiro_minimo {
+                    return Err(XyzError::RetiroMenorAlMinimo { minimo: red.retiro_minimo });
+                }
+                validar_direccion(red.formato, destino).map_err(XyzError::DireccionInvalida)?;
+                let (nombre, comision) = (red.nombre.clone(), red.comision);
+                let total = en_rango(monto.sumar(comision))?;
+                let u = self.buscar_para_retirar(dni)?;
+
+                let bal = *u.balances.get(cripto).ok_or(XyzError::CriptoNoPoseida)?;
+                if bal < total {
+                    return Err(XyzError::SaldoInsuficiente { disponible: Monto::Cripto(cripto.clone(), bal), requerido: Monto::Cripto(cripto.clone(), total) });
+                }
+                let valor = valor_en_fiat(*monto, decimales, cotizacion).ok_or(XyzError::MontoFueraDeRango)?;
+
+                // La posicion en el historial distingue dos retiros iguales del mismo dia
+                let fecha = self.reloj.hoy();
+                let hash = hash_hex(&[
+                    &self.transacciones.len().to_string(), dni, &nombre, destino, cripto.as_str(),
+                    &monto.formatear(decimales), &comision.formatear(decimales), &format!("{}/{}/{}", fecha.dia, fecha.mes, fecha.anio)
+                ]);
+                let t = Transaccion::RetiroCripto {
+                    fecha, dni: dni.to_string(), blockchain: nombre, hash, cripto: cripto.clone(), cotizacion,
+                    monto: *monto, destino: destino.clone(), comision, estado: EstadoRetiro::Pendiente,
+                    momento: self.reloj.momento(), saldos: Vec::new()
+                };
+                Ok((t, valor))
+            }
+        }
+    }
+
+    // Pasa el retiro por las reglas. Si no las cumple queda marcado para compliance y, si solo
+    // le falta la aprobacion manual, retenido hasta que un revisor lo resuelva.
+    fn controlar_retiro(&mut self, dni: &str, pedido: PedidoRetiro, valor: MontoFiat) -> Result<(), XyzError> {
+        let hoy = self.reloj.hoy();
+        let ahora = self.reloj.momento();
+        let (retirado_hoy, retirado_mes) = self.retirado(dni, &hoy);
+        let retiros_ultima_hora = self.retiros_ultima_hora(dni, ahora);
+        let nivel = self.usuarios.iter().find(|u| u.dni == dni).map(|u| u.nivel).unwrap_or_default();
+
+        let solicitud = Solicitud { nivel, valor, destino: pedido.destino(), retirado_hoy, retirado_mes, retiros_ultima_hora: retiros_ultima_hora as u32 };
+        let Err(motivo) = self.reglas.evaluar(&solicitud) else {
+            return Ok(());
+        };
+
+        let marcado = EventoMarcado { fecha: hoy.clone(), dni: dni.to_string(), pedido: pedido.clone(), motivo: motivo.clone() };
+        self.marcados.push(marcado.clone());
+        self.pendientes.push(Evento::Marcado(marcado));
+        let error = match motivo {
+            MotivoRechazo::RequiereAprobacion { .. } => {
+                let retenido = RetiroRetenido { fecha: hoy, dni: dni.to_string(), pedido, estado: EstadoRetenido::Pendiente, revisor: None, nota: String::new() };
+                self.retenidos.push(retenido.clone());
+                self.pendientes.push(Evento::RetiroRetenido(retenido));
+                XyzError::RetiroRetenido(self.retenidos.len() - 1)
+            },
+            motivo => XyzError::RetiroRechazado(motivo)
+        };
+        self.confirmar()?;
+        Err(error)
+    }
+
+    // Los retiros que hizo el usuario en la hora anterior a ese momento, salieran o no
+    fn retiros_ultima_hora(&self, dni: &str, ahora: i64) -> usize {
+        self.transacciones.iter().filter(|t| match t {
+            Transaccion::RetiroFiat { dni: d, momento, .. } | Transaccion::RetiroCripto { dni: d, momento, .. } => d == dni && ahora - momento < SEGUNDOS_POR_HORA,
+            _ => false
+        }).count()
+    }
+
+    // Lo que retiro el usuario hoy y en lo que va del mes, en fiat; la cripto a la cotizacion de cada retiro.
+    // Lo que no entra en un monto queda en el maximo, que supera cualquier tope.
+    fn retirado(&self, dni: &str, hoy: &Fecha) -> (MontoFiat, MontoFiat) {
+        let maximo = MontoFiat::from_centavos(i64::MAX);
+        let mut dia = MontoFiat::CERO;
+        let mut mes = MontoFiat::CERO;
+        for t in &self.transacciones {
+            let valor = match t {
+                Transaccion::RetiroFiat { dni: d, monto, .. } if d == dni => *monto,
+                Transaccion::RetiroCripto { dni: d, cripto, cotizacion, monto, estado, .. } if d == dni && !matches!(estado, EstadoRetiro::Fallida(_)) => {
+                    self.activos.valor_en_fiat(*monto, cripto, *cotizacion).unwrap_or(maximo)
+                },
+                _ => continue
+            };
+            let fecha = t.fecha();
+            if fecha.mes == hoy.mes && fecha.anio == hoy.anio {
+                mes = mes.sumar(valor).unwrap_or(maximo);
+                if fecha.dia == hoy.dia {
+                    dia = dia.sumar(valor).unwrap_or(maximo);
+                }
             }
+        }
+        (dia, mes)
+    }
+
+    pub fn estado_retiro(&self, id: IdTransaccion) -> Option<&EstadoRetiro> {
+        match self.transacciones.get(id) {
+            Some(Transaccion::RetiroCripto { estado, .. }) => Some(estado),
+            _ => None
+        }
+    }
+
+    pub fn marcar_enviada(&mut self, id: IdTransaccion) -> Result<(), XyzError> {
+        match self.estado_retiro(id).ok_or(XyzError::RetiroCriptoNoEncontrado)? {
+            EstadoRetiro::Pendiente => self.cambiar_estado_retiro(id, EstadoRetiro::Enviada { confirmaciones: 0 }),
+            desde => Err(XyzError::TransicionRetiro { desde: desde.clone() })
+        }
+    }
+
+    // Con las confirmaciones que pide la red el retiro queda confirmado
+    pub fn registrar_confirmaciones(&mut self, id: IdTransaccion, confirmaciones: u32) -> Result<(), XyzError> {
+        let Some(Transaccion::RetiroCripto { cripto, blockchain, estado, .. }) = self.transacciones.get(id) else {
+            return Err(XyzError::RetiroCriptoNoEncontrado);
+        };
+        if !matches!(estado, EstadoRetiro::Enviada { .. }) {
+            return Err(XyzError::TransicionRetiro { desde: estado.clone() });
+        }
+
+        let requeridas = self.redes.red(cripto, blockchain).map_or(1, |r| r.confirmaciones);
+        let nuevo = if confirmaciones >= requeridas { EstadoRetiro::Confirmada } else { EstadoRetiro::Enviada { confirmaciones } };
+        self.cambiar_estado_retiro(id, nuevo)
+    }
+
+    // Un retiro que no salio o que la red rechazo: se le devuelve al usuario lo que se le desconto
+    pub fn marcar_fallida(&mut self, id: IdTransaccion, motivo: String) -> Result<IdTransaccion, XyzError> {
+        let Some(Transaccion::RetiroCripto { dni, cripto, cotizacion, monto, comision, estado, .. }) = self.transacciones.get(id) else {
+            return Err(XyzError::RetiroCriptoNoEncontrado);
+        };
+        if !matches!(estado, EstadoRetiro::Pendiente | EstadoRetiro::Enviada { .. }) {
+            return Err(XyzError::TransicionRetiro { desde: estado.clone() });
+        }
+        let reintegro = Transaccion::ReintegroRetiro {
+            fecha: self.reloj.hoy(), dni: dni.clone(), retiro: id, cripto: cripto.clone(), cotizacion: *cotizacion, monto: *monto, comision: *comision, saldos: Vec::new()
+        };
+
+        let dni = dni.clone();
+        let movimientos: Vec<Monto> = reintegro.movimientos(&self.activos).ok_or(XyzError::MontoFueraDeRango)?.into_iter().map(|(_, m)| m).collect();
+        let u = self.usuarios.iter_mut().find(|u| u.dni == dni).ok_or(XyzError::UsuarioNoEncontrado)?;
+        u.mover(&movimientos)?;
+        self.set_estado_retiro(id, EstadoRetiro::Fallida(motivo));
+        self.registrar(reintegro)
+    }
+
+    fn cambiar_estado_retiro(&mut self, id: IdTransaccion, estado: EstadoRetiro) -> Result<(), XyzError> {
+        self.set_estado_retiro(id, estado);
+        self.confirmar()?;
+        Ok(())
+    }
+
+    // Anota el nuevo estado del retiro; se escribe al confirmar
+    fn set_estado_retiro(&mut self, id: IdTransaccion, nuevo: EstadoRetiro) {
+        if let Some(Transaccion::RetiroCripto { estado, .. }) = self.transacciones.get_mut(id) {
+            *estado = nuevo.clone();
+            self.pendientes.push(Evento::EstadoRetiro { id, estado: nuevo });
+        }
+    }
+
+    pub fn colocar_orden(&mut self, dni: String, cripto: Prefijo, lado: Lado, tipo: TipoOrden, cantidad: MontoCripto) -> Result<ResultadoOrden, XyzError> {
+        let precio = match tipo {
+            TipoOrden::Limite(precio) if precio.es_positivo() => Some(precio),
+            TipoOrden::Limite(_) => return Err(XyzError::OrdenInvalida),
+            TipoOrden::Mercado => None
+        };
+        if !cantidad.es_positivo() {
+            return Err(XyzError::OrdenInvalida);
+        }
+        self.validar_cripto(&cripto, cantidad)?;
+        let decimales = self.decimales(&cripto)?;
+
+        // Se retiene lo necesario para cubrir la orden completa; una compra de mercado
+        // no tiene precio, asi que paga con el fiat disponible a medida que se ejecuta
+        let u = self.buscar_validado(&dni)?;
+        let mut reserva_fiat = MontoFiat::CERO;
+        match (lado, precio) {
+            (Lado::Compra, Some(limite)) => {
+                reserva_fiat = valor_en_fiat_con(cantidad, decimales, limite, Redondeo::Arriba).ok_or(XyzError::MontoFueraDeRango)?;
+                if u.fiat < reserva_fiat {
+                    return Err(XyzError::SaldoInsuficiente { disponible: Monto::Fiat(u.fiat), requerido: Monto::Fiat(reserva_fiat) });
+                }
+                u.mover(&[Monto::Fiat(-reserva_fiat)])?;
+            },
+            (Lado::Compra, None) => (),
+            (Lado::Venta, _) => {
+                let bal = u.balances.get(&cripto).copied().ok_or(XyzError::CriptoNoPoseida)?;
+                if bal < cantidad {
+                    return Err(XyzError::SaldoInsuficiente { disponible: Monto::Cripto(cripto.clone(), bal), requerido: Monto::Cripto(cripto, cantidad) });
+                }
+                u.mover(&[Monto::Cripto(cripto.clone(), -cantidad)])?;
+            }
+        }
+
+        let id = self.mercado.nuevo_id();
+        let mut orden = Orden { id, dni, cripto, lado, precio, cantidad, pendiente: cantidad, reserva_fiat };
+        let mut ejecuciones = Vec::new();
+
+        loop {
+            match self.ejecutar_contra_mejor(&mut orden).and_then(|t| t.map(|t| self.nueva_transaccion(t)).transpose()) {
+                Ok(Some(ejecucion)) => ejecuciones.push(ejecucion),
+                Ok(None) => break,
+                // La orden no se hace: se vuelve a lo que dice el diario, que no tiene ni lo retenido ni lo ejecutado
+                Err(error) => {
+                    self.deshacer();
+                    return Err(error);
+                }
+            }
+        }
+
+        let ejecutado = orden.cantidad - orden.pendiente;
+        let en_libro = !orden.esta_completa() && orden.precio.is_some();
+        self.pendientes.push(Evento::OrdenColocada(orden.clone()));
+        if en_libro {
+            self.mercado.libro_mut(&orden.cripto.clone()).insertar(orden);
         } else {
-            false // No se encontro el usuario
+            self.liberar_reserva(&orden)?;
+        }
+        self.confirmar()?;
+        Ok(ResultadoOrden { id, ejecuciones, ejecutado, en_libro })
+    }
+
+    pub fn cancelar_orden(&mut self, dni: String, id: IdOrden) -> Result<(), XyzError> {
+        if self.mercado.buscar(id).is_none_or(|o| o.dni != dni) {
+            return Err(XyzError::OrdenNoEncontrada);
+        }
+
+        if let Some(orden) = self.mercado.quitar(id) {
+            self.liberar_reserva(&orden)?;
+            self.pendientes.push(Evento::OrdenQuitada(id));
+            self.confirmar()?;
+        }
+        Ok(())
+    }
+
+    // Extracto de la cuenta del usuario entre las dos fechas. Las ganancias se calculan FIFO:
+    // cada venta se lleva primero lo comprado antes. Ademas de compras y ventas contra XYZ cuentan
+    // las ejecuciones de ordenes, y lo recibido por blockchain entra a la cotizacion del dia.
+    pub fn extracto(&self, dni: &str, desde: &Fecha, hasta: &Fecha) -> Result<Extracto, XyzError> {
+        let u = self.usuarios.iter().find(|u| u.dni == dni).ok_or(XyzError::UsuarioNoEncontrado)?;
+
+        let mut transacciones = Vec::new();
+        let mut apertura = Saldos::default();
+        let mut cierre = Saldos::default();
+        let mut lotes = Lotes::new();
+        let mut ganancia_realizada = MontoFiat::CERO;
+
+        for t in &self.transacciones {
+            let movimientos: Vec<Monto> = t.movimientos(&self.activos).unwrap_or_default().into_iter().filter(|(d, _)| d == dni).map(|(_, m)| m).collect();
+            // Los movimientos de staking no cambian el saldo total pero figuran en el extracto
+            if movimientos.is_empty() && !matches!(t, Transaccion::Staking { dni: d, .. } if d == dni) {
+                continue;
+            }
+
+            let fecha = t.fecha();
+            let en_periodo = fecha.es_mayor(desde) && hasta.es_mayor(fecha);
+            if !fecha.es_mayor(desde) {
+                movimientos.iter().for_each(|m| apertura.aplicar(m));
+            }
+            if hasta.es_mayor(fecha) {
+                movimientos.iter().for_each(|m| cierre.aplicar(m));
+            }
+            if en_periodo {
+                transacciones.push(t.clone());
+            }
+
+            let ganancia = t.mover_lotes(dni, &mut lotes, &self.activos);
+            if en_periodo {
+                ganancia_realizada += ganancia;
+            }
+        }
+
+        // Lo que tiene hoy segun su cuenta en el mayor, incluido lo retenido en ordenes abiertas y lo bloqueado en staking
+        let actual = self.mayor.saldos(&Cuenta::Usuario(u.dni.clone()));
+
+        let mut cartera = Valuacion { fiat: actual.fiat, total: actual.fiat, ..Valuacion::default() };
+        let mut ganancia_no_realizada = MontoFiat::CERO;
+        for (cripto, monto) in actual.cripto.iter().filter(|(_, m)| m.es_positivo()) {
+            let (decimales, cotizacion) = (self.decimales(cripto)?, self.cotizar(cripto)?);
+            let valor = valor_en_fiat(*monto, decimales, cotizacion).ok_or(XyzError::MontoFueraDeRango)?;
+            cartera.cripto.insert(cripto.clone(), valor);
+            cartera.total += valor;
+
+            let en_lotes = valor_en_fiat(lotes.cantidad(cripto), decimales, cotizacion).ok_or(XyzError::MontoFueraDeRango)?;
+            ganancia_no_realizada += en_lotes - lotes.costo(cripto);
         }
+
+        Ok(Extracto {
+            dni: dni.to_string(),
+            desde: desde.clone(),
+            hasta: hasta.clone(),
+            transacciones,
+            apertura,
+            cierre,
+            cartera,
+            ganancia_realizada,
+            ganancia_no_realizada
+        })
+    }
+
+    pub fn libro(&self, cripto: &Prefijo) -> Option<&LibroOrdenes> {
+        self.mercado.libro(cripto)
+    }
+
+    pub fn usuario(&self, dni: &str) -> Option<&Usuario> {
+        self.usuarios.iter().find(|u| u.dni == dni)
+    }
+
+    pub fn usuarios(&self) -> &[Usuario] {
+        &self.usuarios
     }
 
-    pub fn retirar_blockchain(&mut self, dni: String, cripto: Prefijo, blockchain: String, monto_cripto: f64) -> bool {
-        let user = self.usuarios.iter_mut().find(|u| u.dni == dni);
+    pub fn transacciones(&self) -> &[Transaccion] {
+        &self.transacciones
+    }
+
+    pub fn ordenes_abiertas<'a>(&'a self, dni: &'a str) -> Vec<&'a Orden> {
+        self.mercado.ordenes_de(dni).collect()
+    }
+
+    // Cruza la orden con la mejor del otro lado del libro al precio de esta ultima.
+    // Devuelve la transaccion a anotar, o None si ya no hay nada que ejecutar.
+    fn ejecutar_contra_mejor(&mut self, orden: &mut Orden) -> Result<Option<Transaccion>, XyzError> {
+        if orden.esta_completa() {
+            return Ok(None);
+        }
+
+        let decimales = self.decimales(&orden.cripto)?;
+        let libro = self.mercado.libro_mut(&orden.cripto);
+        let Some(contraparte) = libro.mejor_mut(orden.lado.opuesto()) else { return Ok(None) };
+        let Some(precio) = contraparte.precio.filter(|p| orden.cruza(*p)) else { return Ok(None) };
+
+        let (comprador, vendedor) = match orden.lado {
+            Lado::Compra => (&orden.dni, &contraparte.dni),
+            Lado::Venta => (&contraparte.dni, &orden.dni)
+        };
+        let (Some(i_comprador), Some(i_vendedor)) = (self.usuarios.iter().position(|u| &u.dni == comprador), self.usuarios.iter().position(|u| &u.dni == vendedor)) else {
+            return Ok(None);
+        };
+
+        let mut cantidad = orden.pendiente.min(contraparte.pendiente);
+        if orden.lado == Lado::Compra && orden.precio.is_none() {
+            let Some(alcanza) = cantidad_por_fiat(self.usuarios[i_comprador].fiat, decimales, precio) else { return Ok(None) };
+            cantidad = cantidad.min(alcanza);
+        }
+        // Un cruce que por redondeo no vale ni un centavo no se ejecuta, nadie recibe cripto gratis
+        let Some(valor) = valor_en_fiat(cantidad, decimales, precio) else { return Ok(None) };
+        if !cantidad.es_positivo() || !valor.es_positivo() {
+            return Ok(None);
+        }
+
+        // El fiat sale de la reserva de la compra limite y, si por redondeo no alcanza, del saldo disponible
+        let reserva = match orden.lado {
+            Lado::Compra if orden.precio.is_some() => orden.reserva_fiat,
+            Lado::Compra => MontoFiat::CERO,
+            Lado::Venta => contraparte.reserva_fiat
+        };
+        let de_reserva = valor.min(reserva);
+        let del_saldo = valor - de_reserva;
+        if self.usuarios[i_comprador].fiat < del_saldo {
+            return Ok(None);
+        }
+
+        // La orden que llega es la taker y la que esperaba en el libro la maker
+        let (comision_comprador, comision_vendedor) = match orden.lado {
+            Lado::Compra => (self.comisiones.taker_cripto(cantidad), self.comisiones.maker_fiat(valor)),
+            Lado::Venta => (self.comisiones.maker_cripto(cantidad), self.comisiones.taker_fiat(valor))
+        };
+        let (comision_comprador, comision_vendedor) = (en_rango(comision_comprador)?, en_rango(comision_vendedor)?);
+
+        // Si la contraparte termina se le devuelve lo que le sobro de la reserva. Los saldos se mueven
+        // antes que las ordenes: si alguno no entra en un monto no se toca nada.
+        let completa = cantidad == contraparte.pendiente;
+        let sobrante = if completa && orden.lado == Lado::Venta { reserva - de_reserva } else { MontoFiat::CERO };
+        let al_comprador = [Monto::Fiat(sobrante - del_saldo), Monto::Cripto(orden.cripto.clone(), cantidad - comision_comprador)];
+        let al_vendedor = [Monto::Fiat(valor - comision_vendedor)];
+        self.usuarios[i_comprador].mover(&al_comprador)?;
+        if let Err(error) = self.usuarios[i_vendedor].mover(&al_vendedor) {
+            self.usuarios[i_comprador].mover(&al_comprador.map(|m| -m))?;
+            return Err(error);
+        }
+
+        match orden.lado {
+            Lado::Compra => orden.reserva_fiat -= de_reserva,
+            Lado::Venta => contraparte.reserva_fiat -= de_reserva
+        }
+        orden.pendiente -= cantidad;
+        contraparte.pendiente -= cantidad;
+
+        if completa {
+            // Lo que le sobraba ya se devolvio arriba
+            if let Some(terminada) = libro.quitar_mejor(orden.lado.opuesto()) {
+                self.pendientes.push(Evento::OrdenQuitada(terminada.id));
+            }
+        } else {
+            self.pendientes.push(Evento::OrdenActualizada(contraparte.clone()));
+        }
+
+        let t = Transaccion::EjecucionOrden {
+            fecha: self.reloj.hoy(),
+            comprador: self.usuarios[i_comprador].dni.clone(),
+            vendedor: self.usuarios[i_vendedor].dni.clone(),
+            cripto: orden.cripto.clone(),
+            precio,
+            monto: cantidad,
+            comision_comprador,
+            comision_vendedor,
+            saldos: Vec::new()
+        };
+        Ok(Some(t))
+    }
 
-        if let Some(u) = user {
-            if !u.validado {
-                return false; // No esta validado
+    // Devuelve al usuario lo que la orden tenia retenido y no se uso
+    fn liberar_reserva(&mut self, orden: &Orden) -> Result<(), XyzError> {
+        if let Some(u) = self.usuarios.iter_mut().find(|u| u.dni == orden.dni) {
+            match orden.lado {
+                Lado::Compra => u.mover(&[Monto::Fiat(orden.reserva_fiat)])?,
+                Lado::Venta => u.mover(&[Monto::Cripto(orden.cripto.clone(), orden.pendiente)])?
             }
+        }
+        Ok(())
+    }
+
+    // Las operaciones de todas las transacciones que pasan el filtro
+    pub fn operaciones(&self, filtro: &Filtro) -> Vec<Operacion> {
+        self.transacciones.iter().flat_map(|t| t.operaciones(&self.activos)).filter(|o| filtro.incluye(o)).collect()
+    }
+
+    pub fn resumen(&self, filtro: &Filtro) -> Agregado {
+        analitica::agregar(&self.operaciones(filtro))
+    }
+
+    pub fn ranking(&self, filtro: &Filtro, criterio: Criterio, n: usize) -> Vec<(Prefijo, Agregado)> {
+        analitica::ranking(&self.operaciones(filtro), criterio, n, &self.activos)
+    }
+
+    pub fn serie(&self, filtro: &Filtro, periodo: Periodo) -> Vec<(Fecha, Agregado)> {
+        analitica::serie(&self.operaciones(filtro), periodo, filtro.desde.as_ref(), filtro.hasta.as_ref())
+    }
+
+    pub fn flujo_neto(&self, filtro: &Filtro) -> HashMap<Prefijo, MontoCripto> {
+        analitica::flujo_neto(&self.operaciones(filtro))
+    }
+
+    pub fn top_cripto_ventas(&self) -> Option<Prefijo> {
+        self.top(TipoOperacion::Venta, Criterio::Operaciones)
+    }
+
+    pub fn top_cripto_compras(&self) -> Option<Prefijo> {
+        self.top(TipoOperacion::Compra, Criterio::Operaciones)
+    }
+
+    pub fn top_volumen_ventas(&self) -> Option<Prefijo> {
+        self.top(TipoOperacion::Venta, Criterio::Volumen)
+    }
+
+    pub fn top_volumen_compras(&self) -> Option<Prefijo> {
+        self.top(TipoOperacion::Compra, Criterio::Volumen)
+    }
+
+    // En todo el historial; si hay empate, la primera por ticker
+    fn top(&self, tipo: TipoOperacion, criterio: Criterio) -> Option<Prefijo> {
+        let filtro = Filtro { tipos: vec![tipo], ..Filtro::default() };
+        self.ranking(&filtro, criterio, 1).into_iter().next().map(|(cripto, _)| cripto)
+    }
+}
 
-            if let Some(bal) = u.balances.get_mut(&cripto) {
-                if *bal < monto_cripto {
-                    return false // Balance insuficiente
+impl Transaccion {
+    pub fn fecha(&self) -> &Fecha {
+        match self {
+            Transaccion::IngresoDinero { fecha, .. } |
+            Transaccion::CompraCripto { fecha, .. } |
+            Transaccion::VentaCripto { fecha, .. } |
+            Transaccion::RetiroCripto { fecha, .. } |
+            Transaccion::RecepcionCripto { fecha, .. } |
+            Transaccion::RetiroFiat { fecha, .. } |
+            Transaccion::ReintegroRetiro { fecha, .. } |
+            Transaccion::EjecucionOrden { fecha, .. } |
+            Transaccion::Intercambio { fecha, .. } |
+            Transaccion::Staking { fecha, .. } |
+            Transaccion::RecompensaStaking { fecha, .. } => fecha
+        }
+    }
+
+    // Cuanto cambia el saldo (disponible mas retenido en ordenes) de cada usuario involucrado.
+    // None si algun valor no se puede calcular, lo que solo pasa con datos corruptos.
+    pub fn movimientos(&self, activos: &RegistroActivos) -> Option<Vec<(String, Monto)>> {
+        let movimientos = match self {
+            Transaccion::IngresoDinero { dni, monto, .. } => vec![(dni.clone(), Monto::Fiat(*monto))],
+            Transaccion::CompraCripto { dni, cripto, cotizacion, monto, comision, .. } => {
+                let precio = activos.valor_en_fiat(*monto, cripto, *cotizacion)?;
+                vec![(dni.clone(), Monto::Fiat(-precio.sumar(*comision)?)), (dni.clone(), Monto::Cripto(cripto.clone(), *monto))]
+            },
+            Transaccion::VentaCripto { dni, cripto, cotizacion, monto, comision, .. } => {
+                let valor = activos.valor_en_fiat(*monto, cripto, *cotizacion)?;
+                vec![(dni.clone(), Monto::Cripto(cripto.clone(), -*monto)), (dni.clone(), Monto::Fiat(valor.restar(*comision)?))]
+            },
+            Transaccion::RetiroCripto { dni, cripto, monto, comision, .. } => vec![(dni.clone(), Monto::Cripto(cripto.clone(), -monto.sumar(*comision)?))],
+            Transaccion::RecepcionCripto { dni, cripto, monto, .. } => vec![(dni.clone(), Monto::Cripto(cripto.clone(), *monto))],
+            Transaccion::RetiroFiat { dni, monto, comision, .. } => vec![(dni.clone(), Monto::Fiat(-monto.sumar(*comision)?))],
+            Transaccion::ReintegroRetiro { dni, cripto, monto, comision, .. } => vec![(dni.clone(), Monto::Cripto(cripto.clone(), monto.sumar(*comision)?))],
+            Transaccion::EjecucionOrden { comprador, vendedor, cripto, precio, monto, comision_comprador, comision_vendedor, .. } => {
+                let valor = activos.valor_en_fiat(*monto, cripto, *precio)?;
+                vec![
+                    (comprador.clone(), Monto::Fiat(-valor)),
+                    (comprador.clone(), Monto::Cripto(cripto.clone(), monto.restar(*comision_comprador)?)),
+                    (vendedor.clone(), Monto::Cripto(cripto.clone(), -*monto)),
+                    (vendedor.clone(), Monto::Fiat(valor.restar(*comision_vendedor)?))
+                ]
+            },
+            Transaccion::Intercambio { dni, origen, monto_origen, destino, monto_destino, .. } => {
+                vec![(dni.clone(), Monto::Cripto(origen.clone(), -*monto_origen)), (dni.clone(), Monto::Cripto(destino.clone(), *monto_destino))]
+            },
+            Transaccion::Staking { .. } => vec![],
+            Transaccion::RecompensaStaking { dni, cripto, monto, .. } => vec![(dni.clone(), Monto::Cripto(cripto.clone(), *monto))]
+        };
+        Some(movimientos)
+    }
+
+    // Lo que hizo cada usuario involucrado, para las estadisticas
+    pub fn operaciones(&self, activos: &RegistroActivos) -> Vec<Operacion> {
+        let operacion = |dni: &str, tipo, cripto: &Prefijo, monto: MontoCripto, cotizacion: MontoFiat, neto| Operacion {
+            fecha: self.fecha().clone(), dni: dni.to_string(), tipo, cripto: Some(cripto.clone()), monto,
+            valor: activos.valor_en_fiat(monto, cripto, cotizacion).unwrap_or_default(), neto
+        };
+        let fiat = |dni: &str, tipo, valor: MontoFiat| Operacion {
+            fecha: self.fecha().clone(), dni: dni.to_string(), tipo, cripto: None, monto: MontoCripto::CERO, valor, neto: MontoCripto::CERO
+        };
+
+        match self {
+            Transaccion::IngresoDinero { dni, monto, .. } => vec![fiat(dni, TipoOperacion::Ingreso, *monto)],
+            Transaccion::RetiroFiat { dni, monto, .. } => vec![fiat(dni, TipoOperacion::RetiroFiat, *monto)],
+            Transaccion::CompraCripto { dni, cripto, cotizacion, monto, .. } => vec![operacion(dni, TipoOperacion::Compra, cripto, *monto, *cotizacion, *monto)],
+            Transaccion::VentaCripto { dni, cripto, cotizacion, monto, .. } => vec![operacion(dni, TipoOperacion::Venta, cripto, *monto, *cotizacion, -*monto)],
+            Transaccion::RetiroCripto { dni, cripto, cotizacion, monto, comision, .. } => vec![operacion(dni, TipoOperacion::RetiroCripto, cripto, *monto, *cotizacion, -(*monto + *comision))],
+            Transaccion::RecepcionCripto { dni, cripto, cotizacion, monto, .. } => vec![operacion(dni, TipoOperacion::RecepcionCripto, cripto, *monto, *cotizacion, *monto)],
+            Transaccion::ReintegroRetiro { dni, cripto, cotizacion, monto, comision, .. } => vec![operacion(dni, TipoOperacion::Reintegro, cripto, *monto, *cotizacion, *monto + *comision)],
+            Transaccion::EjecucionOrden { comprador, vendedor, cripto, precio, monto, comision_comprador, .. } => vec![
+                operacion(comprador, TipoOperacion::Compra, cripto, *monto, *precio, *monto - *comision_comprador),
+                operacion(vendedor, TipoOperacion::Venta, cripto, *monto, *precio, -*monto)
+            ],
+            Transaccion::Intercambio { dni, origen, cotizacion_origen, monto_origen, destino, cotizacion_destino, monto_destino, .. } => vec![
+                operacion(dni, TipoOperacion::Intercambio, origen, *monto_origen, *cotizacion_origen, -*monto_origen),
+                operacion(dni, TipoOperacion::Intercambio, destino, *monto_destino, *cotizacion_destino, *monto_destino)
+            ],
+            Transaccion::RecompensaStaking { dni, cripto, cotizacion, monto, .. } => vec![operacion(dni, TipoOperacion::Recompensa, cripto, *monto, *cotizacion, *monto)],
+            Transaccion::Staking { .. } => vec![] // Lo bloqueado no se mueve
+        }
+    }
+
+    // Mueve los lotes de cripto del usuario segun la transaccion y devuelve la ganancia
+    // (o perdida) que realizo si fue una venta: lo cobrado menos lo que costo lo vendido
+    fn mover_lotes(&self, usuario: &str, lotes: &mut Lotes, activos: &RegistroActivos) -> MontoFiat {
+        let mut ganancia = MontoFiat::CERO;
+        match self {
+            Transaccion::CompraCripto { dni, cripto, cotizacion, monto, comision, .. } if dni == usuario => {
+                let precio = activos.valor_en_fiat(*monto, cripto, *cotizacion).unwrap_or_default();
+                lotes.entrada(cripto, *monto, precio + *comision);
+            },
+            Transaccion::VentaCripto { dni, cripto, cotizacion, monto, comision, .. } if dni == usuario => {
+                let valor = activos.valor_en_fiat(*monto, cripto, *cotizacion).unwrap_or_default();
+                ganancia = valor - *comision - lotes.salida(cripto, *monto);
+            },
+            Transaccion::RecepcionCripto { dni, cripto, cotizacion, monto, .. } if dni == usuario => {
+                lotes.entrada(cripto, *monto, activos.valor_en_fiat(*monto, cripto, *cotizacion).unwrap_or_default());
+            },
+            Transaccion::RetiroCripto { dni, cripto, monto, comision, .. } if dni == usuario => {
+                lotes.salida(cripto, *monto + *comision);
+            },
+            // El costo original ya salio de los lotes; vuelve a entrar a la cotizacion del retiro
+            Transaccion::ReintegroRetiro { dni, cripto, cotizacion, monto, comision, .. } if dni == usuario => {
+                let cantidad = *monto + *comision;
+                lotes.entrada(cripto, cantidad, activos.valor_en_fiat(cantidad, cripto, *cotizacion).unwrap_or_default());
+            },
+            Transaccion::EjecucionOrden { comprador, vendedor, cripto, precio, monto, comision_comprador, comision_vendedor, .. } => {
+                let valor = activos.valor_en_fiat(*monto, cripto, *precio).unwrap_or_default();
+                if vendedor == usuario {
+                    ganancia = valor - *comision_vendedor - lotes.salida(cripto, *monto);
                 }
-
-                let info = cripto.get_info();
-                
-                if let Some(bl) = info.blockchains.iter().find(|x| &x.nombre == &blockchain) {
-                    let hash = format!("{}#{}", bl.nombre, rand::random::<u32>());
-                    
-                    *bal -= monto_cripto;
-                    self.transacciones.push(Transaccion::RetiroCripto { fecha: get_fecha_actual(), usuario: u.clone(), blockchain: bl.nombre.clone(), hash, cripto, cotizacion: info.valor, monto: monto_cripto });
-                    self.escribir_archivos();
-                    true
-                } else {
-                    false // Blockchain no valido
+                if comprador == usuario {
+                    lotes.entrada(cripto, *monto - *comision_comprador, valor);
                 }
-            } else {
-                false // No tiene la cripto especificada
-            }
-        } else {
-            false // No es encontro el usuario
+            },
+            // Como vender el origen a su cotizacion y comprar el destino con lo cobrado; el spread queda en el costo
+            Transaccion::Intercambio { dni, origen, cotizacion_origen, monto_origen, destino, monto_destino, .. } if dni == usuario => {
+                let valor = activos.valor_en_fiat(*monto_origen, origen, *cotizacion_origen).unwrap_or_default();
+                ganancia = valor - lotes.salida(origen, *monto_origen);
+                lotes.entrada(destino, *monto_destino, valor);
+            },
+            // Entra como una recepcion, a la cotizacion del dia en que se pago
+            Transaccion::RecompensaStaking { dni, cripto, cotizacion, monto, .. } if dni == usuario => {
+                lotes.entrada(cripto, *monto, activos.valor_en_fiat(*monto, cripto, *cotizacion).unwrap_or_default());
+            },
+            _ => ()
         }
+        ganancia
     }
 
-    pub fn recepcion_blockchain(&mut self, dni: String, cripto: Prefijo, blockchain: String, monto_cripto: f64) -> bool {
-        let user = self.usuarios.iter_mut().find(|u| u.dni == dni);
+    pub fn saldos(&self) -> &[CambioSaldo] {
+        match self {
+            Transaccion::IngresoDinero { saldos, .. } |
+            Transaccion::CompraCripto { saldos, .. } |
+            Transaccion::VentaCripto { saldos, .. } |
+            Transaccion::RetiroCripto { saldos, .. } |
+            Transaccion::RecepcionCripto { saldos, .. } |
+            Transaccion::RetiroFiat { saldos, .. } |
+            Transaccion::ReintegroRetiro { saldos, .. } |
+            Transaccion::EjecucionOrden { saldos, .. } |
+            Transaccion::Intercambio { saldos, .. } |
+            Transaccion::Staking { saldos, .. } |
+            Transaccion::RecompensaStaking { saldos, .. } => saldos
+        }
+    }
 
-        if let Some(u) = user {
-            if !u.validado {
-                return false;
-            }
-            let info = cripto.get_info();
-            
-            *u.balances.entry(cripto.clone()).or_insert(0.0) += monto_cripto;
-            self.transacciones.push(Transaccion::RecepcionCripto { fecha: get_fecha_actual(), usuario: u.clone(), blockchain: blockchain, cripto, cotizacion: info.valor, monto: monto_cripto });
-            self.escribir_archivos();
-            true
-        } else {
-            false // No se encontro al usuario
+    fn saldos_mut(&mut self) -> &mut Vec<CambioSaldo> {
+        match self {
+            Transaccion::IngresoDinero { saldos, .. } |
+            Transaccion::CompraCripto { saldos, .. } |
+            Transaccion::VentaCripto { saldos, .. } |
+            Transaccion::RetiroCripto { saldos, .. } |
+            Transaccion::RecepcionCripto { saldos, .. } |
+            Transaccion::RetiroFiat { saldos, .. } |
+            Transaccion::ReintegroRetiro { saldos, .. } |
+            Transaccion::EjecucionOrden { saldos, .. } |
+            Transaccion::Intercambio { saldos, .. } |
+            Transaccion::Staking { saldos, .. } |
+            Transaccion::RecompensaStaking { saldos, .. } => saldos
         }
     }
 
-    pub fn retirar_fiat(&mut self, dni: String, medio: Medio, monto_fiat: f64) -> bool {
-        let user = self.usuarios.iter_mut().find(|u| u.dni == dni);
+    // Comisiones que cobro la plataforma en esta transaccion, sin las que son cero. Las que devolvio van en negativo.
+    pub fn comisiones(&self) -> Vec<Monto> {
+        let comisiones = match self {
+            Transaccion::CompraCripto { comision, .. } |
+            Transaccion::VentaCripto { comision, .. } |
+            Transaccion::RetiroFiat { comision, .. } => vec![Monto::Fiat(*comision)],
+            Transaccion::RetiroCripto { cripto, comision, .. } => vec![Monto::Cripto(cripto.clone(), *comision)],
+            Transaccion::ReintegroRetiro { cripto, comision, .. } => vec![Monto::Cripto(cripto.clone(), -*comision)], // Se devuelve
+            Transaccion::EjecucionOrden { cripto, comision_comprador, comision_vendedor, .. } => {
+                vec![Monto::Cripto(cripto.clone(), *comision_comprador), Monto::Fiat(*comision_vendedor)]
+            },
+            Transaccion::Intercambio { destino, spread, .. } => vec![Monto::Cripto(destino.clone(), *spread)],
+            Transaccion::IngresoDinero { .. } | Transaccion::RecepcionCripto { .. } |
+            Transaccion::Staking { .. } | Transaccion::RecompensaStaking { .. } => vec![]
+        };
+        comisiones.into_iter().filter(|c| match c {
+            Monto::Fiat(m) => *m != MontoFiat::CERO,
+            Monto::Cripto(_, m) => *m != MontoCripto::CERO
+        }).collect()
+    }
+}
 
-        if let Some(u) = user {
-            if !u.validado {
-                return false;
-            }
-            if u.fiat < monto_fiat {
-                return false // No hay balance suficiente
-            }
+impl Usuario {
+    pub fn new(nombre: String, apellido: String, email: String, dni: String) -> Usuario {
+        Usuario {
+            nombre,
+            apellido,
+            email,
+            dni,
+            estado: EstadoKyc::Pendiente,
+            identidad: None,
+            historial_kyc: Vec::new(),
+            nivel: Nivel::default(),
+            direcciones_deposito: HashMap::new(),
+            fiat: MontoFiat::CERO,
+            balances: HashMap::new()
+        }
+    }
 
-            u.fiat -= monto_fiat;
-            self.transacciones.push(Transaccion::RetiroFiat { fecha: get_fecha_actual(), usuario: u.clone(), monto: monto_fiat, medio });
-            self.escribir_archivos();
-            true
-        } else {
-            false // No se encontro al usuario
+    fn cambiar_estado(&mut self, cambio: CambioEstado, identidad: Option<Identidad>) {
+        if identidad.is_some() {
+            self.identidad = identidad;
         }
+        self.estado = cambio.nuevo;
+        self.historial_kyc.push(cambio);
     }
 
-    pub fn top_cripto_ventas(&self) -> Option<Prefijo> {
-        let mut map: HashMap<Prefijo, u32> = HashMap::new();
-            
-            for t in &self.transacciones {
-                match t {
-                    Transaccion::VentaCripto { cripto, .. } => *map.entry(cripto.clone()).or_insert(0) += 1,
-                    _ => ()
-                }
-            }
+    pub fn dni(&self) -> &str {
+        &self.dni
+    }
 
-            map.into_iter().max_by_key(|&(_, count)| count).map(|(pr, _)| pr) 
+    pub fn fiat(&self) -> MontoFiat {
+        self.fiat
     }
 
-    pub fn top_cripto_compras(&self) -> Option<Prefijo> {
-        let mut map: HashMap<Prefijo, u32> = HashMap::new();
-            
-            for t in &self.transacciones {
-                match t {
-                    Transaccion::CompraCripto { cripto, ..} => *map.entry(cripto.clone()).or_insert(0) += 1,
-                    _ => ()
-                }
-            }
+    pub fn balances(&self) -> &HashMap<Prefijo, MontoCripto> {
+        &self.balances
+    }
 
-            map.into_iter().max_by_key(|&(_, count)| count).map(|(pr, _)| pr) 
+    // Saldo disponible en la moneda del monto
+    pub fn saldo(&self, moneda: &Monto) -> Monto {
+        match moneda {
+            Monto::Fiat(_) => Monto::Fiat(self.fiat),
+            Monto::Cripto(cripto, _) => Monto::Cripto(cripto.clone(), self.balances.get(cripto).copied().unwrap_or_default())
+        }
     }
 
-    pub fn top_volumen_ventas(&self) -> Option<Prefijo> {
-        let mut map: HashMap<Prefijo, f64> = HashMap::new();
-            
-        for t in &self.transacciones {
-            match t {
-                Transaccion::VentaCripto { cripto, monto, ..} => *map.entry(cripto.clone()).or_insert(0.0) += monto,
-                _ => ()
+    // Suma el movimiento al saldo y devuelve el saldo resultante; None si no entra en un monto
+    fn aplicar(&mut self, movimiento: &Monto) -> Option<Monto> {
+        match movimiento {
+            Monto::Fiat(monto) => {
+                self.fiat = self.fiat.sumar(*monto)?;
+                Some(Monto::Fiat(self.fiat))
+            },
+            Monto::Cripto(cripto, monto) => {
+                let bal = self.balances.entry(cripto.clone()).or_default();
+                *bal = bal.sumar(*monto)?;
+                Some(Monto::Cripto(cripto.clone(), *bal))
             }
         }
-
-        map.into_iter().max_by(|(_, totala), (_, totalb)| totala.partial_cmp(totalb).unwrap_or(std::cmp::Ordering::Equal)).map(|(pr, _)| pr)
     }
 
-    pub fn top_volumen_compras(&self) -> Option<Prefijo> {
-        let mut map: HashMap<Prefijo, f64> = HashMap::new();
-            
-        for t in &self.transacciones {
-            match t {
-                Transaccion::CompraCripto { cripto, monto, ..} => *map.entry(cripto.clone()).or_insert(0.0) += monto,
-                _ => ()
+    // Suma los movimientos a los saldos, todos o ninguno: si alguno no entra en un monto no cambia nada
+    fn mover(&mut self, movimientos: &[Monto]) -> Result<(), XyzError> {
+        let (fiat, balances) = (self.fiat, self.balances.clone());
+        for movimiento in movimientos {
+            if self.aplicar(movimiento).is_none() {
+                (self.fiat, self.balances) = (fiat, balances);
+                return Err(XyzError::MontoFueraDeRango);
             }
         }
-
-        map.into_iter().max_by(|(_, totala), (_, totalb)| totala.partial_cmp(totalb).unwrap_or(std::cmp::Ordering::Equal)).map(|(pr, _)| pr)
+        Ok(())
     }
 }
 
-impl Prefijo {
-    fn cotizacion(&self) -> f64 {
-        match  self {
-            Prefijo::BTC => 45000.0,
-            Prefijo::ETH => 3000.0,
-            Prefijo::LTC => 200.0,
-            Prefijo::XRP => 1.0,
-            Prefijo::BCH => 500.0,
-            Prefijo::ADA => 1.23
-        }
-    } 
-
-    fn get_info(&self) -> CriptoMoneda {
-        match self {
-            Prefijo::BTC => CriptoMoneda { valor: self.cotizacion(), blockchains: vec![Blockchain{nombre: "Bitcoin".to_string(), prefijo: "BTC".to_string()}]},
-            Prefijo::ETH => CriptoMoneda { valor: self.cotizacion(), blockchains: vec![Blockchain{nombre: "Ethereum".to_string(), prefijo: "ETH".to_string()}]},
-            Prefijo::LTC => CriptoMoneda { valor: self.cotizacion(), blockchains: vec![Blockchain{nombre: "Litecoin".to_string(), prefijo: "LTC".to_string()}]},
-            Prefijo::XRP => CriptoMoneda { valor: self.cotizacion(), blockchains: vec![Blockchain{nombre: "Ripple".to_string(), prefijo: "XRP".to_string()}]},
-            Prefijo::BCH => CriptoMoneda { valor: self.cotizacion(), blockchains: vec![Blockchain{nombre: "Bitcoin Cash".to_string(), prefijo: "BCH".to_string()}]},
-            Prefijo::ADA => CriptoMoneda { valor: self.cotizacion(), blockchains: vec![Blockchain{nombre: "Cardano".to_string(), prefijo: "ADA".to_string()}]},
-        }
-    }
+#[cfg(test)]
+use crate::cotizador::CotizadorMemoria;
+#[cfg(test)]
+use crate::reglas::Topes;
+#[cfg(test)]
+use crate::blockchain::FormatoDireccion;
+#[cfg(test)]
+use crate::redes::{Red, ARCHIVO_REDES};
+#[cfg(test)]
+use crate::activos::ARCHIVO_ACTIVOS;
+#[cfg(test)]
+use reloj::RelojFijo;
+#[cfg(test)]
+use crate::staking::PlanStaking;
+#[cfg(test)]
+use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+#[cfg(test)]
+use proptest::prelude::{prop, prop_oneof, proptest, Just, ProptestConfig, Strategy};
+
+#[cfg(test)]
+const DIRECCION_BTC: &str = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa";
+#[cfg(test)]
+const DIRECCION_ROBADA: &str = "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy";
+
+#[cfg(test)]
+fn fiat(monto: &str) -> MontoFiat {
+    monto.parse().unwrap()
 }
 
-impl Usuario {
-    pub fn new(nombre: String, apellido: String, email: String, dni: String) -> Usuario {
-        Usuario {
-            nombre,
-            apellido,
-            email,
-            dni,
-            validado: false,
-            fiat: 0.0,
-            balances: HashMap::new()
-        }
-    }
+#[cfg(test)]
+fn cripto(prefijo: &Prefijo, monto: &str) -> MontoCripto {
+    MontoCripto::parse(monto, RegistroActivos::por_defecto().decimales(prefijo).unwrap()).unwrap()
 }
 
 #[test]
@@ -367,7 +2218,7 @@ fn test_usuario_new() {
 
 #[test]
 fn test_xyz_new() {
-    let plataforma = XYZ::new();
+    let plataforma = XYZ::new(Persistencia::memoria());
 
     assert!(plataforma.transacciones.is_empty());
     assert!(plataforma.usuarios.is_empty());
@@ -375,202 +2226,465 @@ fn test_xyz_new() {
 
 #[test]
 fn test_xyz_crear_usuario() {
-    let mut plataforma = XYZ::new();
+    let mut plataforma = XYZ::new(Persistencia::memoria());
 
-    assert!(plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()));
+    assert!(plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).is_ok());
 
     assert_eq!(1, plataforma.usuarios.len());
     assert_eq!("John".to_string(), plataforma.usuarios[0].nombre);
-    assert_eq!(false, plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()));
+    assert_eq!(Err(XyzError::UsuarioExistente), plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()));
 }
 
 #[test]
 fn test_xyz_validar_usuario() {
-    let mut plataforma = XYZ::new();
-    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string());
-
-    assert!(plataforma.validar_usuario("123123123".to_string()));
-    assert_eq!(true, plataforma.usuarios[0].validado);
-    assert_eq!(false, plataforma.validar_usuario("no existe".to_string()));
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    assert_eq!(Some(EstadoKyc::Pendiente), plataforma.estado_kyc("123123123"));
+
+    // Sin datos no se puede aprobar, y los datos tienen que ser validos
+    assert_eq!(Err(XyzError::TransicionKyc { desde: EstadoKyc::Pendiente, hasta: EstadoKyc::Aprobado }), plataforma.aprobar_usuario("123123123".to_string(), "compliance".to_string(), String::new()));
+    let menor = Identidad { fecha_nacimiento: plataforma.hoy(), ..identidad_de_prueba("30.000.000") };
+    assert_eq!(Err(XyzError::Identidad(ErrorIdentidad::MenorDeEdad)), plataforma.enviar_identidad("123123123".to_string(), menor));
+    let sin_dni = identidad_de_prueba("123");
+    assert_eq!(Err(XyzError::Identidad(ErrorIdentidad::DocumentoInvalido)), plataforma.enviar_identidad("123123123".to_string(), sin_dni));
+    let otro_email = Identidad { email: "otro@gmail.com".to_string(), ..identidad_de_prueba("30.000.000") };
+    assert_eq!(Err(XyzError::Identidad(ErrorIdentidad::EmailDistinto)), plataforma.enviar_identidad("123123123".to_string(), otro_email));
+
+    plataforma.enviar_identidad("123123123".to_string(), identidad_de_prueba("30.000.000")).unwrap();
+    plataforma.rechazar_usuario("123123123".to_string(), "compliance".to_string(), "Foto ilegible".to_string()).unwrap();
+    assert_eq!(Err(XyzError::UsuarioNoValidado), plataforma.ingresar_dinero("123123123".to_string(), fiat("100")));
+    validar(&mut plataforma, "123123123");
+    assert_eq!(Some(EstadoKyc::Aprobado), plataforma.estado_kyc("123123123"));
+
+    let historial = plataforma.historial_kyc("123123123").unwrap();
+    let estados: Vec<EstadoKyc> = historial.iter().map(|c| c.nuevo).collect();
+    assert_eq!(vec![EstadoKyc::EnRevision, EstadoKyc::Rechazado, EstadoKyc::EnRevision, EstadoKyc::Aprobado], estados);
+    assert_eq!(None, historial[0].revisor);
+    assert_eq!(Some("compliance".to_string()), historial[1].revisor.clone());
+    assert_eq!("Foto ilegible", historial[1].nota);
+    assert_eq!(Some(identidad_de_prueba("30000000")), plataforma.usuarios[0].identidad);
+
+    // Con o sin puntos, el mismo documento no valida una segunda cuenta mientras la primera lo tenga
+    plataforma.crear_usuario("Jane".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "456".to_string()).unwrap();
+    assert_eq!(Err(XyzError::Identidad(ErrorIdentidad::DocumentoEnUso)), plataforma.enviar_identidad("456".to_string(), identidad_de_prueba("30000000")));
+    assert_eq!(Err(XyzError::Identidad(ErrorIdentidad::DocumentoEnUso)), plataforma.enviar_identidad("456".to_string(), identidad_de_prueba("30.000.000")));
+    plataforma.enviar_identidad("456".to_string(), identidad_de_prueba("30.000.001")).unwrap();
+
+    assert_eq!(Err(XyzError::UsuarioNoEncontrado), plataforma.aprobar_usuario("no existe".to_string(), "compliance".to_string(), String::new()));
+}
 
+#[test]
+fn test_xyz_suspendido_retira_pero_no_opera() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = XYZ::new(persistencia.clone());
+    crear_validado(&mut plataforma, "1", "1000");
+    depositar(&mut plataforma, "1", Prefijo::BTC, "Bitcoin", cripto(&Prefijo::BTC, "1"));
+    plataforma.colocar_orden("1".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("50000")), cripto(&Prefijo::BTC, "0.5")).unwrap();
+
+    // Sus ordenes salen del libro y recupera lo retenido
+    plataforma.suspender_usuario("1".to_string(), "compliance".to_string(), "Actividad sospechosa".to_string()).unwrap();
+    assert!(plataforma.ordenes_abiertas("1").is_empty());
+    assert_eq!(cripto(&Prefijo::BTC, "1"), plataforma.usuarios[0].balances[&Prefijo::BTC]);
+
+    assert_eq!(Err(XyzError::UsuarioSuspendido), plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.001")));
+    assert_eq!(Err(XyzError::UsuarioSuspendido), plataforma.vender_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")));
+    assert!(matches!(plataforma.colocar_orden("1".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Mercado, cripto(&Prefijo::BTC, "0.1")), Err(XyzError::UsuarioSuspendido)));
+    assert_eq!(Err(XyzError::UsuarioSuspendido), plataforma.ingresar_dinero("1".to_string(), fiat("10")));
+    assert!(plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("500")).is_ok());
+    assert!(plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.5")).is_ok());
+
+    let cargada = XYZ::cargar_desde(persistencia).unwrap();
+    mismos_saldos(&plataforma, &cargada);
+    assert_eq!(Some(EstadoKyc::Suspendido), cargada.estado_kyc("1"));
+
+    plataforma.rehabilitar_usuario("1".to_string(), "compliance".to_string(), "Revisado".to_string()).unwrap();
+    assert!(plataforma.vender_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")).is_ok());
 }
 
 #[test]
 fn test_xyz_ingresar_dinero() {
-    let mut plataforma = XYZ::new();
-    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string());
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
 
-    assert_eq!(false, plataforma.ingresar_dinero("123123123".to_string(), 200.0));
-    plataforma.validar_usuario("123123123".to_string());
-    assert!(plataforma.ingresar_dinero("123123123".to_string(), 200.0));
+    assert_eq!(Err(XyzError::UsuarioNoValidado), plataforma.ingresar_dinero("123123123".to_string(), fiat("200")));
+    validar(&mut plataforma, "123123123");
+    assert_eq!(Ok(0), plataforma.ingresar_dinero("123123123".to_string(), fiat("200")));
 
-    match plataforma.transacciones[0] {
-        Transaccion::IngresoDinero { .. } => assert!(true),
-        _ => assert!(false)
-    }
+    assert!(matches!(plataforma.transacciones[0], Transaccion::IngresoDinero { .. }));
 
-    assert_eq!(200.0, plataforma.usuarios[0].fiat);
+    assert_eq!(fiat("200"), plataforma.usuarios[0].fiat);
 
-    assert_eq!(false, plataforma.ingresar_dinero("no existe".to_string(), 200.0));
+    assert_eq!(Err(XyzError::UsuarioNoEncontrado), plataforma.ingresar_dinero("no existe".to_string(), fiat("200")));
 
 }
 
 #[test]
 fn test_xyz_comprar_cripto() {
-    let mut plataforma = XYZ::new();
-    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string());
-    plataforma.validar_usuario("123123123".to_string());
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    validar(&mut plataforma, "123123123");
 
-    assert_eq!(false, plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 1.0));
-    plataforma.ingresar_dinero("123123123".to_string(), 50000.0);
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 1.0));
+    assert_eq!(Err(XyzError::SaldoInsuficiente { disponible: Monto::Fiat(fiat("0")), requerido: Monto::Fiat(fiat("45000")) }), plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")));
+    plataforma.ingresar_dinero("123123123".to_string(), fiat("50000")).unwrap();
+    assert_eq!(Ok(1), plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")));
 
     println!("{:?}", plataforma.transacciones[0]);
 
-    match plataforma.transacciones[1] {
-        Transaccion::CompraCripto { .. } => assert!(true),
-        _ => assert!(false)
-    }
+    assert!(matches!(plataforma.transacciones[1], Transaccion::CompraCripto { .. }));
 
-    assert_eq!(&1.0, plataforma.usuarios[0].balances.get(&Prefijo::BTC).unwrap());
+    assert_eq!(cripto(&Prefijo::BTC, "1"), plataforma.usuarios[0].balances[&Prefijo::BTC]);
 
-    assert_eq!(false, plataforma.comprar_cripto("no existe".to_string(), Prefijo::BTC, 1.0));
+    assert_eq!(Err(XyzError::UsuarioNoEncontrado), plataforma.comprar_cripto("no existe".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")));
 }
 
 #[test]
 fn test_xyz_vender_cripto() {
-    let mut plataforma = XYZ::new();
-    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string());
-    assert_eq!(false, plataforma.vender_cripto("no existe".to_string(), Prefijo::BTC, 1.0)); // no existe
-    
-    assert_eq!(false, plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, 1.0)); // sin validar
-    plataforma.validar_usuario("123123123".to_string());
-    assert_eq!(false, plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, 1.0)); // sin cripto
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    assert_eq!(Err(XyzError::UsuarioNoEncontrado), plataforma.vender_cripto("no existe".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1"))); // no existe
 
-    plataforma.ingresar_dinero("123123123".to_string(), 100000.0);
-    plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 1.5);
+    assert_eq!(Err(XyzError::UsuarioNoValidado), plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1"))); // sin validar
+    validar(&mut plataforma, "123123123");
+    assert_eq!(Err(XyzError::CriptoNoPoseida), plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1"))); // sin cripto
 
-    assert_eq!(false, plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, 5.0)); // sin balance suficiente
+    plataforma.ingresar_dinero("123123123".to_string(), fiat("100000")).unwrap();
+    plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1.5")).unwrap();
 
-    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, 1.0));
+    assert_eq!(Err(XyzError::SaldoInsuficiente { disponible: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "1.5")), requerido: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "5")) }), plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "5"))); // sin balance suficiente
 
-    match plataforma.transacciones[2] {
-        Transaccion::VentaCripto { .. } => assert!(true),
-        _ => assert!(false)
-    }
+    assert_eq!(Ok(2), plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")));
+
+    assert!(matches!(plataforma.transacciones[2], Transaccion::VentaCripto { .. }));
 
 
-    assert_eq!(32500.000022222222, plataforma.usuarios[0].fiat);
-    assert_eq!(&0.5, plataforma.usuarios[0].balances.get(&Prefijo::BTC).unwrap());
+    // 100000 - 1.5 * 45000 + 1 * 45000
+    assert_eq!(fiat("77500"), plataforma.usuarios[0].fiat);
+    assert_eq!(cripto(&Prefijo::BTC, "0.5"), plataforma.usuarios[0].balances[&Prefijo::BTC]);
+}
+
+#[test]
+fn test_xyz_compra_venta_ida_y_vuelta() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    validar(&mut plataforma, "123123123");
+    plataforma.ingresar_dinero("123123123".to_string(), fiat("1000")).unwrap();
+
+    // 0.333333 ADA a 1.23 no es un numero exacto de centavos, pero ida y vuelta deja todo igual
+    for (prefijo, monto) in [(Prefijo::ADA, "0.333333"), (Prefijo::BTC, "0.00012345"), (Prefijo::ETH, "0.123456789"), (Prefijo::XRP, "17.000001")] {
+        plataforma.comprar_cripto("123123123".to_string(), prefijo.clone(), cripto(&prefijo, monto)).unwrap();
+        plataforma.vender_cripto("123123123".to_string(), prefijo.clone(), cripto(&prefijo, monto)).unwrap();
+
+        assert_eq!(fiat("1000"), plataforma.usuarios[0].fiat);
+        assert_eq!(MontoCripto::CERO, plataforma.usuarios[0].balances[&prefijo]);
+    }
 }
 
 #[test]
 fn test_xyz_retirar_blockchain() {
-    let mut plataforma = XYZ::new();
-    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string());
-    assert_eq!(false, plataforma.retirar_blockchain("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string(), 0.2)); // sin validar
-    assert_eq!(false, plataforma.retirar_blockchain("no existe".to_string(), Prefijo::BTC, "Bitcoin".to_string(), 0.2)); // no existe usuario
-    
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    assert_eq!(Err(XyzError::UsuarioNoValidado), plataforma.retirar_blockchain("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.2"))); // sin validar
+    assert_eq!(Err(XyzError::UsuarioNoEncontrado), plataforma.retirar_blockchain("no existe".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.2"))); // no existe usuario
 
-    plataforma.validar_usuario("123123123".to_string());
-    assert_eq!(false, plataforma.retirar_blockchain("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string(), 0.2)); // sin cripto
 
-    plataforma.ingresar_dinero("123123123".to_string(), 100000.0);
-    plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 1.5);
+    validar(&mut plataforma, "123123123");
+    assert_eq!(Err(XyzError::CriptoNoPoseida), plataforma.retirar_blockchain("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.2"))); // sin cripto
 
-    assert_eq!(false, plataforma.retirar_blockchain("123123123".to_string(), Prefijo::BTC, "No existe".to_string(), 0.2));
-    assert_eq!(false, plataforma.retirar_blockchain("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string(), 10.0));
-    assert!(plataforma.retirar_blockchain("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string(), 0.2));
+    plataforma.ingresar_dinero("123123123".to_string(), fiat("100000")).unwrap();
+    plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1.5")).unwrap();
 
-    match plataforma.transacciones[2] {
-        Transaccion::RetiroCripto { .. } => assert!(true),
-        _ => assert!(false)
-    }
+    assert_eq!(Err(XyzError::BlockchainInvalida), plataforma.retirar_blockchain("123123123".to_string(), Prefijo::BTC, "No existe".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.2")));
+    assert_eq!(Err(XyzError::SaldoInsuficiente { disponible: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "1.5")), requerido: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "10")) }), plataforma.retirar_blockchain("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "10")));
+    assert_eq!(Ok(2), plataforma.retirar_blockchain("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.2")));
+
+    assert!(matches!(plataforma.transacciones[2], Transaccion::RetiroCripto { .. }));
 
 
-    assert_eq!(&1.3, plataforma.usuarios[0].balances.get(&Prefijo::BTC).unwrap());
+    assert_eq!(cripto(&Prefijo::BTC, "1.3"), plataforma.usuarios[0].balances[&Prefijo::BTC]);
 }
 
 #[test]
-fn test_xyz_recepcion_blockchain() {
-    let mut plataforma = XYZ::new();
-    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string());
-    assert_eq!(false, plataforma.recepcion_blockchain("no existe".to_string(), Prefijo::BTC, "Bitcoin".to_string(), 0.03)); // sin validar
-    assert_eq!(false, plataforma.recepcion_blockchain("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string(), 0.03)); // sin validar
+fn test_xyz_retirar_blockchain_direccion_y_hash() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "1", "100000");
+    plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")).unwrap();
+
+    assert_eq!(Err(XyzError::DireccionInvalida(ErrorDireccion::Checksum)), plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb".to_string(), cripto(&Prefijo::BTC, "0.1")));
+    assert_eq!(Err(XyzError::DireccionInvalida(ErrorDireccion::Formato)), plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed".to_string(), cripto(&Prefijo::BTC, "0.1")));
+
+    // Dos retiros iguales tienen distinto id, y el id es siempre el mismo para el mismo contenido
+    let primero = plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.1")).unwrap();
+    let segundo = plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.1")).unwrap();
+    let hash = |id: IdTransaccion| match &plataforma.transacciones[id] {
+        Transaccion::RetiroCripto { hash, .. } => hash.clone(),
+        _ => panic!("no es un retiro")
+    };
+    assert_eq!(64, hash(primero).len());
+    assert_ne!(hash(primero), hash(segundo));
+    let fecha = plataforma.hoy();
+    let esperado = hash_hex(&[&primero.to_string(), "1", "Bitcoin", DIRECCION_BTC, "BTC", "0.1", "0", &format!("{}/{}/{}", fecha.dia, fecha.mes, fecha.anio)]);
+    assert_eq!(esperado, hash(primero));
+}
 
-    plataforma.validar_usuario("123123123".to_string());
+#[test]
+fn test_xyz_redes_configurables() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = XYZ::new(persistencia.clone());
+    crear_validado(&mut plataforma, "1", "100000");
+    depositar(&mut plataforma, "1", Prefijo::ETH, "Ethereum", cripto(&Prefijo::ETH, "2"));
+    let destino = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+    // Otra red de la misma cripto, con su propia comision y minimo
+    let arbitrum = Red { comision: cripto(&Prefijo::ETH, "0.0001"), retiro_minimo: cripto(&Prefijo::ETH, "0.01"), ..Red::new("Arbitrum", FormatoDireccion::Ethereum, 20) };
+    plataforma.redes_mut().agregar(Prefijo::ETH, arbitrum);
+    assert_eq!(Err(XyzError::RetiroMenorAlMinimo { minimo: cripto(&Prefijo::ETH, "0.01") }), plataforma.retirar_blockchain("1".to_string(), Prefijo::ETH, "Arbitrum".to_string(), destino.to_string(), cripto(&Prefijo::ETH, "0.005")));
+    let id = plataforma.retirar_blockchain("1".to_string(), Prefijo::ETH, "Arbitrum".to_string(), destino.to_string(), cripto(&Prefijo::ETH, "0.5")).unwrap();
+    assert!(matches!(&plataforma.transacciones[id], Transaccion::RetiroCripto { blockchain, .. } if blockchain == "Arbitrum"));
+    assert_eq!(cripto(&Prefijo::ETH, "1.4999"), plataforma.usuarios[0].balances[&Prefijo::ETH]);
+    assert_eq!(Err(XyzError::BlockchainInvalida), plataforma.retirar_blockchain("1".to_string(), Prefijo::ETH, "Bitcoin".to_string(), destino.to_string(), cripto(&Prefijo::ETH, "0.1")));
+
+    // Una red deshabilitada no deja retirar ni pedir direccion, pero acredita lo que ya llego
+    let direccion = plataforma.direccion_deposito("1".to_string(), Prefijo::ETH, "Ethereum".to_string()).unwrap();
+    assert!(plataforma.redes_mut().habilitar(&Prefijo::ETH, "Ethereum", false));
+    assert_eq!(Err(XyzError::RedDeshabilitada), plataforma.retirar_blockchain("1".to_string(), Prefijo::ETH, "Ethereum".to_string(), destino.to_string(), cripto(&Prefijo::ETH, "0.1")));
+    assert_eq!(Err(XyzError::RedDeshabilitada), plataforma.direccion_deposito("1".to_string(), Prefijo::ETH, "Ethereum".to_string()));
+    assert!(plataforma.recepcion_blockchain(direccion, Prefijo::ETH, "Ethereum".to_string(), "tardio".to_string(), cripto(&Prefijo::ETH, "1"), 100).unwrap().is_some());
+
+    // Las redes tambien se pueden configurar en un archivo, que se lee al cargar
+    persistencia.escribir(ARCHIVO_REDES, r#"{"BTC": [{"nombre": "Lightning", "formato": "Bitcoin", "confirmaciones": 1}]}"#).unwrap();
+    let mut plataforma = XYZ::cargar_desde(persistencia).unwrap();
+    assert!(plataforma.redes().red(&Prefijo::BTC, "Bitcoin").is_none());
+    assert_eq!(Some(1), plataforma.redes().red(&Prefijo::BTC, "Lightning").map(|r| r.confirmaciones));
+    assert_eq!(Err(XyzError::BlockchainInvalida), plataforma.direccion_deposito("1".to_string(), Prefijo::ETH, "Ethereum".to_string()));
+}
 
-    assert!(plataforma.recepcion_blockchain("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string(), 0.03));
+#[test]
+fn test_xyz_activo_nuevo() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "1", "1000");
+    let usdt = Prefijo::new("USDT");
+    let tether = |monto: &str| MontoCripto::parse(monto, 6).unwrap();
+    assert_eq!(Err(XyzError::ActivoDesconocido), plataforma.comprar_cripto("1".to_string(), usdt.clone(), MontoCripto::from_unidades(1)));
+    assert_eq!(Err(XyzError::ActivoDesconocido), plataforma.decimales(&usdt));
+
+    // Un token listado solo con datos: cotizacion fija y la red de Ethereum
+    let activo = Activo { ticker: usdt.clone(), nombre: "Tether".to_string(), decimales: 6, redes: vec![Red::new("Ethereum", FormatoDireccion::Ethereum, 12)], precio: FuentePrecio::Fija(fiat("1")), limites: Limites::default() };
+    plataforma.agregar_activo(activo.clone()).unwrap();
+    plataforma.comprar_cripto("1".to_string(), usdt.clone(), tether("100.5")).unwrap();
+    assert_eq!(fiat("899.5"), plataforma.usuarios[0].fiat);
+    depositar(&mut plataforma, "1", usdt.clone(), "Ethereum", tether("20"));
+    plataforma.retirar_blockchain("1".to_string(), usdt.clone(), "Ethereum".to_string(), "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed".to_string(), tether("0.5")).unwrap();
+    assert_eq!(tether("120"), plataforma.usuarios[0].balances[&usdt]);
+    assert_eq!("120", plataforma.usuarios[0].balances[&usdt].formatear(plataforma.decimales(&usdt).unwrap()));
+
+    // El mismo ticker no puede cambiar de decimales, pero otra plataforma lo lista como quiere
+    let error = plataforma.agregar_activo(Activo { decimales: 8, ..activo.clone() });
+    assert!(matches!(error, Err(XyzError::ActivoInvalido(ErrorActivo::DecimalesDistintos { .. }))));
+    let mut otra = XYZ::new(Persistencia::memoria());
+    otra.agregar_activo(Activo { decimales: 2, ..activo.clone() }).unwrap();
+    assert_eq!(Ok(2), otra.decimales(&usdt));
+    assert_eq!(Ok(6), plataforma.decimales(&usdt));
+
+    // Al cargar, los activos salen del mismo lugar que el diario
+    assert!(XYZ::cargar_desde(plataforma.persistencia.clone()).is_err());
+    plataforma.persistencia.escribir(ARCHIVO_ACTIVOS, &serde_json::to_string(plataforma.activos()).unwrap()).unwrap();
+    let cargada = XYZ::cargar_desde(plataforma.persistencia.clone()).unwrap();
+    assert_eq!(tether("120"), cargada.usuarios[0].balances[&usdt]);
+
+    // Lo que ya se movio no se puede dejar de listar; set_activos reemplaza tambien las redes
+    assert_eq!(Err(XyzError::ActivoInvalido(ErrorActivo::EnUso(usdt.clone()))), plataforma.set_activos(RegistroActivos::por_defecto()));
+    let mut activos = RegistroActivos::por_defecto();
+    activos.agregar(Activo { redes: Vec::new(), ..activo }).unwrap();
+    plataforma.set_activos(activos).unwrap();
+    assert!(plataforma.redes().redes(&usdt).is_empty());
+    otra.set_activos(RegistroActivos::por_defecto()).unwrap();
+    assert_eq!(Err(XyzError::ActivoDesconocido), otra.vender_cripto("1".to_string(), usdt.clone(), tether("1")));
+}
 
-    match plataforma.transacciones[0] {
-        Transaccion::RecepcionCripto { .. } => assert!(true),
-        _ => assert!(false)
-    }
+#[test]
+fn test_xyz_retiro_blockchain_estados() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = XYZ::new(persistencia.clone());
+    plataforma.set_comisiones(TablaComisiones::new(0, 0));
+    plataforma.redes_mut().red_mut(&Prefijo::BTC, "Bitcoin").unwrap().comision = cripto(&Prefijo::BTC, "0.001");
+    crear_validado(&mut plataforma, "1", "100000");
+    plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")).unwrap();
+
+    let confirmado = plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.1")).unwrap();
+    assert_eq!(Some(&EstadoRetiro::Pendiente), plataforma.estado_retiro(confirmado));
+    assert_eq!(Err(XyzError::TransicionRetiro { desde: EstadoRetiro::Pendiente }), plataforma.registrar_confirmaciones(confirmado, 1));
+    plataforma.marcar_enviada(confirmado).unwrap();
+    plataforma.registrar_confirmaciones(confirmado, 3).unwrap();
+    assert_eq!(Some(&EstadoRetiro::Enviada { confirmaciones: 3 }), plataforma.estado_retiro(confirmado));
+    plataforma.registrar_confirmaciones(confirmado, 6).unwrap();
+    assert_eq!(Some(&EstadoRetiro::Confirmada), plataforma.estado_retiro(confirmado));
+    assert!(matches!(plataforma.marcar_fallida(confirmado, "tarde".to_string()), Err(XyzError::TransicionRetiro { .. })));
+
+    // Al fallar se devuelve el monto y la comision de red
+    let fallido = plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.2")).unwrap();
+    plataforma.marcar_enviada(fallido).unwrap();
+    assert_eq!(cripto(&Prefijo::BTC, "0.002"), plataforma.recaudacion().cripto(&Prefijo::BTC));
+    let reintegro = plataforma.marcar_fallida(fallido, "Rechazada por la red".to_string()).unwrap();
+    assert!(matches!(plataforma.transacciones[reintegro], Transaccion::ReintegroRetiro { retiro, .. } if retiro == fallido));
+    assert_eq!(Some(&EstadoRetiro::Fallida("Rechazada por la red".to_string())), plataforma.estado_retiro(fallido));
+    assert_eq!(cripto(&Prefijo::BTC, "0.899"), plataforma.usuarios[0].balances[&Prefijo::BTC]);
+    assert_eq!(cripto(&Prefijo::BTC, "0.001"), plataforma.recaudacion().cripto(&Prefijo::BTC));
+    assert!(matches!(plataforma.marcar_fallida(fallido, String::new()), Err(XyzError::TransicionRetiro { .. })));
+    assert_eq!(Err(XyzError::RetiroCriptoNoEncontrado), plataforma.marcar_enviada(0));
+    assert_eq!(Err(XyzError::RetiroCriptoNoEncontrado), plataforma.marcar_enviada(100));
+
+    let cargada = XYZ::cargar_desde(persistencia).unwrap();
+    mismos_saldos(&plataforma, &cargada);
+    assert_eq!(Some(&EstadoRetiro::Confirmada), cargada.estado_retiro(confirmado));
+    assert_eq!(plataforma.estado_retiro(fallido), cargada.estado_retiro(fallido));
+}
 
+#[test]
+fn test_xyz_recepcion_blockchain() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    assert_eq!(Err(XyzError::UsuarioNoEncontrado), plataforma.direccion_deposito("no existe".to_string(), Prefijo::BTC, "Bitcoin".to_string())); // no existe
+    assert_eq!(Err(XyzError::UsuarioNoValidado), plataforma.direccion_deposito("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string())); // sin validar
+
+    validar(&mut plataforma, "123123123");
+    assert_eq!(Err(XyzError::BlockchainInvalida), plataforma.direccion_deposito("123123123".to_string(), Prefijo::BTC, "Ethereum".to_string()));
+    let direccion = plataforma.direccion_deposito("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string()).unwrap();
+    assert_eq!(Ok(()), validar_direccion(FormatoDireccion::Bitcoin, &direccion));
+    assert_eq!(Ok(direccion.clone()), plataforma.direccion_deposito("123123123".to_string(), Prefijo::BTC, "Bitcoin".to_string()));
+
+    assert_eq!(Err(XyzError::BlockchainInvalida), plataforma.recepcion_blockchain(direccion.clone(), Prefijo::ETH, "Bitcoin".to_string(), "tx1".to_string(), cripto(&Prefijo::ETH, "1"), 6));
+    assert_eq!(Err(XyzError::DireccionDesconocida), plataforma.recepcion_blockchain(DIRECCION_BTC.to_string(), Prefijo::BTC, "Bitcoin".to_string(), "tx1".to_string(), cripto(&Prefijo::BTC, "0.03"), 6));
+    assert_eq!(Err(XyzError::MontoNoPositivo), plataforma.recepcion_blockchain(direccion.clone(), Prefijo::BTC, "Bitcoin".to_string(), "tx1".to_string(), MontoCripto::CERO, 6));
+    assert_eq!(Ok(Some(0)), plataforma.recepcion_blockchain(direccion.clone(), Prefijo::BTC, "Bitcoin".to_string(), "tx1".to_string(), cripto(&Prefijo::BTC, "0.03"), 6));
+    // El mismo deposito informado dos veces no se acredita dos veces
+    assert_eq!(Err(XyzError::DepositoDuplicado), plataforma.recepcion_blockchain(direccion, Prefijo::BTC, "Bitcoin".to_string(), "tx1".to_string(), cripto(&Prefijo::BTC, "0.03"), 6));
+
+    assert!(matches!(&plataforma.transacciones[0], Transaccion::RecepcionCripto { tx_id, .. } if tx_id == "tx1"));
+    assert_eq!(cripto(&Prefijo::BTC, "0.03"), plataforma.usuarios[0].balances[&Prefijo::BTC]);
+}
 
-    assert_eq!(&0.03, plataforma.usuarios[0].balances.get(&Prefijo::BTC).unwrap());
+#[test]
+fn test_xyz_deposito_espera_confirmaciones() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = XYZ::new(persistencia.clone());
+    crear_validado(&mut plataforma, "1", "0");
+    let direccion = plataforma.direccion_deposito("1".to_string(), Prefijo::ETH, "Ethereum".to_string()).unwrap();
+
+    // Ethereum pide 12 confirmaciones
+    assert_eq!(Ok(None), plataforma.recepcion_blockchain(direccion, Prefijo::ETH, "Ethereum".to_string(), "0xabc".to_string(), cripto(&Prefijo::ETH, "2"), 3));
+    assert_eq!(None, plataforma.usuarios[0].balances.get(&Prefijo::ETH));
+    assert!(matches!(plataforma.vender_cripto("1".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "1")), Err(XyzError::CriptoNoPoseida)));
+    assert_eq!(Ok(None), plataforma.confirmar_deposito("Ethereum", "0xabc", 11));
+    assert_eq!(11, plataforma.depositos_de("1")[0].confirmaciones);
+
+    // Se carga con el deposito pendiente y se acredita despues
+    let mut cargada = XYZ::cargar_desde(persistencia.clone()).unwrap();
+    mismos_saldos(&plataforma, &cargada);
+    assert_eq!(plataforma.depositos, cargada.depositos);
+    let id = cargada.confirmar_deposito("Ethereum", "0xabc", 12).unwrap().unwrap();
+    assert_eq!(Some(id), cargada.depositos_de("1")[0].acreditado);
+    assert_eq!(cripto(&Prefijo::ETH, "2"), cargada.usuarios[0].balances[&Prefijo::ETH]);
+    assert_eq!(Ok(None), cargada.confirmar_deposito("Ethereum", "0xabc", 20));
+    assert_eq!(Err(XyzError::DepositoNoEncontrado), cargada.confirmar_deposito("Bitcoin", "0xabc", 20));
+
+    let recargada = XYZ::cargar_desde(persistencia).unwrap();
+    mismos_saldos(&cargada, &recargada);
+    assert_eq!(cargada.depositos, recargada.depositos);
 }
 
 #[test]
 fn test_xyz_retirar_fiat() {
-    let mut plataforma = XYZ::new();
-    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string());
-    plataforma.validar_usuario("123123123".to_string());
-    plataforma.ingresar_dinero("123123123".to_string(), 100000.0);
-    
-    assert!(plataforma.retirar_fiat("123123123".to_string(), Medio::TransferenciaBancaria, 75000.0));
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    validar(&mut plataforma, "123123123");
+    plataforma.ingresar_dinero("123123123".to_string(), fiat("100000")).unwrap();
 
-    match plataforma.transacciones[1] {
-        Transaccion::RetiroFiat { .. } => assert!(true),
-        _ => assert!(false)
-    }
+    assert_eq!(Err(XyzError::SaldoInsuficiente { disponible: Monto::Fiat(fiat("100000")), requerido: Monto::Fiat(fiat("150000")) }), plataforma.retirar_fiat("123123123".to_string(), Medio::MercadoPago, fiat("150000")));
+    assert_eq!(Ok(1), plataforma.retirar_fiat("123123123".to_string(), Medio::TransferenciaBancaria, fiat("75000")));
 
+    assert!(matches!(plataforma.transacciones[1], Transaccion::RetiroFiat { .. }));
+
+
+    assert_eq!(fiat("25000"), plataforma.usuarios[0].fiat);
+}
+
+#[test]
+fn test_xyz_cotizador_memoria() {
+    let cotizador = CotizadorMemoria::new();
+    cotizador.set_cotizacion(Prefijo::ETH, fiat("2000"));
+
+    let mut plataforma = XYZ::con_cotizador(cotizador.clone(), Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    validar(&mut plataforma, "123123123");
+    plataforma.ingresar_dinero("123123123".to_string(), fiat("10000")).unwrap();
+
+    assert_eq!(Err(XyzError::CotizacionNoDisponible), plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")));
+    assert_eq!(Ok(1), plataforma.comprar_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "2")));
+    assert_eq!(fiat("6000"), plataforma.usuarios[0].fiat);
+
+    cotizador.set_cotizacion(Prefijo::ETH, fiat("2500"));
+    assert_eq!(Ok(2), plataforma.comprar_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "2")));
+    assert_eq!(fiat("1000"), plataforma.usuarios[0].fiat);
+
+    match &plataforma.transacciones[1] {
+        Transaccion::CompraCripto { cotizacion, .. } => assert_eq!(fiat("2000"), *cotizacion),
+        _ => panic!("se esperaba una compra")
+    }
+    match &plataforma.transacciones[2] {
+        Transaccion::CompraCripto { cotizacion, .. } => assert_eq!(fiat("2500"), *cotizacion),
+        _ => panic!("se esperaba una compra")
+    }
+}
 
-    assert_eq!(25000.0, plataforma.usuarios[0].fiat);
+#[test]
+fn test_xyz_error_display() {
+    assert_eq!("No se encontro el usuario", XyzError::UsuarioNoEncontrado.to_string());
+    assert_eq!("Saldo insuficiente: disponible 10.00, requerido 20.50", XyzError::SaldoInsuficiente { disponible: Monto::Fiat(fiat("10")), requerido: Monto::Fiat(fiat("20.5")) }.to_string());
+    assert_eq!("Saldo insuficiente: disponible 50000000 unidades minimas de BTC, requerido 100000000 unidades minimas de BTC", XyzError::SaldoInsuficiente { disponible: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "0.5")), requerido: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "1")) }.to_string());
 }
 
 #[test]
 fn test_xyz_top_cripto_compras() {
-    let mut plataforma = XYZ::new();
-    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string());
-    plataforma.validar_usuario("123123123".to_string());
-    plataforma.ingresar_dinero("123123123".to_string(), 1000000.0);
-    
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    validar(&mut plataforma, "123123123");
+    plataforma.ingresar_dinero("123123123".to_string(), fiat("1000000")).unwrap();
+
     assert_eq!(None, plataforma.top_cripto_compras());
 
 
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 2.0));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::ETH, 10.0));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.1));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.3));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.08));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::XRP, 300000.0));
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "2")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "10")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.3")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.08")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::XRP, cripto(&Prefijo::XRP, "300000")).is_ok());
 
     assert_eq!(Prefijo::BTC, plataforma.top_cripto_compras().unwrap());
 }
 
 #[test]
 fn test_xyz_top_cripto_ventas() {
-    let mut plataforma = XYZ::new();
-    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string());
-    plataforma.validar_usuario("123123123".to_string());
-    plataforma.ingresar_dinero("123123123".to_string(), 1000000.0);
-    
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    validar(&mut plataforma, "123123123");
+    plataforma.ingresar_dinero("123123123".to_string(), fiat("1000000")).unwrap();
+
     assert_eq!(None, plataforma.top_cripto_ventas());
 
 
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 2.0));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::ETH, 10.0));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.1));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.3));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.08));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::XRP, 300000.0));
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "2")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "10")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.3")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.08")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::XRP, cripto(&Prefijo::XRP, "300000")).is_ok());
 
-    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, 1.0));
-    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, 0.5));
-    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, 4.0));
-    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, 2.0));
-    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::XRP, 100000.0));
+    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "1")).is_ok());
+    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.5")).is_ok());
+    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "4")).is_ok());
+    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "2")).is_ok());
+    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::XRP, cripto(&Prefijo::XRP, "100000")).is_ok());
 
 
     assert_eq!(Prefijo::ETH, plataforma.top_cripto_ventas().unwrap());
@@ -578,46 +2692,1188 @@ fn test_xyz_top_cripto_ventas() {
 
 #[test]
 fn test_xyz_top_volumen_compras() {
-    let mut plataforma = XYZ::new();
-    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string());
-    plataforma.validar_usuario("123123123".to_string());
-    plataforma.ingresar_dinero("123123123".to_string(), 1000000.0);
-    
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    validar(&mut plataforma, "123123123");
+    plataforma.ingresar_dinero("123123123".to_string(), fiat("1000000")).unwrap();
+
     assert_eq!(None, plataforma.top_volumen_compras());
 
 
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 2.0));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::ETH, 10.0));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.1));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.3));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.08));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::XRP, 300000.0));
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "2")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "10")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.3")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.08")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::XRP, cripto(&Prefijo::XRP, "300000")).is_ok());
 
     assert_eq!(Prefijo::XRP, plataforma.top_volumen_compras().unwrap());
 }
 
 #[test]
 fn test_xyz_top_volumen_ventas() {
-    let mut plataforma = XYZ::new();
-    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string());
-    plataforma.validar_usuario("123123123".to_string());
-    plataforma.ingresar_dinero("123123123".to_string(), 1000000.0);
-    
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), "123123123".to_string()).unwrap();
+    validar(&mut plataforma, "123123123");
+    plataforma.ingresar_dinero("123123123".to_string(), fiat("1000000")).unwrap();
+
     assert_eq!(None, plataforma.top_volumen_ventas());
 
 
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 2.0));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::ETH, 10.0));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.1));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.3));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, 0.08));
-    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::XRP, 300000.0));
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "2")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "10")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.3")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.08")).is_ok());
+    assert!(plataforma.comprar_cripto("123123123".to_string(), Prefijo::XRP, cripto(&Prefijo::XRP, "300000")).is_ok());
 
-    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, 1.0));
-    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, 0.5));
-    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, 4.0));
-    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, 2.0));
+    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "1")).is_ok());
+    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.5")).is_ok());
+    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "4")).is_ok());
+    assert!(plataforma.vender_cripto("123123123".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "2")).is_ok());
 
 
     assert_eq!(Prefijo::ETH, plataforma.top_volumen_ventas().unwrap());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_xyz_swap() {
+    let persistencia = Persistencia::memoria();
+    let cotizador = CotizadorMemoria::new();
+    cotizador.set_cotizacion(Prefijo::BTC, fiat("40000"));
+    cotizador.set_cotizacion(Prefijo::ETH, fiat("3000"));
+    let mut plataforma = XYZ::con_cotizador(cotizador.clone(), persistencia.clone());
+    let mut comisiones = TablaComisiones::new(0, 0);
+    comisiones.set_spread(50); // 0.5%
+    plataforma.set_comisiones(comisiones);
+    crear_validado(&mut plataforma, "1", "4000");
+    plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")).unwrap();
+
+    assert_eq!(Err(XyzError::IntercambioMismaCripto), plataforma.swap("1".to_string(), Prefijo::BTC, Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")));
+    assert_eq!(Err(XyzError::CriptoNoPoseida), plataforma.swap("1".to_string(), Prefijo::ETH, Prefijo::BTC, cripto(&Prefijo::ETH, "1")));
+    assert!(matches!(plataforma.swap("1".to_string(), Prefijo::BTC, Prefijo::ETH, cripto(&Prefijo::BTC, "0.2")), Err(XyzError::SaldoInsuficiente { .. })));
+
+    // 0.1 BTC a 45000 son 4500, o sea 1.5 ETH, de los que XYZ se queda el 0.5%
+    cotizador.set_cotizacion(Prefijo::BTC, fiat("45000"));
+    let id = plataforma.swap("1".to_string(), Prefijo::BTC, Prefijo::ETH, cripto(&Prefijo::BTC, "0.1")).unwrap();
+    assert!(matches!(&plataforma.transacciones[id], Transaccion::Intercambio { monto_destino, spread, .. }
+        if *monto_destino == cripto(&Prefijo::ETH, "1.4925") && *spread == cripto(&Prefijo::ETH, "0.0075")));
+    assert_eq!(MontoCripto::CERO, plataforma.usuarios[0].balances[&Prefijo::BTC]);
+    assert_eq!(cripto(&Prefijo::ETH, "1.4925"), plataforma.usuarios[0].balances[&Prefijo::ETH]);
+    assert_eq!(cripto(&Prefijo::ETH, "0.0075"), plataforma.recaudacion().cripto(&Prefijo::ETH));
+
+    // En el extracto es una venta de BTC con ganancia y una compra de ETH a lo que valia el BTC
+    let hoy = plataforma.hoy();
+    let extracto = plataforma.extracto("1", &hoy, &hoy).unwrap();
+    assert_eq!(fiat("500"), extracto.ganancia_realizada);
+    assert_eq!(cripto(&Prefijo::ETH, "1.4925"), extracto.cierre.cripto(&Prefijo::ETH));
+    assert_eq!(fiat("-22.50"), extracto.ganancia_no_realizada); // 1.4925 ETH a 3000 contra 4500
+
+    // Y en las estadisticas, con una operacion por cada cripto
+    let intercambios = Filtro { tipos: vec![TipoOperacion::Intercambio], ..Filtro::default() };
+    assert_eq!(2, plataforma.resumen(&intercambios).operaciones);
+    assert_eq!(cripto(&Prefijo::BTC, "0.1"), plataforma.resumen(&intercambios).volumen_de(&Prefijo::BTC));
+    let flujo = plataforma.flujo_neto(&Filtro::default());
+    assert_eq!((MontoCripto::CERO, cripto(&Prefijo::ETH, "1.4925")), (flujo[&Prefijo::BTC], flujo[&Prefijo::ETH]));
+
+    let cargada = XYZ::cargar_desde(persistencia).unwrap();
+    mismos_saldos(&plataforma, &cargada);
+    assert_eq!(plataforma.recaudacion(), cargada.recaudacion());
+}
+
+#[test]
+fn test_xyz_staking() {
+    let persistencia = Persistencia::memoria();
+    let reloj = RelojFijo::new(Fecha::new(15, 2, 2024));
+    let mut plataforma = XYZ::new(persistencia.clone());
+    plataforma.set_reloj(reloj.clone());
+    plataforma.planes_staking_mut().set_plan(Prefijo::ETH, PlanStaking { apr_bps: 1000, enfriamiento: 7 });
+    crear_validado(&mut plataforma, "1", "100000");
+    plataforma.comprar_cripto("1".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "10")).unwrap();
+    let eth = |plataforma: &XYZ| plataforma.usuarios[0].balances[&Prefijo::ETH];
+
+    assert_eq!(Err(XyzError::StakingNoDisponible), plataforma.bloquear_staking("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")));
+    assert!(matches!(plataforma.bloquear_staking("1".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "20")), Err(XyzError::SaldoInsuficiente { .. })));
+    assert_eq!(Err(XyzError::MontoNoPositivo), plataforma.bloquear_staking("1".to_string(), Prefijo::ETH, MontoCripto::CERO));
+
+    plataforma.bloquear_staking("1".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "3.65")).unwrap();
+    assert_eq!(cripto(&Prefijo::ETH, "6.35"), eth(&plataforma));
+    assert!(plataforma.procesar_staking().unwrap().is_empty());
+
+    // Del 15/2 al 6/3 de 2024 son 20 dias por el 29 de febrero: 3.65 * 10% * 20 / 365 = 0.02
+    reloj.avanzar_dias(20);
+    let ids = plataforma.procesar_staking().unwrap();
+    assert_eq!(1, ids.len());
+    assert!(matches!(&plataforma.transacciones[ids[0]], Transaccion::RecompensaStaking { monto, desde, hasta, .. }
+        if *monto == cripto(&Prefijo::ETH, "0.02") && *desde == Fecha::new(15, 2, 2024) && *hasta == Fecha::new(6, 3, 2024)));
+    assert_eq!(cripto(&Prefijo::ETH, "6.37"), eth(&plataforma));
+    assert!(plataforma.procesar_staking().unwrap().is_empty()); // Otra vez el mismo dia no paga nada
+
+    // Al agregar o desbloquear primero se paga lo que ya genero
+    plataforma.bloquear_staking("1".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "1")).unwrap();
+    reloj.avanzar_dias(1);
+    assert!(matches!(plataforma.desbloquear_staking("1".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "5")), Err(XyzError::SaldoInsuficiente { .. })));
+    plataforma.desbloquear_staking("1".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "4.65")).unwrap();
+    assert_eq!(cripto(&Prefijo::ETH, "5.371273972"), eth(&plataforma)); // 4.65 * 10% / 365 hacia abajo
+    let posicion = plataforma.posicion_staking("1", &Prefijo::ETH).unwrap();
+    assert_eq!((MontoCripto::CERO, cripto(&Prefijo::ETH, "4.65")), (posicion.bloqueado, posicion.total()));
+    assert_eq!(Fecha::new(14, 3, 2024), posicion.desbloqueos[0].disponible);
+
+    // En el extracto lo bloqueado sigue siendo del usuario y las recompensas entran como ingresos
+    let extracto = plataforma.extracto("1", &Fecha::new(1, 2, 2024), &Fecha::new(31, 3, 2024)).unwrap();
+    assert_eq!(cripto(&Prefijo::ETH, "10.021273972"), extracto.cierre.cripto(&Prefijo::ETH));
+    assert_eq!(fiat("30063.82"), extracto.cartera.cripto[&Prefijo::ETH]);
+    assert_eq!(2, extracto.transacciones.iter().filter(|t| matches!(t, Transaccion::RecompensaStaking { .. })).count());
+    assert_eq!(3, extracto.transacciones.iter().filter(|t| matches!(t, Transaccion::Staking { .. })).count());
+    let recompensas = Filtro { tipos: vec![TipoOperacion::Recompensa], ..Filtro::default() };
+    assert_eq!(cripto(&Prefijo::ETH, "0.021273972"), plataforma.resumen(&recompensas).volumen_de(&Prefijo::ETH));
+
+    // En enfriamiento, desde un snapshot
+    plataforma.compactar().unwrap();
+    let cargada = XYZ::cargar_desde(persistencia.clone()).unwrap();
+    mismos_saldos(&plataforma, &cargada);
+    assert_eq!(plataforma.posicion_staking("1", &Prefijo::ETH), cargada.posicion_staking("1", &Prefijo::ETH));
+
+    reloj.avanzar_dias(6);
+    assert!(plataforma.procesar_staking().unwrap().is_empty());
+    reloj.avanzar_dias(1);
+    assert_eq!(1, plataforma.procesar_staking().unwrap().len());
+    assert_eq!(cripto(&Prefijo::ETH, "10.021273972"), eth(&plataforma));
+    assert_eq!(MontoCripto::CERO, plataforma.posicion_staking("1", &Prefijo::ETH).unwrap().total());
+
+    // Y repitiendo el diario
+    let cargada = XYZ::cargar_desde(persistencia).unwrap();
+    mismos_saldos(&plataforma, &cargada);
+    assert_eq!(plataforma.posicion_staking("1", &Prefijo::ETH), cargada.posicion_staking("1", &Prefijo::ETH));
+}
+
+#[test]
+fn test_xyz_libro_mayor() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = XYZ::new(persistencia.clone());
+    plataforma.set_reloj(RelojFijo::new(Fecha::new(1, 3, 2024)));
+    comisiones_de_prueba(&mut plataforma);
+    plataforma.comisiones.set_spread(50);
+    plataforma.planes_staking_mut().set_plan(Prefijo::ETH, PlanStaking { apr_bps: 1000, enfriamiento: 0 });
+    crear_validado(&mut plataforma, "a", "100000");
+    crear_validado(&mut plataforma, "b", "100000");
+
+    // Una operacion de cada tipo
+    plataforma.comprar_cripto("a".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")).unwrap();
+    plataforma.vender_cripto("a".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")).unwrap();
+    plataforma.retirar_fiat("b".to_string(), Medio::TransferenciaBancaria, fiat("100")).unwrap();
+    let fallido = plataforma.retirar_blockchain("a".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.2")).unwrap();
+    plataforma.retirar_blockchain("a".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.1")).unwrap();
+    plataforma.marcar_enviada(fallido).unwrap();
+    plataforma.marcar_fallida(fallido, "Rechazada por la red".to_string()).unwrap();
+    let direccion = plataforma.direccion_deposito("b".to_string(), Prefijo::ETH, "Ethereum".to_string()).unwrap();
+    plataforma.recepcion_blockchain(direccion, Prefijo::ETH, "Ethereum".to_string(), "tx1".to_string(), cripto(&Prefijo::ETH, "2"), 100).unwrap();
+    plataforma.colocar_orden("a".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("46000")), cripto(&Prefijo::BTC, "0.5")).unwrap();
+    plataforma.colocar_orden("b".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Mercado, cripto(&Prefijo::BTC, "0.2")).unwrap();
+    plataforma.swap("b".to_string(), Prefijo::BTC, Prefijo::ETH, cripto(&Prefijo::BTC, "0.1")).unwrap();
+    plataforma.bloquear_staking("b".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "1")).unwrap();
+    plataforma.set_reloj(RelojFijo::new(Fecha::new(31, 3, 2024)));
+    plataforma.procesar_staking().unwrap();
+
+    // Cada asiento balancea, el mayor cierra y la cuenta de cada usuario es su saldo total
+    let balance = plataforma.balance_de_comprobacion().unwrap();
+    assert_eq!(plataforma.transacciones.len(), plataforma.mayor().asientos().len());
+    assert_eq!(fiat("199900"), balance[&Cuenta::CajaFiat].fiat);
+    assert_eq!(plataforma.recaudacion().fiat, balance[&Cuenta::Comisiones].fiat);
+    assert_eq!(plataforma.recaudacion().cripto(&Prefijo::BTC), balance[&Cuenta::Comisiones].cripto(&Prefijo::BTC));
+    assert_eq!(plataforma.recaudacion().cripto(&Prefijo::ETH), balance[&Cuenta::Comisiones].cripto(&Prefijo::ETH));
+    // Lo retirado sale de la billetera de la red; lo del retiro fallido volvio
+    assert_eq!(cripto(&Prefijo::BTC, "-0.1"), balance[&Cuenta::BilleteraCaliente("Bitcoin".to_string())].cripto(&Prefijo::BTC));
+    assert_eq!(cripto(&Prefijo::ETH, "2"), balance[&Cuenta::BilleteraCaliente("Ethereum".to_string())].cripto(&Prefijo::ETH));
+    assert!(balance[&Cuenta::Recompensas].cripto(&Prefijo::ETH).es_positivo());
+
+    // Lo que cada transaccion le mueve a los usuarios es lo que asienta en sus cuentas
+    for (t, asiento) in plataforma.transacciones.iter().zip(plataforma.mayor().asientos()) {
+        let mut esperado: Vec<(String, Monto)> = agrupar(t.movimientos(plataforma.activos()).unwrap());
+        let mut asentado: Vec<(String, Monto)> = asiento.partidas.iter().filter_map(|p| match &p.cuenta {
+            Cuenta::Usuario(dni) => Some((dni.clone(), -p.monto.clone())),
+            _ => None
+        }).collect();
+        asentado = agrupar(asentado);
+        esperado.sort_by_key(|(dni, m)| (dni.clone(), m.to_string()));
+        asentado.sort_by_key(|(dni, m)| (dni.clone(), m.to_string()));
+        assert_eq!(esperado, asentado);
+    }
+
+    let cargada = XYZ::cargar_desde(persistencia.clone()).unwrap();
+    assert_eq!(balance, cargada.balance_de_comprobacion().unwrap());
+    plataforma.compactar().unwrap();
+    assert_eq!(balance, XYZ::cargar_desde(persistencia).unwrap().balance_de_comprobacion().unwrap());
+
+    // Un saldo que no sale de las transacciones no pasa el balance
+    plataforma.usuarios[1].fiat += fiat("1");
+    assert!(matches!(plataforma.balance_de_comprobacion(), Err(ErrorContable::SaldoNoCoincide { cuenta: Cuenta::Usuario(dni), .. }) if dni == "b"));
+}
+
+#[test]
+fn test_xyz_montos_invalidos() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "1", "1000");
+    plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.01")).unwrap();
+    let (fiat_antes, btc_antes, transacciones) = (plataforma.usuarios[0].fiat, plataforma.usuarios[0].balances[&Prefijo::BTC], plataforma.transacciones.len());
+
+    // Un retiro negativo sumaria saldo y una compra negativa crearia cripto de la nada
+    let negativo = cripto(&Prefijo::BTC, "-1");
+    assert_eq!(Err(XyzError::MontoNoPositivo), plataforma.ingresar_dinero("1".to_string(), fiat("-100")));
+    assert_eq!(Err(XyzError::MontoNoPositivo), plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("-100")));
+    assert_eq!(Err(XyzError::MontoNoPositivo), plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, negativo));
+    assert_eq!(Err(XyzError::MontoNoPositivo), plataforma.vender_cripto("1".to_string(), Prefijo::BTC, negativo));
+    assert_eq!(Err(XyzError::MontoNoPositivo), plataforma.swap("1".to_string(), Prefijo::BTC, Prefijo::ETH, negativo));
+    assert_eq!(Err(XyzError::MontoNoPositivo), plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), negativo));
+    assert_eq!(Err(XyzError::MontoNoPositivo), plataforma.ingresar_dinero("1".to_string(), MontoFiat::CERO));
+    assert_eq!(Err(XyzError::OrdenInvalida), plataforma.colocar_orden("1".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("40000")), negativo));
+    assert_eq!((fiat_antes, btc_antes, transacciones), (plataforma.usuarios[0].fiat, plataforma.usuarios[0].balances[&Prefijo::BTC], plataforma.transacciones.len()));
+
+    // Limites por operacion del fiat y de cada activo
+    plataforma.set_limites_fiat(Limites::new(Some(fiat("10")), Some(fiat("500"))));
+    assert_eq!(Err(XyzError::MontoMenorAlMinimo { minimo: Monto::Fiat(fiat("10")) }), plataforma.ingresar_dinero("1".to_string(), fiat("9.99")));
+    assert_eq!(Err(XyzError::MontoMayorAlMaximo { maximo: Monto::Fiat(fiat("500")) }), plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("500.01")));
+    plataforma.ingresar_dinero("1".to_string(), fiat("500")).unwrap();
+
+    let mut btc = plataforma.activos().activo(&Prefijo::BTC).unwrap().clone();
+    btc.limites = Limites::new(Some(cripto(&Prefijo::BTC, "0.001")), Some(cripto(&Prefijo::BTC, "0.005")));
+    plataforma.agregar_activo(btc).unwrap();
+    let menor = XyzError::MontoMenorAlMinimo { minimo: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "0.001")) };
+    assert_eq!(Err(menor.clone()), plataforma.vender_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.0009")));
+    assert_eq!(Err(menor), plataforma.colocar_orden("1".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Mercado, cripto(&Prefijo::BTC, "0.0009")).map(|_| ()));
+    assert!(matches!(plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.006")), Err(XyzError::MontoMayorAlMaximo { .. })));
+    plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.005")).unwrap();
+
+    // Lo que llega por blockchain se acredita aunque este fuera de los limites
+    depositar(&mut plataforma, "1", Prefijo::BTC, "Bitcoin", cripto(&Prefijo::BTC, "0.0001"));
+}
+
+#[test]
+fn test_xyz_saldos_fuera_de_rango() {
+    let maximo = MontoFiat::from_centavos(i64::MAX);
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "1", "0");
+    crear_validado(&mut plataforma, "2", "0");
+    plataforma.ingresar_dinero("1".to_string(), maximo).unwrap();
+
+    // El saldo del usuario no entra: no cambia nada
+    assert_eq!(Err(XyzError::MontoFueraDeRango), plataforma.ingresar_dinero("1".to_string(), fiat("0.01")));
+    assert_eq!((maximo, 1), (plataforma.usuarios[0].fiat, plataforma.transacciones.len()));
+    assert!(plataforma.balance_de_comprobacion().is_ok());
+
+    // Ni el total de un retiro con su comision
+    comisiones_de_prueba(&mut plataforma);
+    assert_eq!(Err(XyzError::MontoFueraDeRango), plataforma.retirar_fiat("1".to_string(), Medio::TransferenciaBancaria, maximo));
+
+    // El del usuario entra pero no el de la caja: el mayor no lo asienta y se deshace el ingreso
+    let error = plataforma.ingresar_dinero("2".to_string(), fiat("0.01"));
+    assert!(matches!(error, Err(XyzError::Contable(ErrorContable::SaldoFueraDeRango { cuenta: Cuenta::CajaFiat, .. }))));
+    assert_eq!((MontoFiat::CERO, 1), (plataforma.usuarios[1].fiat, plataforma.transacciones.len()));
+    assert!(plataforma.pendientes.is_empty());
+    assert!(plataforma.balance_de_comprobacion().is_ok());
+
+    // Un cruce que no entra en el saldo del comprador no mueve nada y la orden que llega no se coloca
+    let cotizador = CotizadorMemoria::new();
+    cotizador.set_cotizacion(Prefijo::BTC, fiat("0.01"));
+    let mut plataforma = XYZ::con_cotizador(cotizador, Persistencia::memoria());
+    crear_validado(&mut plataforma, "comprador", "10000000000");
+    crear_validado(&mut plataforma, "vendedor", "0");
+    plataforma.comprar_cripto("comprador".to_string(), Prefijo::BTC, MontoCripto::from_unidades(i64::MAX - 100)).unwrap();
+    depositar(&mut plataforma, "vendedor", Prefijo::BTC, "Bitcoin", cripto(&Prefijo::BTC, "1"));
+    let venta = plataforma.colocar_orden("vendedor".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("0.01")), cripto(&Prefijo::BTC, "1")).unwrap();
+    let antes = (plataforma.usuarios[0].fiat, plataforma.usuarios[0].balances[&Prefijo::BTC], plataforma.transacciones.len());
+
+    let compra = plataforma.colocar_orden("comprador".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Limite(fiat("0.01")), cripto(&Prefijo::BTC, "1"));
+    assert_eq!(Err(XyzError::MontoFueraDeRango), compra.map(|_| ()));
+    assert_eq!(antes, (plataforma.usuarios[0].fiat, plataforma.usuarios[0].balances[&Prefijo::BTC], plataforma.transacciones.len()));
+    assert_eq!(Some(cripto(&Prefijo::BTC, "1")), plataforma.mercado.buscar(venta.id).map(|o| o.pendiente));
+    assert_eq!(1, plataforma.mercado.ordenes().count());
+    assert!(plataforma.balance_de_comprobacion().is_ok());
+}
+
+// Lo que puede pedir un usuario en las pruebas de propiedades, con montos de cualquier signo
+#[cfg(test)]
+#[derive(Debug, Clone)]
+enum PedidoPrueba {
+    Ingresar(usize, i64),
+    Comprar(usize, bool, i64), // true para ETH, false para BTC
+    Vender(usize, bool, i64),
+    RetirarFiat(usize, i64),
+    RetirarCripto(usize, i64),
+    Swap(usize, bool, i64),
+    Bloquear(usize, i64),
+    Desbloquear(usize, i64),
+    Orden(usize, bool, Option<i64>, i64), // Compra o venta de BTC, limite o de mercado
+    PasarDias(u32)
+}
+
+#[cfg(test)]
+fn pedido_prueba() -> impl Strategy<Value = PedidoPrueba> {
+    let usuario = 0..2usize;
+    let fiat = -10_000_000i64..1_000_000_000;
+    let cripto = -100_000_000i64..10_000_000_000;
+    prop_oneof![
+        (usuario.clone(), fiat.clone()).prop_map(|(u, m)| PedidoPrueba::Ingresar(u, m)),
+        (usuario.clone(), prop::bool::ANY, cripto.clone()).prop_map(|(u, eth, m)| PedidoPrueba::Comprar(u, eth, m)),
+        (usuario.clone(), prop::bool::ANY, cripto.clone()).prop_map(|(u, eth, m)| PedidoPrueba::Vender(u, eth, m)),
+        (usuario.clone(), fiat.clone()).prop_map(|(u, m)| PedidoPrueba::RetirarFiat(u, m)),
+        (usuario.clone(), cripto.clone()).prop_map(|(u, m)| PedidoPrueba::RetirarCripto(u, m)),
+        (usuario.clone(), prop::bool::ANY, cripto.clone()).prop_map(|(u, eth, m)| PedidoPrueba::Swap(u, eth, m)),
+        (usuario.clone(), cripto.clone()).prop_map(|(u, m)| PedidoPrueba::Bloquear(u, m)),
+        (usuario.clone(), cripto.clone()).prop_map(|(u, m)| PedidoPrueba::Desbloquear(u, m)),
+        (usuario, prop::bool::ANY, prop::option::of(fiat), cripto).prop_map(|(u, compra, precio, m)| PedidoPrueba::Orden(u, compra, precio, m)),
+        (1..40u32).prop_map(PedidoPrueba::PasarDias),
+        Just(PedidoPrueba::PasarDias(0))
+    ]
+}
+
+// Aplica el pedido e indica si tenia un monto que no es positivo
+#[cfg(test)]
+fn aplicar_pedido(plataforma: &mut XYZ, reloj: &RelojFijo, pedido: PedidoPrueba) -> (Result<(), XyzError>, bool) {
+    let dni = |u: usize| u.to_string();
+    let de = |eth: bool| if eth { Prefijo::ETH } else { Prefijo::BTC };
+    let c = MontoCripto::from_unidades;
+    match pedido {
+        PedidoPrueba::Ingresar(u, m) => (plataforma.ingresar_dinero(dni(u), MontoFiat::from_centavos(m)).map(|_| ()), m <= 0),
+        PedidoPrueba::Comprar(u, eth, m) => (plataforma.comprar_cripto(dni(u), de(eth), c(m)).map(|_| ()), m <= 0),
+        PedidoPrueba::Vender(u, eth, m) => (plataforma.vender_cripto(dni(u), de(eth), c(m)).map(|_| ()), m <= 0),
+        PedidoPrueba::RetirarFiat(u, m) => (plataforma.retirar_fiat(dni(u), Medio::MercadoPago, MontoFiat::from_centavos(m)).map(|_| ()), m <= 0),
+        PedidoPrueba::RetirarCripto(u, m) => (plataforma.retirar_blockchain(dni(u), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), c(m)).map(|_| ()), m <= 0),
+        PedidoPrueba::Swap(u, eth, m) => (plataforma.swap(dni(u), de(eth), de(!eth), c(m)).map(|_| ()), m <= 0),
+        PedidoPrueba::Bloquear(u, m) => (plataforma.bloquear_staking(dni(u), Prefijo::ETH, c(m)).map(|_| ()), m <= 0),
+        PedidoPrueba::Desbloquear(u, m) => (plataforma.desbloquear_staking(dni(u), Prefijo::ETH, c(m)).map(|_| ()), m <= 0),
+        PedidoPrueba::Orden(u, compra, precio, m) => {
+            let lado = if compra { Lado::Compra } else { Lado::Venta };
+            let tipo = precio.map_or(TipoOrden::Mercado, |p| TipoOrden::Limite(MontoFiat::from_centavos(p)));
+            (plataforma.colocar_orden(dni(u), Prefijo::BTC, lado, tipo, c(m)).map(|_| ()), m <= 0 || precio.is_some_and(|p| p <= 0))
+        },
+        PedidoPrueba::PasarDias(dias) => {
+            reloj.avanzar_dias(dias);
+            (plataforma.procesar_staking().map(|_| ()), false)
+        }
+    }
+}
+
+#[cfg(test)]
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    // Ninguna secuencia de pedidos deja un saldo negativo ni descuadra el mayor; los montos
+    // que no son positivos se rechazan sin tocar nada
+    #[test]
+    fn test_xyz_ningun_saldo_negativo(pedidos in prop::collection::vec(pedido_prueba(), 1..40)) {
+        let persistencia = Persistencia::memoria();
+        let reloj = RelojFijo::new(Fecha::new(1, 3, 2024));
+        let mut plataforma = XYZ::new(persistencia.clone());
+        plataforma.set_reloj(reloj.clone());
+        plataforma.planes_staking_mut().set_plan(Prefijo::ETH, PlanStaking { apr_bps: 500, enfriamiento: 3 });
+        crear_validado(&mut plataforma, "0", "1000");
+        crear_validado(&mut plataforma, "1", "0");
+
+        for pedido in pedidos {
+            let transacciones = plataforma.transacciones.len();
+            let (resultado, invalido) = aplicar_pedido(&mut plataforma, &reloj, pedido);
+            if invalido {
+                assert!(resultado.is_err());
+                assert_eq!(transacciones, plataforma.transacciones.len());
+            }
+
+            for u in &plataforma.usuarios {
+                assert!(u.fiat >= MontoFiat::CERO, "{} quedo con {} de fiat", u.dni, u.fiat);
+                assert!(u.balances.values().all(|m| *m >= MontoCripto::CERO), "{} quedo con {:?}", u.dni, u.balances);
+            }
+            for orden in plataforma.mercado.ordenes() {
+                assert!(orden.reserva_fiat >= MontoFiat::CERO && orden.pendiente >= MontoCripto::CERO);
+            }
+            assert!(plataforma.staking.posiciones().iter().all(|p| p.bloqueado >= MontoCripto::CERO));
+            assert!(plataforma.balance_de_comprobacion().is_ok());
+        }
+        mismos_saldos(&plataforma, &XYZ::cargar_desde(persistencia).unwrap());
+    }
+}
+
+#[test]
+fn test_xyz_analitica() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "a", "100000");
+    crear_validado(&mut plataforma, "b", "100000");
+    plataforma.comprar_cripto("a".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")).unwrap();
+    plataforma.comprar_cripto("b".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.5")).unwrap();
+    plataforma.comprar_cripto("a".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "2")).unwrap();
+    plataforma.colocar_orden("a".to_string(), Prefijo::ETH, Lado::Venta, TipoOrden::Limite(fiat("3000")), cripto(&Prefijo::ETH, "1")).unwrap();
+    plataforma.colocar_orden("b".to_string(), Prefijo::ETH, Lado::Compra, TipoOrden::Mercado, cripto(&Prefijo::ETH, "1")).unwrap();
+    plataforma.retirar_blockchain("a".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.2")).unwrap();
+
+    // La ejecucion de la orden es una compra de b y una venta de a
+    let compras = Filtro { tipos: vec![TipoOperacion::Compra], ..Filtro::default() };
+    let ranking = plataforma.ranking(&compras, Criterio::Operaciones, 1);
+    assert_eq!(vec![Prefijo::BTC, Prefijo::ETH], ranking.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>());
+    assert_eq!(2, ranking[0].1.usuarios);
+    assert_eq!(fiat("67500"), ranking[0].1.volumen_fiat);
+    assert_eq!(cripto(&Prefijo::ETH, "3"), plataforma.resumen(&compras).volumen_de(&Prefijo::ETH));
+
+    let de_a = Filtro { dni: Some("a".to_string()), ..Filtro::default() };
+    assert_eq!(5, plataforma.resumen(&de_a).operaciones); // Ingreso, dos compras, la venta por orden y el retiro
+    assert_eq!(cripto(&Prefijo::ETH, "1"), plataforma.flujo_neto(&de_a)[&Prefijo::ETH]);
+    assert_eq!(cripto(&Prefijo::BTC, "1.3"), plataforma.flujo_neto(&Filtro::default())[&Prefijo::BTC]);
+
+    let hoy = plataforma.hoy();
+    let mut ayer = hoy.clone();
+    ayer.restar_dias(1);
+    let serie = plataforma.serie(&Filtro { desde: Some(ayer.clone()), hasta: Some(hoy.clone()), ..Filtro::default() }, Periodo::Diario);
+    assert_eq!(vec![(ayer.clone(), 0), (hoy, 8)], serie.into_iter().map(|(f, a)| (f, a.operaciones)).collect::<Vec<_>>());
+    assert!(plataforma.operaciones(&Filtro { hasta: Some(ayer), ..Filtro::default() }).is_empty());
+}
+
+#[cfg(test)]
+fn identidad_de_prueba(documento: &str) -> Identidad {
+    Identidad { documento: documento.to_string(), email: "John.doe@gmail.com".to_string(), fecha_nacimiento: Fecha::new(1, 1, 1990) }
+}
+
+// Cada usuario con su propio documento
+#[cfg(test)]
+fn validar(plataforma: &mut XYZ, dni: &str) {
+    let posicion = plataforma.usuarios.iter().position(|u| u.dni == dni).unwrap();
+    plataforma.enviar_identidad(dni.to_string(), identidad_de_prueba(&(30_000_000 + posicion).to_string())).unwrap();
+    plataforma.aprobar_usuario(dni.to_string(), "compliance".to_string(), "Documento verificado".to_string()).unwrap();
+}
+
+// Un deposito ya confirmado en la direccion del usuario
+#[cfg(test)]
+fn depositar(plataforma: &mut XYZ, dni: &str, cripto: Prefijo, blockchain: &str, monto: MontoCripto) -> IdTransaccion {
+    let direccion = plataforma.direccion_deposito(dni.to_string(), cripto.clone(), blockchain.to_string()).unwrap();
+    let tx_id = format!("tx{}", plataforma.depositos.len());
+    plataforma.recepcion_blockchain(direccion, cripto, blockchain.to_string(), tx_id, monto, 100).unwrap().unwrap()
+}
+
+#[cfg(test)]
+fn crear_validado(plataforma: &mut XYZ, dni: &str, fiat_inicial: &str) {
+    plataforma.crear_usuario("John".to_string(), "Doe".to_string(), "John.doe@gmail.com".to_string(), dni.to_string()).unwrap();
+    validar(plataforma, dni);
+    if fiat(fiat_inicial).es_positivo() {
+        plataforma.ingresar_dinero(dni.to_string(), fiat(fiat_inicial)).unwrap();
+    }
+}
+
+#[test]
+fn test_xyz_orden_limite_queda_en_libro_y_se_ejecuta_parcial() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "vendedor", "100000");
+    crear_validado(&mut plataforma, "comprador", "50000");
+    plataforma.comprar_cripto("vendedor".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")).unwrap();
+
+    let venta = plataforma.colocar_orden("vendedor".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("40000")), cripto(&Prefijo::BTC, "1")).unwrap();
+    assert!(venta.en_libro);
+    assert!(venta.ejecuciones.is_empty());
+    assert_eq!(MontoCripto::CERO, plataforma.usuarios[0].balances[&Prefijo::BTC]); // Queda retenido en la orden
+
+    let compra = plataforma.colocar_orden("comprador".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Mercado, cripto(&Prefijo::BTC, "0.4")).unwrap();
+    assert!(!compra.en_libro);
+    assert_eq!(cripto(&Prefijo::BTC, "0.4"), compra.ejecutado);
+    assert_eq!(1, compra.ejecuciones.len());
+
+    match &plataforma.transacciones[compra.ejecuciones[0]] {
+        Transaccion::EjecucionOrden { comprador, vendedor, precio, monto, .. } => {
+            assert_eq!("comprador", comprador);
+            assert_eq!("vendedor", vendedor);
+            assert_eq!(fiat("40000"), *precio);
+            assert_eq!(cripto(&Prefijo::BTC, "0.4"), *monto);
+        },
+        _ => panic!("se esperaba una ejecucion")
+    }
+
+    assert_eq!(fiat("34000"), plataforma.usuarios[1].fiat);
+    assert_eq!(cripto(&Prefijo::BTC, "0.4"), plataforma.usuarios[1].balances[&Prefijo::BTC]);
+    assert_eq!(fiat("71000"), plataforma.usuarios[0].fiat); // 100000 - 45000 + 16000
+
+    let resto = plataforma.libro(&Prefijo::BTC).unwrap().mejor(Lado::Venta).unwrap();
+    assert_eq!(venta.id, resto.id);
+    assert_eq!(cripto(&Prefijo::BTC, "0.6"), resto.pendiente);
+}
+
+#[test]
+fn test_xyz_orden_prioridad_precio_tiempo() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "a", "1000");
+    crear_validado(&mut plataforma, "b", "1000");
+    crear_validado(&mut plataforma, "c", "1000");
+    crear_validado(&mut plataforma, "comprador", "1000");
+    for dni in ["a", "b", "c"] {
+        plataforma.comprar_cripto(dni.to_string(), Prefijo::XRP, cripto(&Prefijo::XRP, "100")).unwrap();
+    }
+
+    plataforma.colocar_orden("a".to_string(), Prefijo::XRP, Lado::Venta, TipoOrden::Limite(fiat("1.10")), cripto(&Prefijo::XRP, "100")).unwrap();
+    plataforma.colocar_orden("b".to_string(), Prefijo::XRP, Lado::Venta, TipoOrden::Limite(fiat("1.05")), cripto(&Prefijo::XRP, "100")).unwrap();
+    plataforma.colocar_orden("c".to_string(), Prefijo::XRP, Lado::Venta, TipoOrden::Limite(fiat("1.10")), cripto(&Prefijo::XRP, "100")).unwrap();
+
+    // Barre b entero (mejor precio) y despues a (llego antes que c)
+    let compra = plataforma.colocar_orden("comprador".to_string(), Prefijo::XRP, Lado::Compra, TipoOrden::Limite(fiat("1.10")), cripto(&Prefijo::XRP, "150")).unwrap();
+    assert_eq!(2, compra.ejecuciones.len());
+    assert!(!compra.en_libro);
+
+    let vendedores: Vec<(String, MontoFiat, MontoCripto)> = compra.ejecuciones.iter().map(|&i| match &plataforma.transacciones[i] {
+        Transaccion::EjecucionOrden { vendedor, precio, monto, .. } => (vendedor.clone(), *precio, *monto),
+        _ => panic!("se esperaba una ejecucion")
+    }).collect();
+    assert_eq!(vec![("b".to_string(), fiat("1.05"), cripto(&Prefijo::XRP, "100")), ("a".to_string(), fiat("1.10"), cripto(&Prefijo::XRP, "50"))], vendedores);
+
+    // Pago 105 + 55; se le devuelve lo reservado de mas por la mejora de precio
+    assert_eq!(fiat("840"), plataforma.usuarios[3].fiat);
+    assert_eq!(fiat("1005"), plataforma.usuarios[1].fiat); // 1000 - 100 + 105
+
+    let ventas: Vec<&str> = plataforma.libro(&Prefijo::XRP).unwrap().ordenes(Lado::Venta).iter().map(|o| o.dni.as_str()).collect();
+    assert_eq!(vec!["a", "c"], ventas);
+}
+
+#[test]
+fn test_xyz_orden_compra_limite_sin_cruce_y_cancelacion() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "comprador", "1000");
+    crear_validado(&mut plataforma, "otro", "1000");
+
+    assert_eq!(Err(XyzError::OrdenInvalida), plataforma.colocar_orden("comprador".to_string(), Prefijo::ETH, Lado::Compra, TipoOrden::Limite(fiat("0")), cripto(&Prefijo::ETH, "1")));
+    assert_eq!(Err(XyzError::OrdenInvalida), plataforma.colocar_orden("comprador".to_string(), Prefijo::ETH, Lado::Compra, TipoOrden::Mercado, MontoCripto::CERO));
+    assert_eq!(Err(XyzError::SaldoInsuficiente { disponible: Monto::Fiat(fiat("1000")), requerido: Monto::Fiat(fiat("2000")) }),
+        plataforma.colocar_orden("comprador".to_string(), Prefijo::ETH, Lado::Compra, TipoOrden::Limite(fiat("2000")), cripto(&Prefijo::ETH, "1")));
+    assert_eq!(Err(XyzError::CriptoNoPoseida), plataforma.colocar_orden("comprador".to_string(), Prefijo::ETH, Lado::Venta, TipoOrden::Mercado, cripto(&Prefijo::ETH, "1")));
+
+    let compra = plataforma.colocar_orden("comprador".to_string(), Prefijo::ETH, Lado::Compra, TipoOrden::Limite(fiat("2000")), cripto(&Prefijo::ETH, "0.25")).unwrap();
+    assert!(compra.en_libro);
+    assert_eq!(fiat("500"), plataforma.usuarios[0].fiat);
+    assert_eq!(1, plataforma.ordenes_abiertas("comprador").len());
+
+    assert_eq!(Err(XyzError::OrdenNoEncontrada), plataforma.cancelar_orden("otro".to_string(), compra.id));
+    assert_eq!(Ok(()), plataforma.cancelar_orden("comprador".to_string(), compra.id));
+    assert_eq!(Err(XyzError::OrdenNoEncontrada), plataforma.cancelar_orden("comprador".to_string(), compra.id));
+
+    assert_eq!(fiat("1000"), plataforma.usuarios[0].fiat);
+    assert!(plataforma.ordenes_abiertas("comprador").is_empty());
+}
+
+#[test]
+fn test_xyz_orden_venta_mercado_contra_compras() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "comprador", "10000");
+    crear_validado(&mut plataforma, "vendedor", "0");
+    depositar(&mut plataforma, "vendedor", Prefijo::LTC, "Litecoin", cripto(&Prefijo::LTC, "10"));
+
+    plataforma.colocar_orden("comprador".to_string(), Prefijo::LTC, Lado::Compra, TipoOrden::Limite(fiat("190")), cripto(&Prefijo::LTC, "2")).unwrap();
+    plataforma.colocar_orden("comprador".to_string(), Prefijo::LTC, Lado::Compra, TipoOrden::Limite(fiat("195")), cripto(&Prefijo::LTC, "1")).unwrap();
+
+    // Vende lo que hay en el libro (3 LTC) y el resto de la orden de mercado se descarta
+    let venta = plataforma.colocar_orden("vendedor".to_string(), Prefijo::LTC, Lado::Venta, TipoOrden::Mercado, cripto(&Prefijo::LTC, "5")).unwrap();
+    assert_eq!(cripto(&Prefijo::LTC, "3"), venta.ejecutado);
+    assert!(!venta.en_libro);
+
+    assert_eq!(fiat("575"), plataforma.usuarios[1].fiat); // 195 + 2 * 190
+    assert_eq!(cripto(&Prefijo::LTC, "7"), plataforma.usuarios[1].balances[&Prefijo::LTC]);
+    assert_eq!(fiat("9425"), plataforma.usuarios[0].fiat);
+    assert_eq!(cripto(&Prefijo::LTC, "3"), plataforma.usuarios[0].balances[&Prefijo::LTC]);
+    assert!(plataforma.libro(&Prefijo::LTC).unwrap().ordenes(Lado::Compra).is_empty());
+}
+
+#[test]
+fn test_xyz_orden_compra_mercado_limitada_por_fiat() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "vendedor", "0");
+    crear_validado(&mut plataforma, "comprador", "100");
+    depositar(&mut plataforma, "vendedor", Prefijo::ADA, "Cardano", cripto(&Prefijo::ADA, "1000"));
+    plataforma.colocar_orden("vendedor".to_string(), Prefijo::ADA, Lado::Venta, TipoOrden::Limite(fiat("1.50")), cripto(&Prefijo::ADA, "1000")).unwrap();
+
+    let compra = plataforma.colocar_orden("comprador".to_string(), Prefijo::ADA, Lado::Compra, TipoOrden::Mercado, cripto(&Prefijo::ADA, "500")).unwrap();
+    // 66.669999 ADA a 1.50 valen 100.0049985, se redondea a 100
+    assert_eq!(cripto(&Prefijo::ADA, "66.669999"), compra.ejecutado);
+    assert_eq!(1, compra.ejecuciones.len());
+    assert_eq!(MontoFiat::CERO, plataforma.usuarios[1].fiat);
+    assert_eq!(fiat("100"), plataforma.usuarios[0].fiat);
+
+    // El fiat y la cripto totales no cambian al operar en el libro
+    let total_ada: MontoCripto = plataforma.usuarios.iter().filter_map(|u| u.balances.get(&Prefijo::ADA)).copied().sum::<MontoCripto>()
+        + plataforma.libro(&Prefijo::ADA).unwrap().ordenes(Lado::Venta).iter().map(|o| o.pendiente).sum::<MontoCripto>();
+    assert_eq!(cripto(&Prefijo::ADA, "1000"), total_ada);
+}
+
+#[test]
+fn test_xyz_top_incluye_ejecuciones_de_ordenes() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "a", "100000");
+    crear_validado(&mut plataforma, "b", "100000");
+    plataforma.comprar_cripto("a".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")).unwrap();
+    plataforma.vender_cripto("a".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")).unwrap();
+    depositar(&mut plataforma, "b", Prefijo::ETH, "Ethereum", cripto(&Prefijo::ETH, "5"));
+
+    plataforma.colocar_orden("b".to_string(), Prefijo::ETH, Lado::Venta, TipoOrden::Limite(fiat("3000")), cripto(&Prefijo::ETH, "5")).unwrap();
+    plataforma.colocar_orden("a".to_string(), Prefijo::ETH, Lado::Compra, TipoOrden::Mercado, cripto(&Prefijo::ETH, "1")).unwrap();
+    plataforma.colocar_orden("a".to_string(), Prefijo::ETH, Lado::Compra, TipoOrden::Mercado, cripto(&Prefijo::ETH, "1")).unwrap();
+
+    assert_eq!(Some(Prefijo::ETH), plataforma.top_cripto_compras());
+    assert_eq!(Some(Prefijo::ETH), plataforma.top_cripto_ventas());
+    assert_eq!(Some(Prefijo::ETH), plataforma.top_volumen_ventas());
+}
+
+#[cfg(test)]
+fn comisiones_de_prueba(plataforma: &mut XYZ) {
+    let mut tabla = TablaComisiones::new(10, 50); // maker 0.1%, taker 0.5%
+    tabla.set_retiro_fiat(Medio::TransferenciaBancaria, fiat("2.50"));
+    plataforma.set_comisiones(tabla);
+    plataforma.redes_mut().red_mut(&Prefijo::BTC, "Bitcoin").unwrap().comision = cripto(&Prefijo::BTC, "0.0005");
+}
+
+#[test]
+fn test_xyz_comisiones_compra_venta_y_retiros() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    comisiones_de_prueba(&mut plataforma);
+    crear_validado(&mut plataforma, "1", "50000");
+
+    // 1.11 BTC valen 49950, con el 0.5% de comision ya no alcanza
+    assert_eq!(Err(XyzError::SaldoInsuficiente { disponible: Monto::Fiat(fiat("50000")), requerido: Monto::Fiat(fiat("50199.75")) }), plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1.11")));
+    let id = plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")).unwrap();
+    assert!(matches!(plataforma.transacciones[id], Transaccion::CompraCripto { comision, .. } if comision == fiat("225")));
+    assert_eq!(fiat("4775"), plataforma.usuarios[0].fiat);
+
+    plataforma.vender_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.5")).unwrap();
+    assert_eq!(fiat("27162.50"), plataforma.usuarios[0].fiat); // 4775 + 22500 - 112.50
+
+    // La comision de red sale del saldo ademas del monto retirado
+    assert_eq!(Err(XyzError::SaldoInsuficiente { disponible: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "0.5")), requerido: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "0.5005")) }), plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.5")));
+    plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.2")).unwrap();
+    assert_eq!(cripto(&Prefijo::BTC, "0.2995"), plataforma.usuarios[0].balances[&Prefijo::BTC]);
+
+    plataforma.retirar_fiat("1".to_string(), Medio::TransferenciaBancaria, fiat("100")).unwrap();
+    plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("100")).unwrap();
+    assert_eq!(fiat("26960"), plataforma.usuarios[0].fiat);
+
+    assert_eq!(fiat("340"), plataforma.recaudacion().fiat); // 225 + 112.50 + 2.50
+    assert_eq!(cripto(&Prefijo::BTC, "0.0005"), plataforma.recaudacion().cripto(&Prefijo::BTC));
+}
+
+#[test]
+fn test_xyz_comisiones_maker_taker() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    comisiones_de_prueba(&mut plataforma);
+    crear_validado(&mut plataforma, "vendedor", "0");
+    crear_validado(&mut plataforma, "comprador", "50000");
+    depositar(&mut plataforma, "vendedor", Prefijo::BTC, "Bitcoin", cripto(&Prefijo::BTC, "1"));
+
+    // El vendedor espera en el libro (maker) y el comprador lo cruza (taker)
+    plataforma.colocar_orden("vendedor".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("40000")), cripto(&Prefijo::BTC, "1")).unwrap();
+    let compra = plataforma.colocar_orden("comprador".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Limite(fiat("40000")), cripto(&Prefijo::BTC, "1")).unwrap();
+
+    assert!(matches!(&plataforma.transacciones[compra.ejecuciones[0]],
+        Transaccion::EjecucionOrden { comision_comprador, comision_vendedor, .. }
+        if *comision_comprador == cripto(&Prefijo::BTC, "0.005") && *comision_vendedor == fiat("40")));
+    assert_eq!(cripto(&Prefijo::BTC, "0.995"), plataforma.usuarios[1].balances[&Prefijo::BTC]);
+    assert_eq!(fiat("10000"), plataforma.usuarios[1].fiat);
+    assert_eq!(fiat("39960"), plataforma.usuarios[0].fiat);
+
+    assert_eq!(fiat("40"), plataforma.recaudacion().fiat);
+    assert_eq!(cripto(&Prefijo::BTC, "0.005"), plataforma.recaudacion().cripto(&Prefijo::BTC));
+}
+
+#[test]
+fn test_xyz_reporte_comisiones_por_periodo() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    comisiones_de_prueba(&mut plataforma);
+    crear_validado(&mut plataforma, "1", "1000");
+    plataforma.retirar_fiat("1".to_string(), Medio::TransferenciaBancaria, fiat("10")).unwrap();
+    plataforma.comprar_cripto("1".to_string(), Prefijo::XRP, cripto(&Prefijo::XRP, "100")).unwrap();
+
+    let hoy = plataforma.hoy();
+    let reporte = plataforma.reporte_comisiones(&hoy, &hoy);
+    assert_eq!(fiat("3"), reporte.fiat); // 2.50 del retiro + 0.50 de la compra
+    assert_eq!(plataforma.recaudacion(), &reporte);
+
+    let mut ayer = hoy.clone();
+    ayer.restar_dias(1);
+    assert_eq!(MontoFiat::CERO, plataforma.reporte_comisiones(&Fecha::new(1, 1, 2000), &ayer).fiat);
+}
+
+#[cfg(test)]
+fn directorio_de_prueba(nombre: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("xyz_{nombre}_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_xyz_cargar_restaura_estado() {
+    let dir = directorio_de_prueba("cargar");
+    let mut plataforma = XYZ::new(Persistencia::directorio(&dir));
+    comisiones_de_prueba(&mut plataforma);
+    crear_validado(&mut plataforma, "vendedor", "1000");
+    crear_validado(&mut plataforma, "comprador", "50000");
+    depositar(&mut plataforma, "vendedor", Prefijo::BTC, "Bitcoin", cripto(&Prefijo::BTC, "2"));
+    plataforma.vender_cripto("vendedor".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")).unwrap();
+    plataforma.colocar_orden("vendedor".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("40000")), cripto(&Prefijo::BTC, "1")).unwrap();
+    plataforma.colocar_orden("comprador".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Mercado, cripto(&Prefijo::BTC, "0.3")).unwrap();
+    plataforma.colocar_orden("comprador".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Limite(fiat("30000")), cripto(&Prefijo::BTC, "0.5")).unwrap();
+    plataforma.retirar_fiat("vendedor".to_string(), Medio::TransferenciaBancaria, fiat("100")).unwrap();
+
+    let cargada = XYZ::cargar(&dir).unwrap();
+    assert_eq!(plataforma.transacciones.len(), cargada.transacciones.len());
+    assert_eq!(plataforma.recaudacion(), cargada.recaudacion());
+    assert_eq!(2, cargada.ordenes_abiertas("vendedor").len() + cargada.ordenes_abiertas("comprador").len());
+    for (a, b) in plataforma.usuarios.iter().zip(cargada.usuarios.iter()) {
+        assert_eq!(a.dni, b.dni);
+        assert_eq!(a.fiat, b.fiat);
+        assert_eq!(a.balances, b.balances);
+        assert_eq!(EstadoKyc::Aprobado, b.estado);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_xyz_cargar_detecta_inconsistencias() {
+    let dir = directorio_de_prueba("inconsistente");
+    let mut plataforma = XYZ::new(Persistencia::directorio(&dir));
+    crear_validado(&mut plataforma, "1", "100");
+
+    // Alguien edito el saldo a mano
+    plataforma.usuarios[0].fiat = fiat("1000");
+    plataforma.compactar().unwrap();
+    assert_eq!(Err(ErrorCarga::SaldoNoCoincide { dni: "1".to_string(), guardado: Monto::Fiat(fiat("1000")), calculado: Monto::Fiat(fiat("100")) }), XYZ::cargar(&dir).map(|_| ()));
+
+    // Transaccion de un usuario que no esta en los balances
+    plataforma.usuarios.clear();
+    plataforma.compactar().unwrap();
+    assert_eq!(Err(ErrorCarga::UsuarioDesconocido { transaccion: 0, dni: "1".to_string() }), XYZ::cargar(&dir).map(|_| ()));
+
+    std::fs::write(dir.join("snapshot.json"), "{\"ultimo\":").unwrap();
+    assert!(matches!(XYZ::cargar(&dir), Err(ErrorCarga::Formato { archivo, .. }) if archivo == "snapshot.json"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(matches!(XYZ::cargar(&dir), Err(ErrorCarga::Lectura { archivo, .. }) if archivo == "eventos.jsonl"));
+}
+
+#[test]
+fn test_xyz_persistencia_en_memoria() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = XYZ::new(persistencia.clone());
+    crear_validado(&mut plataforma, "1", "100");
+    plataforma.comprar_cripto("1".to_string(), Prefijo::XRP, cripto(&Prefijo::XRP, "10")).unwrap();
+
+    let cargada = XYZ::cargar_desde(persistencia.clone()).unwrap();
+    assert_eq!(fiat("90"), cargada.usuarios[0].fiat);
+    assert_eq!(2, cargada.transacciones.len());
+
+    assert!(matches!(XYZ::cargar_desde(Persistencia::memoria()), Err(ErrorCarga::Lectura { archivo, .. }) if archivo == "eventos.jsonl"));
+}
+
+#[test]
+fn test_xyz_escritura_fallida_deshace_la_operacion() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = XYZ::new(persistencia.clone());
+    crear_validado(&mut plataforma, "vendedor", "0");
+    crear_validado(&mut plataforma, "comprador", "100000");
+    depositar(&mut plataforma, "vendedor", Prefijo::BTC, "Bitcoin", cripto(&Prefijo::BTC, "2"));
+    plataforma.colocar_orden("vendedor".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("40000")), cripto(&Prefijo::BTC, "1")).unwrap();
+    let antes = XYZ::cargar_desde(persistencia.clone()).unwrap();
+
+    // Ni la compra ni la ejecucion de la orden llegan al diario, asi que tampoco quedan en memoria
+    plataforma.set_persistencia(persistencia.solo_lectura());
+    assert!(matches!(plataforma.comprar_cripto("comprador".to_string(), Prefijo::XRP, cripto(&Prefijo::XRP, "10")), Err(XyzError::Persistencia(_))));
+    assert!(matches!(plataforma.colocar_orden("comprador".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Mercado, cripto(&Prefijo::BTC, "0.5")), Err(XyzError::Persistencia(_))));
+    mismos_saldos(&antes, &plataforma);
+    assert!(plataforma.pendientes.is_empty());
+    assert_eq!(antes.recaudacion(), plataforma.recaudacion());
+
+    plataforma.set_persistencia(persistencia.clone());
+    plataforma.colocar_orden("comprador".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Mercado, cripto(&Prefijo::BTC, "0.5")).unwrap();
+    mismos_saldos(&plataforma, &XYZ::cargar_desde(persistencia).unwrap());
+}
+
+#[cfg(test)]
+fn mismos_saldos(a: &XYZ, b: &XYZ) {
+    assert_eq!(a.usuarios.len(), b.usuarios.len());
+    for (x, y) in a.usuarios.iter().zip(b.usuarios.iter()) {
+        assert_eq!((&x.dni, x.estado, &x.historial_kyc, x.nivel, &x.direcciones_deposito, x.fiat, &x.balances), (&y.dni, y.estado, &y.historial_kyc, y.nivel, &y.direcciones_deposito, y.fiat, &y.balances));
+    }
+    assert_eq!(a.transacciones.len(), b.transacciones.len());
+    assert_eq!(a.mercado.ordenes().count(), b.mercado.ordenes().count());
+    for orden in a.mercado.ordenes() {
+        assert_eq!(Some(orden), b.mercado.buscar(orden.id));
+    }
+}
+
+#[test]
+fn test_xyz_diario_un_registro_por_operacion() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = XYZ::new(persistencia.clone());
+    crear_validado(&mut plataforma, "vendedor", "0");
+    crear_validado(&mut plataforma, "comprador", "100000");
+    depositar(&mut plataforma, "vendedor", Prefijo::BTC, "Bitcoin", cripto(&Prefijo::BTC, "2"));
+    plataforma.colocar_orden("vendedor".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("40000")), cripto(&Prefijo::BTC, "1")).unwrap();
+    plataforma.colocar_orden("vendedor".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("41000")), cripto(&Prefijo::BTC, "0.5")).unwrap();
+    // Completa la primera y deja la segunda ejecutada en parte
+    plataforma.colocar_orden("comprador".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Mercado, cripto(&Prefijo::BTC, "1.2")).unwrap();
+
+    let diario = persistencia.leer("eventos.jsonl").unwrap().unwrap();
+    assert_eq!(12, diario.lines().count());
+    assert_eq!(None, persistencia.leer("balances.json").unwrap());
+    assert_eq!(None, persistencia.leer("snapshot.json").unwrap());
+
+    let cargada = XYZ::cargar_desde(persistencia.clone()).unwrap();
+    mismos_saldos(&plataforma, &cargada);
+    assert_eq!(12, cargada.ultimo_registro);
+
+    // Un corte mientras se agregaba la ultima linea pierde solo esa operacion
+    persistencia.escribir("eventos.jsonl", &format!("{diario}{{\"numero\":13,\"eventos\":[{{\"UsuarioVal")).unwrap();
+    let mut cargada = XYZ::cargar_desde(persistencia.clone()).unwrap();
+    mismos_saldos(&plataforma, &cargada);
+    let orden = cargada.colocar_orden("vendedor".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("42000")), cripto(&Prefijo::BTC, "0.1")).unwrap();
+    assert!(plataforma.mercado.buscar(orden.id).is_none() && orden.id > plataforma.mercado.ordenes().map(|o| o.id).max().unwrap());
+
+    // Lo que se escribio despues del corte se vuelve a leer
+    let recargada = XYZ::cargar_desde(persistencia.clone()).unwrap();
+    mismos_saldos(&cargada, &recargada);
+    assert_eq!(Some(cripto(&Prefijo::BTC, "0.1")), recargada.mercado.buscar(orden.id).map(|o| o.pendiente));
+    assert_eq!(13, persistencia.leer("eventos.jsonl").unwrap().unwrap().lines().count());
+
+    persistencia.escribir("eventos.jsonl", &format!("{{\"numero\":\n{diario}")).unwrap();
+    assert!(matches!(XYZ::cargar_desde(persistencia), Err(ErrorCarga::Formato { archivo, .. }) if archivo == "eventos.jsonl"));
+}
+
+#[test]
+fn test_xyz_snapshot_y_compactacion() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = XYZ::new(persistencia.clone());
+    plataforma.set_intervalo_snapshot(4);
+    crear_validado(&mut plataforma, "1", "100000");
+    crear_validado(&mut plataforma, "2", "0");
+    plataforma.colocar_orden("1".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Limite(fiat("40000")), cripto(&Prefijo::BTC, "1")).unwrap();
+
+    // El snapshot quedo en el registro 8 y el diario sigue teniendo todo
+    let snapshot: serde_json::Value = persistencia.cargar("snapshot.json").unwrap().unwrap();
+    assert_eq!(8, snapshot["ultimo"]);
+    assert_eq!(8, persistencia.leer("eventos.jsonl").unwrap().unwrap().lines().count());
+    mismos_saldos(&plataforma, &XYZ::cargar_desde(persistencia.clone()).unwrap());
+
+    plataforma.compactar().unwrap();
+    assert_eq!(Some(String::new()), persistencia.leer("eventos.jsonl").unwrap());
+    let mut cargada = XYZ::cargar_desde(persistencia.clone()).unwrap();
+    mismos_saldos(&plataforma, &cargada);
+
+    // La numeracion sigue despues del snapshot
+    depositar(&mut cargada, "2", Prefijo::BTC, "Bitcoin", cripto(&Prefijo::BTC, "1"));
+    cargada.colocar_orden("2".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Mercado, cripto(&Prefijo::BTC, "0.25")).unwrap();
+    assert_eq!(11, cargada.ultimo_registro);
+    let recargada = XYZ::cargar_desde(persistencia).unwrap();
+    mismos_saldos(&cargada, &recargada);
+    assert_eq!(fiat("10000"), recargada.usuarios[1].fiat);
+    assert_eq!(fiat("30000"), recargada.mercado.ordenes().next().unwrap().reserva_fiat);
+}
+
+#[test]
+fn test_xyz_migra_archivos_viejos() {
+    let persistencia = Persistencia::memoria();
+    let mut vieja = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut vieja, "1", "100");
+    vieja.comprar_cripto("1".to_string(), Prefijo::XRP, cripto(&Prefijo::XRP, "10")).unwrap();
+    persistencia.guardar("balances.json", &vieja.usuarios).unwrap();
+    persistencia.guardar("transacciones.json", &vieja.transacciones).unwrap();
+
+    let migrada = XYZ::cargar_desde(persistencia.clone()).unwrap();
+    mismos_saldos(&vieja, &migrada);
+    assert!(persistencia.leer("snapshot.json").unwrap().is_some());
+    mismos_saldos(&vieja, &XYZ::cargar_desde(persistencia).unwrap());
+}
+
+#[test]
+fn test_xyz_transacciones_anotan_saldos_por_dni() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "vendedor", "0");
+    crear_validado(&mut plataforma, "comprador", "50000");
+    depositar(&mut plataforma, "vendedor", Prefijo::BTC, "Bitcoin", cripto(&Prefijo::BTC, "1"));
+    plataforma.comprar_cripto("comprador".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")).unwrap();
+
+    let compra = plataforma.transacciones.last().unwrap();
+    assert_eq!(&[
+        CambioSaldo { dni: "comprador".to_string(), antes: Monto::Fiat(fiat("50000")), despues: Monto::Fiat(fiat("45500")) },
+        CambioSaldo { dni: "comprador".to_string(), antes: Monto::Cripto(Prefijo::BTC, MontoCripto::CERO), despues: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "0.1")) }
+    ], compra.saldos());
+    let json = serde_json::to_value(compra).unwrap();
+    assert_eq!("comprador", json["CompraCripto"]["dni"]);
+    assert!(json["CompraCripto"].get("usuario").is_none());
+
+    // El total del comprador incluye lo que sigue retenido en su orden, que todavia no llego al libro
+    plataforma.colocar_orden("vendedor".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("40000")), cripto(&Prefijo::BTC, "0.5")).unwrap();
+    let orden = plataforma.colocar_orden("comprador".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Limite(fiat("40000")), cripto(&Prefijo::BTC, "1")).unwrap();
+    let ejecucion = &plataforma.transacciones[orden.ejecuciones[0]];
+    assert!(ejecucion.saldos().contains(&CambioSaldo { dni: "comprador".to_string(), antes: Monto::Fiat(fiat("45500")), despues: Monto::Fiat(fiat("25500")) }));
+    assert!(ejecucion.saldos().contains(&CambioSaldo { dni: "vendedor".to_string(), antes: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "1")), despues: Monto::Cripto(Prefijo::BTC, cripto(&Prefijo::BTC, "0.5")) }));
+}
+
+#[test]
+fn test_xyz_saldo_anotado_alterado() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = XYZ::new(persistencia.clone());
+    crear_validado(&mut plataforma, "1", "100");
+    plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("30")).unwrap();
+
+    if let Transaccion::RetiroFiat { saldos, .. } = &mut plataforma.transacciones[1] {
+        saldos[0].despues = Monto::Fiat(fiat("80"));
+    }
+    plataforma.compactar().unwrap();
+    assert_eq!(
+        Err(ErrorCarga::SaldoAnotadoNoCoincide { transaccion: 1, dni: "1".to_string(), anotado: Monto::Fiat(fiat("80")), calculado: Monto::Fiat(fiat("70")) }),
+        XYZ::cargar_desde(persistencia).map(|_| ())
+    );
+}
+
+#[test]
+fn test_xyz_migra_transacciones_con_usuario_embebido() {
+    let persistencia = Persistencia::memoria();
+    let usuario = r#"{"nombre": "John", "apellido": "Doe", "email": "john@doe.com", "dni": "1", "validado": true, "fiat": 0, "balances": {}}"#;
+    persistencia.escribir("balances.json", &format!("[{}]", usuario.replace(r#""fiat": 0, "balances": {}"#, r#""fiat": 8600000, "balances": {"BTC": 10000000}"#))).unwrap();
+    persistencia.escribir("transacciones.json", &format!(r#"[
+        {{"IngresoDinero": {{"fecha": {{"dia": 1, "mes": 1, "anio": 2024}}, "usuario": {usuario}, "monto": 9000000}}}},
+        {{"CompraCripto": {{"fecha": {{"dia": 2, "mes": 1, "anio": 2024}}, "usuario": {usuario}, "cripto": "BTC", "cotizacion": 4000000, "monto": 10000000}}}}
+    ]"#)).unwrap();
+
+    let migrada = XYZ::migrar(persistencia.clone()).unwrap();
+    assert_eq!(fiat("86000"), migrada.usuarios[0].fiat);
+    assert_eq!(Monto::Fiat(fiat("90000")), migrada.transacciones[1].saldos()[0].antes);
+
+    // Quedo reescrito en el formato nuevo: por dni, con saldos y sin copias del usuario
+    let snapshot: serde_json::Value = persistencia.cargar("snapshot.json").unwrap().unwrap();
+    let compra = &snapshot["transacciones"][1]["CompraCripto"];
+    assert_eq!("1", compra["dni"]);
+    assert_eq!(2, compra["saldos"].as_array().unwrap().len());
+    assert!(!serde_json::to_string(&snapshot["transacciones"]).unwrap().contains("apellido"));
+
+    // Y se vuelve a cargar tal cual
+    let cargada = XYZ::cargar_desde(persistencia).unwrap();
+    assert_eq!(migrada.transacciones[1].saldos(), cargada.transacciones[1].saldos());
+}
+
+#[test]
+fn test_xyz_extracto_con_ganancias_fifo() {
+    let cotizador = CotizadorMemoria::new();
+    let mut plataforma = XYZ::con_cotizador(cotizador.clone(), Persistencia::memoria());
+    crear_validado(&mut plataforma, "1", "10000");
+    crear_validado(&mut plataforma, "2", "500");
+
+    cotizador.set_cotizacion(Prefijo::BTC, fiat("1000"));
+    plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "2")).unwrap();
+    cotizador.set_cotizacion(Prefijo::BTC, fiat("2000"));
+    plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")).unwrap();
+    cotizador.set_cotizacion(Prefijo::BTC, fiat("3000"));
+    // Se lleva los 2 BTC a 1000 y medio de los comprados a 2000
+    plataforma.vender_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "2.5")).unwrap();
+    plataforma.colocar_orden("1".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("10000")), cripto(&Prefijo::BTC, "0.5")).unwrap();
+    cotizador.set_cotizacion(Prefijo::BTC, fiat("4000"));
+
+    let hoy = plataforma.hoy();
+    let extracto = plataforma.extracto("1", &hoy, &hoy).unwrap();
+    assert_eq!(4, extracto.transacciones.len());
+    assert_eq!(Saldos::default(), extracto.apertura);
+    assert_eq!(fiat("13500"), extracto.cierre.fiat);
+    assert_eq!(cripto(&Prefijo::BTC, "0.5"), extracto.cierre.cripto(&Prefijo::BTC));
+    assert_eq!(fiat("4500"), extracto.ganancia_realizada);
+
+    // Lo que esta en la orden sigue siendo suyo
+    assert_eq!(Some(&fiat("2000")), extracto.cartera.cripto.get(&Prefijo::BTC));
+    assert_eq!(fiat("15500"), extracto.cartera.total);
+    assert_eq!(fiat("1000"), extracto.ganancia_no_realizada);
+
+    // Un periodo que termino ayer no tiene nada, pero la cartera es la de hoy
+    let mut ayer = hoy.clone();
+    ayer.restar_dias(1);
+    let pasado = plataforma.extracto("1", &Fecha::new(1, 1, 2000), &ayer).unwrap();
+    assert!(pasado.transacciones.is_empty());
+    assert_eq!(Saldos::default(), pasado.cierre);
+    assert_eq!(MontoFiat::CERO, pasado.ganancia_realizada);
+    assert_eq!(fiat("15500"), pasado.cartera.total);
+
+    assert_eq!(fiat("500"), plataforma.extracto("2", &hoy, &hoy).unwrap().cartera.total);
+    assert!(matches!(plataforma.extracto("3", &hoy, &hoy), Err(XyzError::UsuarioNoEncontrado)));
+    cotizador.quitar_cotizacion(&Prefijo::BTC);
+    assert!(matches!(plataforma.extracto("1", &hoy, &hoy), Err(XyzError::CotizacionNoDisponible)));
+}
+
+// Las cotizaciones fijas, contando cuantas se pidieron
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct CotizadorContado {
+    pedidas: Arc<AtomicUsize>
+}
+
+#[cfg(test)]
+impl Cotizador for CotizadorContado {
+    fn cotizacion(&self, cripto: &Prefijo) -> Option<MontoFiat> {
+        self.pedidas.fetch_add(1, Ordering::SeqCst);
+        CotizadorFijo.cotizacion(cripto)
+    }
+}
+
+#[test]
+fn test_xyz_extracto_cotiza_una_vez_por_activo() {
+    let cotizador = CotizadorContado::default();
+    let mut plataforma = XYZ::con_cotizador(cotizador.clone(), Persistencia::memoria());
+    crear_validado(&mut plataforma, "1", "100000");
+    plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")).unwrap();
+    plataforma.comprar_cripto("1".to_string(), Prefijo::ETH, cripto(&Prefijo::ETH, "2")).unwrap();
+
+    // La cartera y la ganancia no realizada se valuan con la misma cotizacion
+    cotizador.pedidas.store(0, Ordering::SeqCst);
+    let hoy = plataforma.hoy();
+    plataforma.extracto("1", &hoy, &hoy).unwrap();
+    assert_eq!(2, cotizador.pedidas.load(Ordering::SeqCst));
+}
+
+#[test]
+fn test_xyz_extracto_ejecuciones_y_recepciones() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "vendedor", "0");
+    crear_validado(&mut plataforma, "comprador", "50000");
+    // Entra a la cotizacion fija de 45000
+    depositar(&mut plataforma, "vendedor", Prefijo::BTC, "Bitcoin", cripto(&Prefijo::BTC, "1"));
+    plataforma.colocar_orden("vendedor".to_string(), Prefijo::BTC, Lado::Venta, TipoOrden::Limite(fiat("46000")), cripto(&Prefijo::BTC, "0.5")).unwrap();
+    plataforma.colocar_orden("comprador".to_string(), Prefijo::BTC, Lado::Compra, TipoOrden::Mercado, cripto(&Prefijo::BTC, "0.5")).unwrap();
+
+    let hoy = plataforma.hoy();
+    let vendedor = plataforma.extracto("vendedor", &hoy, &hoy).unwrap();
+    assert_eq!(fiat("500"), vendedor.ganancia_realizada);
+    assert_eq!(MontoFiat::CERO, vendedor.ganancia_no_realizada);
+
+    let comprador = plataforma.extracto("comprador", &hoy, &hoy).unwrap();
+    assert_eq!(fiat("-500"), comprador.ganancia_no_realizada);
+    assert_eq!(fiat("27000"), comprador.cierre.fiat);
+}
+
+#[test]
+fn test_xyz_reglas_topes_por_nivel() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "1", "100000");
+    plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")).unwrap();
+    let mut reglas = ReglasRetiro::new();
+    reglas.set_topes(Nivel::Basico, Topes { diario: fiat("1000"), mensual: fiat("5000") });
+    reglas.set_topes(Nivel::Premium, Topes { diario: fiat("100000"), mensual: fiat("100000") });
+    plataforma.set_reglas(reglas);
+
+    plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("600")).unwrap();
+    let fiat_antes = plataforma.usuarios[0].fiat;
+    assert_eq!(Err(XyzError::RetiroRechazado(MotivoRechazo::TopeDiario { tope: fiat("1000"), retirado: fiat("600"), pedido: fiat("500") })), plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("500")));
+    assert_eq!(fiat_antes, plataforma.usuarios[0].fiat);
+    // La cripto cuenta por su valor en fiat: 0.01 BTC a 45000 son 450
+    assert!(matches!(plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.01")), Err(XyzError::RetiroRechazado(MotivoRechazo::TopeDiario { .. }))));
+
+    plataforma.set_nivel("1".to_string(), Nivel::Premium).unwrap();
+    plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.01")).unwrap();
+    assert_eq!(Err(XyzError::UsuarioNoEncontrado), plataforma.set_nivel("2".to_string(), Nivel::Premium));
+
+    let marcados = plataforma.marcados_de("1");
+    assert_eq!(2, marcados.len());
+    assert_eq!(PedidoRetiro::Fiat { medio: Medio::MercadoPago, monto: fiat("500") }, marcados[0].pedido);
+    assert!(plataforma.marcados_de("2").is_empty());
+}
+
+#[test]
+fn test_xyz_reglas_velocidad_y_direcciones_bloqueadas() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "1", "100000");
+    plataforma.comprar_cripto("1".to_string(), Prefijo::BTC, cripto(&Prefijo::BTC, "1")).unwrap();
+    plataforma.reglas_mut().set_maximo_por_hora(2);
+    plataforma.reglas_mut().bloquear_direccion(DIRECCION_ROBADA);
+
+    assert_eq!(Err(XyzError::RetiroRechazado(MotivoRechazo::DireccionBloqueada(DIRECCION_ROBADA.to_string()))), plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_ROBADA.to_string(), cripto(&Prefijo::BTC, "0.1")));
+    plataforma.retirar_blockchain("1".to_string(), Prefijo::BTC, "Bitcoin".to_string(), DIRECCION_BTC.to_string(), cripto(&Prefijo::BTC, "0.1")).unwrap();
+    plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("100")).unwrap();
+    assert_eq!(Err(XyzError::RetiroRechazado(MotivoRechazo::Velocidad { maximo_por_hora: 2 })), plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("100")));
+
+    assert!(matches!(&plataforma.transacciones[2], Transaccion::RetiroCripto { destino, .. } if destino == DIRECCION_BTC));
+    let motivos: Vec<&MotivoRechazo> = plataforma.eventos_marcados().iter().map(|m| &m.motivo).collect();
+    assert_eq!(vec![&MotivoRechazo::DireccionBloqueada(DIRECCION_ROBADA.to_string()), &MotivoRechazo::Velocidad { maximo_por_hora: 2 }], motivos);
+}
+
+#[test]
+fn test_xyz_reglas_con_reloj_fijo() {
+    let reloj = RelojFijo::new(Fecha::new(28, 2, 2024));
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    plataforma.set_reloj(reloj.clone());
+    crear_validado(&mut plataforma, "1", "100000");
+    plataforma.reglas_mut().set_topes(Nivel::Basico, Topes { diario: fiat("1000"), mensual: fiat("1500") });
+    plataforma.reglas_mut().set_maximo_por_hora(2);
+
+    plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("600")).unwrap();
+    assert_eq!(Fecha::new(28, 2, 2024), *plataforma.transacciones[1].fecha());
+    assert!(matches!(plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("500")), Err(XyzError::RetiroRechazado(MotivoRechazo::TopeDiario { .. }))));
+
+    // 2024 es bisiesto: el dia siguiente es el 29 y sigue siendo febrero
+    reloj.avanzar_dias(1);
+    plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("600")).unwrap();
+    assert_eq!(Fecha::new(29, 2, 2024), *plataforma.transacciones[2].fecha());
+    assert_eq!(Err(XyzError::RetiroRechazado(MotivoRechazo::TopeMensual { tope: fiat("1500"), retirado: fiat("1200"), pedido: fiat("400") })), plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("400")));
+
+    // En marzo arranca el mes de nuevo, pero van dos retiros en la ultima hora
+    reloj.avanzar_dias(1);
+    plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("900")).unwrap();
+    assert_eq!(Fecha::new(1, 3, 2024), *plataforma.transacciones[3].fecha());
+    reloj.avanzar_segundos(60);
+    plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("50")).unwrap();
+    assert_eq!(Err(XyzError::RetiroRechazado(MotivoRechazo::Velocidad { maximo_por_hora: 2 })), plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("50")));
+
+    // Los retiros de la ultima hora salen del historial, asi que el limite sigue despues de cargar
+    let mut cargada = XYZ::cargar_desde(plataforma.persistencia.clone()).unwrap();
+    cargada.set_reloj(reloj.clone());
+    cargada.set_reglas(plataforma.reglas().clone());
+    assert_eq!(Err(XyzError::RetiroRechazado(MotivoRechazo::Velocidad { maximo_por_hora: 2 })), cargada.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("50")));
+    reloj.avanzar_segundos(SEGUNDOS_POR_HORA);
+    cargada.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("50")).unwrap();
+}
+
+#[test]
+fn test_xyz_retiro_retenido_aprobar_y_rechazar() {
+    let mut plataforma = XYZ::new(Persistencia::memoria());
+    crear_validado(&mut plataforma, "1", "100000");
+    plataforma.reglas_mut().set_umbral_aprobacion(fiat("10000"));
+
+    assert_eq!(Err(XyzError::RetiroRetenido(0)), plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("20000")));
+    assert_eq!(fiat("100000"), plataforma.usuarios[0].fiat);
+    assert_eq!(EstadoRetenido::Pendiente, plataforma.retiros_retenidos()[0].estado);
+    assert!(matches!(plataforma.eventos_marcados()[0].motivo, MotivoRechazo::RequiereAprobacion { .. }));
+
+    let id = plataforma.aprobar_retiro(0, "compliance".to_string(), "Cliente verificado por telefono".to_string()).unwrap();
+    assert!(matches!(plataforma.transacciones[id], Transaccion::RetiroFiat { .. }));
+    assert_eq!(fiat("80000"), plataforma.usuarios[0].fiat);
+    assert_eq!(Some("compliance".to_string()), plataforma.retiros_retenidos()[0].revisor);
+    assert_eq!(Err(XyzError::RetiroNoEncontrado), plataforma.aprobar_retiro(0, "compliance".to_string(), String::new()));
+
+    assert_eq!(Err(XyzError::RetiroRetenido(1)), plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("50000")));
+    plataforma.rechazar_retiro(1, "compliance".to_string(), "Destino sospechoso".to_string()).unwrap();
+    assert_eq!(EstadoRetenido::Rechazado, plataforma.retiros_retenidos()[1].estado);
+    assert_eq!(Err(XyzError::RetiroNoEncontrado), plataforma.aprobar_retiro(1, "compliance".to_string(), String::new()));
+    assert_eq!(Err(XyzError::RetiroNoEncontrado), plataforma.rechazar_retiro(5, "compliance".to_string(), String::new()));
+
+    // Si al aprobarlo ya no alcanza el saldo, sigue pendiente
+    assert_eq!(Err(XyzError::RetiroRetenido(2)), plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("75000")));
+    plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("9000")).unwrap();
+    assert!(matches!(plataforma.aprobar_retiro(2, "compliance".to_string(), String::new()), Err(XyzError::SaldoInsuficiente { .. })));
+    assert_eq!(EstadoRetenido::Pendiente, plataforma.retiros_retenidos()[2].estado);
+}
+
+#[test]
+fn test_xyz_reglas_marcados_y_retenidos_se_guardan() {
+    let persistencia = Persistencia::memoria();
+    let mut plataforma = XYZ::new(persistencia.clone());
+    crear_validado(&mut plataforma, "1", "100000");
+    plataforma.reglas_mut().set_umbral_aprobacion(fiat("10000"));
+    plataforma.set_nivel("1".to_string(), Nivel::Intermedio).unwrap();
+    plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("20000")).unwrap_err();
+    plataforma.retirar_fiat("1".to_string(), Medio::MercadoPago, fiat("30000")).unwrap_err();
+    plataforma.aprobar_retiro(1, "compliance".to_string(), "Ok".to_string()).unwrap();
+
+    let cargada = XYZ::cargar_desde(persistencia.clone()).unwrap();
+    mismos_saldos(&plataforma, &cargada);
+    assert_eq!(plataforma.eventos_marcados(), cargada.eventos_marcados());
+    assert_eq!(plataforma.retiros_retenidos(), cargada.retiros_retenidos());
+
+    plataforma.compactar().unwrap();
+    let mut cargada = XYZ::cargar_desde(persistencia).unwrap();
+    assert_eq!(2, cargada.eventos_marcados().len());
+    assert_eq!(Nivel::Intermedio, cargada.usuarios[0].nivel);
+    // Las reglas son configuracion: no se guardan, asi que sin ellas se puede aprobar el pendiente
+    assert!(cargada.aprobar_retiro(0, "compliance".to_string(), String::new()).is_ok());
+    assert_eq!(fiat("50000"), cargada.usuarios[0].fiat);
+}
@@ -0,0 +1,76 @@
+use std::{io::{Read, Write}, net::{SocketAddr, TcpStream}, sync::Arc, thread};
+use serde_json::{json, Value};
+use tiny_http::Server;
+
+use ejercicio6::api::{self, Api};
+use persistencia::Persistencia;
+use ejercicio6::servicio::Servicio;
+use ejercicio6::xyz::XYZ;
+
+// Levanta el servidor en un puerto libre, guardando en memoria
+fn levantar() -> SocketAddr {
+    let xyz = XYZ::new(Persistencia::memoria());
+    let servidor = Arc::new(Server::http("127.0.0.1:0").unwrap());
+    let direccion = servidor.server_addr().to_ip().unwrap();
+    thread::spawn(move || api::servir(&servidor, &Api::new(Servicio::new(xyz)), 2));
+    direccion
+}
+
+// Un cliente HTTP/1.1 minimo: un pedido por conexion
+fn pedir(direccion: SocketAddr, metodo: &str, ruta: &str, cuerpo: Option<Value>) -> (u16, Value) {
+    let cuerpo = cuerpo.map(|c| c.to_string()).unwrap_or_default();
+    let mut conexion = TcpStream::connect(direccion).unwrap();
+    write!(conexion, "{metodo} {ruta} HTTP/1.1\r\nHost: {direccion}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{cuerpo}", cuerpo.len()).unwrap();
+
+    let mut respuesta = String::new();
+    conexion.read_to_string(&mut respuesta).unwrap();
+    let (cabecera, cuerpo) = respuesta.split_once("\r\n\r\n").unwrap();
+    assert!(cabecera.to_lowercase().contains("content-type: application/json"), "{cabecera}");
+    let estado = cabecera.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (estado, serde_json::from_str(cuerpo).unwrap())
+}
+
+#[test]
+fn test_api_usuario_completo() {
+    let xyz = levantar();
+
+    let (estado, _) = pedir(xyz, "POST", "/usuarios", Some(json!({ "nombre": "John", "apellido": "Doe", "email": "john@doe.com", "dni": "1" })));
+    assert_eq!(201, estado);
+    let identidad = json!({ "documento": "30.123.456", "email": "john@doe.com", "fecha_nacimiento": { "dia": 1, "mes": 1, "anio": 1990 } });
+    assert_eq!(200, pedir(xyz, "POST", "/usuarios/1/identidad", Some(identidad)).0);
+    let (estado, cuerpo) = pedir(xyz, "POST", "/usuarios/1/aprobar", Some(json!({ "revisor": "ana" })));
+    assert_eq!((200, json!("Aprobado")), (estado, cuerpo["estado"].clone()));
+
+    let (estado, deposito) = pedir(xyz, "POST", "/usuarios/1/depositos", Some(json!({ "monto": "100000" })));
+    assert_eq!(201, estado);
+    assert!(deposito["transaccion"]["IngresoDinero"].is_object(), "{deposito}");
+    let (estado, compra) = pedir(xyz, "POST", "/usuarios/1/compras", Some(json!({ "cripto": "BTC", "monto": "1" })));
+    assert_eq!(201, estado);
+    assert_eq!(deposito["id"].as_u64().unwrap() + 1, compra["id"].as_u64().unwrap());
+    assert_eq!(201, pedir(xyz, "POST", "/usuarios/1/ventas", Some(json!({ "cripto": "BTC", "monto": "0.25" }))).0);
+    assert_eq!(201, pedir(xyz, "POST", "/usuarios/1/retiros", Some(json!({ "medio": "MercadoPago", "monto": "1000" }))).0);
+
+    let (estado, usuario) = pedir(xyz, "GET", "/usuarios/1", None);
+    assert_eq!(200, estado);
+    assert_eq!(json!("0.75"), usuario["cripto"]["BTC"]);
+    assert_eq!(json!("Aprobado"), usuario["estado"]);
+
+    let (estado, transaccion) = pedir(xyz, "GET", &format!("/transacciones/{}", compra["id"]), None);
+    assert_eq!((200, &compra["transaccion"]), (estado, &transaccion["transaccion"]));
+    assert_eq!(200, pedir(xyz, "GET", "/usuarios/1/extracto?desde=1/1/2000", None).0);
+    assert_eq!(json!("BTC"), pedir(xyz, "GET", "/top/volumen-compras", None).1["cripto"]);
+}
+
+#[test]
+fn test_api_errores() {
+    let xyz = levantar();
+    assert_eq!(201, pedir(xyz, "POST", "/usuarios", Some(json!({ "nombre": "John", "apellido": "Doe", "email": "john@doe.com", "dni": "1" }))).0);
+
+    let (estado, cuerpo) = pedir(xyz, "POST", "/usuarios/1/compras", Some(json!({ "cripto": "BTC", "monto": "1" })));
+    assert_eq!((422, json!("UsuarioNoValidado")), (estado, cuerpo["error"]["codigo"].clone()));
+    assert_eq!(404, pedir(xyz, "GET", "/usuarios/2", None).0);
+    assert_eq!(404, pedir(xyz, "GET", "/transacciones/99", None).0);
+    assert_eq!(404, pedir(xyz, "GET", "/no/existe", None).0);
+    assert_eq!(400, pedir(xyz, "POST", "/usuarios/1/depositos", Some(json!({ "monto": "mucho" }))).0);
+    assert_eq!(400, pedir(xyz, "POST", "/usuarios/1/depositos", Some(json!({ "cantidad": "10" }))).0);
+}
@@ -0,0 +1,56 @@
+use std::{env, fs, path::PathBuf, process::{self, Command}};
+use serde_json::Value;
+
+// Un directorio de estado nuevo para cada test
+fn directorio(nombre: &str) -> PathBuf {
+    let directorio = env::temp_dir().join(format!("xyz-cli-{nombre}-{}", process::id()));
+    let _ = fs::remove_dir_all(&directorio);
+    directorio
+}
+
+// Corre el binario con el directorio de estado y devuelve (codigo, stdout)
+fn correr(directorio: &PathBuf, linea: &str) -> (i32, String) {
+    let salida = Command::new(env!("CARGO_BIN_EXE_ejercicio6"))
+        .arg("--dir").arg(directorio)
+        .args(linea.split_whitespace())
+        .output().unwrap();
+    (salida.status.code().unwrap(), String::from_utf8(salida.stdout).unwrap())
+}
+
+fn json(directorio: &PathBuf, linea: &str) -> Value {
+    let (codigo, salida) = correr(directorio, &format!("--json {linea}"));
+    let valor: Value = serde_json::from_str(&salida).unwrap();
+    assert_eq!(0, codigo, "{valor}");
+    valor
+}
+
+#[test]
+fn test_cli_guarda_entre_invocaciones() {
+    let estado = directorio("invocaciones");
+    json(&estado, "usuario crear 1 John Doe john@doe.com");
+    json(&estado, "usuario identidad 1 30.123.456 john@doe.com 15/6/1990");
+    json(&estado, "usuario validar 1 ana");
+    json(&estado, "-u 1 depositar 100000");
+    json(&estado, "-u 1 comprar BTC 0.5");
+    json(&estado, "-u 1 vender BTC 0.2");
+
+    let usuario = json(&estado, "usuario ver 1");
+    assert_eq!("Aprobado", usuario["estado"]);
+    assert_eq!("0.3", usuario["cripto"]["BTC"]);
+
+    let (codigo, tabla) = correr(&estado, "reporte top-volumen");
+    assert_eq!(0, codigo);
+    assert!(tabla.lines().any(|l| l.split_whitespace().collect::<Vec<_>>() == ["BTC", "2", "0.7", "31500.00"]), "{tabla}");
+    fs::remove_dir_all(&estado).unwrap();
+}
+
+#[test]
+fn test_cli_errores() {
+    let estado = directorio("errores");
+    let (codigo, salida) = correr(&estado, "--json -u 1 comprar BTC 0.5");
+    assert_eq!(1, codigo);
+    assert_eq!("UsuarioNoEncontrado", serde_json::from_str::<Value>(&salida).unwrap()["error"]["codigo"]);
+    assert_eq!(2, correr(&estado, "comprar BTC 0.5").0); // Sin --usuario
+    assert_eq!(2, correr(&estado, "cualquier cosa").0);
+    let _ = fs::remove_dir_all(&estado);
+}
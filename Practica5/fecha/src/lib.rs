@@ -1,3 +1,5 @@
+use std::{fmt, str::FromStr};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
@@ -17,10 +19,10 @@ impl Fecha {
     }
 
     pub fn es_bisiesto(&self) -> bool {
-        if self.anio % 100 == 0 && (self.anio / 100) % 4 == 0 {
+        if self.anio.is_multiple_of(100) && (self.anio / 100).is_multiple_of(4) {
             true
         } else {
-            self.anio % 4 == 0
+            self.anio.is_multiple_of(4)
         }
     }
 
@@ -106,13 +108,57 @@ impl Fecha {
             return true;
         }
 
-        if self.anio < f.anio ||
-           self.anio == f.anio && self.mes < f.mes ||
-           self.anio == f.anio && self.mes == f.mes && self.dia < f.dia {
-            false
-        } else {
-            true
+        !(self.anio < f.anio ||
+          self.anio == f.anio && self.mes < f.mes ||
+          self.anio == f.anio && self.mes == f.mes && self.dia < f.dia)
+    }
+
+    // Anios cumplidos entre esta fecha (por ejemplo, un nacimiento) y la otra
+    pub fn anios_hasta(&self, f: &Fecha) -> u32 {
+        if !f.es_mayor(self) {
+            return 0;
         }
+        let cumplio = (f.mes, f.dia) >= (self.mes, self.dia);
+        f.anio - self.anio - if cumplio { 0 } else { 1 }
+    }
+
+    // Dias corridos desde esta fecha hasta la otra; 0 si la otra no es posterior
+    pub fn dias_hasta(&self, f: &Fecha) -> u32 {
+        let naive = |f: &Fecha| NaiveDate::from_ymd_opt(f.anio as i32, f.mes, f.dia);
+        match (naive(self), naive(f)) {
+            (Some(desde), Some(hasta)) if hasta > desde => (hasta - desde).num_days() as u32,
+            _ => 0
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FechaInvalida(pub String);
+
+impl fmt::Display for FechaInvalida {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Fecha invalida: {} (se espera dia/mes/año)", self.0)
+    }
+}
+
+impl std::error::Error for FechaInvalida {}
+
+// "15/6/2010"
+impl FromStr for Fecha {
+    type Err = FechaInvalida;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let partes: Vec<u32> = s.trim().split('/').map(|p| p.parse()).collect::<Result<_, _>>().map_err(|_| FechaInvalida(s.to_string()))?;
+        match partes[..] {
+            [dia, mes, anio] if Fecha::new(dia, mes, anio).es_fecha_valida() => Ok(Fecha::new(dia, mes, anio)),
+            _ => Err(FechaInvalida(s.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for Fecha {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.dia, self.mes, self.anio)
     }
 }
 
@@ -129,8 +175,8 @@ fn test_es_fecha_valida() {
     let f = Fecha::new(200, 30, 1000);
     let f2 = Fecha::new(20, 12, 2000);
 
-    assert_eq!(false, f.es_fecha_valida());
-    assert_eq!(true, f2.es_fecha_valida());
+    assert!(!f.es_fecha_valida());
+    assert!(f2.es_fecha_valida());
 }
 #[test]
 fn test_es_bisiesto() {
@@ -138,9 +184,9 @@ fn test_es_bisiesto() {
     let bisiesto1 = Fecha::new(20, 2, 2000);
     let no_bisiesto = Fecha::new(10, 1, 2025);
 
-    assert_eq!(true, bisiesto.es_bisiesto());
-    assert_eq!(true, bisiesto1.es_bisiesto());
-    assert_ne!(true, no_bisiesto.es_bisiesto());
+    assert!(bisiesto.es_bisiesto());
+    assert!(bisiesto1.es_bisiesto());
+    assert!(!no_bisiesto.es_bisiesto());
 }
 #[test]
 fn test_restar_dias() {
@@ -177,7 +223,35 @@ fn test_es_mayor() {
     let f2 = Fecha::new(29, 2, 2024);
     let f3 = Fecha::new(30, 4, 2020);
 
-    assert_eq!(false, f.es_mayor(&Fecha::new(17, 1, 2005)));
-    assert_eq!(true, f2.es_mayor(&Fecha::new(29, 1, 2024)));
-    assert_eq!(true, f3.es_mayor(&Fecha::new(15, 4, 2020)));
-}
\ No newline at end of file
+    assert!(!f.es_mayor(&Fecha::new(17, 1, 2005)));
+    assert!(f2.es_mayor(&Fecha::new(29, 1, 2024)));
+    assert!(f3.es_mayor(&Fecha::new(15, 4, 2020)));
+}
+#[test]
+fn test_anios_hasta() {
+    let nacimiento = Fecha::new(15, 6, 2000);
+
+    assert_eq!(18, nacimiento.anios_hasta(&Fecha::new(15, 6, 2018)));
+    assert_eq!(17, nacimiento.anios_hasta(&Fecha::new(14, 6, 2018)));
+    assert_eq!(0, nacimiento.anios_hasta(&Fecha::new(1, 1, 1999)));
+}
+
+#[test]
+fn test_dias_hasta() {
+    let f = Fecha::new(28, 2, 2024);
+
+    assert_eq!(2, f.dias_hasta(&Fecha::new(1, 3, 2024)));
+    assert_eq!(1, Fecha::new(28, 2, 2023).dias_hasta(&Fecha::new(1, 3, 2023)));
+    assert_eq!(366, Fecha::new(1, 1, 2024).dias_hasta(&Fecha::new(1, 1, 2025)));
+    assert_eq!(0, f.dias_hasta(&Fecha::new(27, 2, 2024)));
+}
+
+#[test]
+fn test_fecha_parse() {
+    assert_eq!(Ok(Fecha::new(15, 6, 2010)), "15/6/2010".parse());
+    assert_eq!(Ok(Fecha::new(29, 2, 2024)), "29/02/2024".parse());
+    assert!("29/2/2023".parse::<Fecha>().is_err());
+    assert!("15-6-2010".parse::<Fecha>().is_err());
+    assert!("15/6".parse::<Fecha>().is_err());
+    assert_eq!("1/3/2024", Fecha::new(1, 3, 2024).to_string());
+}
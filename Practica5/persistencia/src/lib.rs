@@ -0,0 +1,189 @@
+use std::{collections::HashMap, fmt, fs::{self, OpenOptions}, io::{self, ErrorKind, Write}, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+use serde::{de::DeserializeOwned, Serialize};
+
+// Donde guarda sus archivos .json un sistema: un directorio en disco, o memoria para los tests.
+// Los clones comparten el mismo almacenamiento. No hay uno por defecto: quien quiera escribir
+// en disco tiene que decir en que directorio.
+#[derive(Debug, Clone)]
+pub enum Persistencia {
+    Directorio(PathBuf),
+    Memoria(Arc<Mutex<HashMap<String, String>>>),
+    SoloLectura(Box<Persistencia>) // Lee lo de la otra, pero cualquier escritura falla
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorPersistencia {
+    Io { archivo: String, error: String },
+    Serializacion { archivo: String, error: String }
+}
+
+impl fmt::Display for ErrorPersistencia {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorPersistencia::Io { archivo, error } => write!(f, "Error de entrada/salida con {archivo}: {error}"),
+            ErrorPersistencia::Serializacion { archivo, error } => write!(f, "Error al convertir {archivo} a/desde JSON: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ErrorPersistencia {}
+
+impl Persistencia {
+    pub fn directorio(directorio: impl Into<PathBuf>) -> Persistencia {
+        Persistencia::Directorio(directorio.into())
+    }
+
+    pub fn memoria() -> Persistencia {
+        Persistencia::Memoria(Arc::new(Mutex::new(HashMap::new())))
+    }
+
+    pub fn solo_lectura(&self) -> Persistencia {
+        Persistencia::SoloLectura(Box::new(self.clone()))
+    }
+
+    // Reemplaza el archivo completo. En disco se escribe un temporal y se renombra,
+    // asi un corte a mitad de escritura nunca deja el .json truncado. El renombre queda
+    // en el directorio, que tambien se sincroniza para que sobreviva al corte.
+    pub fn escribir(&self, archivo: &str, contenido: &str) -> Result<(), ErrorPersistencia> {
+        match self {
+            Persistencia::Directorio(directorio) => {
+                let error = |e: io::Error| ErrorPersistencia::Io { archivo: archivo.to_string(), error: e.to_string() };
+                fs::create_dir_all(directorio).map_err(error)?;
+
+                let temporal = directorio.join(format!(".{archivo}.tmp"));
+                let mut file = fs::File::create(&temporal).map_err(error)?;
+                file.write_all(contenido.as_bytes()).map_err(error)?;
+                file.sync_all().map_err(error)?;
+                fs::rename(&temporal, directorio.join(archivo)).map_err(error)?;
+                sincronizar_directorio(directorio).map_err(error)
+            },
+            Persistencia::Memoria(archivos) => {
+                archivos.lock().unwrap().insert(archivo.to_string(), contenido.to_string());
+                Ok(())
+            },
+            Persistencia::SoloLectura(_) => Err(solo_lectura(archivo))
+        }
+    }
+
+    // Agrega una linea al final del archivo sin reescribirlo; lo crea si no existe
+    pub fn agregar_linea(&self, archivo: &str, linea: &str) -> Result<(), ErrorPersistencia> {
+        match self {
+            Persistencia::Directorio(directorio) => {
+                let error = |e: io::Error| ErrorPersistencia::Io { archivo: archivo.to_string(), error: e.to_string() };
+                fs::create_dir_all(directorio).map_err(error)?;
+
+                let ruta = directorio.join(archivo);
+                let nuevo = !ruta.exists();
+                let mut file = OpenOptions::new().create(true).append(true).open(ruta).map_err(error)?;
+                file.write_all(format!("{linea}\n").as_bytes()).map_err(error)?;
+                file.sync_data().map_err(error)?;
+                if nuevo {
+                    sincronizar_directorio(directorio).map_err(error)?;
+                }
+                Ok(())
+            },
+            Persistencia::Memoria(archivos) => {
+                let mut archivos = archivos.lock().unwrap();
+                let contenido = archivos.entry(archivo.to_string()).or_default();
+                contenido.push_str(linea);
+                contenido.push('\n');
+                Ok(())
+            },
+            Persistencia::SoloLectura(_) => Err(solo_lectura(archivo))
+        }
+    }
+
+    // None si el archivo no existe
+    pub fn leer(&self, archivo: &str) -> Result<Option<String>, ErrorPersistencia> {
+        match self {
+            Persistencia::Directorio(directorio) => match fs::read_to_string(directorio.join(archivo)) {
+                Ok(contenido) => Ok(Some(contenido)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(ErrorPersistencia::Io { archivo: archivo.to_string(), error: e.to_string() })
+            },
+            Persistencia::Memoria(archivos) => Ok(archivos.lock().unwrap().get(archivo).cloned()),
+            Persistencia::SoloLectura(persistencia) => persistencia.leer(archivo)
+        }
+    }
+
+    pub fn guardar<T: Serialize + ?Sized>(&self, archivo: &str, valor: &T) -> Result<(), ErrorPersistencia> {
+        let contenido = serde_json::to_string_pretty(valor).map_err(|e| ErrorPersistencia::Serializacion { archivo: archivo.to_string(), error: e.to_string() })?;
+        self.escribir(archivo, &contenido)
+    }
+
+    pub fn cargar<T: DeserializeOwned>(&self, archivo: &str) -> Result<Option<T>, ErrorPersistencia> {
+        match self.leer(archivo)? {
+            Some(contenido) => serde_json::from_str(&contenido)
+                .map(Some)
+                .map_err(|e| ErrorPersistencia::Serializacion { archivo: archivo.to_string(), error: e.to_string() }),
+            None => Ok(None)
+        }
+    }
+}
+
+fn solo_lectura(archivo: &str) -> ErrorPersistencia {
+    ErrorPersistencia::Io { archivo: archivo.to_string(), error: "la persistencia es de solo lectura".to_string() }
+}
+
+#[cfg(unix)]
+fn sincronizar_directorio(directorio: &Path) -> io::Result<()> {
+    fs::File::open(directorio)?.sync_all()
+}
+
+// Fuera de unix no se puede abrir un directorio como archivo
+#[cfg(not(unix))]
+fn sincronizar_directorio(_directorio: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[test]
+fn test_persistencia_memoria() {
+    let persistencia = Persistencia::memoria();
+    let compartida = persistencia.clone();
+
+    assert_eq!(Ok(None), persistencia.cargar::<Vec<u32>>("numeros.json"));
+    persistencia.guardar("numeros.json", &vec![1, 2, 3]).unwrap();
+    assert_eq!(Ok(Some(vec![1, 2, 3])), compartida.cargar::<Vec<u32>>("numeros.json"));
+
+    persistencia.agregar_linea("log.jsonl", "{}").unwrap();
+    compartida.agregar_linea("log.jsonl", "{}").unwrap();
+    assert_eq!(Ok(Some("{}\n{}\n".to_string())), persistencia.leer("log.jsonl"));
+
+    compartida.escribir("numeros.json", "[1, ").unwrap();
+    assert!(matches!(persistencia.cargar::<Vec<u32>>("numeros.json"), Err(ErrorPersistencia::Serializacion { .. })));
+}
+
+#[test]
+fn test_persistencia_solo_lectura() {
+    let persistencia = Persistencia::memoria();
+    persistencia.agregar_linea("log.jsonl", "1").unwrap();
+    let lectura = persistencia.solo_lectura();
+
+    assert_eq!(Ok(Some("1\n".to_string())), lectura.leer("log.jsonl"));
+    assert!(matches!(lectura.agregar_linea("log.jsonl", "2"), Err(ErrorPersistencia::Io { .. })));
+    assert!(matches!(lectura.guardar("numeros.json", &vec![1]), Err(ErrorPersistencia::Io { .. })));
+    assert_eq!(Ok(Some("1\n".to_string())), persistencia.leer("log.jsonl"));
+    assert_eq!(Ok(None), persistencia.leer("numeros.json"));
+}
+
+#[test]
+fn test_persistencia_directorio() {
+    let directorio = std::env::temp_dir().join(format!("persistencia_{}", std::process::id()));
+    let persistencia = Persistencia::directorio(&directorio);
+
+    assert_eq!(Ok(None), persistencia.leer("datos.json"));
+    persistencia.guardar("datos.json", &vec!["a", "b"]).unwrap();
+    persistencia.guardar("datos.json", &vec!["c"]).unwrap();
+    assert_eq!(Ok(Some(vec!["c".to_string()])), persistencia.cargar::<Vec<String>>("datos.json"));
+
+    persistencia.agregar_linea("log.jsonl", "1").unwrap();
+    persistencia.agregar_linea("log.jsonl", "2").unwrap();
+    assert_eq!(Ok(Some("1\n2\n".to_string())), persistencia.leer("log.jsonl"));
+    fs::remove_file(directorio.join("log.jsonl")).unwrap();
+
+    // No queda el temporal dando vueltas
+    let archivos: Vec<_> = fs::read_dir(&directorio).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(vec![std::ffi::OsString::from("datos.json")], archivos);
+
+    fs::remove_dir_all(&directorio).unwrap();
+}
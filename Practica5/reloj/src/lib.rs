@@ -0,0 +1,82 @@
+use std::sync::{Arc, Mutex};
+use chrono::prelude::*;
+
+use fecha::Fecha;
+
+// De donde sale la fecha de hoy. En los tests se usa un RelojFijo para que
+// los vencimientos no dependan del dia en que se corren.
+pub trait Reloj: Send {
+    fn ahora(&self) -> DateTime<Utc>;
+
+    fn hoy(&self) -> Fecha {
+        let ahora = self.ahora();
+        Fecha::new(ahora.day(), ahora.month(), ahora.year() as u32)
+    }
+
+    // Segundos desde 1970
+    fn momento(&self) -> i64 {
+        self.ahora().timestamp()
+    }
+}
+
+pub struct RelojSistema;
+
+impl Reloj for RelojSistema {
+    fn ahora(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+// Un reloj que solo avanza cuando se lo piden. Los clones comparten la misma hora.
+#[derive(Clone)]
+pub struct RelojFijo {
+    ahora: Arc<Mutex<DateTime<Utc>>>
+}
+
+impl RelojFijo {
+    // A las 12 del mediodia de esa fecha
+    pub fn new(fecha: Fecha) -> RelojFijo {
+        let ahora = Utc.with_ymd_and_hms(fecha.anio as i32, fecha.mes, fecha.dia, 12, 0, 0).unwrap();
+        RelojFijo::en(ahora)
+    }
+
+    pub fn en(ahora: DateTime<Utc>) -> RelojFijo {
+        RelojFijo { ahora: Arc::new(Mutex::new(ahora)) }
+    }
+
+    pub fn set(&self, ahora: DateTime<Utc>) {
+        *self.ahora.lock().unwrap() = ahora;
+    }
+
+    pub fn avanzar_dias(&self, dias: u32) {
+        *self.ahora.lock().unwrap() += chrono::Duration::days(dias as i64);
+    }
+
+    pub fn avanzar_segundos(&self, segundos: i64) {
+        *self.ahora.lock().unwrap() += chrono::Duration::seconds(segundos);
+    }
+}
+
+impl Reloj for RelojFijo {
+    fn ahora(&self) -> DateTime<Utc> {
+        *self.ahora.lock().unwrap()
+    }
+}
+
+#[test]
+fn test_reloj_fijo() {
+    let reloj = RelojFijo::new(Fecha::new(27, 2, 2024));
+    let compartido = reloj.clone();
+    assert_eq!(Fecha::new(27, 2, 2024), reloj.hoy());
+
+    compartido.avanzar_dias(2);
+    assert_eq!(Fecha::new(29, 2, 2024), reloj.hoy());
+    compartido.avanzar_dias(1);
+    assert_eq!(Fecha::new(1, 3, 2024), reloj.hoy());
+
+    reloj.set(Utc.with_ymd_and_hms(2023, 12, 31, 23, 59, 30).unwrap());
+    let antes = reloj.momento();
+    reloj.avanzar_segundos(30);
+    assert_eq!(Fecha::new(1, 1, 2024), reloj.hoy());
+    assert_eq!(antes + 30, compartido.momento());
+}